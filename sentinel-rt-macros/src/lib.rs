@@ -0,0 +1,167 @@
+//! Attribute macros for sentinel-rt. Use them through its re-exports.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, Error, FnArg, ItemFn, ReturnType, Type};
+
+/// The sources `sentinel_rt::irq::dispatch` calls handlers for, by name,
+/// and whether the handler is passed a byte.
+const SOURCES: &[(&str, bool)] = &[
+    ("TIMER", false),
+    ("UART_RX", true),
+    ("UART_TX", false),
+    ("GPIO", true),
+];
+
+/// Make a function the handler for the interrupt source it's named after.
+/// See `sentinel_rt::irq`.
+#[proc_macro_attribute]
+pub fn interrupt(args: TokenStream, input: TokenStream) -> TokenStream {
+    if !args.is_empty() {
+        let args = TokenStream2::from(args);
+        return Error::new(args.span(), "`#[interrupt]` takes no arguments")
+            .to_compile_error()
+            .into();
+    }
+    let f = parse_macro_input!(input as ItemFn);
+    expand(f).unwrap_or_else(Error::into_compile_error).into()
+}
+
+fn expand(f: ItemFn) -> syn::Result<TokenStream2> {
+    let sig = &f.sig;
+    let name = sig.ident.to_string();
+    let Some(&(_, takes_byte)) = SOURCES.iter().find(|(source, _)| *source == name) else {
+        let names: Vec<_> = SOURCES.iter().map(|(source, _)| *source).collect();
+        let msg = format!("no interrupt source `{name}`; expected one of {}", names.join(", "));
+        return Err(Error::new(sig.ident.span(), msg));
+    };
+
+    if sig.constness.is_some()
+        || sig.asyncness.is_some()
+        || sig.unsafety.is_some()
+        || sig.abi.is_some()
+        || !sig.generics.params.is_empty()
+        || sig.generics.where_clause.is_some()
+        || sig.variadic.is_some()
+        || !matches!(sig.output, ReturnType::Default)
+    {
+        let msg = "interrupt handlers must be plain `fn`s, with no return value";
+        return Err(Error::new(sig.span(), msg));
+    }
+
+    let args_ok = match (takes_byte, sig.inputs.len()) {
+        (false, 0) => true,
+        (true, 1) => sig.inputs.iter().all(is_u8),
+        _ => false,
+    };
+    if !args_ok {
+        let msg = if takes_byte {
+            format!("`{name}` takes the byte: `fn {name}(b: u8)`")
+        } else {
+            format!("`{name}` takes no arguments: `fn {name}()`")
+        };
+        let span = if sig.inputs.is_empty() { sig.ident.span() } else { sig.inputs.span() };
+        return Err(Error::new(span, msg));
+    }
+
+    let ItemFn { attrs, vis, sig, block } = &f;
+    let (ident, inputs) = (&sig.ident, &sig.inputs);
+    let symbol = syn::LitStr::new(&format!("__sentinel_irq_{name}"), Span::call_site());
+    Ok(quote! {
+        #(#attrs)*
+        #[allow(non_snake_case)]
+        #[export_name = #symbol]
+        #vis extern "C" fn #ident(#inputs) #block
+    })
+}
+
+/// Make a function a test, for `sentinel_rt::testing::run` to run on the
+/// target. See `sentinel_rt::testing`.
+#[proc_macro_attribute]
+pub fn sentinel_test(args: TokenStream, input: TokenStream) -> TokenStream {
+    if !args.is_empty() {
+        let args = TokenStream2::from(args);
+        return Error::new(args.span(), "`#[sentinel_test]` takes no arguments")
+            .to_compile_error()
+            .into();
+    }
+    let f = parse_macro_input!(input as ItemFn);
+    expand_test(f).unwrap_or_else(Error::into_compile_error).into()
+}
+
+fn expand_test(f: ItemFn) -> syn::Result<TokenStream2> {
+    let sig = &f.sig;
+    if sig.constness.is_some()
+        || sig.asyncness.is_some()
+        || sig.unsafety.is_some()
+        || sig.abi.is_some()
+        || !sig.generics.params.is_empty()
+        || sig.generics.where_clause.is_some()
+        || sig.variadic.is_some()
+        || !sig.inputs.is_empty()
+    {
+        let msg = "tests must be plain `fn`s, with no arguments";
+        return Err(Error::new(sig.span(), msg));
+    }
+
+    let ident = &sig.ident;
+    let name = syn::LitStr::new(&ident.to_string(), ident.span());
+    Ok(quote! {
+        #f
+
+        const _: () = {
+            #[used]
+            #[link_section = ".sentinel.test"]
+            static TEST: ::sentinel_rt::testing::Test = ::sentinel_rt::testing::Test::new(
+                concat!(module_path!(), "::", #name),
+                || ::sentinel_rt::testing::Outcome::into_result(#ident()),
+            );
+        };
+    })
+}
+
+fn is_u8(arg: &FnArg) -> bool {
+    match arg {
+        FnArg::Typed(arg) => matches!(&*arg.ty, Type::Path(ty) if ty.path.is_ident("u8")),
+        FnArg::Receiver(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use syn::parse_quote;
+
+    use super::*;
+
+    fn err(f: ItemFn) -> String {
+        expand(f).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn sources() {
+        let out = expand(parse_quote! { fn UART_RX(b: u8) { let _ = b; } }).unwrap();
+        assert!(out.to_string().contains("\"__sentinel_irq_UART_RX\""));
+        assert!(expand(parse_quote! { pub fn TIMER() {} }).is_ok());
+
+        assert!(err(parse_quote! { fn UART() {} }).starts_with("no interrupt source `UART`"));
+        assert!(err(parse_quote! { fn GPIO() {} }).contains("takes the byte"));
+        assert!(err(parse_quote! { fn TIMER(n: u8) {} }).contains("takes no arguments"));
+        assert!(err(parse_quote! { fn TIMER() -> u8 { 0 } }).contains("plain `fn`s"));
+    }
+
+    #[test]
+    fn tests() {
+        let out = expand_test(parse_quote! { fn adds() { assert_eq!(1 + 1, 2); } }).unwrap();
+        let out = out.to_string();
+        assert!(out.contains("\".sentinel.test\""));
+        assert!(out.contains("\"adds\""));
+        assert!(expand_test(parse_quote! { fn ok() -> Result<(), &'static str> { Ok(()) } })
+            .is_ok());
+
+        let msg = expand_test(parse_quote! { fn takes(n: u8) {} }).unwrap_err().to_string();
+        assert!(msg.contains("no arguments"));
+        assert!(expand_test(parse_quote! { async fn later() {} }).is_err());
+    }
+}
@@ -0,0 +1,115 @@
+use std::{env, fs, path::PathBuf};
+
+use toml::{Table, Value};
+
+/// Board descriptions, by feature. The first enabled one wins; with none,
+/// it's the AttoSoC.
+const BOARDS: &[(&str, &str)] = &[
+    ("CARGO_FEATURE_BOARD_HX8K", "hx8k"),
+    ("CARGO_FEATURE_BOARD_ICEBREAKER", "icebreaker"),
+    ("CARGO_FEATURE_BOARD_ICEBREAKER_BRAM", "icebreaker-bram"),
+];
+
+fn main() {
+    // Put the linker script fragments somewhere the linker can find them,
+    // so that device scripts can INCLUDE them by name.
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+
+    fs::copy("sentinel.x", out_dir.join("sentinel.x")).unwrap();
+    fs::copy("minimal.x", out_dir.join("minimal.x")).unwrap();
+    // rt.x is whichever start-up script the `minimal` feature says.
+    let rt = if env::var_os("CARGO_FEATURE_MINIMAL").is_some() { "minimal.x" } else { "link.x" };
+    fs::write(out_dir.join("rt.x"), format!("INCLUDE {rt}\n")).unwrap();
+    println!("cargo:rustc-link-search={}", out_dir.display());
+    println!("cargo:rerun-if-changed=sentinel.x");
+    println!("cargo:rerun-if-changed=minimal.x");
+    println!("cargo:rerun-if-changed=build.rs");
+
+    // The board: memory.x and board.x for the linker, io_map.rs for the
+    // crate.
+    let path = board_path();
+    println!("cargo:rerun-if-env-changed=SENTINEL_BOARD");
+    println!("cargo:rerun-if-changed={}", path.display());
+
+    let text = fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("{}: {e}", path.display()));
+    let board: Table = text.parse()
+        .unwrap_or_else(|e| panic!("{}: {e}", path.display()));
+    let get = |table: &str, key: &str| -> u32 {
+        let val = board.get(table).and_then(|t| t.get(key)).and_then(Value::as_integer);
+        let val = val.unwrap_or_else(|| panic!("{}: needs {table}.{key}", path.display()));
+        u32::try_from(val)
+            .unwrap_or_else(|_| panic!("{}: {table}.{key} doesn't fit in 32 bits", path.display()))
+    };
+
+    let region = |table: &str| {
+        let (origin, length) = (get(table, "origin"), get(table, "length"));
+        if length == 0 || origin as u64 + length as u64 > 1 << 32 {
+            panic!("{}: {table} doesn't fit in the address space", path.display());
+        }
+        (origin, length)
+    };
+    let (origin, length) = region("ram");
+    let spram = board.contains_key("spram").then(|| region("spram"));
+
+    let mut memory = format!("MEMORY\n{{\n    RAM : ORIGIN = {origin:#010x}, LENGTH = {length:#x}\n");
+    if let Some((origin, length)) = spram {
+        memory += &format!("    SPRAM : ORIGIN = {origin:#010x}, LENGTH = {length:#x}\n");
+    }
+    memory += "}\n";
+    fs::write(out_dir.join("memory.x"), memory).unwrap();
+
+    // board.x: memory.x, and which region each section goes in, so a
+    // device script needn't know the board.
+    let mut script = format!("/* Generated by build.rs from {}. */\n\nINCLUDE memory.x\n\n", path.display());
+    for region in ["TEXT", "RODATA", "DATA", "BSS", "STACK"] {
+        script += &format!("REGION_ALIAS(\"REGION_{region}\", RAM);\n");
+    }
+    if spram.is_some() {
+        script += "\n/* All of SPRAM is the heap. Set _heap_size after this to use less. */\n\
+                   REGION_ALIAS(\"REGION_HEAP\", SPRAM);\n\
+                   _heap_size = LENGTH(SPRAM);\n";
+    } else {
+        script += "REGION_ALIAS(\"REGION_HEAP\", RAM);\n";
+    }
+    fs::write(out_dir.join("board.x"), script).unwrap();
+
+    let (spram_origin, spram_length) = spram.unwrap_or((0, 0));
+    let mut map = format!(
+        "// Generated by build.rs from {}.\n\n\
+         pub const RAM_ORIGIN: u32 = {origin:#010x};\n\
+         pub const RAM_LENGTH: u32 = {length:#x};\n\
+         pub const SPRAM_ORIGIN: u32 = {spram_origin:#010x};\n\
+         pub const SPRAM_LENGTH: u32 = {spram_length:#x};\n\
+         pub const CLK_HZ: u32 = {};\n\
+         pub const BAUD: u32 = {};\n",
+        path.display(),
+        get("soc", "clk_hz"),
+        get("soc", "baud"),
+    );
+    for (table, prefix) in [("csr", "CSR"), ("wishbone", "WB")] {
+        for key in ["gpio", "timer", "serial"] {
+            let name = key.to_uppercase();
+            map += &format!("pub const {prefix}_{name}: u32 = {:#010x};\n", get(table, key));
+        }
+    }
+    fs::write(out_dir.join("io_map.rs"), map).unwrap();
+}
+
+/// `SENTINEL_BOARD` (a name under `boards/`, or a path to a description),
+/// else the board feature, else the AttoSoC.
+fn board_path() -> PathBuf {
+    if let Ok(board) = env::var("SENTINEL_BOARD") {
+        let path = PathBuf::from(&board);
+        if path.extension().is_some() {
+            return path;
+        }
+        return PathBuf::from(format!("boards/{board}.toml"));
+    }
+
+    let name = BOARDS
+        .iter()
+        .find(|(feature, _)| env::var_os(feature).is_some())
+        .map_or("attosoc", |(_, name)| name);
+    PathBuf::from(format!("boards/{name}.toml"))
+}
@@ -0,0 +1,57 @@
+//! Lines of text kept on the heap, with `Vec` and `String`.
+//!
+//! Each line typed is stored; an empty line prints them all back, with the
+//! heap's stats. Keep typing and the heap runs out, which is reported over
+//! the UART before the panic.
+//!
+//! Needs the `alloc` feature, and a heap: the iCEBreaker's SPRAM, or a
+//! `_heap_size` in the device script.
+//!
+//! ```text
+//! cargo build --release --example alloc_lines --features alloc,board-icebreaker-bram
+//! ```
+
+#![cfg_attr(target_os = "none", no_std)]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+#[cfg(target_os = "none")]
+use panic_halt as _;
+use sentinel_rt::heap::GLOBAL;
+use sentinel_rt::prelude::*;
+use sentinel_rt::shell::Output;
+
+#[entry]
+fn main() -> ! {
+    let Soc { mut serial, .. } = Soc::init();
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+
+    loop {
+        let Some(b) = serial.read_byte() else {
+            continue;
+        };
+        match b {
+            b'\r' | b'\n' if line.is_empty() => {
+                for l in &lines {
+                    serial.write_str(l);
+                    serial.write_str("\r\n");
+                }
+                GLOBAL.stats().print(&mut serial);
+            }
+            b'\r' | b'\n' => {
+                serial.write_str("\r\n");
+                lines.push(core::mem::take(&mut line));
+            }
+            _ if b.is_ascii() => {
+                serial.write_byte(b);
+                line.push(b as char);
+            }
+            _ => {}
+        }
+    }
+}
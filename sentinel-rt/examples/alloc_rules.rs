@@ -0,0 +1,75 @@
+//! A heap workout: rule 30's history, kept until the heap runs out.
+//!
+//! Each generation of the automaton is a `Vec` one cell wider on each side
+//! than the last, pushed onto a growing history. Every 16 generations, half
+//! the history is dropped, leaving holes of every size between what's
+//! kept, and the heap's stats are printed; how `largest-free` lags behind
+//! `free` is the fragmentation. Sooner or later a generation doesn't fit,
+//! and the allocator reports it before the panic.
+//!
+//! It needs no input and prints the same thing every run, so a simulation's
+//! UART output can be checked against `alloc_rules.expect`:
+//!
+//! ```text
+//! cargo build --release --example alloc_rules --features alloc,board-icebreaker-bram
+//! uart-replay sentinel-rt/examples/alloc_rules.expect --vcd sim.vcd
+//! ```
+
+#![cfg_attr(target_os = "none", no_std)]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+#[cfg(target_os = "none")]
+use panic_halt as _;
+use sentinel_rt::hex;
+use sentinel_rt::heap::GLOBAL;
+use sentinel_rt::prelude::*;
+use sentinel_rt::shell::Output;
+
+const RULE: u8 = 30;
+
+/// Generations shown in full; after that they're too wide.
+const SHOWN: u32 = 16;
+
+/// The generation after `prev`, one cell wider on each side.
+fn step(prev: &[u8]) -> Vec<u8> {
+    let mut padded = vec![0; prev.len() + 4];
+    padded[2..prev.len() + 2].copy_from_slice(prev);
+    padded.windows(3).map(|w| (RULE >> (w[0] << 2 | w[1] << 1 | w[2])) & 1).collect()
+}
+
+#[entry]
+fn main() -> ! {
+    let Soc { mut serial, .. } = Soc::init();
+    let mut history: Vec<Vec<u8>> = vec![vec![1]];
+
+    let mut n = 0;
+    loop {
+        n += 1;
+        let next = step(history.last().unwrap());
+        if n <= SHOWN {
+            // Centred: generation n is 2n + 1 cells wide.
+            for _ in n..SHOWN {
+                serial.write_str(" ");
+            }
+            for &c in &next {
+                serial.write_str(if c == 1 { "#" } else { "." });
+            }
+            serial.write_str("\r\n");
+        }
+        history.push(next);
+
+        if n % 16 == 0 {
+            // Every other generation, so the holes are all different sizes.
+            history.retain(|g| g.len() % 4 == 1);
+            serial.write_str("gen ");
+            serial.write_bytes(&hex::u32_digits(n));
+            serial.write_str("\r\n");
+            GLOBAL.stats().print(&mut serial);
+        }
+    }
+}
@@ -0,0 +1,85 @@
+//! Line echo, written as `async` code on the single-task executor.
+//!
+//! Reads a line from the UART, with echo and backspace, and sends it back
+//! reversed. Between keystrokes the core sleeps in `wfi`; the UART's
+//! interrupt wakes it.
+//!
+//! ```text
+//! > hello
+//! olleh
+//! ```
+//!
+//! It doesn't fit in the AttoSoC's 4 KiB, but does in the HX8K's 8 KiB
+//! (see `io_map` for the boards):
+//!
+//! ```text
+//! cargo build --release --example async_echo --features board-hx8k
+//! ```
+
+#![cfg_attr(target_os = "none", no_std)]
+#![no_main]
+
+#[cfg(target_os = "none")]
+use panic_halt as _;
+use riscv::register::{mie, mstatus};
+use sentinel_rt::executor;
+use sentinel_rt::hal::serial::{AsyncPort, Port};
+use sentinel_rt::irq::{self, Handler};
+use sentinel_rt::prelude::*;
+
+static CONSOLE: Port<16, 64> = Port::new();
+
+#[no_mangle]
+#[allow(non_snake_case)]
+fn MachineExternal() {
+    irq::dispatch();
+}
+
+/// Read a line into `buf`, echoing it, and return how long it is.
+async fn read_line(con: &AsyncPort<'_, 16, 64>, buf: &mut [u8]) -> usize {
+    let mut len = 0;
+    loop {
+        match con.read_byte().await {
+            b'\r' | b'\n' => {
+                con.write_all(b"\r\n").await;
+                return len;
+            }
+            0x08 | 0x7f if len > 0 => {
+                len -= 1;
+                con.write_all(b"\x08 \x08").await;
+            }
+            b @ 0x20..=0x7e if len < buf.len() => {
+                buf[len] = b;
+                len += 1;
+                con.write_all(&[b]).await;
+            }
+            _ => con.write_all(b"\x07").await,
+        }
+    }
+}
+
+#[entry]
+fn main() -> ! {
+    let soc = Soc::init();
+    // The UART has to be attached before interrupts go on: the Wishbone
+    // UART's IRQ is pending from reset, and only the port can clear it.
+    CONSOLE.attach(soc.serial.base());
+    irq::set(Handler::Serial(|| CONSOLE.on_interrupt()));
+    // SAFETY: The ISR's state is all set up.
+    unsafe {
+        mie::set_mext();
+        mstatus::set_mie();
+    }
+
+    executor::block_on(async {
+        let con = CONSOLE.as_async();
+        let mut line = [0; 32];
+        loop {
+            con.write_all(b"> ").await;
+            let len = read_line(&con, &mut line).await;
+            line[..len].reverse();
+            con.write_all(&line[..len]).await;
+            con.write_all(b"\r\n").await;
+        }
+    })
+}
@@ -0,0 +1,34 @@
+//! Remote I/O bridge: GPIO, the timer, and bit-banged SPI and I2C, driven
+//! from a PC over the UART. See `sentinel_rt::bridge` for the protocol, and
+//! `sentinel-tools`' `bridge` for a client:
+//!
+//! ```text
+//! $ bridge /dev/ttyUSB1 ping
+//! version 1
+//! $ bridge /dev/ttyUSB1 i2c-write-read 0x44 6 24 00
+//! ```
+//!
+//! It doesn't fit in the AttoSoC's 4 KiB, but does in the HX8K's 8 KiB
+//! (see `io_map` for the boards):
+//!
+//! ```text
+//! cargo build --release --example bridge --features board-hx8k
+//! ```
+
+#![cfg_attr(target_os = "none", no_std)]
+#![no_main]
+
+#[cfg(target_os = "none")]
+use panic_halt as _;
+use sentinel_rt::bridge::{Bridge, Pins};
+use sentinel_rt::prelude::*;
+
+#[entry]
+fn main() -> ! {
+    let Soc { gpio, mut serial, timer, .. } = Soc::init();
+    let mut bridge = Bridge::new(gpio, timer, Pins::DEFAULT);
+
+    loop {
+        bridge.poll(&mut serial);
+    }
+}
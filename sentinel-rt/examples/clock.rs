@@ -0,0 +1,169 @@
+//! Clock and stopwatch, shown on the LEDs, a TM1637 display, and the UART.
+//!
+//! The time of day comes from the software RTC (starting at midnight), and
+//! is shown on the UART as `hh:mm:ss`, on the LEDs as the seconds in
+//! binary, and on a TM1637 4-digit display (CLK on GPIO 0, DIO on GPIO 1)
+//! as `hh:mm`. Keys:
+//!
+//! * `h`/`m`: advance the clock an hour/minute
+//! * space: start/stop the stopwatch; `r`: reset it
+//! * `d`: switch the display between the clock and the stopwatch (`mm:ss`)
+//! * `a`: toggle a chime on every minute (an RTC alarm)
+//! * Ctrl-C: start over
+//!
+//! It doesn't fit in the AttoSoC's 4 KiB, or the HX8K's 8 KiB; build it
+//! for the iCEBreaker (see `io_map` for the boards):
+//!
+//! ```text
+//! cargo build --release --example clock --features board-icebreaker
+//! ```
+
+#![cfg_attr(target_os = "none", no_std)]
+#![no_main]
+
+#[cfg(target_os = "none")]
+use panic_halt as _;
+use sentinel_rt::hal::i2c::GpioPins;
+use sentinel_rt::input::{self, Key};
+use sentinel_rt::prelude::*;
+use sentinel_rt::rtc::{self, Alarm};
+use sentinel_rt::seg7::{self, Tm1637};
+use sentinel_rt::timebase::{self, CLK_HZ, CYCLES_PER_TICK};
+
+/// Redraw the stopwatch this often while it runs, in ticks (about 0.1 s).
+const REFRESH_TICKS: u32 = CLK_HZ / CYCLES_PER_TICK / 10;
+
+#[derive(Default)]
+struct Stopwatch {
+    /// Ticks counted before the last start.
+    total: u32,
+    started: Option<u32>,
+}
+
+impl Stopwatch {
+    fn toggle(&mut self, now: u32) {
+        match self.started.take() {
+            Some(start) => self.total += now.wrapping_sub(start),
+            None => self.started = Some(now),
+        }
+    }
+
+    /// Elapsed time in hundredths of a second.
+    fn centis(&self, now: u32) -> u32 {
+        let ticks = self.total + self.started.map_or(0, |s| now.wrapping_sub(s));
+        (ticks as u64 * CYCLES_PER_TICK as u64 * 100 / CLK_HZ as u64) as u32
+    }
+}
+
+fn two(n: u32) -> [u8; 2] {
+    [b'0' + (n / 10 % 10) as u8, b'0' + (n % 10) as u8]
+}
+
+fn status_line(now: &rtc::DateTime, centis: u32, chime: bool) -> [u8; 35] {
+    let mut line = *b"\r00:00:00  stopwatch 00:00.00      ";
+    line[1..3].copy_from_slice(&two(now.hour as u32));
+    line[4..6].copy_from_slice(&two(now.minute as u32));
+    line[7..9].copy_from_slice(&two(now.second as u32));
+    line[21..23].copy_from_slice(&two(centis / 6000));
+    line[24..26].copy_from_slice(&two(centis / 100 % 60));
+    line[27..29].copy_from_slice(&two(centis % 100));
+    if chime {
+        line[29..].copy_from_slice(b" chime");
+    }
+    line
+}
+
+/// Send `bytes`, keeping the tick count going. With interrupts off, the
+/// timer's IRQ flag is the only record of a tick, and a byte at 9600 baud
+/// takes most of a tick to send.
+fn send(ser: &mut Serial, timer: &mut Timer, bytes: &[u8]) {
+    for b in bytes {
+        ser.write_byte(*b);
+        if timer.ack() {
+            timebase::tick();
+        }
+    }
+}
+
+#[entry]
+fn main() -> ! {
+    let Soc { gpio, mut serial, mut timer, .. } = Soc::init();
+    // The TM1637 only uses the bidirectional pins, so a second handle can
+    // have the LEDs.
+    let mut leds = Gpio::new(gpio.base());
+    let mut disp = Tm1637::new(GpioPins::new(gpio, 0, 1));
+
+    let mut sw = Stopwatch::default();
+    let mut show_stopwatch = false;
+    let mut last_sec = u64::MAX;
+    let mut last_refresh = 0;
+    let mut ding: u8 = 0;
+    input::on_interrupt(input::restart);
+
+    loop {
+        if timer.ack() {
+            timebase::tick();
+        }
+        let ticks = timebase::ticks32();
+
+        let mut redraw = false;
+        input::poll(&mut serial);
+        if let Some(Key::Char(b)) = input::next() {
+            let now = rtc::unix();
+            match b {
+                b'h' => rtc::set_unix(now + 3600),
+                b'm' => rtc::set_unix(now + 60),
+                b' ' => sw.toggle(ticks),
+                b'r' => sw = Stopwatch::default(),
+                b'd' => show_stopwatch = !show_stopwatch,
+                b'a' => match rtc::alarm() {
+                    Some(_) => rtc::cancel_alarm(),
+                    None => rtc::set_alarm(Alarm::every(now - now % 60 + 60, 60)),
+                },
+                _ => {}
+            }
+            redraw = true;
+        }
+
+        if rtc::alarm_due() {
+            send(&mut serial, &mut timer, b"\r\n*ding*\r\n");
+            ding = 4;
+        }
+
+        let unix = rtc::unix();
+        let running = sw.started.is_some();
+        if unix != last_sec {
+            last_sec = unix;
+            ding = ding.saturating_sub(1);
+            redraw = true;
+        }
+        if running && ticks.wrapping_sub(last_refresh) >= REFRESH_TICKS {
+            redraw = true;
+        }
+        if !redraw {
+            continue;
+        }
+        last_refresh = ticks;
+
+        let now = rtc::now();
+        let centis = sw.centis(ticks);
+        let line = status_line(&now, centis, rtc::alarm().is_some());
+        send(&mut serial, &mut timer, &line);
+
+        // Flash everything for a few seconds after the chime.
+        leds.set_leds(match ding {
+            0 => now.second,
+            d if d.is_multiple_of(2) => 0xff,
+            _ => 0,
+        });
+
+        let colon = now.second.is_multiple_of(2);
+        let segs = if show_stopwatch {
+            seg7::clock((centis / 6000) as u8, (centis / 100 % 60) as u8, colon)
+        } else {
+            seg7::clock(now.hour, now.minute, colon)
+        };
+        // No display attached is fine; the UART still works.
+        let _ = disp.show(&segs);
+    }
+}
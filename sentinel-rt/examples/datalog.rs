@@ -0,0 +1,200 @@
+//! Data logger: sample the GPIO inputs once a second, and append them to a
+//! CSV log in SPI flash, which can be dumped or cleared from a shell on the
+//! UART.
+//!
+//! ```text
+//! > dump
+//! ticks,inputs
+//! 000002dc,03
+//! 000005b8,01
+//! > clear
+//! ```
+//!
+//! The flash is any SPI NOR part (a PMOD, say) on GPIO pins 0-3, driven by
+//! [`hal::spi`](sentinel_rt::hal::spi): CS on 0, MOSI 1, MISO 2 and SCK 3.
+//! The log is its first 4 KiB sector, so survives power cycles. Erased
+//! flash reads `ff`, so the log ends at the first record starting with
+//! one, and logging stops when the sector is full. The inputs logged are
+//! pins 4-7.
+//!
+//! It doesn't fit in the AttoSoC's 4 KiB, or the HX8K's 8 KiB; build it
+//! for the iCEBreaker (see `io_map` for the boards):
+//!
+//! ```text
+//! cargo build --release --example datalog --features board-icebreaker
+//! ```
+
+#![cfg_attr(target_os = "none", no_std)]
+#![no_main]
+
+#[cfg(target_os = "none")]
+use panic_halt as _;
+use core::cell::RefCell;
+use core::time::Duration;
+use critical_section::Mutex;
+use sentinel_rt::hal::spi::{Pins, Spi};
+use sentinel_rt::prelude::*;
+use sentinel_rt::shell::{Command, Shell};
+use sentinel_rt::{hex, io_addrs, periodic, timebase};
+
+const PINS: Pins = Pins { sck: 3, mosi: 1, miso: 2, cs: 0 };
+/// The first pin that isn't the flash's.
+const INPUTS: u8 = 4;
+
+/// `"tttttttt,ii\n"`
+const RECORD: usize = 12;
+const LOG_LEN: u32 = 4096;
+const PAGE: u32 = 256;
+
+// SPI NOR flash commands, common to (almost) every part.
+const WRITE_ENABLE: u8 = 0x06;
+const READ_STATUS: u8 = 0x05;
+const READ: u8 = 0x03;
+const PAGE_PROGRAM: u8 = 0x02;
+const SECTOR_ERASE: u8 = 0x20;
+const WAKE: u8 = 0xab;
+/// Status bit: a program or erase is in progress.
+const BUSY: u8 = 0x01;
+
+struct Flash {
+    spi: Spi,
+    /// Bytes of log, found at boot by looking for the first erased record.
+    len: u32,
+}
+
+impl Flash {
+    fn new(spi: Spi) -> Self {
+        let mut flash = Self { spi, len: 0 };
+        // Some parts are left asleep after loading the FPGA.
+        flash.spi.transfer(&mut [WAKE]);
+        let mut first = [0];
+        while flash.len + RECORD as u32 <= LOG_LEN {
+            flash.read(flash.len, &mut first);
+            if first[0] == 0xff {
+                break;
+            }
+            flash.len += RECORD as u32;
+        }
+        flash
+    }
+
+    /// A command with a 24-bit address, then `data`, in `buf`.
+    fn command(cmd: u8, addr: u32, data: &[u8]) -> ([u8; 4 + RECORD], usize) {
+        let mut buf = [0; 4 + RECORD];
+        buf[0] = cmd;
+        buf[1..4].copy_from_slice(&addr.to_be_bytes()[1..]);
+        buf[4..4 + data.len()].copy_from_slice(data);
+        (buf, 4 + data.len())
+    }
+
+    fn read(&mut self, addr: u32, out: &mut [u8]) {
+        let (mut buf, len) = Self::command(READ, addr, &[0; RECORD][..out.len()]);
+        self.spi.transfer(&mut buf[..len]);
+        out.copy_from_slice(&buf[4..len]);
+    }
+
+    /// Program or erase, and wait for it to finish.
+    fn write(&mut self, buf: &mut [u8]) {
+        self.spi.transfer(&mut [WRITE_ENABLE]);
+        self.spi.transfer(buf);
+        loop {
+            let mut status = [READ_STATUS, 0];
+            self.spi.transfer(&mut status);
+            if status[1] & BUSY == 0 {
+                break;
+            }
+        }
+    }
+
+    fn append(&mut self, record: &[u8; RECORD]) -> bool {
+        if self.len + RECORD as u32 > LOG_LEN {
+            return false;
+        }
+        // A page program wraps at the end of the page, so a record
+        // straddling two takes two.
+        let mut done = 0;
+        while done < RECORD {
+            let addr = self.len + done as u32;
+            let n = (RECORD - done).min((PAGE - addr % PAGE) as usize);
+            let (mut buf, len) = Self::command(PAGE_PROGRAM, addr, &record[done..done + n]);
+            self.write(&mut buf[..len]);
+            done += n;
+        }
+        self.len += RECORD as u32;
+        true
+    }
+
+    fn clear(&mut self) {
+        let (mut buf, len) = Self::command(SECTOR_ERASE, 0, &[]);
+        self.write(&mut buf[..len]);
+        self.len = 0;
+    }
+}
+
+static FLASH: Mutex<RefCell<Option<Flash>>> = Mutex::new(RefCell::new(None));
+
+fn flash<R>(f: impl FnOnce(&mut Flash) -> R) -> R {
+    critical_section::with(|cs| f(FLASH.borrow_ref_mut(cs).as_mut().unwrap()))
+}
+
+fn sample() {
+    let Some(bases) = io_addrs::detected() else {
+        return;
+    };
+    // Reading doesn't disturb the SPI pins, which are outputs.
+    let inputs = Gpio::new(bases.gpio).read_inputs() >> INPUTS;
+
+    let mut record = *b"00000000,00\n";
+    record[..8].copy_from_slice(&hex::u32_digits(timebase::ticks32()));
+    record[9..11].copy_from_slice(&hex::u8_digits(inputs));
+    // Full; dropped.
+    let _ = flash(|f| f.append(&record));
+}
+
+static COMMANDS: &[Command] = sentinel_rt::commands! {
+    /// Print the log as CSV.
+    dump => |_, out| {
+        out.write_str("ticks,inputs\r\n");
+        let mut record = [0; RECORD];
+        let mut addr = 0;
+        while addr < flash(|f| f.len) {
+            flash(|f| f.read(addr, &mut record));
+            out.write_bytes(&record[..RECORD - 1]);
+            out.write_bytes(b"\r\n");
+            addr += RECORD as u32;
+        }
+        Ok(())
+    },
+    /// Erase the log.
+    clear => |_, _| {
+        flash(Flash::clear);
+        Ok(())
+    },
+    /// Bytes used, out of the total.
+    status => |_, out| {
+        out.write_bytes(&hex::u32_digits(flash(|f| f.len)));
+        out.write_str(" / ");
+        out.write_bytes(&hex::u32_digits(LOG_LEN));
+        out.write_bytes(b"\r\n");
+        Ok(())
+    },
+};
+
+#[entry]
+fn main() -> ! {
+    let Soc { mut serial, mut timer, gpio, .. } = Soc::init();
+    let spi = Spi::new(gpio, PINS);
+    critical_section::with(|cs| FLASH.borrow(cs).replace(Some(Flash::new(spi))));
+
+    let _ = periodic::every(Duration::from_secs(1), sample);
+    let mut shell = Shell::<16>::new(COMMANDS);
+    shell.prompt(&mut serial);
+
+    loop {
+        if timer.ack() {
+            timebase::tick();
+            periodic::run_due();
+        }
+        shell.poll(&mut serial);
+    }
+}
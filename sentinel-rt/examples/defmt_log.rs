@@ -0,0 +1,65 @@
+//! defmt logging on the console UART, between plain text.
+//!
+//! Echoes what it receives as text, and logs each byte with defmt, and
+//! the time every second from the timer ISR. The messages are deferred,
+//! for the UART's TX interrupt to send between the echoed bytes. It
+//! doesn't fit in the AttoSoC's 4 KiB; use a bigger board (see `io_map`).
+//! defmt leaves out all but errors unless `DEFMT_LOG` says otherwise, so
+//! build and watch it with:
+//!
+//! ```text
+//! DEFMT_LOG=debug RUSTFLAGS="-C link-arg=-Tdefmt.x" \
+//!     cargo build --release --example defmt_log --features defmt,board-icebreaker
+//! defmt-split /dev/ttyUSB0 | defmt-print -e target/riscv32i-unknown-none-elf/release/examples/defmt_log
+//! ```
+
+#![cfg_attr(target_os = "none", no_std)]
+#![no_main]
+
+#[cfg(target_os = "none")]
+use panic_halt as _;
+use core::time::Duration;
+use riscv::register::{mie, mstatus};
+use sentinel_rt::defmt_uart;
+use sentinel_rt::hal::serial::Port;
+use sentinel_rt::irq::{self, Handler};
+use sentinel_rt::periodic;
+use sentinel_rt::prelude::*;
+use sentinel_rt::timebase;
+
+static PORT: Port<16, 128> = Port::new();
+
+#[no_mangle]
+#[allow(non_snake_case)]
+fn MachineExternal() {
+    irq::dispatch();
+}
+
+#[entry]
+fn main() -> ! {
+    let soc = Soc::init();
+    // The UART has to be attached before interrupts go on: the Wishbone
+    // UART's IRQ is pending from reset, and only the port can clear it.
+    PORT.attach(soc.serial.base());
+    PORT.set_tx_source(defmt_uart::pop);
+    defmt_uart::set_deferred(|| PORT.start_tx());
+    irq::set(Handler::Serial(|| PORT.on_interrupt()));
+    irq::set(Handler::Timer(periodic::run_due));
+    let _ = periodic::every(Duration::from_secs(1), || {
+        defmt::info!("tick {=u32}", timebase::ticks32());
+    });
+    // SAFETY: The ISR's state is all set up.
+    unsafe {
+        mie::set_mext();
+        mstatus::set_mie();
+    }
+
+    PORT.write_all(b"defmt_log\r\n");
+    defmt::info!("up at tick {=u32}", timebase::ticks32());
+    loop {
+        while let Some(b) = PORT.read() {
+            PORT.write_all(&[b]);
+            defmt::debug!("got {=u8:#x}", b);
+        }
+    }
+}
@@ -1,14 +1,8 @@
-MEMORY
-{
-    RAM : ORIGIN = 0x00000000, LENGTH = 4K
-}
-
-REGION_ALIAS("REGION_TEXT", RAM);
-REGION_ALIAS("REGION_RODATA", RAM);
-REGION_ALIAS("REGION_DATA", RAM);
-REGION_ALIAS("REGION_BSS", RAM);
-REGION_ALIAS("REGION_HEAP", RAM);
-REGION_ALIAS("REGION_STACK", RAM);
+/* The board's RAM, and which of it each section goes in, from its
+   description. See sentinel_rt::io_map. */
+INCLUDE board.x
 
 _hart_stack_size = 256;
-INCLUDE link.x
+/* riscv-rt's link.x, or minimal.x with the `minimal` feature. */
+INCLUDE rt.x
+INCLUDE sentinel.x
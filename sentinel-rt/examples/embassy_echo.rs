@@ -0,0 +1,67 @@
+//! UART echo and an LED blinker, running at once on `embassy-time`.
+//!
+//! Two `async` loops, joined into one future on the single-task executor:
+//! one echoes whatever arrives on the UART, the other toggles LED 0 every
+//! half second with an `embassy_time::Ticker`. Between events the core
+//! sleeps in `wfi`; the UART's interrupt and the timer tick wake it.
+//!
+//! Needs the `embassy-time` feature:
+//!
+//! ```text
+//! cargo build --release --example embassy_echo --features embassy-time
+//! ```
+
+#![cfg_attr(target_os = "none", no_std)]
+#![no_main]
+
+use embassy_futures::join::join;
+use embassy_time::{Duration, Ticker};
+#[cfg(target_os = "none")]
+use panic_halt as _;
+use riscv::register::{mie, mstatus};
+use sentinel_rt::executor;
+use sentinel_rt::hal::gpio::Led;
+use sentinel_rt::hal::serial::Port;
+use sentinel_rt::irq::{self, Handler};
+use sentinel_rt::prelude::*;
+
+static CONSOLE: Port<16, 64> = Port::new();
+
+#[no_mangle]
+#[allow(non_snake_case)]
+fn MachineExternal() {
+    irq::dispatch();
+}
+
+async fn echo() -> ! {
+    let con = CONSOLE.as_async();
+    loop {
+        let b = con.read_byte().await;
+        con.write_all(&[b]).await;
+    }
+}
+
+async fn blink(mut led: Led) -> ! {
+    let mut ticker = Ticker::every(Duration::from_millis(500));
+    loop {
+        led.set(!led.is_on());
+        ticker.next().await;
+    }
+}
+
+#[entry]
+fn main() -> ! {
+    let soc = Soc::init();
+    // As in async_echo: the port has to own the UART's IRQ before
+    // interrupts go on. The timer needs nothing; dispatch ticks it.
+    CONSOLE.attach(soc.serial.base());
+    irq::set(Handler::Serial(|| CONSOLE.on_interrupt()));
+    let [led, ..] = soc.gpio.split().leds;
+    // SAFETY: The ISR's state is all set up.
+    unsafe {
+        mie::set_mext();
+        mstatus::set_mie();
+    }
+
+    executor::block_on(join(echo(), blink(led))).0
+}
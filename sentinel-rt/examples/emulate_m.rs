@@ -0,0 +1,113 @@
+//! The M extension's instructions, emulated.
+//!
+//! Runs each of the eight (as `.insn`, since the example itself is built
+//! for rv32i) on a spread of operands, and checks what lands in `rd`
+//! against the same arithmetic done the rv32i way:
+//!
+//! ```text
+//! mul    ok
+//! mulh   ok
+//! ...
+//! remu   ok
+//! ```
+//!
+//! A wrong result prints its operands, and the line ends `FAIL`. It needs
+//! no input and prints the same thing every run, so a simulation's UART
+//! output can be checked against `emulate_m.expect`:
+//!
+//! ```text
+//! cargo build --release --example emulate_m --features emulate-m,board-icebreaker
+//! uart-replay sentinel-rt/examples/emulate_m.expect --vcd sim.vcd
+//! ```
+
+#![cfg_attr(target_os = "none", no_std)]
+#![no_main]
+
+#[cfg(target_os = "none")]
+use panic_halt as _;
+use sentinel_rt::hex;
+use sentinel_rt::prelude::*;
+use sentinel_rt::shell::Output;
+
+type Op = fn(u32, u32) -> u32;
+
+/// The M instruction with this `funct3`, on `rs1` and `rs2`.
+macro_rules! m_op {
+    ($funct3:literal) => {
+        |a: u32, b: u32| -> u32 {
+            #[cfg(target_arch = "riscv32")]
+            {
+                let rd;
+                // SAFETY: Just arithmetic, once the trap handler's done it.
+                unsafe {
+                    core::arch::asm!(
+                        ".insn r 0x33, {f}, 1, {rd}, {rs1}, {rs2}",
+                        f = const $funct3,
+                        rd = out(reg) rd,
+                        rs1 = in(reg) a,
+                        rs2 = in(reg) b,
+                    )
+                };
+                rd
+            }
+            #[cfg(not(target_arch = "riscv32"))]
+            expected($funct3, a, b)
+        }
+    };
+}
+
+/// What the spec says `funct3` gives.
+fn expected(funct3: u32, a: u32, b: u32) -> u32 {
+    let (sa, sb) = (a as i32 as i64, b as i32 as i64);
+    match funct3 {
+        0 => a.wrapping_mul(b),
+        1 => ((sa * sb) >> 32) as u32,
+        2 => ((sa * b as i64) >> 32) as u32,
+        3 => ((a as u64 * b as u64) >> 32) as u32,
+        4 if b == 0 => u32::MAX,
+        4 => (a as i32).wrapping_div(b as i32) as u32,
+        5 => a.checked_div(b).unwrap_or(u32::MAX),
+        6 if b == 0 => a,
+        6 => (a as i32).wrapping_rem(b as i32) as u32,
+        _ => a.checked_rem(b).unwrap_or(a),
+    }
+}
+
+const OPERANDS: [u32; 10] =
+    [0, 1, 7, 0x1234_5678, 0x7fff_ffff, 0x8000_0000, 0xffff_ffff, 0xffff_fff9, 0xedcb_a988, 0x1_0000];
+
+#[entry]
+fn main() -> ! {
+    let Soc { mut serial, .. } = Soc::init();
+
+    let ops: [(&str, Op); 8] = [
+        ("mul    ", m_op!(0)),
+        ("mulh   ", m_op!(1)),
+        ("mulhsu ", m_op!(2)),
+        ("mulhu  ", m_op!(3)),
+        ("div    ", m_op!(4)),
+        ("divu   ", m_op!(5)),
+        ("rem    ", m_op!(6)),
+        ("remu   ", m_op!(7)),
+    ];
+    for (funct3, (name, op)) in (0..).zip(ops) {
+        serial.write_str(name);
+        let mut ok = true;
+        for a in OPERANDS {
+            for b in OPERANDS {
+                if op(a, b) != expected(funct3, a, b) {
+                    serial.write_bytes(&hex::u32_digits(a));
+                    serial.write_str(" ");
+                    serial.write_bytes(&hex::u32_digits(b));
+                    serial.write_str(" ");
+                    ok = false;
+                }
+            }
+        }
+        serial.write_str(if ok { "ok\r\n" } else { "FAIL\r\n" });
+    }
+
+    loop {
+        serial.read_byte();
+    }
+}
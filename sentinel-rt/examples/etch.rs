@@ -0,0 +1,141 @@
+//! Etch-a-sketch on a VT100 terminal.
+//!
+//! Arrow keys move the pen, drawing with the current brush. Keys:
+//!
+//! * `1`-`4`: brush (`#`, `*`, `.`, or the eraser)
+//! * space: lift/lower the pen
+//! * `c`: clear
+//! * `s`/`l`: save/load the sketch
+//! * Ctrl-C: start over
+//!
+//! The saved sketch lives in `.noinit`, so it survives a soft reset (and a
+//! simulator can preload one), but not a power cycle.
+//!
+//! It doesn't fit in the AttoSoC's 4 KiB, or the HX8K's 8 KiB; build it
+//! for the iCEBreaker (see `io_map` for the boards):
+//!
+//! ```text
+//! cargo build --release --example etch --features board-icebreaker
+//! ```
+
+#![cfg_attr(target_os = "none", no_std)]
+#![no_main]
+
+#[cfg(target_os = "none")]
+use panic_halt as _;
+use core::mem::MaybeUninit;
+use core::ptr::addr_of_mut;
+use sentinel_rt::checksum::crc32;
+use sentinel_rt::prelude::*;
+use sentinel_rt::input::{self, Key};
+use sentinel_rt::term::{Attr, Cell, Color, Grid, Renderer};
+
+const W: usize = 32;
+const H: usize = 10;
+
+const BRUSHES: [u8; 4] = [b'#', b'*', b'.', b' '];
+
+#[repr(C)]
+struct Saved {
+    crc: u32,
+    chars: [[u8; W]; H],
+}
+
+// Drawing area, and a status line underneath.
+static mut GRID: Grid<W, { H + 1 }> = Grid::new();
+
+#[link_section = ".noinit.etch"]
+static mut SAVED: MaybeUninit<Saved> = MaybeUninit::uninit();
+
+fn save(grid: &Grid<W, { H + 1 }>, saved: &mut Saved) {
+    for (y, row) in saved.chars.iter_mut().enumerate() {
+        for (x, c) in row.iter_mut().enumerate() {
+            *c = grid.cell(x, y).map_or(b' ', Cell::ch);
+        }
+    }
+    saved.crc = crc32(saved.chars.as_flattened());
+}
+
+fn load(grid: &mut Grid<W, { H + 1 }>, saved: &Saved) -> bool {
+    if crc32(saved.chars.as_flattened()) != saved.crc {
+        return false;
+    }
+
+    for (y, row) in saved.chars.iter().enumerate() {
+        for (x, c) in row.iter().enumerate() {
+            grid.set(x, y, Cell::new(*c, Attr::DEFAULT));
+        }
+    }
+    true
+}
+
+fn status(grid: &mut Grid<W, { H + 1 }>, brush: usize, pen_down: bool,
+          msg: &str) {
+    grid.set_attr(Attr::new(Color::Black, Color::Cyan));
+    grid.set_cursor(0, H);
+    grid.write_bytes(&[b'[', BRUSHES[brush], b']']);
+    grid.write_str(if pen_down { " down " } else { " up   " });
+    grid.write_str(msg);
+    while grid.cursor().0 < W - 1 {
+        grid.put(b' ');
+    }
+    grid.set_attr(Attr::DEFAULT);
+}
+
+#[entry]
+fn main() -> ! {
+    let mut soc = Soc::init();
+    // SAFETY: Single-threaded, and these are the only references made.
+    let grid = unsafe { &mut *addr_of_mut!(GRID) };
+    // SAFETY: Any bit pattern is a valid Saved; load() checks the CRC.
+    let saved = unsafe { (*addr_of_mut!(SAVED)).assume_init_mut() };
+
+    let mut term = Renderer::new();
+    let (mut x, mut y) = (W / 2, H / 2);
+    let mut brush = 0;
+    let mut pen_down = true;
+
+    input::on_interrupt(input::restart);
+    term.reset(grid, |b| soc.serial.write_bytes(b));
+    status(grid, brush, pen_down, "arrows draw");
+
+    loop {
+        grid.set_cursor(x, y);
+        term.render(grid, |b| soc.serial.write_bytes(b));
+
+        input::poll(&mut soc.serial);
+        let Some(key) = input::next() else {
+            continue;
+        };
+
+        let mut msg = "";
+        match key {
+            Key::Up => y = y.saturating_sub(1),
+            Key::Down => y = (y + 1).min(H - 1),
+            Key::Left => x = x.saturating_sub(1),
+            Key::Right => x = (x + 1).min(W - 1),
+            Key::Char(c @ b'1'..=b'4') => brush = (c - b'1') as usize,
+            Key::Char(b' ') => pen_down = !pen_down,
+            Key::Char(b'c') => {
+                for y in 0..H {
+                    for x in 0..W {
+                        grid.set(x, y, Cell::BLANK);
+                    }
+                }
+            }
+            Key::Char(b's') => {
+                save(grid, saved);
+                msg = "saved";
+            }
+            Key::Char(b'l') => {
+                msg = if load(grid, saved) { "loaded" } else { "nothing saved" };
+            }
+            Key::Char(_) | Key::Escape | Key::Interrupt => continue,
+        }
+
+        if pen_down && matches!(key, Key::Up | Key::Down | Key::Left | Key::Right) {
+            grid.set(x, y, Cell::new(BRUSHES[brush], Attr::DEFAULT));
+        }
+        status(grid, brush, pen_down, msg);
+    }
+}
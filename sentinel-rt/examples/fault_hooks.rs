@@ -0,0 +1,70 @@
+//! Faulting accesses, with the trap hooks' entry.
+//!
+//! `fault-handler` skips a faulting access by writing `mepc`, and with
+//! `trap-hooks` (or `emulate-*` or `kernel`, which use its trap entry) the
+//! trap has to return there rather than to the `mepc` it saved on the way
+//! in. This takes a misaligned load and a misaligned store, and checks
+//! each comes back as a [`Fault`], having been through the exit hook once:
+//!
+//! ```text
+//! load  ok
+//! store ok
+//! ```
+//!
+//! A wrong cause, or hook count, ends the line `FAIL`; if the skip were
+//! lost, the first access would trap forever and nothing would print. It
+//! needs no input and prints the same thing every run, so a simulation's
+//! UART output can be checked against `fault_hooks.expect`:
+//!
+//! ```text
+//! cargo build --release --example fault_hooks --features fault-handler,trap-hooks,board-icebreaker
+//! uart-replay sentinel-rt/examples/fault_hooks.expect --vcd sim.vcd
+//! ```
+//!
+//! `fault-handler`'s exception report doesn't leave room for it in the
+//! AttoSoC's 4 KiB.
+//!
+//! [`Fault`]: sentinel_rt::fault::Fault
+
+#![cfg_attr(target_os = "none", no_std)]
+#![no_main]
+
+#[cfg(target_os = "none")]
+use panic_halt as _;
+use sentinel_rt::atomic::{AtomicU32, Ordering};
+use sentinel_rt::fault::{self, Cause, Fault};
+use sentinel_rt::io_map;
+use sentinel_rt::prelude::*;
+use sentinel_rt::shell::Output;
+use sentinel_rt::trap::{self, TrapFrame};
+
+static EXITS: AtomicU32 = AtomicU32::new(0);
+
+fn count(_: &mut TrapFrame) {
+    EXITS.fetch_add(1, Ordering::Relaxed);
+}
+
+fn check(serial: &mut Serial, name: &str, r: Result<(), Fault>, cause: Cause, exits: u32) {
+    serial.write_str(name);
+    let ok = r.map_err(|f| f.cause) == Err(cause) && EXITS.load(Ordering::Relaxed) == exits;
+    serial.write_str(if ok { "ok\r\n" } else { "FAIL\r\n" });
+}
+
+#[entry]
+fn main() -> ! {
+    let Soc { mut serial, .. } = Soc::init();
+    trap::on_trap_exit(Some(count));
+
+    // Misaligned, so the core traps without the bus having to report
+    // anything.
+    let addr = io_map::RAM_ORIGIN as usize + 1;
+    // SAFETY: Both trap before touching RAM.
+    let load = unsafe { fault::try_read_volatile(addr) }.map(drop);
+    check(&mut serial, "load  ", load, Cause::LoadMisaligned, 1);
+    let store = unsafe { fault::try_write_volatile(addr, 0) };
+    check(&mut serial, "store ", store, Cause::StoreMisaligned, 2);
+
+    loop {
+        serial.read_byte();
+    }
+}
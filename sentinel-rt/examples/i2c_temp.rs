@@ -0,0 +1,106 @@
+//! Read an SHT3x temperature/humidity sensor over bit-banged I2C.
+//!
+//! Wire the sensor to the PMOD's I2C pins (3 = SCL, 4 = SDA; GPIO 0 and 1),
+//! with pull-ups. About once a second, a line like
+//!
+//! ```text
+//! temp_c 23.45 rh 41.20
+//! ```
+//!
+//! is printed to the UART, or what went wrong, e.g.
+//! `i2c: address nack <- sht3x: measure`.
+//!
+//! It doesn't fit in the AttoSoC's 4 KiB, but does in the HX8K's 8 KiB
+//! (see `io_map` for the boards):
+//!
+//! ```text
+//! cargo build --release --example i2c_temp --features board-hx8k
+//! ```
+
+#![cfg_attr(target_os = "none", no_std)]
+#![no_main]
+
+#[cfg(target_os = "none")]
+use panic_halt as _;
+use sentinel_rt::checksum::crc8;
+use sentinel_rt::error::{Ctx, Describe, ResultExt};
+use sentinel_rt::fixed;
+use sentinel_rt::hal::i2c::{self, GpioPins, I2c};
+use sentinel_rt::prelude::*;
+use sentinel_rt::timebase::{CLK_HZ, CYCLES_PER_TICK};
+
+const ADDR: u8 = 0x44;
+/// Single shot, high repeatability, no clock stretching.
+const MEASURE: [u8; 2] = [0x24, 0x00];
+/// Up to 15.5 ms for a high-repeatability measurement.
+const MEASURE_TICKS: u32 = 12;
+
+#[derive(Debug)]
+enum Error {
+    I2c(i2c::Error),
+    Crc,
+}
+
+impl Describe for Error {
+    fn describe(&self) -> &'static str {
+        match self {
+            Self::I2c(e) => e.describe(),
+            Self::Crc => "sht3x: bad crc",
+        }
+    }
+}
+
+/// Temperature in hundredths of a degree C, and relative humidity in
+/// hundredths of a percent.
+fn measure(i2c: &mut I2c<GpioPins>, timer: &mut Timer)
+           -> Result<(i32, i32), Ctx<Error>> {
+    i2c.write(ADDR, &MEASURE)
+        .map_err(Error::I2c)
+        .context("sht3x: measure")?;
+
+    for _ in 0..MEASURE_TICKS {
+        timer.wait_tick();
+    }
+
+    let mut buf = [0; 6];
+    i2c.read(ADDR, &mut buf)
+        .map_err(Error::I2c)
+        .context("sht3x: read")?;
+
+    // Each word is followed by its CRC.
+    let mut words = [0; 2];
+    for (w, chunk) in words.iter_mut().zip(buf.chunks(3)) {
+        if crc8(&chunk[..2]) != chunk[2] {
+            return Err(Ctx::new(Error::Crc).context("sht3x: read"));
+        }
+        *w = i32::from(u16::from_be_bytes([chunk[0], chunk[1]]));
+    }
+
+    let temp = fixed::scale(words[0], 17500, 65535) - 4500;
+    let rh = fixed::scale(words[1], 10000, 65535);
+    Ok((temp, rh))
+}
+
+#[entry]
+fn main() -> ! {
+    let Soc { gpio, mut serial, mut timer, .. } = Soc::init();
+    let mut i2c = I2c::new(GpioPins::new(gpio, 0, 1));
+    let mut buf = [0; fixed::MAX_LEN];
+
+    loop {
+        match measure(&mut i2c, &mut timer) {
+            Ok((temp, rh)) => {
+                serial.write_bytes(b"temp_c ");
+                serial.write_bytes(fixed::format(temp, 2, &mut buf));
+                serial.write_bytes(b" rh ");
+                serial.write_bytes(fixed::format(rh, 2, &mut buf));
+            }
+            Err(e) => e.report(|b| serial.write_bytes(b)),
+        }
+        serial.write_bytes(b"\r\n");
+
+        for _ in 0..CLK_HZ / CYCLES_PER_TICK {
+            timer.wait_tick();
+        }
+    }
+}
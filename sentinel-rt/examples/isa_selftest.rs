@@ -0,0 +1,412 @@
+//! Every RV32I and Zicsr instruction that doesn't trap, checked on the core.
+//!
+//! A quick sanity check of a freshly synthesized core. Each group of
+//! instructions runs on operands chosen for their edge cases (shifts by 31
+//! and by 32, sign extension at every width, `jalr` with an odd target,
+//! CSR writes that set and clear bits), and prints a line:
+//!
+//! ```text
+//! alu     ok
+//! alui    ok
+//! ...
+//! divzero ok
+//! ```
+//!
+//! A failing case prints its number and what the core gave instead, and
+//! the line ends `FAIL`. There's no divide instruction in RV32I;
+//! `divzero` checks that `softmath`'s routines give what they promise for
+//! a zero divisor and `i32::MIN / -1`. It needs no input and prints the
+//! same thing every run, so a simulation's UART output can be checked
+//! against `isa_selftest.expect`:
+//!
+//! ```text
+//! cargo build --release --example isa_selftest --features minimal
+//! uart-replay sentinel-rt/examples/isa_selftest.expect --vcd sim.vcd
+//! ```
+//!
+//! It only fits in the AttoSoC's 4 KiB with the `minimal` start-up.
+//!
+//! Only the core's answers mean anything: built for the host, every
+//! instruction gives 0, and every group fails.
+
+#![cfg_attr(target_os = "none", no_std)]
+#![no_main]
+
+#[cfg(target_os = "none")]
+use panic_halt as _;
+use core::ptr::addr_of_mut;
+use sentinel_rt::prelude::*;
+use sentinel_rt::{console, hex, io_addrs, softmath};
+
+/// `op rd, rs1, rs2`.
+macro_rules! r_op {
+    ($op:literal, $a:expr, $b:expr) => {{
+        let (a, b): (u32, u32) = ($a, $b);
+        #[cfg(target_arch = "riscv32")]
+        {
+            let rd: u32;
+            // SAFETY: Just arithmetic.
+            unsafe {
+                core::arch::asm!(
+                    concat!($op, " {rd}, {a}, {b}"),
+                    rd = out(reg) rd, a = in(reg) a, b = in(reg) b,
+                    options(pure, nomem, nostack)
+                )
+            };
+            rd
+        }
+        #[cfg(not(target_arch = "riscv32"))]
+        {
+            let _ = (a, b);
+            0
+        }
+    }};
+}
+
+/// `op rd, rs1, imm`.
+macro_rules! i_op {
+    ($op:literal, $a:expr, $imm:literal) => {{
+        let a: u32 = $a;
+        #[cfg(target_arch = "riscv32")]
+        {
+            let rd: u32;
+            // SAFETY: Just arithmetic.
+            unsafe {
+                core::arch::asm!(
+                    concat!($op, " {rd}, {a}, {imm}"),
+                    rd = out(reg) rd, a = in(reg) a, imm = const $imm,
+                    options(pure, nomem, nostack)
+                )
+            };
+            rd
+        }
+        #[cfg(not(target_arch = "riscv32"))]
+        {
+            let _ = a;
+            0
+        }
+    }};
+}
+
+/// 1 if `op rs1, rs2` branches, else 0.
+macro_rules! b_op {
+    ($op:literal, $a:expr, $b:expr) => {{
+        let (a, b): (u32, u32) = ($a, $b);
+        #[cfg(target_arch = "riscv32")]
+        {
+            let taken: u32;
+            // SAFETY: Branches only within the block.
+            unsafe {
+                core::arch::asm!(
+                    "li {t}, 1",
+                    concat!($op, " {a}, {b}, 2f"),
+                    "li {t}, 0",
+                    "2:",
+                    t = out(reg) taken, a = in(reg) a, b = in(reg) b,
+                    options(pure, nomem, nostack)
+                )
+            };
+            taken
+        }
+        #[cfg(not(target_arch = "riscv32"))]
+        {
+            let _ = (a, b);
+            0
+        }
+    }};
+}
+
+/// `op rd, offset(base)`.
+macro_rules! load {
+    ($op:literal, $base:expr, $off:literal) => {{
+        let base: *mut u32 = $base;
+        #[cfg(target_arch = "riscv32")]
+        {
+            let rd: u32;
+            // SAFETY: The caller keeps `base + offset` inside MEM.
+            unsafe {
+                core::arch::asm!(
+                    concat!($op, " {rd}, {off}({base})"),
+                    rd = out(reg) rd, base = in(reg) base, off = const $off,
+                    options(readonly, nostack)
+                )
+            };
+            rd
+        }
+        #[cfg(not(target_arch = "riscv32"))]
+        {
+            let _ = base;
+            0
+        }
+    }};
+}
+
+/// `op val, offset(base)`.
+macro_rules! store {
+    ($op:literal, $val:expr, $base:expr, $off:literal) => {{
+        let (val, base): (u32, *mut u32) = ($val, $base);
+        #[cfg(target_arch = "riscv32")]
+        // SAFETY: The caller keeps `base + offset` inside MEM.
+        unsafe {
+            core::arch::asm!(
+                concat!($op, " {val}, {off}({base})"),
+                val = in(reg) val, base = in(reg) base, off = const $off,
+                options(nostack)
+            )
+        };
+        #[cfg(not(target_arch = "riscv32"))]
+        let _ = (val, base);
+    }};
+}
+
+/// `op rd, mscratch, rs1`, giving the old value.
+macro_rules! csr_op {
+    ($op:literal, $val:expr) => {{
+        let val: u32 = $val;
+        #[cfg(target_arch = "riscv32")]
+        {
+            let rd: u32;
+            // SAFETY: Nothing else uses mscratch.
+            unsafe {
+                core::arch::asm!(
+                    concat!($op, " {rd}, mscratch, {val}"),
+                    rd = out(reg) rd, val = in(reg) val,
+                    options(nomem, nostack)
+                )
+            };
+            rd
+        }
+        #[cfg(not(target_arch = "riscv32"))]
+        {
+            let _ = val;
+            0
+        }
+    }};
+}
+
+/// `op rd, mscratch, uimm`, giving the old value.
+macro_rules! csr_imm {
+    ($op:literal, $uimm:literal) => {{
+        #[cfg(target_arch = "riscv32")]
+        {
+            let rd: u32;
+            // SAFETY: Nothing else uses mscratch.
+            unsafe {
+                core::arch::asm!(
+                    concat!($op, " {rd}, mscratch, {uimm}"),
+                    rd = out(reg) rd, uimm = const $uimm,
+                    options(nomem, nostack)
+                )
+            };
+            rd
+        }
+        #[cfg(not(target_arch = "riscv32"))]
+        0
+    }};
+}
+
+/// Print `name`, then `ok`, or the number of each case where the core's
+/// answer (first) isn't the right one (second), what it was, and `FAIL`.
+macro_rules! report {
+    ($name:literal, [$(($got:expr, $want:expr)),* $(,)?]) => {
+        // The right answers are constants, kept out of the code.
+        report($name, &[$($got),*], &[$($want),*])
+    };
+}
+
+fn report(name: &str, got: &[u32], want: &[u32]) {
+    console::write_bytes(name.as_bytes());
+    let mut ok = true;
+    for (i, (&got, &want)) in got.iter().zip(want).enumerate() {
+        if got != want {
+            console::write_bytes(&hex::u8_digits(i as u8));
+            console::write_bytes(b":");
+            console::write_bytes(&hex::u32_digits(got));
+            console::write_bytes(b" ");
+            ok = false;
+        }
+    }
+    console::write_bytes(if ok { b"ok\r\n" } else { b"FAIL\r\n" });
+}
+
+fn alu() {
+    report!("alu     ", [
+        (r_op!("add", 0x7fff_ffff, 1), 0x8000_0000),
+        (r_op!("sub", 0, 1), 0xffff_ffff),
+        (r_op!("sll", 1, 31), 0x8000_0000),
+        // Only the low 5 bits of the amount count.
+        (r_op!("sll", 1, 32), 1),
+        (r_op!("slt", 0xffff_ffff, 0), 1),
+        (r_op!("sltu", 0xffff_ffff, 0), 0),
+        (r_op!("xor", 0xff00_ff00, 0x0ff0_0ff0), 0xf0f0_f0f0),
+        (r_op!("srl", 0x8000_0000, 31), 1),
+        (r_op!("sra", 0x8000_0000, 31), 0xffff_ffff),
+        (r_op!("sra", 0x7fff_ffff, 63), 0),
+        (r_op!("or", 0xf0f0_f0f0, 0x0f0f_0f0f), 0xffff_ffff),
+        (r_op!("and", 0xf0f0_f0f0, 0x3c3c_3c3c), 0x3030_3030),
+    ]);
+}
+
+fn alu_imm() {
+    report!("alui    ", [
+        (i_op!("addi", 0, -1), 0xffff_ffff),
+        (i_op!("addi", 0x7fff_ffff, 1), 0x8000_0000),
+        (i_op!("slti", 0xffff_fffe, -1), 1),
+        // The immediate is sign-extended, then compared unsigned.
+        (i_op!("sltiu", 5, -1), 1),
+        (i_op!("xori", 0x1234_5678, -1), 0xedcb_a987),
+        (i_op!("ori", 0x100, 0xff), 0x1ff),
+        (i_op!("andi", 0xffff_ffff, 0x7ff), 0x7ff),
+        (i_op!("andi", 0x1234_5678, -2048), 0x1234_5000),
+        (i_op!("slli", 1, 31), 0x8000_0000),
+        (i_op!("srli", 0xffff_ffff, 31), 1),
+        (i_op!("srai", 0x8000_0000, 31), 0xffff_ffff),
+        (i_op!("srai", 0x7fff_ffff, 4), 0x07ff_ffff),
+    ]);
+}
+
+/// `lui`'s result, and how far apart two `auipc`s a word apart land.
+fn upper() -> (u32, u32) {
+    #[cfg(target_arch = "riscv32")]
+    {
+        let (lui, a, b): (u32, u32, u32);
+        // SAFETY: Just arithmetic.
+        unsafe {
+            core::arch::asm!(
+                "lui {lui}, 0xfffff",
+                "auipc {a}, 0",
+                "auipc {b}, 1",
+                lui = out(reg) lui, a = out(reg) a, b = out(reg) b,
+                options(pure, nomem, nostack)
+            )
+        };
+        (lui, b.wrapping_sub(a))
+    }
+    #[cfg(not(target_arch = "riscv32"))]
+    (0, 0)
+}
+
+/// What `jal` and `jalr` link, relative to the `auipc` before them, and
+/// whether anything they jump over ran.
+fn jumps() -> (u32, u32, u32) {
+    #[cfg(target_arch = "riscv32")]
+    {
+        let (jal, jalr, skipped): (u32, u32, u32);
+        // SAFETY: Jumps only within the block.
+        unsafe {
+            core::arch::asm!(
+                "li {s}, 0",
+                "auipc {base}, 0",
+                "jal {jal}, 2f",
+                "addi {s}, {s}, 1",
+                "2:",
+                "sub {jal}, {jal}, {base}",
+                "auipc {base}, 0",
+                // 17 is odd: the target's low bit is cleared, to 16.
+                "jalr {jalr}, 17({base})",
+                "addi {s}, {s}, 1",
+                "addi {s}, {s}, 1",
+                "sub {jalr}, {jalr}, {base}",
+                base = out(reg) _, jal = out(reg) jal, jalr = out(reg) jalr,
+                s = out(reg) skipped,
+                options(pure, nomem, nostack)
+            )
+        };
+        (jal, jalr, skipped)
+    }
+    #[cfg(not(target_arch = "riscv32"))]
+    (0, 0, 0)
+}
+
+static mut MEM: [u32; 2] = [0; 2];
+
+fn loads_stores() {
+    let mem = addr_of_mut!(MEM).cast::<u32>();
+    store!("sw", 0x80ff_7f01, mem, 0);
+    store!("sw", 0, mem, 4);
+    store!("sb", 0x1234_56aa, mem, 5);
+    store!("sh", 0x1234_beef, mem, 6);
+    #[cfg(target_arch = "riscv32")]
+    // SAFETY: Orders memory accesses, and that's all.
+    unsafe {
+        core::arch::asm!("fence", options(nostack))
+    };
+    report!("ldst    ", [
+        (load!("lw", mem, 0), 0x80ff_7f01),
+        (load!("lb", mem, 0), 0x01),
+        (load!("lb", mem, 1), 0x7f),
+        (load!("lb", mem, 2), 0xffff_ffff),
+        (load!("lb", mem, 3), 0xffff_ff80),
+        (load!("lbu", mem, 3), 0x80),
+        (load!("lh", mem, 0), 0x7f01),
+        (load!("lh", mem, 2), 0xffff_80ff),
+        (load!("lhu", mem, 2), 0x80ff),
+        // Only the byte and halfword stored changed.
+        (load!("lw", mem, 4), 0xbeef_aa00),
+    ]);
+}
+
+fn csrs() {
+    let saved = csr_op!("csrrw", 0x1234_5678);
+    report!("csr     ", [
+        (csr_op!("csrrs", 0xf), 0x1234_5678),
+        (csr_op!("csrrc", 0x78), 0x1234_567f),
+        (csr_imm!("csrrwi", 0x1f), 0x1234_5607),
+        // A zero mask reads without writing.
+        (csr_imm!("csrrsi", 0), 0x1f),
+        (csr_imm!("csrrci", 0x10), 0x1f),
+        (csr_op!("csrrs", 0), 0x0f),
+        // Putting it back.
+        (csr_op!("csrrw", saved), 0x0f),
+    ]);
+}
+
+#[entry]
+fn main() -> ! {
+    // Not Soc::init: its boot logging doesn't leave room.
+    // SAFETY: Interrupts are disabled, and stay that way.
+    let (_, _, ser) = unsafe { io_addrs::get_bases() };
+    console::init(Serial::new(ser));
+
+    alu();
+    alu_imm();
+    let (lui, auipc) = upper();
+    report!("upper   ", [(lui, 0xffff_f000), (auipc, 0x1004)]);
+    report!("branch  ", [
+        (b_op!("beq", 5, 5), 1),
+        (b_op!("beq", 5, 6), 0),
+        (b_op!("bne", 5, 6), 1),
+        (b_op!("bne", 6, 6), 0),
+        (b_op!("blt", 0xffff_ffff, 0), 1),
+        (b_op!("blt", 0, 0xffff_ffff), 0),
+        (b_op!("bge", 0xffff_ffff, 0xffff_ffff), 1),
+        (b_op!("bge", 0xffff_ffff, 0), 0),
+        (b_op!("bltu", 0, 0xffff_ffff), 1),
+        (b_op!("bltu", 0xffff_ffff, 0), 0),
+        (b_op!("bgeu", 0xffff_ffff, 0), 1),
+        (b_op!("bgeu", 0, 1), 0),
+    ]);
+    let (jal, jalr, skipped) = jumps();
+    report!("jump    ", [(jal, 8), (jalr, 8), (skipped, 0)]);
+    loads_stores();
+    csrs();
+
+    let idiv = |n, d| {
+        let (q, r) = softmath::idivmod(core::hint::black_box(n), d);
+        (q as u32, r as u32)
+    };
+    let (q0, r0) = softmath::udivmod(core::hint::black_box(7), 0);
+    let (q1, r1) = idiv(-7, 0);
+    let (q2, r2) = idiv(i32::MIN, -1);
+    let (q3, r3) = idiv(-7, 2);
+    report!("divzero ", [
+        (q0, 0), (r0, 7),
+        (q1, 0), (r1, -7_i32 as u32),
+        (q2, i32::MIN as u32), (r2, 0),
+        (q3, -3_i32 as u32), (r3, -1_i32 as u32),
+    ]);
+
+    loop {
+        core::hint::spin_loop();
+    }
+}
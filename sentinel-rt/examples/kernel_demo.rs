@@ -0,0 +1,98 @@
+//! Three [`kernel`] tasks sharing the core:
+//!
+//! * `main`, echoing the console
+//! * a bouncing LED, sleeping 80 ms between steps
+//! * a prime counter that never yields, printing every 256th prime
+//!
+//! The counter would starve the other two if nothing took the core off
+//! it; with the timer tick preempting it, the LED keeps bouncing and the
+//! echo keeps up. It doesn't fit in the AttoSoC's 4 KiB; use a bigger
+//! board (see `io_map`).
+//!
+//! [`kernel`]: sentinel_rt::kernel
+
+#![cfg_attr(target_os = "none", no_std)]
+#![no_main]
+
+#[cfg(target_os = "none")]
+use panic_halt as _;
+use core::time::Duration;
+use riscv::register::{mie, mstatus};
+use sentinel_rt::hal::serial::Port;
+use sentinel_rt::hex;
+use sentinel_rt::io_addrs;
+use sentinel_rt::irq::{self, Handler};
+use sentinel_rt::kernel::{self, Stack};
+use sentinel_rt::prelude::*;
+
+static PORT: Port<16, 128> = Port::new();
+static LED_STACK: Stack<256> = Stack::new();
+static PRIME_STACK: Stack<256> = Stack::new();
+
+fn leds() {
+    loop {
+        // Out to LED 7 and back again.
+        for step in 0..14 {
+            let pos = if step < 8 { step } else { 14 - step };
+            if let Some(bases) = io_addrs::detected() {
+                Gpio::new(bases.gpio).set_leds(1 << pos);
+            }
+            kernel::sleep(Duration::from_millis(80));
+        }
+    }
+}
+
+fn primes() {
+    let mut found = 0u32;
+    let mut n = 2u32;
+    loop {
+        // Trial division by repeated subtraction: there's no `mul` or
+        // `div`, and the point is to be slow.
+        let prime = (2..=n / 2).all(|d| {
+            let mut r = n;
+            while r >= d {
+                r -= d;
+            }
+            r != 0
+        });
+        if prime {
+            found += 1;
+            if found & 0xff == 0 {
+                let mut line = *b"prime 00000000\r\n";
+                line[6..14].copy_from_slice(&hex::u32_digits(n));
+                PORT.write_all(&line);
+            }
+        }
+        n += 1;
+    }
+}
+
+#[no_mangle]
+#[allow(non_snake_case)]
+fn MachineExternal() {
+    irq::dispatch();
+}
+
+#[entry]
+fn main() -> ! {
+    let soc = Soc::init();
+    // The UART has to be attached before interrupts go on: the Wishbone
+    // UART's IRQ is pending from reset, and only the port can clear it.
+    PORT.attach(soc.serial.base());
+    irq::set(Handler::Serial(|| PORT.on_interrupt()));
+    // SAFETY: The ISR's state is all set up.
+    unsafe {
+        mie::set_mext();
+        mstatus::set_mie();
+    }
+
+    kernel::start();
+    let _ = kernel::spawn(leds, &LED_STACK);
+    let _ = kernel::spawn(primes, &PRIME_STACK);
+    loop {
+        while let Some(b) = PORT.read() {
+            PORT.write_all(&[b]);
+        }
+        kernel::sleep(Duration::from_millis(10));
+    }
+}
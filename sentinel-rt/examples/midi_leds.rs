@@ -0,0 +1,47 @@
+//! Light an LED for each MIDI note held down.
+//!
+//! Needs gateware with the UART at MIDI's 31250 baud (which divides the
+//! 12 MHz clock evenly), and the usual opto-isolated MIDI input circuit on
+//! the RX pin. Note `n` lights LED `n % 8`; "all notes off" (CC 123) and
+//! program changes clear them.
+
+#![cfg_attr(target_os = "none", no_std)]
+#![no_main]
+
+#[cfg(target_os = "none")]
+use panic_halt as _;
+use sentinel_rt::midi::{Message, Parser, ALL_NOTES_OFF};
+use sentinel_rt::prelude::*;
+
+#[entry]
+fn main() -> ! {
+    let mut soc = Soc::init();
+    let mut parser = Parser::new();
+    // How many times each note class is held, so overlapping octaves don't
+    // turn each other's LED off.
+    let mut held = [0u8; 8];
+
+    loop {
+        let Some(b) = soc.serial.read_byte() else {
+            continue;
+        };
+
+        match parser.feed(b) {
+            Some(Message::NoteOn { note, .. }) => {
+                let n = &mut held[note as usize % 8];
+                *n = n.saturating_add(1);
+            }
+            Some(Message::NoteOff { note, .. }) => {
+                let n = &mut held[note as usize % 8];
+                *n = n.saturating_sub(1);
+            }
+            Some(Message::ControlChange { control: ALL_NOTES_OFF, .. })
+            | Some(Message::ProgramChange { .. }) => held = [0; 8],
+            _ => continue,
+        }
+
+        let leds = held.iter().enumerate()
+            .fold(0, |acc, (i, n)| acc | (((*n != 0) as u8) << i));
+        soc.gpio.set_leds(leds);
+    }
+}
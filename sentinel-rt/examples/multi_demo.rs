@@ -0,0 +1,152 @@
+//! Several demos at once, time-sliced by the periodic scheduler:
+//!
+//! * Rule 110, a row every 200 ms, on the UART
+//! * a bouncing LED, every 80 ms
+//! * a telemetry line every 2 s: uptime, rows drawn, and how often each
+//!   task fell behind, or found the UART's buffer full
+//!
+//! The timer ISR only keeps time, checks the watchdog and services the
+//! UART; the tasks run from the main loop, which sleeps until the next
+//! interrupt in between, and feeds the watchdog. Output goes through an
+//! interrupt-driven [`Port`], so no task waits on the UART.
+//!
+//! If a task wedges the main loop for half a second, the watchdog resets
+//! the demo, which says so when it comes back up.
+//!
+//! ```text
+//!                                                                #
+//!                                                               ##
+//!                                                              ###
+//! t 000005b8 rows 0000000a late 00 00 full 00
+//! ```
+//!
+//! It doesn't fit in the AttoSoC's 4 KiB, or the HX8K's 8 KiB; build it
+//! for the iCEBreaker (see `io_map` for the boards):
+//!
+//! ```text
+//! cargo build --release --example multi_demo --features board-icebreaker
+//! ```
+
+#![cfg_attr(target_os = "none", no_std)]
+#![no_main]
+
+#[cfg(target_os = "none")]
+use panic_halt as _;
+use core::cell::Cell;
+use core::time::Duration;
+use critical_section::Mutex;
+use riscv::register::{mie, mstatus};
+use sentinel_rt::atomic::{AtomicU32, AtomicU64, AtomicU8, Ordering};
+use sentinel_rt::hal::serial::Port;
+use sentinel_rt::periodic::{self, Handle};
+use sentinel_rt::prelude::*;
+use sentinel_rt::irq::{self, Handler};
+use sentinel_rt::reset::{self, Reason};
+use sentinel_rt::watchdog::{self, Watchdog};
+use sentinel_rt::{hex, io_addrs, power, timebase};
+
+static CONSOLE: Port<16, 128> = Port::new();
+
+static ROW: AtomicU64 = AtomicU64::new(1);
+static ROWS: AtomicU32 = AtomicU32::new(0);
+/// Rows (or telemetry lines) dropped because the TX buffer was full.
+static FULL: AtomicU32 = AtomicU32::new(0);
+/// Where the bouncing LED is up to.
+static LED: AtomicU8 = AtomicU8::new(0);
+static TASKS: Mutex<Cell<[Option<Handle>; 2]>> = Mutex::new(Cell::new([None; 2]));
+
+/// Queue `line` whole, or not at all.
+fn send(line: &[u8]) {
+    if CONSOLE.write(line) < line.len() {
+        FULL.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Cell `i` (0 is leftmost, wrapping around) of `row`.
+fn cell(row: u64, i: i32) -> u64 {
+    (row >> (63 - i.rem_euclid(64))) & 1
+}
+
+fn rule110(row: u64) -> u64 {
+    (0..64).fold(0, |next, i| {
+        let pattern = cell(row, i - 1) << 2 | cell(row, i) << 1 | cell(row, i + 1);
+        next | ((110 >> pattern) & 1) << (63 - i)
+    })
+}
+
+fn draw_row() {
+    let row = ROW.load(Ordering::Relaxed);
+    let mut line = [b' '; 66];
+    for (i, b) in line[..64].iter_mut().enumerate() {
+        if row & (1 << (63 - i)) != 0 {
+            *b = b'#';
+        }
+    }
+    line[64..].copy_from_slice(b"\r\n");
+    send(&line);
+
+    ROW.store(rule110(row), Ordering::Relaxed);
+    ROWS.fetch_add(1, Ordering::Relaxed);
+}
+
+fn bounce() {
+    // Steps 0-13 go out to LED 7 and back again.
+    let step = LED.load(Ordering::Relaxed);
+    LED.store((step + 1) % 14, Ordering::Relaxed);
+
+    let pos = if step < 8 { step } else { 14 - step };
+    if let Some(bases) = io_addrs::detected() {
+        Gpio::new(bases.gpio).set_leds(1 << pos);
+    }
+}
+
+fn telemetry() {
+    let [rows, leds] = critical_section::with(|cs| TASKS.borrow(cs).get());
+    let late = |h: Option<Handle>| hex::u8_digits(h.map_or(0, periodic::overruns) as u8);
+
+    let mut line = *b"t 00000000 rows 00000000 late 00 00 full 00\r\n";
+    line[2..10].copy_from_slice(&hex::u32_digits(timebase::ticks32()));
+    line[16..24].copy_from_slice(&hex::u32_digits(ROWS.load(Ordering::Relaxed)));
+    line[30..32].copy_from_slice(&late(rows));
+    line[33..35].copy_from_slice(&late(leds));
+    line[41..43].copy_from_slice(&hex::u8_digits(FULL.load(Ordering::Relaxed) as u8));
+    send(&line);
+}
+
+#[no_mangle]
+#[allow(non_snake_case)]
+fn MachineExternal() {
+    irq::dispatch();
+}
+
+#[entry]
+fn main() -> ! {
+    let soc = Soc::init();
+    // The UART has to be attached before interrupts go on: the Wishbone
+    // UART's IRQ is pending from reset, and only the port can clear it.
+    CONSOLE.attach(soc.serial.base());
+    irq::set(Handler::Serial(|| CONSOLE.on_interrupt()));
+    irq::set(Handler::Timer(watchdog::check));
+    // SAFETY: The ISR's state is all set up.
+    unsafe {
+        mie::set_mext();
+        mstatus::set_mie();
+    }
+
+    let rows = periodic::every(Duration::from_millis(200), draw_row).ok();
+    let leds = periodic::every(Duration::from_millis(80), bounce).ok();
+    let _ = periodic::every(Duration::from_secs(2), telemetry);
+    critical_section::with(|cs| TASKS.borrow(cs).set([rows, leds]));
+
+    if reset::take_reason() == Some(Reason::Watchdog) {
+        send(b"reset by the watchdog\r\n");
+    }
+    let mut wdt = watchdog::Software;
+    let _ = wdt.start(Duration::from_millis(500));
+
+    loop {
+        wdt.feed();
+        periodic::run_due();
+        power::idle();
+    }
+}
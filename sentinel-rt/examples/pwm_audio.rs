@@ -0,0 +1,141 @@
+//! 1-bit audio: a tune played by delta-sigma modulating a GPIO pin.
+//!
+//! The timer ISR is the sample clock: each tick it steps a sine oscillator
+//! and publishes the next 8-bit sample. The main loop runs a first-order
+//! delta-sigma modulator on the current sample as fast as it can, so the
+//! pin's average level follows the waveform. Put an RC low-pass (e.g. 1k
+//! and 100n) and a small amplifier or piezo on the pin.
+//!
+//! The timer ticks at about 732 Hz, which is also the sample rate, so notes
+//! are kept below 366 Hz. Anything typed at the UART is counted (on the
+//! upper LEDs) to show the output keeps going while other interrupts are
+//! serviced; the lower LEDs show which note is playing.
+//!
+//! It doesn't fit in the AttoSoC's 4 KiB, but does in the HX8K's 8 KiB
+//! (see `io_map` for the boards):
+//!
+//! ```text
+//! cargo build --release --example pwm_audio --features board-hx8k
+//! ```
+
+#![cfg_attr(target_os = "none", no_std)]
+#![no_main]
+
+#[cfg(target_os = "none")]
+use panic_halt as _;
+use sentinel_rt::atomic::{AtomicU8, Ordering};
+use sentinel_rt::irq;
+use sentinel_rt::prelude::*;
+use sentinel_rt::timebase::{CLK_HZ, CYCLES_PER_TICK};
+
+/// PMOD pin 3 on the iCEstick.
+const AUDIO_PIN: u8 = 0;
+
+const SINE: [u8; 32] = [
+    128, 153, 177, 199, 218, 234, 245, 253, 255, 253, 245, 234, 218, 199,
+    177, 153, 128, 103, 79, 57, 38, 22, 11, 3, 1, 3, 11, 22, 38, 57, 79, 103,
+];
+
+/// Phase increment per tick for `hz`, with the phase's top 5 bits indexing
+/// [`SINE`].
+const fn step(hz: u32) -> u16 {
+    (((hz as u64) << 16) * CYCLES_PER_TICK as u64 / CLK_HZ as u64) as u16
+}
+
+/// Ticks for `ms` milliseconds.
+const fn ticks(ms: u32) -> u16 {
+    (ms as u64 * CLK_HZ as u64 / 1000 / CYCLES_PER_TICK as u64) as u16
+}
+
+/// (phase step, length in ticks). A step of 0 is a rest.
+const TUNE: [(u16, u16); 9] = [
+    (step(196), ticks(300)), // G3
+    (step(262), ticks(300)), // C4
+    (step(330), ticks(300)), // E4
+    (step(294), ticks(600)), // D4
+    (0, ticks(150)),
+    (step(262), ticks(300)), // C4
+    (step(247), ticks(300)), // B3
+    (step(262), ticks(900)), // C4
+    (0, ticks(600)),
+];
+
+static SAMPLE: AtomicU8 = AtomicU8::new(128);
+static NOTE: AtomicU8 = AtomicU8::new(0);
+static RX_COUNT: AtomicU8 = AtomicU8::new(0);
+
+struct Synth {
+    note: usize,
+    left: u16,
+    phase: u16,
+}
+
+// Only touched by the ISR. Starts on the last note with nothing left of
+// it, so the first tick moves on to the first note.
+static mut SYNTH: Synth = Synth {
+    note: TUNE.len() - 1,
+    left: 0,
+    phase: 0,
+};
+
+impl Synth {
+    fn next(&mut self) -> u8 {
+        if self.left == 0 {
+            self.note = (self.note + 1) % TUNE.len();
+            self.left = TUNE[self.note].1;
+            self.phase = 0;
+            NOTE.store(self.note as u8, Ordering::Relaxed);
+        }
+        self.left -= 1;
+
+        let step = TUNE[self.note].0;
+        if step == 0 {
+            return 128;
+        }
+        self.phase = self.phase.wrapping_add(step);
+        SINE[(self.phase >> 11) as usize]
+    }
+}
+
+#[no_mangle]
+#[allow(non_snake_case)]
+fn MachineExternal() {
+    irq::dispatch();
+}
+
+#[interrupt]
+fn TIMER() {
+    // SAFETY: Interrupts don't nest, and nothing else touches SYNTH.
+    let synth = unsafe { &mut *core::ptr::addr_of_mut!(SYNTH) };
+    SAMPLE.store(synth.next(), Ordering::Relaxed);
+}
+
+#[interrupt]
+fn UART_RX(_: u8) {
+    RX_COUNT.fetch_add(1, Ordering::Relaxed);
+}
+
+#[entry]
+fn main() -> ! {
+    let mut soc = Soc::builder().interrupts(true).init();
+    let mask = 1 << AUDIO_PIN;
+    soc.gpio.set_output_enable(mask);
+
+    let mut acc: u16 = 0;
+    let mut leds = 0;
+    loop {
+        // First-order delta-sigma: output the carry out of the
+        // accumulator.
+        acc += SAMPLE.load(Ordering::Relaxed) as u16;
+        let high = acc >= 256;
+        acc &= 0xff;
+        soc.gpio.write_outputs(if high { mask } else { 0 });
+
+        let new_leds = (RX_COUNT.load(Ordering::Relaxed) << 4)
+            | (NOTE.load(Ordering::Relaxed) & 0x0f);
+        if new_leds != leds {
+            leds = new_leds;
+            soc.gpio.set_leds(leds);
+        }
+    }
+}
@@ -0,0 +1,121 @@
+//! Reaction-time game.
+//!
+//! Press the button (active low, on GPIO 0) to start. After a random delay
+//! of 1-4 seconds the LEDs light; press again as fast as you can. The
+//! reaction time is printed in milliseconds, along with the best so far,
+//! which is kept in `.noinit` across soft resets. Pressing before the LEDs
+//! light is a false start.
+//!
+//! Times are measured in timer ticks, so they're good to about 1.4 ms.
+//!
+//! It doesn't fit in the AttoSoC's 4 KiB, but does in the HX8K's 8 KiB
+//! (see `io_map` for the boards):
+//!
+//! ```text
+//! cargo build --release --example reaction --features board-hx8k
+//! ```
+
+#![cfg_attr(target_os = "none", no_std)]
+#![no_main]
+
+#[cfg(target_os = "none")]
+use panic_halt as _;
+use core::mem::MaybeUninit;
+use core::ptr::addr_of_mut;
+use sentinel_rt::fixed;
+use sentinel_rt::hal::button::Button;
+use sentinel_rt::hal::capture::Edge;
+use sentinel_rt::prelude::*;
+use sentinel_rt::stimulus::Rng;
+use sentinel_rt::timebase::{self, CLK_HZ, CYCLES_PER_TICK};
+
+const TICK_HZ: u32 = CLK_HZ / CYCLES_PER_TICK;
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct Best {
+    ms: u32,
+    /// `!ms`, so garbage RAM is (very likely) recognized as no score.
+    check: u32,
+}
+
+#[link_section = ".noinit.reaction"]
+static mut BEST: MaybeUninit<Best> = MaybeUninit::uninit();
+
+enum State {
+    Idle,
+    /// LEDs light at this tick.
+    Waiting(u32),
+    /// LEDs lit at this tick.
+    Lit(u32),
+}
+
+fn ms(ticks: u32) -> u32 {
+    (ticks as u64 * CYCLES_PER_TICK as u64 * 1000 / CLK_HZ as u64) as u32
+}
+
+fn print_ms(ser: &mut Serial, label: &[u8], ms: u32) {
+    let mut buf = [0; fixed::MAX_LEN];
+    ser.write_bytes(label);
+    ser.write_bytes(fixed::format(ms as i32, 0, &mut buf));
+    ser.write_bytes(b" ms");
+}
+
+#[entry]
+fn main() -> ! {
+    let mut soc = Soc::init();
+    let mut button = Button::active_low(0, 8);
+    // SAFETY: Single-threaded, and the only reference made. Any bit pattern
+    // is a valid Best.
+    let best = unsafe { (*addr_of_mut!(BEST)).assume_init_mut() };
+    if best.check != !best.ms {
+        *best = Best { ms: u32::MAX, check: 0 };
+    }
+
+    let mut rng = None;
+    let mut state = State::Idle;
+    soc.serial.write_bytes(b"press to start\r\n");
+
+    loop {
+        // Everything is driven from the tick, so the button is sampled at a
+        // steady rate.
+        if !soc.timer.ack() {
+            continue;
+        }
+        timebase::tick();
+        let now = timebase::ticks32();
+        let pressed = button.poll(&soc.gpio) == Some(Edge::Rising);
+
+        state = match state {
+            State::Idle if pressed => {
+                // How long it took to press the first time is the entropy.
+                let rng = rng.get_or_insert_with(|| Rng::seeded(|| now));
+                soc.gpio.set_leds(0);
+                State::Waiting(now.wrapping_add(TICK_HZ + rng.below(3 * TICK_HZ)))
+            }
+            State::Waiting(_) if pressed => {
+                soc.serial.write_bytes(b"false start!\r\n");
+                State::Idle
+            }
+            State::Waiting(at) if now.wrapping_sub(at) as i32 >= 0 => {
+                soc.gpio.set_leds(0xff);
+                State::Lit(now)
+            }
+            State::Lit(start) if pressed => {
+                soc.gpio.set_leds(0);
+                let t = ms(now.wrapping_sub(start));
+                print_ms(&mut soc.serial, b"", t);
+                if t < best.ms {
+                    *best = Best { ms: t, check: !t };
+                    soc.serial.write_bytes(b" (new best!)");
+                } else {
+                    print_ms(&mut soc.serial, b" (best ", best.ms);
+                    soc.serial.write_bytes(b")");
+                }
+                soc.serial.write_bytes(b"\r\npress to start\r\n");
+                State::Idle
+            }
+            s => s,
+        };
+    }
+}
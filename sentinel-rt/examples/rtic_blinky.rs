@@ -0,0 +1,86 @@
+//! RTIC's blinky, laid out the way it would be under `#[rtic::app]`.
+//!
+//! `#[rtic::app]` has no backend for this core yet, so each piece maps onto
+//! what sentinel-rt has instead:
+//!
+//! * `init` is the top of `main`.
+//! * The hardware task bound to the UART is an [`irq::Handler`]: each byte
+//!   received toggles LED 1 and pends the software task.
+//! * The software task is [`Handler::Soft`], run by [`irq::pend`] at the
+//!   next dispatch.
+//! * The `async` idle task blinks LED 0 every half second on
+//!   [`Mono`], RTIC's monotonic API over the timebase.
+//!
+//! Needs the `rtic` feature:
+//!
+//! ```text
+//! cargo build --release --example rtic_blinky --features rtic
+//! ```
+
+#![cfg_attr(target_os = "none", no_std)]
+#![no_main]
+
+use core::cell::RefCell;
+
+use critical_section::Mutex;
+use fugit::ExtU32;
+#[cfg(target_os = "none")]
+use panic_halt as _;
+use riscv::register::{mie, mstatus};
+use rtic_time::Monotonic;
+use sentinel_rt::events::{self, Event};
+use sentinel_rt::executor;
+use sentinel_rt::hal::gpio::Led;
+use sentinel_rt::irq::{self, Handler};
+use sentinel_rt::prelude::*;
+use sentinel_rt::rtic::Mono;
+use sentinel_rt::timebase;
+
+/// The hardware task's LED; RTIC's `#[local]`.
+static RX_LED: Mutex<RefCell<Option<Led>>> = Mutex::new(RefCell::new(None));
+
+#[no_mangle]
+#[allow(non_snake_case)]
+fn MachineExternal() {
+    irq::dispatch();
+}
+
+/// Hardware task: a byte arrived.
+fn uart_rx(_: u8) {
+    critical_section::with(|cs| {
+        if let Some(led) = RX_LED.borrow_ref_mut(cs).as_mut() {
+            led.set(!led.is_on());
+        }
+    });
+    irq::pend();
+}
+
+/// Software task: runs after the hardware task, at the end of the same
+/// dispatch. Logs when.
+fn received() {
+    events::record(Event::Other(0, timebase::ticks32()));
+}
+
+#[entry]
+fn main() -> ! {
+    // init
+    let soc = Soc::init();
+    let [mut led, rx_led, ..] = soc.gpio.split().leds;
+    critical_section::with(|cs| RX_LED.replace(cs, Some(rx_led)));
+    irq::set(Handler::Rx(uart_rx));
+    irq::set(Handler::Soft(received));
+    Mono::start();
+    // SAFETY: The ISR's state is all set up.
+    unsafe {
+        mie::set_mext();
+        mstatus::set_mie();
+    }
+
+    // idle
+    executor::block_on(async {
+        loop {
+            led.set(!led.is_on());
+            Mono::delay(500.millis()).await;
+        }
+    })
+}
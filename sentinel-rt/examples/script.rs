@@ -0,0 +1,132 @@
+//! Run an uploaded script on the bytecode VM. See `sentinel_rt::vm`.
+//!
+//! Starts with a script that chases a light across the LEDs. Upload another
+//! with `sentinel-tools`' `vm-asm`:
+//!
+//! ```text
+//! $ vm-asm blink.s --upload /dev/ttyUSB1
+//! ```
+//!
+//! The script runs from a periodic task, a bounded number of instructions
+//! at a time, so a runaway script can't take over; if it faults, the fault
+//! is printed.
+//!
+//! It doesn't fit in the AttoSoC's 4 KiB, or the HX8K's 8 KiB; build it
+//! for the iCEBreaker (see `io_map` for the boards):
+//!
+//! ```text
+//! cargo build --release --example script --features board-icebreaker
+//! ```
+
+#![cfg_attr(target_os = "none", no_std)]
+#![no_main]
+
+#[cfg(target_os = "none")]
+use panic_halt as _;
+use core::cell::RefCell;
+use core::time::Duration;
+use critical_section::Mutex;
+use sentinel_rt::error::Describe;
+use sentinel_rt::prelude::*;
+use sentinel_rt::vm::{Io, Op, State, Vm};
+use sentinel_rt::{io_addrs, periodic, timebase};
+
+/// Instructions per run of the task.
+const BUDGET: u32 = 64;
+
+static VM: Mutex<RefCell<Vm<256>>> = Mutex::new(RefCell::new(Vm::new()));
+
+/// Shift a bit left every 20 ticks, wrapping around:
+///
+/// ```text
+///       push 1
+///       store 0
+/// top:  load 0
+///       leds
+///       push 20
+///       sleep
+///       load 0
+///       push 1
+///       shl
+///       dup
+///       push 0x100
+///       eq
+///       jz store
+///       drop
+///       push 1
+/// store: store 0
+///       jmp top
+/// ```
+const CHASE: &[u8] = &[
+    Op::Push8 as u8, 1, Op::Store as u8, 0,
+    // top (4)
+    Op::Load as u8, 0, Op::Leds as u8, Op::Push8 as u8, 20, Op::Sleep as u8,
+    Op::Load as u8, 0, Op::Push8 as u8, 1, Op::Shl as u8,
+    Op::Dup as u8, Op::Push16 as u8, 0x00, 0x01, Op::Eq as u8, Op::Jz as u8, 26, 0,
+    Op::Drop as u8, Op::Push8 as u8, 1,
+    // store (26)
+    Op::Store as u8, 0, Op::Jmp as u8, 4, 0,
+];
+
+struct Board;
+
+impl Io for Board {
+    fn set_leds(&mut self, val: u8) {
+        if let Some(bases) = io_addrs::detected() {
+            Gpio::new(bases.gpio).set_leds(val);
+        }
+    }
+
+    fn inputs(&mut self) -> u8 {
+        io_addrs::detected().map_or(0, |bases| Gpio::new(bases.gpio).read_inputs())
+    }
+
+    fn ticks(&self) -> u32 {
+        timebase::ticks32()
+    }
+}
+
+fn run_script() {
+    critical_section::with(|cs| {
+        VM.borrow_ref_mut(cs).run(&mut Board, BUDGET);
+    });
+}
+
+#[entry]
+fn main() -> ! {
+    let mut soc = Soc::init();
+    critical_section::with(|cs| VM.borrow_ref_mut(cs).load(CHASE)).unwrap();
+    let _ = periodic::every(Duration::from_millis(5), run_script);
+    let mut reported = false;
+
+    loop {
+        if soc.timer.ack() {
+            timebase::tick();
+        }
+        periodic::run_due();
+
+        let (done, state) = critical_section::with(|cs| {
+            let mut vm = VM.borrow_ref_mut(cs);
+            (soc.serial.read_byte().and_then(|b| vm.receive(b)), vm.state())
+        });
+        match done {
+            Some(Ok(_)) => {
+                soc.serial.write_bytes(b"ok\r\n");
+                reported = false;
+            }
+            Some(Err(e)) => {
+                soc.serial.write_bytes(e.describe().as_bytes());
+                soc.serial.write_bytes(b"\r\n");
+            }
+            None => {}
+        }
+
+        if let State::Faulted(e) = state {
+            if !reported {
+                soc.serial.write_bytes(e.describe().as_bytes());
+                soc.serial.write_bytes(b"\r\n");
+                reported = true;
+            }
+        }
+    }
+}
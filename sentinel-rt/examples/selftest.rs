@@ -0,0 +1,81 @@
+//! sentinel-rt's arithmetic and encoders, tested on the core itself.
+//!
+//! The host runs the same code under `cargo test`, but not with RV32I's
+//! software multiply and divide, or its 64-bit arithmetic done a word at a
+//! time. These are run by [`testing::run`], which prints a line for each
+//! and ends the simulation with the result:
+//!
+//! ```text
+//! cargo build --release --example selftest --features sim,board-icebreaker
+//! ```
+//!
+//! With `sim`, the results go to the testbench's putchar port rather than
+//! the UART. It doesn't fit in the AttoSoC's 4 KiB; use a bigger board
+//! (see `io_map`).
+
+#![cfg_attr(target_os = "none", no_std)]
+#![no_main]
+
+#[cfg(all(target_os = "none", not(feature = "panic-handler")))]
+use panic_halt as _;
+use core::hint::black_box;
+use sentinel_rt::checksum::crc32;
+use sentinel_rt::prelude::*;
+use sentinel_rt::{cobs, console, fixed, hex, sentinel_test, sim, testing};
+
+#[sentinel_test]
+fn divides() {
+    let (n, d) = black_box((0xdead_beef_u32, 7));
+    assert_eq!(n / d, 533_704_079);
+    assert_eq!(n % d, 6);
+    assert_eq!(black_box(-100_i32) / 7, -14);
+    assert_eq!(black_box(-100_i32) % 7, -2);
+}
+
+#[sentinel_test]
+fn multiplies_wide() {
+    let (a, b) = black_box((0x1234_5678_u64, 0x9abc_def0_u64));
+    assert_eq!(a * b, 0x0b00_ea4e_242d_2080);
+    assert_eq!((a << 32 | b) >> 28, 0x1_2345_6789);
+}
+
+#[sentinel_test]
+fn scales() -> Result<(), &'static str> {
+    let mut buf = [0; fixed::MAX_LEN];
+    let mv = fixed::scale(black_box(2047), 3300, 4095);
+    (fixed::format(mv, 3, &mut buf) == b"1.650").then_some(()).ok_or("2047 counts isn't 1.650 V")
+}
+
+#[sentinel_test]
+fn checksums() {
+    assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    assert_eq!(&hex::u32_digits(0xcbf4_3926), b"cbf43926");
+}
+
+#[sentinel_test]
+fn frames() {
+    let mut frame = [0; 16];
+    let mut len = 0;
+    let mut out = |b: &[u8]| {
+        frame[len..len + b.len()].copy_from_slice(b);
+        len += b.len();
+    };
+    let mut enc = cobs::Encoder::new();
+    enc.start(&mut out);
+    enc.write(b"\x01\x00\x02", &mut out);
+    enc.finish(&mut out);
+
+    let mut decoded = [0; 16];
+    assert_eq!(cobs::decode(&frame[1..len - 1], &mut decoded), Some(&b"\x01\x00\x02"[..]));
+}
+
+#[entry]
+fn main() -> ! {
+    let soc = Soc::init();
+    if cfg!(feature = "sim") {
+        console::set_output(sim::write_bytes);
+    } else {
+        console::init(soc.serial);
+    }
+    testing::run()
+}
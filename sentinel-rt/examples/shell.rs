@@ -0,0 +1,84 @@
+//! Interactive shell for poking at the AttoSoC's peripherals.
+//!
+//! ```text
+//! > help
+//! leds <val> - Set the LEDs.
+//! inputs - Read the GPIO inputs.
+//! uptime - Milliseconds since boot, in hex.
+//! help [cmd] - this text
+//! ```
+//!
+//! It doesn't fit in the AttoSoC's 4 KiB, or the HX8K's 8 KiB; build it
+//! for the iCEBreaker (see `io_map` for the boards):
+//!
+//! ```text
+//! cargo build --release --example shell --features board-icebreaker
+//! ```
+
+#![cfg_attr(target_os = "none", no_std)]
+#![no_main]
+
+#[cfg(target_os = "none")]
+use panic_halt as _;
+use sentinel_rt::hex;
+use sentinel_rt::io_addrs;
+use sentinel_rt::prelude::*;
+use sentinel_rt::shell::{Args, Command, Output, Shell};
+use sentinel_rt::timebase;
+
+// The shell owns the UART, so commands find the GPIO from the detected bus.
+fn gpio() -> Result<Gpio, &'static str> {
+    io_addrs::detected()
+        .map(|b| Gpio::new(b.gpio))
+        .ok_or("no SoC")
+}
+
+fn print_hex(out: &mut dyn Output, val: u32) {
+    out.write_bytes(&hex::u32_digits(val));
+    out.write_bytes(b"\r\n");
+}
+
+fn leds(args: &mut Args<'_>, _: &mut dyn Output) -> Result<(), &'static str> {
+    gpio()?.set_leds(args.next_u32()? as u8);
+    Ok(())
+}
+
+fn inputs(_: &mut Args<'_>, out: &mut dyn Output) -> Result<(), &'static str> {
+    print_hex(out, gpio()?.read_inputs() as u32);
+    Ok(())
+}
+
+static COMMANDS: &[Command] = sentinel_rt::commands! {
+    /// Set the LEDs.
+    leds "<val>" => leds,
+    /// Read the GPIO inputs.
+    inputs => inputs,
+    /// Milliseconds since boot, in hex.
+    uptime => |_, out| {
+        let ms = timebase::uptime_ms();
+        out.write_bytes(&hex::u32_digits((ms >> 32) as u32));
+        print_hex(out, ms as u32);
+        Ok(())
+    },
+    /// Show (or clear) the event log.
+    events "[clear]" => sentinel_rt::events::command,
+    /// Read a word of memory.
+    peek "<addr>" => sentinel_rt::fault::peek,
+    /// Evaluate an expression, e.g. `x = peek(0x02000004) & 0xf`.
+    calc "<expr>" => sentinel_rt::expr::command,
+};
+
+#[entry]
+fn main() -> ! {
+    let mut soc = Soc::init();
+    sentinel_rt::banner!(&mut soc);
+    let mut shell = Shell::<64>::new(COMMANDS);
+
+    shell.prompt(&mut soc.serial);
+    loop {
+        if soc.timer.ack() {
+            timebase::tick();
+        }
+        shell.poll(&mut soc.serial);
+    }
+}
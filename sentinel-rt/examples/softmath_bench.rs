@@ -0,0 +1,78 @@
+//! Multiply and divide speed, for comparing `fast-softmath` with
+//! compiler-builtins on the core.
+//!
+//! Each line is how many of one kind of operation ran in 64 timer ticks
+//! (about 87 ms), in hex; more is faster. The operands are random, but the
+//! same sequence every run, and the lines repeat until reset:
+//!
+//! ```text
+//! mul-small 0000....
+//! mul       0000....
+//! div-10    0000....
+//! div       0000....
+//! idiv      0000....
+//! ```
+//!
+//! Build it both ways and compare:
+//!
+//! ```text
+//! cargo build --release --example softmath_bench
+//! cargo build --release --example softmath_bench --features fast-softmath
+//! ```
+
+#![cfg_attr(target_os = "none", no_std)]
+#![no_main]
+
+use core::hint::black_box;
+
+#[cfg(target_os = "none")]
+use panic_halt as _;
+use sentinel_rt::hex;
+use sentinel_rt::prelude::*;
+use sentinel_rt::shell::Output;
+use sentinel_rt::stimulus::Rng;
+
+const TICKS: u32 = 64;
+
+type Op = fn(u32, u32) -> u32;
+
+/// How many times `op` runs in [`TICKS`] ticks, on operands from `rng`.
+fn rate(timer: &mut Timer, rng: &mut Rng, op: Op) -> u32 {
+    timer.wait_tick();
+    let (mut ticks, mut n) = (0, 0);
+    while ticks < TICKS {
+        // A few at a time between polls, well inside a tick even for the
+        // slowest, so the poll costs little and no tick is missed.
+        for _ in 0..4 {
+            let (a, b) = (rng.next_u32(), rng.next_u32());
+            black_box(op(black_box(a), black_box(b)));
+        }
+        n += 4;
+        if timer.ack() {
+            ticks += 1;
+        }
+    }
+    n
+}
+
+#[entry]
+fn main() -> ! {
+    let Soc { mut serial, mut timer, .. } = Soc::init();
+    let mut rng = Rng::new(1);
+
+    let benches: [(&str, Op); 5] = [
+        ("mul-small ", |a, b| a.wrapping_mul(b & 15)),
+        ("mul       ", |a, b| a.wrapping_mul(b)),
+        ("div-10    ", |a, _| a / black_box(10)),
+        ("div       ", |a, b| a / (b >> 16 | 1)),
+        ("idiv      ", |a, b| (a as i32).wrapping_div(b as i32 >> 16 | 1) as u32),
+    ];
+    loop {
+        for (name, op) in benches {
+            let n = rate(&mut timer, &mut rng, op);
+            serial.write_str(name);
+            serial.write_bytes(&hex::u32_digits(n));
+            serial.write_str("\r\n");
+        }
+    }
+}
@@ -0,0 +1,52 @@
+//! The stack's high-water mark, over the UART.
+//!
+//! `Soc::init` paints the stack (the `stack-paint` feature). Each digit
+//! received recurses that many frames deep, and every byte prints how much
+//! of the stack has ever been used, so the mark climbs with the deepest
+//! digit so far:
+//!
+//! ```text
+//! stack-used 00000040
+//! stack-size 000003fc
+//! ```
+//!
+//! Needs the `stack-paint` feature:
+//!
+//! ```text
+//! cargo build --release --example stack_usage --features stack-paint
+//! ```
+
+#![cfg_attr(target_os = "none", no_std)]
+#![no_main]
+
+use core::hint::black_box;
+
+#[cfg(target_os = "none")]
+use panic_halt as _;
+use sentinel_rt::prelude::*;
+use sentinel_rt::stack;
+
+/// Use a frame's worth of stack `depth` times over.
+#[inline(never)]
+fn recurse(depth: u8) -> u32 {
+    let frame = black_box([depth as u32; 8]);
+    if depth == 0 {
+        return frame[0];
+    }
+    frame[7] + recurse(depth - 1)
+}
+
+#[entry]
+fn main() -> ! {
+    let Soc { mut serial, .. } = Soc::init();
+
+    loop {
+        let Some(b) = serial.read_byte() else {
+            continue;
+        };
+        if b.is_ascii_digit() {
+            black_box(recurse(b - b'0'));
+        }
+        stack::stack_usage().print(&mut serial);
+    }
+}
@@ -0,0 +1,109 @@
+//! The attosoc demo's sort of jobs, as cooperative [`Tasks`]:
+//!
+//! * a console, echoing whatever it receives
+//! * a bouncing LED, every 80 ms
+//! * Rule 110 on 32 cells, a row every 200 ms
+//!
+//! Each is a function run when an ISR signals it: the UART's handler
+//! when bytes come in, and periodic callbacks off the timer tick for the
+//! other two. In between, the core sleeps. It doesn't fit in the AttoSoC's
+//! 4 KiB; use a bigger board (see `io_map`).
+//!
+//! ```text
+//!                               ##
+//!                              ###
+//!                             ## #
+//! ```
+
+#![cfg_attr(target_os = "none", no_std)]
+#![no_main]
+
+#[cfg(target_os = "none")]
+use panic_halt as _;
+use core::time::Duration;
+use riscv::register::{mie, mstatus};
+use sentinel_rt::atomic::{AtomicU32, AtomicU8, Ordering};
+use sentinel_rt::hal::serial::Port;
+use sentinel_rt::irq::{self, Handler};
+use sentinel_rt::prelude::*;
+use sentinel_rt::tasks::{Events, Tasks};
+use sentinel_rt::{io_addrs, periodic};
+
+const CONSOLE: usize = 0;
+const LEDS: usize = 1;
+const AUTOMATON: usize = 2;
+
+/// Bytes have come in.
+const RX: Events = 1 << 0;
+/// Time for the next step.
+const TICK: Events = 1 << 0;
+
+static PORT: Port<16, 128> = Port::new();
+static TASKS: Tasks<3> = Tasks::new([console, leds, automaton]);
+
+/// Where the bouncing LED is up to.
+static LED: AtomicU8 = AtomicU8::new(0);
+static ROW: AtomicU32 = AtomicU32::new(1);
+
+fn console(_: Events) {
+    while let Some(b) = PORT.read() {
+        PORT.write_all(&[b]);
+    }
+}
+
+fn leds(_: Events) {
+    // Steps 0-13 go out to LED 7 and back again.
+    let step = LED.load(Ordering::Relaxed);
+    LED.store((step + 1) % 14, Ordering::Relaxed);
+
+    let pos = if step < 8 { step } else { 14 - step };
+    if let Some(bases) = io_addrs::detected() {
+        Gpio::new(bases.gpio).set_leds(1 << pos);
+    }
+}
+
+fn automaton(_: Events) {
+    let row = ROW.load(Ordering::Relaxed);
+    let mut line = [b' '; 34];
+    for (i, b) in line[..32].iter_mut().enumerate() {
+        if row & (1 << (31 - i)) != 0 {
+            *b = b'#';
+        }
+    }
+    line[32..].copy_from_slice(b"\r\n");
+    PORT.write_all(&line);
+
+    // Each cell's left and right neighbours, wrapping around.
+    let (l, r) = (row.rotate_right(1), row.rotate_left(1));
+    ROW.store((row | r) & !(l & row & r), Ordering::Relaxed);
+}
+
+#[no_mangle]
+#[allow(non_snake_case)]
+fn MachineExternal() {
+    irq::dispatch();
+}
+
+#[entry]
+fn main() -> ! {
+    let soc = Soc::init();
+    // The UART has to be attached before interrupts go on: the Wishbone
+    // UART's IRQ is pending from reset, and only the port can clear it.
+    PORT.attach(soc.serial.base());
+    irq::set(Handler::Serial(|| {
+        PORT.on_interrupt();
+        if PORT.rx_len() > 0 {
+            TASKS.signal(CONSOLE, RX);
+        }
+    }));
+    irq::set(Handler::Timer(periodic::run_due));
+    let _ = periodic::every(Duration::from_millis(80), || TASKS.signal(LEDS, TICK));
+    let _ = periodic::every(Duration::from_millis(200), || TASKS.signal(AUTOMATON, TICK));
+    // SAFETY: The ISR's state is all set up.
+    unsafe {
+        mie::set_mext();
+        mstatus::set_mie();
+    }
+
+    TASKS.run()
+}
@@ -0,0 +1,101 @@
+/* Replacement for riscv-rt's link.x with the `minimal` feature. See
+   sentinel_rt::minimal. Expects the same REGION_* aliases; INCLUDE
+   sentinel.x after it, as after link.x. */
+
+ENTRY(_sentinel_start);
+
+PROVIDE(_stext = ORIGIN(REGION_TEXT));
+PROVIDE(_stack_start = ORIGIN(REGION_STACK) + LENGTH(REGION_STACK));
+PROVIDE(_hart_stack_size = 2K);
+PROVIDE(_heap_size = 0);
+
+/* riscv-rt's defaults, which spin. Without a table of per-cause handlers,
+   every interrupt goes to MachineExternal. */
+PROVIDE(ExceptionHandler = DefaultExceptionHandler);
+PROVIDE(MachineExternal = DefaultInterruptHandler);
+PROVIDE(__pre_init = default_pre_init);
+PROVIDE(_start_trap = _sentinel_trap);
+
+SECTIONS
+{
+  .text.dummy (NOLOAD) :
+  {
+    . = ABSOLUTE(_stext);
+  } > REGION_TEXT
+
+  .text _stext :
+  {
+    /* The reset vector, first. */
+    KEEP(*(.text.sentinel.start));
+    *(.text .text.*);
+  } > REGION_TEXT
+
+  .rodata : ALIGN(4)
+  {
+    *(.srodata .srodata.*);
+    *(.rodata .rodata.*);
+    . = ALIGN(4);
+  } > REGION_RODATA
+
+  /* Loaded in place (LMA == VMA) and never copied: the bitstream or the
+     loader puts the initial values there. */
+  .data : ALIGN(4)
+  {
+    _sidata = .;
+    _sdata = .;
+    PROVIDE(__global_pointer$ = . + 0x800);
+    *(.sdata .sdata.* .sdata2 .sdata2.*);
+    *(.data .data.*);
+    . = ALIGN(4);
+    _edata = .;
+  } > REGION_DATA
+
+  .bss (NOLOAD) : ALIGN(4)
+  {
+    _sbss = .;
+    *(.sbss .sbss.* .bss .bss.*);
+    . = ALIGN(4);
+    _ebss = .;
+  } > REGION_BSS
+
+  .heap (NOLOAD) :
+  {
+    _sheap = .;
+    . += _heap_size;
+    . = ALIGN(4);
+    _eheap = .;
+  } > REGION_HEAP
+
+  /* One hart: the stack is everything left. */
+  .stack (NOLOAD) :
+  {
+    _estack = .;
+    . = ABSOLUTE(_stack_start);
+    _sstack = .;
+  } > REGION_STACK
+
+  .got (INFO) :
+  {
+    KEEP(*(.got .got.*));
+  }
+
+  /* riscv-rt's start-up, which the linker would otherwise keep. */
+  /DISCARD/ :
+  {
+    *(.init .init.rust);
+  }
+
+  .eh_frame (INFO) : { KEEP(*(.eh_frame)) }
+  .eh_frame_hdr (INFO) : { *(.eh_frame_hdr) }
+}
+
+ASSERT(_stext % 4 == 0, "
+ERROR(sentinel-rt): `_stext` must be 4-byte aligned");
+
+ASSERT(SIZEOF(.stack) > _hart_stack_size, "
+ERROR(sentinel-rt): .stack section is too small. Consider changing
+`_hart_stack_size`.");
+
+ASSERT(SIZEOF(.got) == 0, "
+ERROR(sentinel-rt): .got section detected in the input files. Dynamic
+relocations are not supported.");
@@ -0,0 +1,85 @@
+/* Sentinel-specific additions to riscv-rt's link.x (or minimal.x). INCLUDE
+   this _after_ rt.x in your device linker script. */
+
+/* Size of the snapshot area at the end of .noinit. See sentinel_rt::snapshot. */
+PROVIDE(_snapshot_size = 0);
+
+/* Region hot-reloaded applications are loaded into. Empty by default. See
+   sentinel_rt::reload. */
+PROVIDE(_sreload = 0);
+PROVIDE(_ereload = 0);
+
+/* Guard zone at the bottom of the stack. Empty by default. See
+   sentinel_rt::guard, and sentinel_rt::trap for overflow reports. */
+PROVIDE(_stack_guard_size = 0);
+_sstack_guard = _estack;
+_estack_guard = _estack + _stack_guard_size;
+ASSERT(_stack_guard_size % 4 == 0, "
+ERROR(sentinel-rt): _stack_guard_size must be a whole number of words");
+
+/* Handlers defined with #[sentinel_rt::interrupt]; by default, none. See
+   sentinel_rt::irq. */
+PROVIDE(__sentinel_irq_TIMER = __sentinel_irq_default);
+PROVIDE(__sentinel_irq_UART_RX = __sentinel_irq_default);
+PROVIDE(__sentinel_irq_UART_TX = __sentinel_irq_default);
+PROVIDE(__sentinel_irq_GPIO = __sentinel_irq_default);
+
+/* Where soft resets and the panic-bootloader policy jump. minimal.x's
+   start-up defines _sentinel_reset itself. See sentinel_rt::reset. */
+PROVIDE(_sentinel_reset = _start);
+PROVIDE(_bootloader = _sentinel_reset);
+
+/* What the trap-hooks entry dispatches with. See sentinel_rt::trap. */
+PROVIDE(_sentinel_trap_rust = _start_trap_rust);
+
+SECTIONS
+{
+  /* Statics registered with sentinel_rt::snapshot!(). */
+  .snapshot_table : ALIGN(4)
+  {
+    __ssnapshot_table = .;
+    KEEP(*(.sentinel.snapshot));
+    __esnapshot_table = .;
+  } > REGION_RODATA
+
+  /* Functions marked #[sentinel_rt::sentinel_test]. */
+  .test_table : ALIGN(4)
+  {
+    __stest_table = .;
+    KEEP(*(.sentinel.test));
+    __etest_table = .;
+  } > REGION_RODATA
+}
+INSERT AFTER .rodata;
+
+SECTIONS
+{
+  /* riscv-arch-test/RISCOF result area. This lives in REGION_DATA with
+     LMA == VMA; on the AttoSoC all of RAM is initialized with the bitstream,
+     so the initial canary values are already in place at reset. */
+  .signature : ALIGN(16)
+  {
+    begin_signature = .;
+    KEEP(*(.signature .signature.*));
+    . = ALIGN(16);
+    end_signature = .;
+  } > REGION_DATA
+}
+INSERT AFTER .data;
+
+SECTIONS
+{
+  /* Neither zeroed nor initialized at start-up, so contents survive a soft
+     reset (and can be preloaded by a simulator). */
+  .noinit (NOLOAD) : ALIGN(4)
+  {
+    _snoinit = .;
+    *(.noinit .noinit.*);
+    . = ALIGN(4);
+    _ssnapshot = .;
+    . += _snapshot_size;
+    _esnapshot = .;
+    _enoinit = .;
+  } > REGION_BSS
+}
+INSERT AFTER .bss;
@@ -0,0 +1,39 @@
+//! Atomics that work on Sentinel.
+//!
+//! Sentinel has no A extension, so `core::sync::atomic`'s types have no
+//! read-modify-write operations (`fetch_add`, `swap`, `compare_exchange`)
+//! on it, and code using them fails to build. These are `portable-atomic`'s
+//! types, which sentinel-rt configures for the target: there's one hart,
+//! so loads and stores are plain ones, and the rest are done with
+//! interrupts masked, as are all of `AtomicU64`'s and `AtomicI64`'s
+//! operations. On the host they're the native ones.
+//!
+//! Use these rather than depending on `portable-atomic` directly: there's
+//! nothing to configure, so no way to get it wrong.
+//!
+//! ```ignore
+//! use sentinel_rt::atomic::{AtomicU32, Ordering};
+//!
+//! static COUNT: AtomicU32 = AtomicU32::new(0);
+//! COUNT.fetch_add(1, Ordering::Relaxed);
+//! ```
+//!
+//! Crates of your own that need atomics from `portable-atomic` can depend
+//! on it with no features; Cargo unifies them with sentinel-rt's.
+
+pub use portable_atomic::{
+    compiler_fence, fence, AtomicBool, AtomicI16, AtomicI32, AtomicI64, AtomicI8, AtomicIsize,
+    AtomicPtr, AtomicU16, AtomicU32, AtomicU64, AtomicU8, AtomicUsize, Ordering,
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rmw() {
+        static N: AtomicU64 = AtomicU64::new(u32::MAX as u64);
+        assert_eq!(N.fetch_add(1, Ordering::Relaxed), u32::MAX as u64);
+        assert_eq!(N.swap(0, Ordering::Relaxed), 1 << 32);
+    }
+}
@@ -0,0 +1,157 @@
+//! Frame-pointer backtraces.
+//!
+//! With frame pointers, every frame keeps its return address and the
+//! caller's frame pointer just below where `s0` points, so the call stack
+//! can be walked without unwind tables:
+//!
+//! ```text
+//! fp - 1 word: return address
+//! fp - 2 words: caller's fp
+//! ```
+//!
+//! Frame pointers have to be asked for when building:
+//!
+//! ```text
+//! RUSTFLAGS="-C force-frame-pointers=yes" cargo build --features backtrace
+//! ```
+//!
+//! and the `backtrace` feature adds the return addresses to the panic
+//! handler's report. Without frame pointers, `s0` is an ordinary register,
+//! and a backtrace is garbage; the walk stays inside the stack and gives up
+//! at the first frame that doesn't make sense, so it's harmless garbage.
+
+use core::mem::size_of;
+use core::ops::Range;
+
+use crate::mem;
+
+/// Most frames recorded.
+pub const MAX_FRAMES: usize = 8;
+
+const WORD: usize = size_of::<usize>();
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Backtrace {
+    frames: [usize; MAX_FRAMES],
+    len: usize,
+}
+
+impl Backtrace {
+    /// Walk the stack from the caller's frame.
+    #[inline(always)]
+    pub fn capture() -> Self {
+        // SAFETY: The walk only reads words inside the stack.
+        unsafe { Self::walk(frame_pointer(), mem::stack()) }
+    }
+
+    /// Walk the frame chain starting at `fp`, reading nothing outside
+    /// `stack`. The walk stops at a misaligned or out-of-bounds frame
+    /// pointer, a null return address, or a caller's frame that isn't
+    /// above the current one (which also breaks loops in a corrupt chain).
+    ///
+    /// # Safety
+    ///
+    /// `stack` must be readable memory.
+    pub unsafe fn walk(mut fp: usize, stack: Range<usize>) -> Self {
+        let mut bt = Self::default();
+
+        while bt.len < MAX_FRAMES {
+            let in_stack = fp.is_multiple_of(WORD)
+                && fp >= stack.start + 2 * WORD
+                && fp <= stack.end;
+            if !in_stack {
+                break;
+            }
+
+            let ra = ((fp - WORD) as *const usize).read_volatile();
+            let caller = ((fp - 2 * WORD) as *const usize).read_volatile();
+            if ra == 0 {
+                break;
+            }
+
+            bt.frames[bt.len] = ra;
+            bt.len += 1;
+            if caller <= fp {
+                break;
+            }
+            fp = caller;
+        }
+
+        bt
+    }
+
+    /// Return addresses, innermost first.
+    pub fn frames(&self) -> &[usize] {
+        &self.frames[..self.len]
+    }
+}
+
+#[cfg(target_arch = "riscv32")]
+#[inline(always)]
+fn frame_pointer() -> usize {
+    let fp;
+    // SAFETY: Just reads a register.
+    unsafe { core::arch::asm!("mv {0}, s0", out(reg) fp) };
+    fp
+}
+
+#[cfg(not(target_arch = "riscv32"))]
+fn frame_pointer() -> usize {
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A fake stack, indices standing in for the frame layout above.
+    fn fake_stack(words: &mut [usize; 16]) -> Range<usize> {
+        let base = words.as_ptr() as usize;
+        let fp = |i: usize| base + i * WORD;
+
+        // Innermost frame: fp = 4, ra = 0x100, caller fp = 8.
+        words[3] = 0x100;
+        words[2] = fp(8);
+        // fp = 8, ra = 0x200, caller fp = 14.
+        words[7] = 0x200;
+        words[6] = fp(14);
+        // Outermost: fp = 14, ra = 0x300, caller fp points back down.
+        words[13] = 0x300;
+        words[12] = fp(4);
+
+        base..base + words.len() * WORD
+    }
+
+    #[test]
+    fn walk() {
+        let mut words = [0; 16];
+        let stack = fake_stack(&mut words);
+        let fp = stack.start + 4 * WORD;
+
+        let bt = unsafe { Backtrace::walk(fp, stack.clone()) };
+        assert_eq!(bt.frames(), &[0x100, 0x200, 0x300]);
+
+        // Out of bounds, or misaligned.
+        let bt = unsafe { Backtrace::walk(stack.end + WORD, stack.clone()) };
+        assert_eq!(bt.frames(), &[]);
+        let bt = unsafe { Backtrace::walk(fp + 1, stack.clone()) };
+        assert_eq!(bt.frames(), &[]);
+        let bt = unsafe { Backtrace::walk(stack.start + WORD, stack) };
+        assert_eq!(bt.frames(), &[]);
+    }
+
+    #[test]
+    fn bounded() {
+        // Every frame's caller is the frame above it, forever.
+        let mut words = [0; 32];
+        let base = words.as_ptr() as usize;
+        for i in (2..32).step_by(2) {
+            words[i - 1] = 0x1000 + i;
+            words[i - 2] = base + (i + 2) * WORD;
+        }
+        let stack = base..base + words.len() * WORD;
+
+        let bt = unsafe { Backtrace::walk(base + 2 * WORD, stack) };
+        assert_eq!(bt.frames().len(), MAX_FRAMES);
+    }
+}
@@ -0,0 +1,187 @@
+//! Boot banner, identifying the SoC.
+//!
+//! Opt-in; print it first thing, to know straight away which core, bus and
+//! firmware you're talking to:
+//!
+//! ```ignore
+//! let mut soc = Soc::init();
+//! sentinel_rt::banner!(&mut soc);
+//! ```
+//!
+//! ```text
+//! my-firmware 0.1.0
+//! core   rv32i mimpid 00000000
+//! bus    wishbone
+//! clock  12000000 Hz, tick 732 Hz
+//! ram    00000000..00001000
+//! gpio   02000000 v0.0 caps 00000000
+//! timer  40000000 v0.0 caps 00000000
+//! serial 80000000 v0.0 caps 00000000
+//! ```
+//!
+//! Peripheral versions and capabilities are as of the last
+//! [`caps::probe`], if any.
+
+use core::ops::Range;
+
+use crate::caps::{self, Ident};
+use crate::fixed;
+use crate::hex;
+use crate::io_addrs::{self, Bases, Bus};
+use crate::mem;
+use crate::power::Peripheral;
+use crate::shell::Output;
+use crate::soc::{Clocks, Soc};
+
+/// Which firmware this is. [`banner!`](crate::banner!) fills it in from
+/// the calling crate's manifest.
+#[derive(Clone, Copy)]
+pub struct Firmware {
+    pub name: &'static str,
+    pub version: &'static str,
+}
+
+/// Everything in the banner.
+pub struct Report {
+    pub firmware: Firmware,
+    /// `None` where the CSR isn't implemented (or reads as zero).
+    pub misa: Option<usize>,
+    pub mimpid: Option<usize>,
+    pub bases: Bases,
+    pub clocks: Clocks,
+    pub ram: Range<usize>,
+    pub idents: [Ident; 3],
+}
+
+impl Report {
+    pub fn gather(soc: &Soc, firmware: Firmware) -> Self {
+        Self {
+            firmware,
+            misa: riscv::register::misa::read().map(|m| m.bits()),
+            mimpid: riscv::register::mimpid::read().map(|m| m.bits()),
+            bases: io_addrs::detected().unwrap_or(Bases::for_bus(soc.bus)),
+            clocks: soc.clocks,
+            ram: mem::text().start..mem::stack().end,
+            idents: Peripheral::ALL.map(caps::get),
+        }
+    }
+
+    pub fn write(&self, out: &mut dyn Output) {
+        let mut buf = [0; fixed::MAX_LEN];
+
+        out.write_str(self.firmware.name);
+        out.write_bytes(b" ");
+        out.write_str(self.firmware.version);
+
+        out.write_str("\r\ncore   ");
+        match self.misa {
+            Some(misa) => write_isa(out, misa),
+            None => out.write_str("unknown"),
+        }
+        out.write_str(" mimpid ");
+        out.write_bytes(&hex::u32_digits(self.mimpid.unwrap_or(0) as u32));
+
+        out.write_str("\r\nbus    ");
+        out.write_str(match self.bases.bus {
+            Bus::Wishbone => "wishbone",
+            Bus::Csr => "csr",
+        });
+
+        out.write_str("\r\nclock  ");
+        out.write_bytes(fixed::format(self.clocks.sysclk_hz as i32, 0, &mut buf));
+        out.write_str(" Hz, tick ");
+        out.write_bytes(fixed::format(self.clocks.tick_hz() as i32, 0, &mut buf));
+        out.write_str(" Hz");
+
+        out.write_str("\r\nram    ");
+        out.write_bytes(&hex::u32_digits(self.ram.start as u32));
+        out.write_str("..");
+        out.write_bytes(&hex::u32_digits(self.ram.end as u32));
+
+        let bases = [
+            ("\r\ngpio   ", u32::from(self.bases.gpio)),
+            ("\r\ntimer  ", u32::from(self.bases.timer)),
+            ("\r\nserial ", u32::from(self.bases.serial)),
+        ];
+        for ((name, base), ident) in bases.into_iter().zip(self.idents) {
+            out.write_str(name);
+            out.write_bytes(&hex::u32_digits(base));
+            out.write_str(" v");
+            out.write_bytes(fixed::format(ident.major as i32, 0, &mut buf));
+            out.write_str(".");
+            out.write_bytes(fixed::format(ident.minor as i32, 0, &mut buf));
+            out.write_str(" caps ");
+            out.write_bytes(&hex::u32_digits(ident.caps.0));
+        }
+        out.write_str("\r\n");
+    }
+}
+
+/// `misa` as an ISA string, e.g. `rv32imc`.
+fn write_isa(out: &mut dyn Output, misa: usize) {
+    // MXL is the top two bits, whatever the XLEN.
+    out.write_str(match misa >> (usize::BITS - 2) {
+        1 => "rv32",
+        2 => "rv64",
+        3 => "rv128",
+        _ => "rv?",
+    });
+    for (i, ext) in (b'a'..=b'z').enumerate() {
+        if misa & (1 << i) != 0 {
+            out.write_bytes(&[ext]);
+        }
+    }
+}
+
+/// Print the [boot banner](crate::banner) to a `&mut Soc`'s UART. Use
+/// [`Report`] directly to print it elsewhere.
+#[macro_export]
+macro_rules! banner {
+    ($soc:expr) => {{
+        let soc: &mut $crate::soc::Soc = $soc;
+        $crate::banner::Report::gather(soc, $crate::banner::Firmware {
+            name: env!("CARGO_PKG_NAME"),
+            version: env!("CARGO_PKG_VERSION"),
+        })
+        .write(&mut soc.serial)
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use heapless::Vec;
+
+    use super::*;
+    use crate::caps::Caps;
+
+    impl Output for Vec<u8, 512> {
+        fn write_bytes(&mut self, bytes: &[u8]) {
+            self.extend_from_slice(bytes).unwrap();
+        }
+    }
+
+    #[test]
+    fn report() {
+        let report = Report {
+            firmware: Firmware { name: "demo", version: "1.2.3" },
+            misa: Some(1 << (usize::BITS - 2) | 1 << 8 | 1 << 12),
+            mimpid: None,
+            bases: Bases::for_bus(Bus::Csr),
+            clocks: Clocks { sysclk_hz: 12_000_000, cycles_per_tick: 16384 },
+            ram: 0..0x1000,
+            idents: [Ident::BASELINE, Ident::BASELINE,
+                     Ident { major: 1, minor: 2, caps: Caps::SERIAL_DIVISOR }],
+        };
+
+        let mut out: Vec<u8, 512> = Vec::new();
+        report.write(&mut out);
+        assert_eq!(core::str::from_utf8(&out).unwrap(), "demo 1.2.3\r\n\
+            core   rv32im mimpid 00000000\r\n\
+            bus    csr\r\n\
+            clock  12000000 Hz, tick 732 Hz\r\n\
+            ram    00000000..00001000\r\n\
+            gpio   02000000 v0.0 caps 00000000\r\n\
+            timer  02800000 v0.0 caps 00000000\r\n\
+            serial 03000000 v1.2 caps 00000001\r\n");
+    }
+}
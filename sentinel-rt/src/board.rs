@@ -0,0 +1,170 @@
+//! Board configuration, checked at compile time.
+//!
+//! Use [`Board::validated`] in a `const` and a misconfigured board fails the
+//! build with a (terse, since const panics can't format) message, instead of
+//! producing garbage baud rates or corrupted buffers on hardware:
+//!
+//! ```ignore
+//! const MY_BOARD: Board = Board { rx_buffer_len: 256, ..board::CURRENT }.validated();
+//! ```
+
+use crate::io_map;
+
+/// A range of the address space.
+#[derive(Clone, Copy)]
+pub struct Region {
+    pub name: &'static str,
+    pub start: u32,
+    pub len: u32,
+}
+
+impl Region {
+    pub const fn new(name: &'static str, start: u32, len: u32) -> Self {
+        Self { name, start, len }
+    }
+
+    /// One past the last address, as a u64 so a region ending at 4 GiB is
+    /// representable.
+    pub const fn end(&self) -> u64 {
+        self.start as u64 + self.len as u64
+    }
+
+    pub const fn overlaps(&self, other: &Region) -> bool {
+        (self.start as u64) < other.end() && (other.start as u64) < self.end()
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Board {
+    pub clk_hz: u32,
+    /// Clocks between timer IRQs.
+    pub cycles_per_tick: u32,
+    pub baud: u32,
+    /// Sizes of the UART's software buffers. Powers of two, so indices can
+    /// be masked rather than divided.
+    pub rx_buffer_len: usize,
+    pub tx_buffer_len: usize,
+    /// RAM and peripherals. Must not overlap.
+    pub regions: &'static [Region],
+}
+
+/// `rate` divides `clk_hz` with no remainder.
+pub const fn divides_evenly(clk_hz: u32, rate: u32) -> bool {
+    rate != 0 && clk_hz.is_multiple_of(rate)
+}
+
+/// No two of `regions` overlap, and none is empty.
+pub const fn regions_disjoint(regions: &[Region]) -> bool {
+    let mut i = 0;
+    while i < regions.len() {
+        if regions[i].len == 0 {
+            return false;
+        }
+
+        let mut j = i + 1;
+        while j < regions.len() {
+            if regions[i].overlaps(&regions[j]) {
+                return false;
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+
+    true
+}
+
+impl Board {
+    /// Panic (at compile time, when used in a `const`) if the config is
+    /// inconsistent; otherwise return it unchanged.
+    pub const fn validated(self) -> Self {
+        if self.clk_hz == 0 {
+            panic!("board: clk_hz is zero");
+        }
+        // The timer IRQ is a prescaler bit.
+        if !self.cycles_per_tick.is_power_of_two() {
+            panic!("board: cycles_per_tick must be a power of two");
+        }
+        if !divides_evenly(self.clk_hz, self.baud) {
+            panic!("board: baud rate doesn't divide the clock evenly");
+        }
+        if !self.rx_buffer_len.is_power_of_two() {
+            panic!("board: rx_buffer_len must be a power of two");
+        }
+        if !self.tx_buffer_len.is_power_of_two() {
+            panic!("board: tx_buffer_len must be a power of two");
+        }
+        if !regions_disjoint(self.regions) {
+            panic!("board: memory regions overlap (or are empty)");
+        }
+
+        self
+    }
+
+    pub const fn tick_hz(&self) -> u32 {
+        self.clk_hz / self.cycles_per_tick
+    }
+}
+
+/// The board being built for: the AttoSoC (`examples/attosoc.py`), with
+/// RAM, peripherals, clock and baud rate as its description (see
+/// [`io_map`]) says. Both peripheral bus variants are listed, they live at
+/// separate addresses, as is the SPRAM heap if the board has one.
+/// [`Soc::init`](crate::soc::Soc::init)'s clocks, and the panic handler's
+/// UART timing, come from here.
+pub const CURRENT: Board = Board {
+    clk_hz: io_map::CLK_HZ,
+    cycles_per_tick: crate::timebase::CYCLES_PER_TICK,
+    baud: io_map::BAUD,
+    rx_buffer_len: 64,
+    tx_buffer_len: 64,
+    regions: if io_map::SPRAM_LENGTH == 0 { &REGIONS } else { &REGIONS_SPRAM },
+}
+.validated();
+
+const REGIONS: [Region; 7] = [
+    Region::new("ram", io_map::RAM_ORIGIN, io_map::RAM_LENGTH),
+    Region::new("gpio", io_map::CSR_GPIO, 0x10),
+    Region::new("timer (csr)", io_map::CSR_TIMER, 0x8),
+    Region::new("serial (csr)", io_map::CSR_SERIAL, 0x8),
+    Region::new("host port", 0x0400_0000, 0x10),
+    Region::new("timer (wb)", io_map::WB_TIMER, 0x4),
+    Region::new("serial (wb)", io_map::WB_SERIAL, 0x8),
+];
+
+/// [`REGIONS`], and the heap's SPRAM, on a board that has it.
+const REGIONS_SPRAM: [Region; 8] = {
+    let mut r = [Region::new("spram", io_map::SPRAM_ORIGIN, io_map::SPRAM_LENGTH); 8];
+    let mut i = 0;
+    while i < REGIONS.len() {
+        r[i] = REGIONS[i];
+        i += 1;
+    }
+    r
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overlap() {
+        let a = Region::new("a", 0x100, 0x100);
+        assert!(a.overlaps(&Region::new("b", 0x1ff, 1)));
+        assert!(!a.overlaps(&Region::new("b", 0x200, 1)));
+        assert!(Region::new("top", 0xffff_ff00, 0x100).end() == 1 << 32);
+        assert!(!regions_disjoint(&[a, Region::new("b", 0, 0x101)]));
+    }
+
+    #[test]
+    #[should_panic(expected = "power of two")]
+    fn bad_buffer() {
+        Board { rx_buffer_len: 48, ..CURRENT }.validated();
+    }
+
+    #[test]
+    #[should_panic(expected = "baud")]
+    fn bad_baud() {
+        Board { baud: 115_200, ..CURRENT }.validated();
+    }
+}
@@ -0,0 +1,452 @@
+//! Remote I/O over the UART, Firmata-style.
+//!
+//! Firmware that runs a [`Bridge`] turns the board into an I/O bridge a PC
+//! can script (see `sentinel-tools`' `bridge` client), without reflashing
+//! for each experiment:
+//!
+//! ```ignore
+//! let soc = Soc::init();
+//! let mut bridge = Bridge::new(soc.gpio, soc.timer, Pins::DEFAULT);
+//! loop {
+//!     bridge.poll(&mut soc.serial);
+//! }
+//! ```
+//!
+//! Each request is one frame, and gets one response frame back:
+//!
+//! ```text
+//! a5 len payload[len] crc8(len, payload)
+//! ```
+//!
+//! A request's payload is an [`Op`] followed by its arguments; a response's
+//! is a [`Status`] followed by the results. Multi-byte numbers are little
+//! endian.
+//!
+//! | op                 | arguments            | results        |
+//! |--------------------|----------------------|----------------|
+//! | `Ping`             |                      | [`VERSION`]    |
+//! | `GpioRead`         |                      | inputs         |
+//! | `GpioWrite`        | level                |                |
+//! | `GpioOutputEnable` | mask                 |                |
+//! | `Leds`             | value                |                |
+//! | `Ticks`            |                      | `u32` ticks    |
+//! | `Wait`             | `u16` ticks          |                |
+//! | `Spi`              | bytes                | bytes read     |
+//! | `I2cWrite`         | addr, bytes          |                |
+//! | `I2cRead`          | addr, n              | n bytes        |
+//! | `I2cWriteRead`     | addr, n, bytes       | n bytes        |
+//!
+//! SPI and I2C are bit-banged on the GPIO pins in [`Pins`]. A transaction
+//! takes the pins over, and the GPIO output and output enable registers are
+//! put back as the host last set them afterwards.
+
+use crate::checksum::crc8;
+use crate::hal::gpio::Gpio;
+use crate::hal::i2c::{self, GpioPins, I2c};
+use crate::hal::serial::Serial;
+use crate::hal::spi::{self, Spi};
+use crate::hal::timer::Timer;
+use crate::io_addrs::GpioBase;
+use crate::timebase;
+
+/// Protocol version, returned by [`Op::Ping`].
+pub const VERSION: u8 = 1;
+
+/// Starts every frame.
+pub const SYNC: u8 = 0xa5;
+
+/// Longest payload, either way.
+pub const MAX_PAYLOAD: usize = 64;
+
+/// Longest frame: sync, length, payload, CRC.
+pub const MAX_FRAME: usize = MAX_PAYLOAD + 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Op {
+    Ping = 0x00,
+    GpioRead = 0x10,
+    GpioWrite = 0x11,
+    GpioOutputEnable = 0x12,
+    Leds = 0x13,
+    Ticks = 0x20,
+    Wait = 0x21,
+    Spi = 0x30,
+    I2cWrite = 0x40,
+    I2cRead = 0x41,
+    I2cWriteRead = 0x42,
+}
+
+impl Op {
+    pub const fn from_u8(val: u8) -> Option<Self> {
+        Some(match val {
+            0x00 => Self::Ping,
+            0x10 => Self::GpioRead,
+            0x11 => Self::GpioWrite,
+            0x12 => Self::GpioOutputEnable,
+            0x13 => Self::Leds,
+            0x20 => Self::Ticks,
+            0x21 => Self::Wait,
+            0x30 => Self::Spi,
+            0x40 => Self::I2cWrite,
+            0x41 => Self::I2cRead,
+            0x42 => Self::I2cWriteRead,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Status {
+    Ok = 0,
+    /// The request's CRC didn't match.
+    BadFrame = 1,
+    UnknownOp = 2,
+    /// Too few arguments, or a result that wouldn't fit.
+    BadArgs = 3,
+    I2cAddressNack = 4,
+    I2cDataNack = 5,
+    I2cTimeout = 6,
+}
+
+impl Status {
+    pub const fn from_u8(val: u8) -> Option<Self> {
+        Some(match val {
+            0 => Self::Ok,
+            1 => Self::BadFrame,
+            2 => Self::UnknownOp,
+            3 => Self::BadArgs,
+            4 => Self::I2cAddressNack,
+            5 => Self::I2cDataNack,
+            6 => Self::I2cTimeout,
+            _ => return None,
+        })
+    }
+}
+
+impl crate::error::Describe for Status {
+    fn describe(&self) -> &'static str {
+        match self {
+            Self::Ok => "bridge: ok",
+            Self::BadFrame => "bridge: bad frame",
+            Self::UnknownOp => "bridge: unknown op",
+            Self::BadArgs => "bridge: bad arguments",
+            Self::I2cAddressNack => "i2c: address nack",
+            Self::I2cDataNack => "i2c: data nack",
+            Self::I2cTimeout => "i2c: scl held low",
+        }
+    }
+}
+
+impl From<i2c::Error> for Status {
+    fn from(e: i2c::Error) -> Self {
+        match e {
+            i2c::Error::AddressNack => Self::I2cAddressNack,
+            i2c::Error::DataNack => Self::I2cDataNack,
+            i2c::Error::Timeout => Self::I2cTimeout,
+        }
+    }
+}
+
+/// Frame `payload` (at most [`MAX_PAYLOAD`] bytes) into `out`.
+pub fn encode<'a>(payload: &[u8], out: &'a mut [u8; MAX_FRAME]) -> &'a [u8] {
+    let len = payload.len();
+    assert!(len <= MAX_PAYLOAD, "bridge: payload too long");
+    out[0] = SYNC;
+    out[1] = len as u8;
+    out[2..2 + len].copy_from_slice(payload);
+    out[2 + len] = crc8(&out[1..2 + len]);
+    &out[..3 + len]
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Sync,
+    Len,
+    Payload,
+    Crc,
+}
+
+/// Picks frames out of a byte stream.
+pub struct Decoder {
+    state: State,
+    buf: [u8; MAX_PAYLOAD + 1],
+    len: usize,
+    pos: usize,
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder {
+    pub const fn new() -> Self {
+        Self {
+            state: State::Sync,
+            buf: [0; MAX_PAYLOAD + 1],
+            len: 0,
+            pos: 0,
+        }
+    }
+
+    /// Take the next byte. At the end of a frame, returns its payload, or
+    /// `Err` if the CRC was wrong. Anything between frames, and a length
+    /// over [`MAX_PAYLOAD`], is skipped.
+    pub fn feed(&mut self, b: u8) -> Option<Result<&[u8], Status>> {
+        match self.state {
+            State::Sync => {
+                if b == SYNC {
+                    self.state = State::Len;
+                }
+            }
+            State::Len => {
+                if b as usize > MAX_PAYLOAD {
+                    self.state = State::Sync;
+                    return None;
+                }
+                self.buf[0] = b;
+                self.len = b as usize;
+                self.pos = 0;
+                self.state = if b == 0 { State::Crc } else { State::Payload };
+            }
+            State::Payload => {
+                self.pos += 1;
+                self.buf[self.pos] = b;
+                if self.pos == self.len {
+                    self.state = State::Crc;
+                }
+            }
+            State::Crc => {
+                self.state = State::Sync;
+                let frame = &self.buf[..1 + self.len];
+                return Some(if crc8(frame) == b {
+                    Ok(&frame[1..])
+                } else {
+                    Err(Status::BadFrame)
+                });
+            }
+        }
+        None
+    }
+}
+
+/// GPIO pins for the bit-banged buses.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Pins {
+    pub i2c_scl: u8,
+    pub i2c_sda: u8,
+    pub spi: spi::Pins,
+}
+
+impl Pins {
+    /// I2C on PMOD pins 3 and 4 (as in [`i2c`]), SPI on the rest.
+    pub const DEFAULT: Self = Self {
+        i2c_scl: 0,
+        i2c_sda: 1,
+        spi: spi::Pins { cs: 4, mosi: 5, miso: 6, sck: 7 },
+    };
+}
+
+pub struct Bridge {
+    gpio: GpioBase,
+    timer: Timer,
+    pins: Pins,
+    /// The host's view of the (write-only) GPIO outputs.
+    out: u8,
+    oe: u8,
+    decoder: Decoder,
+}
+
+impl Bridge {
+    pub fn new(mut gpio: Gpio, timer: Timer, pins: Pins) -> Self {
+        gpio.write_outputs(0);
+        gpio.set_output_enable(0);
+        Self {
+            gpio: gpio.base(),
+            timer,
+            pins,
+            out: 0,
+            oe: 0,
+            decoder: Decoder::new(),
+        }
+    }
+
+    /// Keep [`timebase`] going, and handle any request that's come in.
+    /// Run with the timer interrupt off: the bridge acks it itself.
+    pub fn poll(&mut self, ser: &mut Serial) {
+        if self.timer.ack() {
+            timebase::tick();
+        }
+        let Some(b) = ser.read_byte() else {
+            return;
+        };
+
+        let mut req = [0; MAX_PAYLOAD];
+        let req = match self.decoder.feed(b) {
+            None => return,
+            Some(Ok(payload)) => {
+                req[..payload.len()].copy_from_slice(payload);
+                Ok(&req[..payload.len()])
+            }
+            Some(Err(status)) => Err(status),
+        };
+
+        let mut resp = [0; MAX_PAYLOAD];
+        let len = match req.and_then(|req| self.handle(req, &mut resp[1..])) {
+            Ok(len) => 1 + len,
+            Err(status) => {
+                resp[0] = status as u8;
+                1
+            }
+        };
+        let mut frame = [0; MAX_FRAME];
+        ser.write_bytes(encode(&resp[..len], &mut frame));
+    }
+
+    /// Put the GPIO back as the host left it, after a bus transaction.
+    fn restore(&self) {
+        let mut gpio = Gpio::new(self.gpio);
+        gpio.write_outputs(self.out);
+        gpio.set_output_enable(self.oe);
+    }
+
+    /// Run one request. Returns the length of the results put in `resp`.
+    fn handle(&mut self, req: &[u8], resp: &mut [u8]) -> Result<usize, Status> {
+        let (&op, args) = req.split_first().ok_or(Status::BadArgs)?;
+        let op = Op::from_u8(op).ok_or(Status::UnknownOp)?;
+        let arg = |i: usize| args.get(i).copied().ok_or(Status::BadArgs);
+        let mut gpio = Gpio::new(self.gpio);
+
+        match op {
+            Op::Ping => {
+                resp[0] = VERSION;
+                Ok(1)
+            }
+            Op::GpioRead => {
+                resp[0] = gpio.read_inputs();
+                Ok(1)
+            }
+            Op::GpioWrite => {
+                self.out = arg(0)?;
+                gpio.write_outputs(self.out);
+                Ok(0)
+            }
+            Op::GpioOutputEnable => {
+                self.oe = arg(0)?;
+                gpio.set_output_enable(self.oe);
+                Ok(0)
+            }
+            Op::Leds => {
+                gpio.set_leds(arg(0)?);
+                Ok(0)
+            }
+            Op::Ticks => {
+                resp[..4].copy_from_slice(&timebase::ticks32().to_le_bytes());
+                Ok(4)
+            }
+            Op::Wait => {
+                for _ in 0..u16::from_le_bytes([arg(0)?, arg(1)?]) {
+                    self.timer.wait_tick();
+                    timebase::tick();
+                }
+                Ok(0)
+            }
+            Op::Spi => {
+                let buf = resp.get_mut(..args.len()).ok_or(Status::BadArgs)?;
+                buf.copy_from_slice(args);
+                let mut spi = Spi::new(gpio, self.pins.spi);
+                spi.transfer(buf);
+                spi.free();
+                self.restore();
+                Ok(args.len())
+            }
+            Op::I2cWrite | Op::I2cRead | Op::I2cWriteRead => {
+                let addr = arg(0)?;
+                let (n, bytes) = match op {
+                    Op::I2cWrite => (0, &args[1..]),
+                    _ => (arg(1)? as usize, &args[2..]),
+                };
+                let buf = resp.get_mut(..n).ok_or(Status::BadArgs)?;
+
+                let pins = GpioPins::new(gpio, self.pins.i2c_scl, self.pins.i2c_sda);
+                let mut i2c = I2c::new(pins);
+                let res = match op {
+                    Op::I2cWrite => i2c.write(addr, bytes),
+                    Op::I2cRead => i2c.read(addr, buf),
+                    _ => i2c.write_read(addr, bytes, buf),
+                };
+                i2c.free().free();
+                self.restore();
+                res.map_err(Status::from)?;
+                Ok(n)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use heapless::Vec;
+
+    use super::*;
+
+    type Payload = Vec<u8, MAX_PAYLOAD>;
+
+    /// The last frame in `bytes`.
+    fn decode_all(d: &mut Decoder, bytes: &[u8]) -> Option<Result<Payload, Status>> {
+        let mut last = None;
+        for b in bytes {
+            if let Some(r) = d.feed(*b) {
+                last = Some(r.map(|p| Vec::from_slice(p).unwrap()));
+            }
+        }
+        last
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut frame = [0; MAX_FRAME];
+        let bytes = encode(&[Op::I2cRead as u8, 0x48, 2], &mut frame);
+        assert_eq!(&bytes[..5], &[SYNC, 3, 0x41, 0x48, 2]);
+
+        // Noise before the frame is skipped.
+        let mut d = Decoder::new();
+        let mut stream: Vec<u8, 16> = Vec::from_slice(b"xx").unwrap();
+        stream.extend_from_slice(bytes).unwrap();
+        let payload = decode_all(&mut d, &stream).unwrap().unwrap();
+        assert_eq!(&payload[..], &[0x41, 0x48, 2]);
+
+        let empty = encode(&[], &mut frame);
+        assert_eq!(decode_all(&mut d, empty), Some(Ok(Vec::new())));
+    }
+
+    #[test]
+    fn bad_crc() {
+        let mut frame = [0; MAX_FRAME];
+        let len = encode(&[Op::Ping as u8], &mut frame).len();
+        frame[len - 1] ^= 1;
+        let mut d = Decoder::new();
+        assert_eq!(decode_all(&mut d, &frame[..len]), Some(Err(Status::BadFrame)));
+
+        // And it's back in sync for the next one.
+        let good = encode(&[Op::Ping as u8], &mut frame);
+        assert!(matches!(decode_all(&mut d, good), Some(Ok(_))));
+    }
+
+    #[test]
+    fn codes() {
+        for op in 0..=0xff {
+            if let Some(o) = Op::from_u8(op) {
+                assert_eq!(o as u8, op);
+            }
+        }
+        for s in 0..=0xff {
+            if let Some(st) = Status::from_u8(s) {
+                assert_eq!(st as u8, s);
+            }
+        }
+    }
+}
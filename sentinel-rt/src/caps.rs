@@ -0,0 +1,161 @@
+//! Peripheral version and capability registers.
+//!
+//! No AttoSoC bitstream has these yet. The planned layout is two read-only
+//! words at each peripheral's `base + OFFSET`: an ID word, `0x5349_MMmm`
+//! ("SI", then the major and minor version), followed by a word of [`Caps`]
+//! bits.
+//!
+//! What a read of an unimplemented address returns depends on the bus, so
+//! [`probe`] is opt-in: call it only on bitstreams known to decode the ID
+//! registers. Without a probe (or if the ID word doesn't check out),
+//! [`get`] returns [`Ident::BASELINE`], the features every AttoSoC has. One
+//! firmware binary then adapts, rather than assuming a bitstream:
+//!
+//! ```ignore
+//! if caps::get(Peripheral::Serial).supports(Caps::SERIAL_DIVISOR) {
+//!     autobaud(&mut soc.serial);
+//! }
+//! ```
+
+use core::cell::Cell;
+use core::ops::BitOr;
+use core::ptr::read_volatile;
+
+use critical_section::Mutex;
+
+use crate::io_addrs::Bases;
+use crate::power::Peripheral;
+
+/// Offset of the ID word from a peripheral's base. The capability word
+/// follows it.
+pub const OFFSET: u32 = 0x18;
+
+const MAGIC: u32 = 0x5349;
+
+/// Optional features of a peripheral. Bits are only meaningful for the
+/// peripheral they're named after.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Caps(pub u32);
+
+impl Caps {
+    pub const NONE: Self = Self(0);
+    /// The GPIO inputs can raise an IRQ on change.
+    pub const GPIO_IRQ: Self = Self(1 << 0);
+    /// The timer's free-running counter is readable.
+    pub const TIMER_COUNTER: Self = Self(1 << 0);
+    /// The timer's period is writable, instead of fixed at
+    /// [`CYCLES_PER_TICK`](crate::timebase::CYCLES_PER_TICK).
+    pub const TIMER_PERIOD: Self = Self(1 << 1);
+    /// The baud rate divisor is writable.
+    pub const SERIAL_DIVISOR: Self = Self(1 << 0);
+    /// The UART has a hardware RX FIFO.
+    pub const SERIAL_FIFO: Self = Self(1 << 1);
+
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for Caps {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Ident {
+    pub major: u8,
+    pub minor: u8,
+    pub caps: Caps,
+}
+
+impl Ident {
+    /// A peripheral without ID registers: version 0.0, no optional
+    /// features.
+    pub const BASELINE: Self = Self {
+        major: 0,
+        minor: 0,
+        caps: Caps::NONE,
+    };
+
+    /// Decode the ID and capability words. `None` if the ID word isn't one.
+    pub const fn decode(id: u32, caps: u32) -> Option<Self> {
+        if id >> 16 != MAGIC {
+            return None;
+        }
+
+        Some(Self {
+            major: (id >> 8) as u8,
+            minor: id as u8,
+            caps: Caps(caps),
+        })
+    }
+
+    pub const fn supports(&self, cap: Caps) -> bool {
+        self.caps.contains(cap)
+    }
+
+    /// Of the features in `wanted`, the ones this peripheral has.
+    pub const fn negotiate(&self, wanted: Caps) -> Caps {
+        Caps(self.caps.0 & wanted.0)
+    }
+}
+
+static IDENTS: Mutex<Cell<[Ident; 3]>> =
+    Mutex::new(Cell::new([Ident::BASELINE; 3]));
+
+/// Read the ID registers at `base`, falling back to [`Ident::BASELINE`].
+///
+/// # Safety
+///
+/// `base + OFFSET` and the word after it must be readable without side
+/// effects, i.e. the bitstream must implement the ID registers.
+pub unsafe fn read(base: u32) -> Ident {
+    let id = read_volatile((base + OFFSET) as *const u32);
+    let caps = read_volatile((base + OFFSET + 4) as *const u32);
+    Ident::decode(id, caps).unwrap_or(Ident::BASELINE)
+}
+
+/// Read every peripheral's ID registers, and remember them for [`get`].
+///
+/// # Safety
+///
+/// Same as [`read`], for each peripheral in `bases`.
+pub unsafe fn probe(bases: &Bases) {
+    let idents = [
+        read(u32::from(bases.gpio)),
+        read(u32::from(bases.timer)),
+        read(u32::from(bases.serial)),
+    ];
+    critical_section::with(|cs| IDENTS.borrow(cs).set(idents));
+}
+
+/// `p`'s identity, as of the last [`probe`].
+pub fn get(p: Peripheral) -> Ident {
+    critical_section::with(|cs| IDENTS.borrow(cs).get()[p as usize])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode() {
+        let ident = Ident::decode(0x5349_0102, 0b11).unwrap();
+        assert_eq!((ident.major, ident.minor), (1, 2));
+        assert!(ident.supports(Caps::SERIAL_DIVISOR | Caps::SERIAL_FIFO));
+        assert_eq!(ident.negotiate(Caps(0b101)), Caps::SERIAL_DIVISOR);
+
+        // Unimplemented registers, reading as zeros or all ones.
+        assert_eq!(Ident::decode(0, 0), None);
+        assert_eq!(Ident::decode(u32::MAX, u32::MAX), None);
+    }
+
+    #[test]
+    fn unprobed_is_baseline() {
+        assert_eq!(get(Peripheral::Serial), Ident::BASELINE);
+        assert!(!get(Peripheral::Timer).supports(Caps::TIMER_PERIOD));
+    }
+}
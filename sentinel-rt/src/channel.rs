@@ -0,0 +1,211 @@
+//! Passing values between an ISR and the main loop, from a plain static.
+//!
+//! [`spsc`](crate::spsc)'s queue hands out a producer and a consumer, and
+//! whichever end the ISR uses has to be parked somewhere it can reach,
+//! usually a `Mutex<RefCell<Option<..>>>` filled in at startup. These need
+//! no such setup: construct one in a `static`, and both sides use it
+//! directly.
+//!
+//! [`IrqChannel`] is a queue, for a stream of values (bytes to send,
+//! samples taken) where every one counts:
+//!
+//! ```ignore
+//! static TX: IrqChannel<u8, 64> = IrqChannel::new();
+//!
+//! let _ = TX.send(b'A');
+//!
+//! // In the ISR:
+//! if let Some(b) = TX.recv() {
+//!     serial.start_write(b);
+//! }
+//! ```
+//!
+//! [`Mailbox`] holds one value, for when only the latest matters (the
+//! last reading, the current setpoint): posting overwrites whatever
+//! wasn't taken yet.
+//!
+//! ```ignore
+//! static ADC: Mailbox<u16> = Mailbox::new();
+//!
+//! ADC.post(sample); // in the ISR
+//! if let Some(sample) = ADC.take() { /* ... */ }
+//! ```
+
+use core::cell::{Cell, UnsafeCell};
+use core::mem::MaybeUninit;
+
+use critical_section::Mutex;
+
+use crate::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// A queue of up to `N` values; `N` must be a power of two.
+///
+/// Meant for one sender and one receiver, typically one in an ISR and the
+/// other in the main loop. Each side's index is only written by that side,
+/// so sending and receiving don't mask interrupts, except to flag the side
+/// busy. That flag keeps it safe to use any other way too: a send that
+/// interrupts another send (an ISR sending while main was) fails, and
+/// likewise for receives.
+pub struct IrqChannel<T, const N: usize> {
+    buf: UnsafeCell<[MaybeUninit<T>; N]>,
+    /// Free-running counts of values sent and received. Only the sender
+    /// writes `write`, only the receiver `read`.
+    write: AtomicUsize,
+    read: AtomicUsize,
+    sending: AtomicBool,
+    receiving: AtomicBool,
+}
+
+// SAFETY: The sender only touches slots between `write` and `read + N`,
+// the receiver only those between `read` and `write`, each publishes its
+// index (Release) only after it's done with the slot it passes over, and
+// the busy flags keep each side to one at a time.
+unsafe impl<T: Send, const N: usize> Sync for IrqChannel<T, N> {}
+
+impl<T: Copy, const N: usize> Default for IrqChannel<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Copy, const N: usize> IrqChannel<T, N> {
+    const POWER_OF_TWO: () = assert!(N.is_power_of_two(), "channel length must be a power of two");
+
+    pub const fn new() -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::POWER_OF_TWO;
+        Self {
+            buf: UnsafeCell::new([MaybeUninit::uninit(); N]),
+            write: AtomicUsize::new(0),
+            read: AtomicUsize::new(0),
+            sending: AtomicBool::new(false),
+            receiving: AtomicBool::new(false),
+        }
+    }
+
+    /// Queue `value`. Hands it back if the channel is full, or if this
+    /// interrupted another send.
+    pub fn send(&self, value: T) -> Result<(), T> {
+        if self.sending.swap(true, Ordering::Acquire) {
+            return Err(value);
+        }
+        let w = self.write.load(Ordering::Relaxed);
+        let full = w.wrapping_sub(self.read.load(Ordering::Acquire)) == N;
+        if !full {
+            // SAFETY: The slot is the sender's, per the indices, and this
+            // is the only sender, per the flag.
+            unsafe { (*self.buf.get())[w % N].write(value) };
+            self.write.store(w.wrapping_add(1), Ordering::Release);
+        }
+        self.sending.store(false, Ordering::Release);
+        if full {
+            Err(value)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Take the oldest value. `None` if the channel is empty, or if this
+    /// interrupted another receive.
+    pub fn recv(&self) -> Option<T> {
+        if self.receiving.swap(true, Ordering::Acquire) {
+            return None;
+        }
+        let r = self.read.load(Ordering::Relaxed);
+        let value = (self.write.load(Ordering::Acquire) != r).then(|| {
+            // SAFETY: The slot is the receiver's, and was written before
+            // `write` passed it.
+            let value = unsafe { (*self.buf.get())[r % N].assume_init() };
+            self.read.store(r.wrapping_add(1), Ordering::Release);
+            value
+        });
+        self.receiving.store(false, Ordering::Release);
+        value
+    }
+
+    /// Values queued.
+    pub fn len(&self) -> usize {
+        let w = self.write.load(Ordering::Acquire);
+        w.wrapping_sub(self.read.load(Ordering::Acquire))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// The latest of a series of values, until taken.
+pub struct Mailbox<T> {
+    value: Mutex<Cell<Option<T>>>,
+}
+
+impl<T: Copy> Default for Mailbox<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Copy> Mailbox<T> {
+    pub const fn new() -> Self {
+        Self { value: Mutex::new(Cell::new(None)) }
+    }
+
+    /// Leave `value`, replacing any not yet taken. Returns that one.
+    pub fn post(&self, value: T) -> Option<T> {
+        critical_section::with(|cs| self.value.borrow(cs).replace(Some(value)))
+    }
+
+    /// The value, leaving the mailbox empty.
+    pub fn take(&self) -> Option<T> {
+        critical_section::with(|cs| self.value.borrow(cs).take())
+    }
+
+    /// The value, leaving it there.
+    pub fn peek(&self) -> Option<T> {
+        critical_section::with(|cs| self.value.borrow(cs).get())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channel() {
+        let ch = IrqChannel::<u16, 4>::new();
+        assert_eq!(ch.recv(), None);
+        for v in 1..=4 {
+            ch.send(v).unwrap();
+        }
+        assert_eq!(ch.send(5), Err(5));
+        assert_eq!([ch.recv(), ch.recv()], [Some(1), Some(2)]);
+        // Round the end of the buffer.
+        ch.send(6).unwrap();
+        assert_eq!(ch.len(), 3);
+        assert_eq!([ch.recv(), ch.recv(), ch.recv(), ch.recv()], [Some(3), Some(4), Some(6), None]);
+        assert!(ch.is_empty());
+    }
+
+    #[test]
+    fn busy() {
+        let ch = IrqChannel::<u8, 2>::new();
+        ch.send(1).unwrap();
+        // As if an ISR came in mid-send, or mid-receive.
+        ch.sending.store(true, Ordering::Relaxed);
+        ch.receiving.store(true, Ordering::Relaxed);
+        assert_eq!(ch.send(2), Err(2));
+        assert_eq!(ch.recv(), None);
+        ch.receiving.store(false, Ordering::Relaxed);
+        assert_eq!(ch.recv(), Some(1));
+    }
+
+    #[test]
+    fn mailbox() {
+        let mb = Mailbox::new();
+        assert_eq!(mb.post(1), None);
+        assert_eq!(mb.post(2), Some(1));
+        assert_eq!(mb.peek(), Some(2));
+        assert_eq!(mb.take(), Some(2));
+        assert_eq!(mb.take(), None);
+    }
+}
@@ -0,0 +1,98 @@
+//! Small checksums for RAM/ROM integrity checks and framing.
+//!
+//! These are bitwise rather than table-driven; a 1 KiB table is a quarter
+//! of the AttoSoC's RAM.
+
+/// Incremental CRC-32 (IEEE 802.3, reflected, poly `0xEDB88320`).
+#[derive(Clone, Copy)]
+pub struct Crc32(u32);
+
+impl Crc32 {
+    pub const fn new() -> Self {
+        Self(0xffff_ffff)
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.update_byte(*b);
+        }
+    }
+
+    pub fn update_byte(&mut self, b: u8) {
+        let mut crc = self.0 ^ b as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+        self.0 = crc;
+    }
+
+    pub fn finish(self) -> u32 {
+        !self.0
+    }
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// CRC-32 of a byte slice in one go.
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(bytes);
+    crc.finish()
+}
+
+/// CRC-8 with poly `0x31` and initial value `0xff`, as used by Sensirion
+/// sensors to check each 16-bit word they send.
+pub fn crc8(bytes: &[u8]) -> u8 {
+    let mut crc: u8 = 0xff;
+    for b in bytes {
+        crc ^= b;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 { (crc << 1) ^ 0x31 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+/// CRC-32 of a memory range, read one byte at a time with volatile reads.
+///
+/// # Safety
+///
+/// Every address in `range` must be readable.
+pub unsafe fn crc32_range(range: core::ops::Range<usize>) -> u32 {
+    let mut crc = Crc32::new();
+    for addr in range {
+        crc.update_byte(core::ptr::read_volatile(addr as *const u8));
+    }
+    crc.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_value() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn crc8_check_value() {
+        assert_eq!(crc8(b"123456789"), 0xf7);
+        // From the SHT3x datasheet.
+        assert_eq!(crc8(&[0xbe, 0xef]), 0x92);
+    }
+
+    #[test]
+    fn incremental() {
+        let mut crc = Crc32::new();
+        crc.update(b"1234");
+        crc.update(b"56789");
+        assert_eq!(crc.finish(), crc32(b"123456789"));
+    }
+}
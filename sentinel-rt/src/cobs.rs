@@ -0,0 +1,155 @@
+//! COBS frames with a CRC-32, for binary data sharing a UART with text.
+//!
+//! A frame is the data and its CRC-32 (little endian), COBS-encoded so it
+//! has no zero bytes, between two zeros:
+//!
+//! ```text
+//! 00 cobs(data crc32(data)) 00
+//! ```
+//!
+//! Console text has no zeros in it, so a host splitting the stream at
+//! zeros gets whole frames and runs of text; a run that doesn't decode, or
+//! whose CRC doesn't match, is text (or a frame that lost bytes). Frames
+//! can go out between text anywhere, even mid-line.
+//!
+//! [`Encoder`] frames data as it comes, a piece at a time, holding back at
+//! most 254 bytes; [`decode`] takes a frame apart again.
+
+use crate::checksum::{crc32, Crc32};
+
+/// Longest run COBS encodes with one code byte.
+const BLOCK: usize = 254;
+
+/// Frames data a piece at a time, passing the encoded bytes to an `out`
+/// closure as they're ready.
+pub struct Encoder {
+    block: [u8; BLOCK],
+    len: usize,
+    crc: Crc32,
+}
+
+impl Default for Encoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Encoder {
+    pub const fn new() -> Self {
+        Self { block: [0; BLOCK], len: 0, crc: Crc32::new() }
+    }
+
+    /// Begin a frame, dropping whatever was left of the last one.
+    pub fn start(&mut self, mut out: impl FnMut(&[u8])) {
+        self.len = 0;
+        self.crc = Crc32::new();
+        out(&[0]);
+    }
+
+    /// Add `data` to the frame.
+    pub fn write(&mut self, data: &[u8], mut out: impl FnMut(&[u8])) {
+        self.crc.update(data);
+        for &b in data {
+            self.push(b, &mut out);
+        }
+    }
+
+    /// Add the CRC, and end the frame.
+    pub fn finish(&mut self, mut out: impl FnMut(&[u8])) {
+        for b in self.crc.finish().to_le_bytes() {
+            self.push(b, &mut out);
+        }
+        self.flush(&mut out);
+        out(&[0]);
+    }
+
+    fn push(&mut self, b: u8, out: &mut impl FnMut(&[u8])) {
+        if b == 0 {
+            self.flush(out);
+            return;
+        }
+        self.block[self.len] = b;
+        self.len += 1;
+        // A full block's code says there's no zero after it.
+        if self.len == BLOCK {
+            self.flush(out);
+        }
+    }
+
+    /// Send the block so far, with its code.
+    fn flush(&mut self, out: &mut impl FnMut(&[u8])) {
+        out(&[self.len as u8 + 1]);
+        out(&self.block[..self.len]);
+        self.len = 0;
+    }
+}
+
+/// The data in `frame` (what was between the zeros), decoded into `out`,
+/// which needs to be as long as `frame`. `None` if it doesn't decode, or
+/// its CRC is wrong.
+pub fn decode<'a>(frame: &[u8], out: &'a mut [u8]) -> Option<&'a [u8]> {
+    let mut i = 0;
+    let mut n = 0;
+    while i < frame.len() {
+        let code = frame[i] as usize;
+        let run = frame.get(i + 1..i + code)?;
+        out.get_mut(n..n + run.len())?.copy_from_slice(run);
+        n += run.len();
+        i += code;
+        // Every block but a full one, or the last, stood for a zero.
+        if code <= BLOCK && i < frame.len() {
+            *out.get_mut(n)? = 0;
+            n += 1;
+        }
+    }
+    let (data, crc) = out[..n].split_at(n.checked_sub(4)?);
+    (crc32(data).to_le_bytes() == crc).then_some(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use heapless::Vec;
+
+    fn frame(pieces: &[&[u8]]) -> Vec<u8, 1024> {
+        let mut enc = Encoder::new();
+        let mut v = Vec::new();
+        let mut out = |b: &[u8]| v.extend_from_slice(b).unwrap();
+        enc.start(&mut out);
+        for p in pieces {
+            enc.write(p, &mut out);
+        }
+        enc.finish(&mut out);
+        v
+    }
+
+    #[test]
+    fn round_trip() {
+        let long = [0x55; 600];
+        for data in [&b""[..], b"\0", b"a\0\0b\0", &long, &long[..BLOCK], &long[..BLOCK - 1]] {
+            let f = frame(&[data]);
+            assert_eq!((f[0], f[f.len() - 1]), (0, 0));
+            assert!(!f[1..f.len() - 1].contains(&0));
+            let mut out = [0; 1024];
+            assert_eq!(decode(&f[1..f.len() - 1], &mut out), Some(data));
+        }
+    }
+
+    #[test]
+    fn pieces() {
+        assert_eq!(frame(&[b"ab\0", b"", b"cd"]), frame(&[b"ab\0cd"]));
+    }
+
+    #[test]
+    fn rejects() {
+        let mut out = [0; 64];
+        let f = frame(&[b"defmt"]);
+        let mut bad = f.clone();
+        bad[3] ^= 1;
+        assert_eq!(decode(&bad[1..bad.len() - 1], &mut out), None);
+        // Cut short.
+        assert_eq!(decode(&f[1..f.len() - 2], &mut out), None);
+        assert_eq!(decode(b"hello, world\r\n", &mut out), None);
+        assert_eq!(decode(b"", &mut out), None);
+    }
+}
@@ -0,0 +1,135 @@
+//! A console to print to from anywhere, with [`sprint!`](crate::sprint) and
+//! [`sprintln!`](crate::sprintln).
+//!
+//! Rather than handing the UART driver down to every function that prints,
+//! give it to the console once, at startup:
+//!
+//! ```ignore
+//! let soc = Soc::init();
+//! console::init(soc.serial);
+//!
+//! sprintln!("pass {}", pass);
+//! console::write_bytes(b"no core::fmt here\r\n");
+//! ```
+//!
+//! [`init`] takes the polled [`Serial`], and writes with interrupts masked,
+//! so lines from the main loop and from ISRs don't run into each other
+//! (and, as with the polled driver anywhere, interrupts wait while bytes
+//! go out). With an interrupt-driven [`Port`](crate::hal::serial::Port),
+//! print through it instead:
+//!
+//! ```ignore
+//! console::set_output(|b| CONSOLE.write_all(b));
+//! ```
+//!
+//! Until one or the other is called, printing does nothing. The macros use
+//! `core::fmt`, which is a lot of code on RV32I; [`write_bytes`] (with
+//! [`hex`](crate::hex) for numbers) prints without it.
+
+use core::cell::RefCell;
+use core::fmt;
+
+use critical_section::Mutex;
+
+use crate::hal::serial::Serial;
+use crate::shell;
+
+/// Where the console's bytes go, with [`set_output`].
+pub type Output = fn(&[u8]);
+
+enum Sink {
+    Serial(Serial),
+    Output(Output),
+}
+
+static SINK: Mutex<RefCell<Option<Sink>>> = Mutex::new(RefCell::new(None));
+
+/// Print to `serial` from now on.
+pub fn init(serial: Serial) {
+    critical_section::with(|cs| SINK.replace(cs, Some(Sink::Serial(serial))));
+}
+
+/// Print through `output` from now on.
+pub fn set_output(output: Output) {
+    critical_section::with(|cs| SINK.replace(cs, Some(Sink::Output(output))));
+}
+
+/// Print `bytes` as they are.
+pub fn write_bytes(bytes: &[u8]) {
+    let output = critical_section::with(|cs| match &mut *SINK.borrow_ref_mut(cs) {
+        Some(Sink::Serial(serial)) => {
+            serial.write_bytes(bytes);
+            None
+        }
+        Some(Sink::Output(output)) => Some(*output),
+        None => None,
+    });
+    if let Some(output) = output {
+        output(bytes);
+    }
+}
+
+/// Print formatted text; what [`sprint!`](crate::sprint) does.
+pub fn write_fmt(args: fmt::Arguments<'_>) {
+    let _ = fmt::Write::write_fmt(&mut Console, args);
+}
+
+/// The console, as a writer for `write!` and the [`shell`].
+pub struct Console;
+
+impl fmt::Write for Console {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        write_bytes(s.as_bytes());
+        Ok(())
+    }
+}
+
+impl shell::Output for Console {
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        write_bytes(bytes);
+    }
+}
+
+/// Print to the [console](crate::console), as `print!` would.
+#[macro_export]
+macro_rules! sprint {
+    ($($arg:tt)*) => {
+        $crate::console::write_fmt(format_args!($($arg)*))
+    };
+}
+
+/// Print a line to the [console](crate::console), ending it with `\r\n`.
+#[macro_export]
+macro_rules! sprintln {
+    () => {
+        $crate::console::write_bytes(b"\r\n")
+    };
+    ($($arg:tt)*) => {{
+        $crate::console::write_fmt(format_args!($($arg)*));
+        $crate::console::write_bytes(b"\r\n");
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use heapless::Vec;
+
+    use super::*;
+
+    static OUT: Mutex<RefCell<Vec<u8, 64>>> = Mutex::new(RefCell::new(Vec::new()));
+
+    fn capture(bytes: &[u8]) {
+        critical_section::with(|cs| OUT.borrow_ref_mut(cs).extend_from_slice(bytes).unwrap());
+    }
+
+    #[test]
+    fn macros() {
+        write_bytes(b"dropped");
+        set_output(capture);
+        sprint!("{}-", 1);
+        sprintln!("{:02x}", 10);
+        sprintln!();
+        shell::Output::write_str(&mut Console, "ok");
+        assert_eq!(critical_section::with(|cs| OUT.borrow_ref(cs).clone()), b"1-0a\r\n\r\nok");
+    }
+}
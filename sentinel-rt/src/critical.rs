@@ -0,0 +1,117 @@
+//! Critical sections on Sentinel.
+//!
+//! With the `critical-section-single-hart` feature (part of the default
+//! `interrupts`), sentinel-rt provides the `critical-section`
+//! implementation: clear `mstatus.MIE` on the way in, and set it on the
+//! way out if it was set before. It's the `riscv` crate's
+//! `critical-section-single-hart`, but the read and the clear are one
+//! `csrrci`, each instruction being several cycles of microcode.
+//!
+//! Where the caller knows more about the interrupt state than
+//! `critical_section::with` does, these skip the bookkeeping, and check
+//! what they assume rather than taking an `unsafe`
+//! `CriticalSection::new()` on trust:
+//!
+//! * [`interrupt_free_main_only`]: from the main loop, with interrupts on.
+//!   Masks and unmasks them, without remembering anything.
+//! * [`in_interrupt`]: from an interrupt or exception handler, where the
+//!   core has already masked them. Masks nothing.
+//!
+//! ```ignore
+//! fn MachineExternal() {
+//!     critical::in_interrupt(|cs| COUNT.borrow(cs).set(COUNT.borrow(cs).get() + 1));
+//! }
+//! ```
+//!
+//! In `polled` builds, and on the host, both are just
+//! `critical_section::with`.
+
+use critical_section::CriticalSection;
+
+#[cfg(all(feature = "critical-section-single-hart", target_os = "none"))]
+mod imp {
+    use critical_section::RawRestoreState;
+
+    struct SingleHart;
+    critical_section::set_impl!(SingleHart);
+
+    // SAFETY: One hart, and with MIE clear nothing else runs on it. The asm
+    // blocks are compiler fences too.
+    unsafe impl critical_section::Impl for SingleHart {
+        unsafe fn acquire() -> RawRestoreState {
+            let mstatus: usize;
+            core::arch::asm!("csrrci {0}, mstatus, 8", out(reg) mstatus);
+            mstatus & 8 != 0
+        }
+
+        unsafe fn release(was_on: RawRestoreState) {
+            if was_on {
+                core::arch::asm!("csrsi mstatus, 8");
+            }
+        }
+    }
+}
+
+/// Run `f` with interrupts masked, from code that only runs with them
+/// enabled: the main loop, after start-up.
+///
+/// # Panics
+///
+/// If interrupts are already masked (e.g. when called from a handler), as
+/// unmasking them afterwards would be wrong.
+#[cfg(all(not(feature = "polled"), target_os = "none"))]
+pub fn interrupt_free_main_only<R>(f: impl FnOnce(CriticalSection<'_>) -> R) -> R {
+    use riscv::register::mstatus;
+
+    let mstatus: usize;
+    // SAFETY: Masking interrupts is always sound.
+    unsafe { core::arch::asm!("csrrci {0}, mstatus, 8", out(reg) mstatus) };
+    assert!(mstatus & 8 != 0, "interrupt_free_main_only: interrupts already off");
+    // SAFETY: Interrupts are masked until `f` returns.
+    let r = f(unsafe { CriticalSection::new() });
+    // SAFETY: They were on before.
+    unsafe { mstatus::set_mie() };
+    r
+}
+
+/// Run `f` with interrupts masked, from code that only runs with them
+/// enabled: the main loop, after start-up.
+#[cfg(not(all(not(feature = "polled"), target_os = "none")))]
+pub fn interrupt_free_main_only<R>(f: impl FnOnce(CriticalSection<'_>) -> R) -> R {
+    critical_section::with(f)
+}
+
+/// Run `f` in the critical section a trap handler is already in.
+///
+/// # Panics
+///
+/// If interrupts aren't masked: outside a handler, or in one that has
+/// turned them back on (see [`irq::set_nesting`](crate::irq::set_nesting)).
+#[cfg(all(not(feature = "polled"), target_os = "none"))]
+pub fn in_interrupt<R>(f: impl FnOnce(CriticalSection<'_>) -> R) -> R {
+    assert!(!riscv::register::mstatus::read().mie(), "in_interrupt: interrupts are on");
+    // SAFETY: They're masked, and stay so for the rest of the handler.
+    f(unsafe { CriticalSection::new() })
+}
+
+/// Run `f` in the critical section a trap handler is already in.
+#[cfg(not(all(not(feature = "polled"), target_os = "none")))]
+pub fn in_interrupt<R>(f: impl FnOnce(CriticalSection<'_>) -> R) -> R {
+    critical_section::with(f)
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+
+    use critical_section::Mutex;
+
+    use super::*;
+
+    #[test]
+    fn sections() {
+        let n = Mutex::new(Cell::new(1));
+        interrupt_free_main_only(|cs| n.borrow(cs).set(2));
+        assert_eq!(in_interrupt(|cs| n.borrow(cs).get()), 2);
+    }
+}
@@ -0,0 +1,84 @@
+//! Cycle budgets for timing-critical code.
+//!
+//! [`assert_within_cycles!`](crate::assert_within_cycles!) measures a block
+//! and reports a [`Violation`] if it took longer than its budget:
+//!
+//! ```ignore
+//! let frame = sentinel_rt::assert_within_cycles!(200_000, {
+//!     render(&mut fb)
+//! });
+//! ```
+//!
+//! By default a violation panics, which is what you want in a test. Install
+//! a handler with [`set_handler`] to e.g. print a warning over the UART and
+//! keep going instead.
+//!
+//! Sentinel's `mcycle` reads as zero, so on Sentinel proper, cycles are
+//! estimated from [`timebase::ticks32`](crate::timebase::ticks32), with a
+//! resolution of one timer period. That is coarse, but enough to catch a
+//! render loop or ISR that blows its budget by a wide margin. Cores with a
+//! real `mcycle` get exact counts.
+
+use core::cell::Cell;
+
+use critical_section::Mutex;
+use riscv::register::mcycle;
+
+use crate::timebase;
+
+/// A block that took longer than its budget.
+pub struct Violation {
+    pub file: &'static str,
+    pub line: u32,
+    pub budget: u32,
+    pub spent: u32,
+}
+
+pub type Handler = fn(&Violation);
+
+static HANDLER: Mutex<Cell<Option<Handler>>> = Mutex::new(Cell::new(None));
+
+/// Current cycle count (or estimate). Only differences are meaningful.
+pub fn now() -> u32 {
+    match mcycle::read() as u32 {
+        0 => timebase::ticks32().wrapping_mul(timebase::CYCLES_PER_TICK),
+        c => c,
+    }
+}
+
+/// Replace the default (panicking) violation handler.
+pub fn set_handler(handler: Handler) {
+    critical_section::with(|cs| HANDLER.borrow(cs).set(Some(handler)));
+}
+
+#[doc(hidden)]
+#[inline(never)]
+pub fn violation(file: &'static str, line: u32, budget: u32, spent: u32) {
+    let v = Violation {
+        file,
+        line,
+        budget,
+        spent,
+    };
+
+    match critical_section::with(|cs| HANDLER.borrow(cs).get()) {
+        Some(handler) => handler(&v),
+        None => panic!("cycle budget exceeded"),
+    }
+}
+
+/// Evaluate a block, and report a [`Violation`] if it took more than
+/// `budget` cycles. Evaluates to the value of the block.
+#[macro_export]
+macro_rules! assert_within_cycles {
+    ($budget:expr, $body:block) => {{
+        let budget: u32 = $budget;
+        let start = $crate::cycles::now();
+        let res = $body;
+        let spent = $crate::cycles::now().wrapping_sub(start);
+        if spent > budget {
+            $crate::cycles::violation(file!(), line!(), budget, spent);
+        }
+        res
+    }};
+}
@@ -0,0 +1,282 @@
+//! A defmt global logger over the UART, with the `defmt` feature.
+//!
+//! defmt sends a log message as its format string's index and the raw
+//! arguments, for the host to put back together from the firmware's ELF,
+//! so `defmt::info!("{=u16} samples", n)` is a handful of bytes on the wire
+//! and no `core::fmt` in the firmware. Each message goes out as one
+//! [`cobs`](crate::cobs) frame, so the UART can carry console text too:
+//!
+//! ```ignore
+//! static CONSOLE: Port<16, 128> = Port::new();
+//!
+//! CONSOLE.attach(soc.serial.base());
+//! defmt_uart::set_output(|bytes| {
+//!     CONSOLE.write(bytes);
+//! });
+//! defmt::info!("up at {=u32:x}", timebase::ticks32());
+//! ```
+//!
+//! On the host, `sentinel-tools`' `defmt-split` passes the text through
+//! and hands the messages on to `defmt-print`:
+//!
+//! ```text
+//! defmt-split /dev/ttyUSB0 | defmt-print -e target/riscv32i-unknown-none-elf/release/app
+//! ```
+//!
+//! The output is called with interrupts masked, a piece of a frame at a
+//! time, so it mustn't wait for room: queue what fits, as
+//! [`Port::write`](crate::hal::serial::Port::write) does, and drop the
+//! rest. A frame missing bytes fails its CRC, and the host skips it. Until
+//! [`set_output`] (or [`set_deferred`]) is called, messages go nowhere.
+//!
+//! # Deferred
+//!
+//! Encoding straight into the port's TX buffer shares it with the console,
+//! and a burst of logging can fill it. With [`set_deferred`], messages
+//! are framed into a buffer of their own instead, [`BUFFER`] bytes, and
+//! the port's TX interrupt sends them from there, ahead of the console's
+//! bytes:
+//!
+//! ```ignore
+//! CONSOLE.set_tx_source(defmt_uart::pop);
+//! defmt_uart::set_deferred(|| CONSOLE.start_tx());
+//! ```
+//!
+//! Logging is then a copy into RAM, and as quick from an ISR as anywhere.
+//! Frames go in whole, as in a bbqueue: one that doesn't fit is dropped
+//! (and counted, see [`dropped`]) rather than sent in part.
+//!
+//! Firmware using defmt links with its `defmt.x` as well as the usual
+//! script (`-C link-arg=-Tdefmt.x`). The frames hold defmt's raw encoding,
+//! which this feature turns on, so no other `encoding-*` feature can be.
+
+use core::cell::{Cell, RefCell};
+use core::ptr::addr_of_mut;
+
+use critical_section::{CriticalSection, Mutex, RestoreState};
+
+use crate::atomic::{AtomicBool, Ordering};
+use crate::cobs::Encoder;
+
+/// Where framed bytes go.
+pub type Output = fn(&[u8]);
+
+/// Bytes of frames held for the TX interrupt, with [`set_deferred`].
+pub const BUFFER: usize = 256;
+
+#[derive(Clone, Copy)]
+enum Mode {
+    Off,
+    Direct(Output),
+    /// Into [`DEFERRED`], and then call this to start sending.
+    Deferred(fn()),
+}
+
+static MODE: Mutex<Cell<Mode>> = Mutex::new(Cell::new(Mode::Off));
+
+/// Send defmt's frames to `output`.
+pub fn set_output(output: Output) {
+    critical_section::with(|cs| MODE.borrow(cs).set(Mode::Direct(output)));
+}
+
+/// Hold defmt's frames for [`pop`], calling `start` after each one so
+/// the UART gets going if it's idle.
+pub fn set_deferred(start: fn()) {
+    critical_section::with(|cs| MODE.borrow(cs).set(Mode::Deferred(start)));
+}
+
+/// Frames held back, for the TX interrupt.
+struct Deferred {
+    buf: [u8; BUFFER],
+    /// Free-running counts: taken by [`pop`], in whole frames, and
+    /// written so far (including the frame being logged).
+    read: usize,
+    committed: usize,
+    write: usize,
+    /// The frame being logged didn't fit.
+    overflowed: bool,
+    dropped: u32,
+}
+
+impl Deferred {
+    const fn new() -> Self {
+        Self { buf: [0; BUFFER], read: 0, committed: 0, write: 0, overflowed: false, dropped: 0 }
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        if self.overflowed || BUFFER - self.write.wrapping_sub(self.read) < bytes.len() {
+            self.overflowed = true;
+            return;
+        }
+        for &b in bytes {
+            self.buf[self.write % BUFFER] = b;
+            self.write = self.write.wrapping_add(1);
+        }
+    }
+
+    /// The frame is done: let [`pop`] have it, or drop it if it didn't fit.
+    fn commit(&mut self) {
+        if self.overflowed {
+            self.write = self.committed;
+            self.dropped = self.dropped.saturating_add(1);
+        } else {
+            self.committed = self.write;
+        }
+        self.overflowed = false;
+    }
+
+    fn pop(&mut self) -> Option<u8> {
+        if self.read == self.committed {
+            return None;
+        }
+        let b = self.buf[self.read % BUFFER];
+        self.read = self.read.wrapping_add(1);
+        Some(b)
+    }
+}
+
+static DEFERRED: Mutex<RefCell<Deferred>> = Mutex::new(RefCell::new(Deferred::new()));
+
+/// The next byte of the frames held back, for the port's
+/// [TX source](crate::hal::serial::Port::set_tx_source).
+pub fn pop() -> Option<u8> {
+    critical_section::with(|cs| DEFERRED.borrow_ref_mut(cs).pop())
+}
+
+/// Frames dropped for want of room in the deferred buffer.
+pub fn dropped() -> u32 {
+    critical_section::with(|cs| DEFERRED.borrow_ref(cs).dropped)
+}
+
+#[defmt::global_logger]
+struct Logger;
+
+/// A message is being logged.
+static TAKEN: AtomicBool = AtomicBool::new(false);
+static mut RESTORE: RestoreState = RestoreState::invalid();
+static mut ENCODER: Encoder = Encoder::new();
+
+/// The critical section acquire took.
+fn logging() -> CriticalSection<'static> {
+    // SAFETY: Only called between acquire and release.
+    unsafe { CriticalSection::new() }
+}
+
+fn emit(bytes: &[u8]) {
+    let cs = logging();
+    match MODE.borrow(cs).get() {
+        Mode::Off => {}
+        Mode::Direct(output) => output(bytes),
+        Mode::Deferred(_) => DEFERRED.borrow_ref_mut(cs).write(bytes),
+    }
+}
+
+// SAFETY: acquire takes a critical section, which release gives back, so
+// one message is logged at a time, and TAKEN catches an output that logs.
+unsafe impl defmt::Logger for Logger {
+    fn acquire() {
+        // SAFETY: Released in release, which defmt always calls next.
+        let restore = unsafe { critical_section::acquire() };
+        if TAKEN.load(Ordering::Relaxed) {
+            panic!("defmt_uart: logged while logging");
+        }
+        TAKEN.store(true, Ordering::Relaxed);
+        // SAFETY: Only touched with TAKEN set, in the critical section.
+        unsafe {
+            RESTORE = restore;
+            (*addr_of_mut!(ENCODER)).start(emit);
+        }
+    }
+
+    unsafe fn flush() {}
+
+    unsafe fn release() {
+        (*addr_of_mut!(ENCODER)).finish(emit);
+        let cs = logging();
+        let start = match MODE.borrow(cs).get() {
+            Mode::Deferred(start) => {
+                DEFERRED.borrow_ref_mut(cs).commit();
+                Some(start)
+            }
+            _ => None,
+        };
+        TAKEN.store(false, Ordering::Relaxed);
+        if let Some(start) = start {
+            start();
+        }
+        critical_section::release(RESTORE);
+    }
+
+    unsafe fn write(bytes: &[u8]) {
+        (*addr_of_mut!(ENCODER)).write(bytes, emit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use defmt::Logger as _;
+    use heapless::Vec;
+
+    static SENT: Mutex<RefCell<Vec<u8, 64>>> = Mutex::new(RefCell::new(Vec::new()));
+    static STARTED: AtomicBool = AtomicBool::new(false);
+
+    fn log(data: &[&[u8]]) {
+        Logger::acquire();
+        // SAFETY: Acquired.
+        unsafe {
+            for d in data {
+                Logger::write(d);
+            }
+            Logger::release();
+        }
+    }
+
+    fn decoded(sent: &[u8]) -> Vec<u8, 64> {
+        let mut out = [0; 64];
+        let data = crate::cobs::decode(&sent[1..sent.len() - 1], &mut out).unwrap();
+        Vec::from_slice(data).unwrap()
+    }
+
+    // The logger is global, so one test goes through both modes.
+    #[test]
+    fn direct_and_deferred() {
+        set_output(|b| {
+            critical_section::with(|cs| SENT.borrow_ref_mut(cs).extend_from_slice(b).unwrap())
+        });
+        log(&[&[1, 0], b"xyz"]);
+        let sent = critical_section::with(|cs| SENT.borrow_ref(cs).clone());
+        assert_eq!(&decoded(&sent)[..], b"\x01\0xyz");
+
+        set_deferred(|| STARTED.store(true, Ordering::Relaxed));
+        log(&[b"later"]);
+        assert!(STARTED.load(Ordering::Relaxed));
+        let mut held = Vec::<u8, 64>::new();
+        while let Some(b) = pop() {
+            held.push(b).unwrap();
+        }
+        assert_eq!(&decoded(&held)[..], b"later");
+    }
+
+    #[test]
+    fn whole_frames() {
+        let mut d = Deferred::new();
+        d.write(&[7; BUFFER - 10]);
+        d.commit();
+        // Half fits, and is dropped with the rest.
+        d.write(&[8; 6]);
+        d.write(&[9; 6]);
+        d.commit();
+        assert_eq!(d.dropped, 1);
+        for _ in 0..BUFFER - 10 {
+            assert_eq!(d.pop(), Some(7));
+        }
+        assert_eq!(d.pop(), None);
+
+        // Round the end, and nothing to pop until it's committed.
+        d.write(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]);
+        assert_eq!(d.pop(), None);
+        d.commit();
+        assert_eq!((d.pop(), d.pop()), (Some(1), Some(2)));
+    }
+}
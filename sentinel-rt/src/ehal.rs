@@ -0,0 +1,112 @@
+//! [`embedded-hal`](https://docs.rs/embedded-hal) 1.0 support, with the
+//! `embedded-hal` feature.
+//!
+//! The single LEDs and pins of a [split](crate::hal::gpio::Gpio::split)
+//! GPIO implement the `digital` traits, so they can be handed to generic
+//! driver crates:
+//!
+//! ```ignore
+//! let Parts { leds: [led0, ..], pins: [p0, ..] } = soc.gpio.split();
+//! let mut busy = SomeDisplay::new(p0.into_output(), led0);
+//! ```
+//!
+//! and a [`Delay`] is a `DelayNs`.
+
+use core::convert::Infallible;
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::{ErrorType, InputPin, OutputPin, StatefulOutputPin};
+
+use crate::hal::delay::Delay;
+use crate::hal::gpio::{Input, Led, Output};
+
+impl ErrorType for Led {
+    type Error = Infallible;
+}
+
+impl OutputPin for Led {
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        self.set(false);
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        self.set(true);
+        Ok(())
+    }
+}
+
+impl StatefulOutputPin for Led {
+    fn is_set_high(&mut self) -> Result<bool, Infallible> {
+        Ok(self.is_on())
+    }
+
+    fn is_set_low(&mut self) -> Result<bool, Infallible> {
+        Ok(!self.is_on())
+    }
+}
+
+impl ErrorType for Input {
+    type Error = Infallible;
+}
+
+impl InputPin for Input {
+    fn is_high(&mut self) -> Result<bool, Infallible> {
+        Ok(Input::is_high(self))
+    }
+
+    fn is_low(&mut self) -> Result<bool, Infallible> {
+        Ok(!Input::is_high(self))
+    }
+}
+
+impl ErrorType for Output {
+    type Error = Infallible;
+}
+
+impl OutputPin for Output {
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        self.set(false);
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        self.set(true);
+        Ok(())
+    }
+}
+
+impl StatefulOutputPin for Output {
+    fn is_set_high(&mut self) -> Result<bool, Infallible> {
+        Ok(Output::is_set_high(self))
+    }
+
+    fn is_set_low(&mut self) -> Result<bool, Infallible> {
+        Ok(!Output::is_set_high(self))
+    }
+}
+
+/// Reads back the level on the pin.
+impl InputPin for Output {
+    fn is_high(&mut self) -> Result<bool, Infallible> {
+        Ok(Output::is_high(self))
+    }
+
+    fn is_low(&mut self) -> Result<bool, Infallible> {
+        Ok(!Output::is_high(self))
+    }
+}
+
+impl DelayNs for Delay {
+    fn delay_ns(&mut self, ns: u32) {
+        Delay::delay_ns(self, ns);
+    }
+
+    fn delay_us(&mut self, us: u32) {
+        Delay::delay_us(self, us);
+    }
+
+    fn delay_ms(&mut self, ms: u32) {
+        Delay::delay_ms(self, ms);
+    }
+}
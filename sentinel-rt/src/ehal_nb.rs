@@ -0,0 +1,89 @@
+//! [`embedded-hal-nb`](https://docs.rs/embedded-hal-nb) support, with the
+//! `embedded-hal-nb` feature.
+//!
+//! [`Serial`], and a shared reference to a [`Port`], implement the `serial`
+//! traits, on top of their `try_read`/`try_write`, so `nb`-based drivers
+//! and `block!` work on them as is:
+//!
+//! ```ignore
+//! use embedded_hal_nb::nb::block;
+//! use embedded_hal_nb::serial::Write;
+//!
+//! block!(soc.serial.write(b'>'))?;
+//! block!(soc.serial.flush())?;
+//! ```
+
+use core::convert::Infallible;
+
+use embedded_hal_nb::nb;
+use embedded_hal_nb::serial::{ErrorType, Read, Write};
+
+use crate::hal::serial::{Port, Serial, WouldBlock};
+
+fn would_block(_: WouldBlock) -> nb::Error<Infallible> {
+    nb::Error::WouldBlock
+}
+
+impl ErrorType for Serial {
+    type Error = Infallible;
+}
+
+impl Read for Serial {
+    fn read(&mut self) -> nb::Result<u8, Infallible> {
+        self.try_read().map_err(would_block)
+    }
+}
+
+impl Write for Serial {
+    fn write(&mut self, word: u8) -> nb::Result<(), Infallible> {
+        self.try_write(word).map_err(would_block)
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Infallible> {
+        self.try_flush().map_err(would_block)
+    }
+}
+
+impl<const RX: usize, const TX: usize> ErrorType for &Port<RX, TX> {
+    type Error = Infallible;
+}
+
+impl<const RX: usize, const TX: usize> Read for &Port<RX, TX> {
+    fn read(&mut self) -> nb::Result<u8, Infallible> {
+        self.try_read().map_err(would_block)
+    }
+}
+
+impl<const RX: usize, const TX: usize> Write for &Port<RX, TX> {
+    fn write(&mut self, word: u8) -> nb::Result<(), Infallible> {
+        self.try_write(word).map_err(would_block)
+    }
+
+    /// Whether everything written has been sent. Needs interrupts on (or a
+    /// `polled` build) to ever get there.
+    fn flush(&mut self) -> nb::Result<(), Infallible> {
+        if self.tx_idle() {
+            Ok(())
+        } else {
+            self.wait();
+            Err(nb::Error::WouldBlock)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unattached_port() {
+        let port = Port::<4, 4>::new();
+        let mut p = &port;
+        for b in b"abcd" {
+            assert_eq!(Write::write(&mut p, *b), Ok(()));
+        }
+        assert_eq!(Write::write(&mut p, b'e'), Err(nb::Error::WouldBlock));
+        assert_eq!(Write::flush(&mut p), Err(nb::Error::WouldBlock));
+        assert_eq!(Read::read(&mut p), Err(nb::Error::WouldBlock));
+    }
+}
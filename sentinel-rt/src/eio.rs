@@ -0,0 +1,98 @@
+//! [`embedded-io`](https://docs.rs/embedded-io) support, with the
+//! `embedded-io` feature.
+//!
+//! A shared reference to an interrupt-driven [`Port`] is a blocking
+//! `Read`/`Write` (plus `ReadReady`/`WriteReady`), so code written against
+//! `embedded-io` runs on the console as is:
+//!
+//! ```ignore
+//! static CONSOLE: Port<16, 64> = Port::new();
+//!
+//! CONSOLE.attach(soc.serial.base());
+//! let mut con = &CONSOLE;
+//! embedded_io::Write::write_all(&mut con, b"hello\r\n")?;
+//! ```
+//!
+//! Blocking calls need interrupts on (or a `polled` build), like
+//! [`Port::write_all`].
+
+use core::convert::Infallible;
+
+use embedded_io::{ErrorType, Read, ReadReady, Write, WriteReady};
+
+use crate::hal::serial::Port;
+
+impl<const RX: usize, const TX: usize> ErrorType for &Port<RX, TX> {
+    type Error = Infallible;
+}
+
+impl<const RX: usize, const TX: usize> Read for &Port<RX, TX> {
+    /// Waits for at least one byte, then returns as many as are buffered
+    /// (and fit).
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Infallible> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        while self.rx_len() == 0 {
+            self.wait();
+        }
+
+        let mut n = 0;
+        while let (Some(slot), Some(b)) = (buf.get_mut(n), Port::read(self)) {
+            *slot = b;
+            n += 1;
+        }
+        Ok(n)
+    }
+}
+
+impl<const RX: usize, const TX: usize> ReadReady for &Port<RX, TX> {
+    fn read_ready(&mut self) -> Result<bool, Infallible> {
+        Ok(self.rx_len() > 0)
+    }
+}
+
+impl<const RX: usize, const TX: usize> Write for &Port<RX, TX> {
+    /// Waits for room for at least one byte, then queues as much as fits.
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Infallible> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let n = Port::write(self, buf);
+            if n > 0 {
+                return Ok(n);
+            }
+            self.wait();
+        }
+    }
+
+    /// Waits until everything written has been sent.
+    fn flush(&mut self) -> Result<(), Infallible> {
+        while !self.tx_idle() {
+            self.wait();
+        }
+        Ok(())
+    }
+}
+
+impl<const RX: usize, const TX: usize> WriteReady for &Port<RX, TX> {
+    fn write_ready(&mut self) -> Result<bool, Infallible> {
+        Ok(self.tx_free() > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unattached_port() {
+        let port = Port::<4, 4>::new();
+        let mut p = &port;
+        assert_eq!(p.read_ready(), Ok(false));
+        assert_eq!(Write::write(&mut p, b"hello"), Ok(4));
+        assert_eq!(p.write_ready(), Ok(false));
+        assert_eq!(Read::read(&mut p, &mut []), Ok(0));
+    }
+}
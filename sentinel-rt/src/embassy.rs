@@ -0,0 +1,184 @@
+//! [`embassy-time`](https://docs.rs/embassy-time) driver, with the
+//! `embassy-time` feature.
+//!
+//! With this, `embassy_time::Timer::after()` and friends work on Sentinel,
+//! driven by the [`timebase`](crate::timebase) tick:
+//!
+//! ```ignore
+//! executor::block_on(async {
+//!     loop {
+//!         led.toggle();
+//!         embassy_time::Timer::after_millis(250).await;
+//!     }
+//! })
+//! ```
+//!
+//! The AttoSoC timer can't be programmed to fire at a given time; it ticks
+//! about every 1.37 ms. So the driver keeps its own queue of up to
+//! [`ALARMS`] waiting tasks, checked on every
+//! [`timebase::tick`](crate::timebase::tick), and
+//! timers have that resolution. Embassy's `tick-hz-*` features pick the
+//! unit of `now()` as usual; the driver converts.
+
+use core::cell::RefCell;
+use core::task::Waker;
+
+use critical_section::Mutex;
+use embassy_time_driver::{Driver, TICK_HZ};
+
+use crate::timebase::{CLK_HZ, CYCLES_PER_TICK};
+
+/// How many tasks can wait on a timer at once. Any more are woken straight
+/// away, and so poll (and reschedule) until a slot frees up.
+pub const ALARMS: usize = 8;
+
+const fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// Embassy ticks per timebase tick, as a fraction in lowest terms, so the
+/// conversions don't overflow.
+const NUM: u64 = CYCLES_PER_TICK as u64 * TICK_HZ / GCD;
+const DEN: u64 = CLK_HZ as u64 / GCD;
+const GCD: u64 = gcd(CYCLES_PER_TICK as u64 * TICK_HZ, CLK_HZ as u64);
+
+fn to_embassy(ticks: u64) -> u64 {
+    ticks * NUM / DEN
+}
+
+/// The first timebase tick at or after `at`.
+fn from_embassy(at: u64) -> u64 {
+    at.saturating_mul(DEN).div_ceil(NUM)
+}
+
+struct Alarms {
+    /// Timebase ticks, extended to 64 bits.
+    now: u64,
+    waiting: [Option<(u64, Waker)>; ALARMS],
+}
+
+impl Alarms {
+    /// Wake `waker` at tick `at`. `false` if it has to be woken now
+    /// instead: it's due, or there's no room.
+    fn schedule(&mut self, at: u64, waker: &Waker) -> bool {
+        if at <= self.now {
+            return false;
+        }
+        // A task only waits on one timer at a time, so a waker already
+        // here is being rescheduled. Keep the earlier time; a wake too
+        // soon is harmless, as the task just schedules again.
+        if let Some((t, _)) = self
+            .waiting
+            .iter_mut()
+            .flatten()
+            .find(|(_, w)| w.will_wake(waker))
+        {
+            *t = (*t).min(at);
+            return true;
+        }
+        match self.waiting.iter_mut().find(|slot| slot.is_none()) {
+            Some(slot) => {
+                *slot = Some((at, waker.clone()));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Advance a tick, and wake whatever is due.
+    fn tick(&mut self) {
+        self.now += 1;
+        for slot in &mut self.waiting {
+            if slot.as_ref().is_some_and(|(at, _)| *at <= self.now) {
+                if let Some((_, w)) = slot.take() {
+                    w.wake();
+                }
+            }
+        }
+    }
+}
+
+static ALARMS_STATE: Mutex<RefCell<Alarms>> = Mutex::new(RefCell::new(Alarms {
+    now: 0,
+    waiting: [const { None }; ALARMS],
+}));
+
+/// Called by [`timebase::tick`](crate::timebase::tick).
+pub(crate) fn on_tick() {
+    critical_section::with(|cs| ALARMS_STATE.borrow_ref_mut(cs).tick());
+}
+
+struct TimebaseDriver;
+
+impl Driver for TimebaseDriver {
+    fn now(&self) -> u64 {
+        to_embassy(critical_section::with(|cs| ALARMS_STATE.borrow_ref(cs).now))
+    }
+
+    fn schedule_wake(&self, at: u64, waker: &Waker) {
+        let at = from_embassy(at);
+        let queued =
+            critical_section::with(|cs| ALARMS_STATE.borrow_ref_mut(cs).schedule(at, waker));
+        if !queued {
+            waker.wake_by_ref();
+        }
+    }
+}
+
+embassy_time_driver::time_driver_impl!(static DRIVER: TimebaseDriver = TimebaseDriver);
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::{AtomicU32, Ordering};
+    use core::task::{RawWaker, RawWakerVTable};
+
+    use super::*;
+
+    static WAKES: AtomicU32 = AtomicU32::new(0);
+    static VTABLE: RawWakerVTable = RawWakerVTable::new(
+        |p| RawWaker::new(p, &VTABLE),
+        |_| {
+            WAKES.fetch_add(1, Ordering::Relaxed);
+        },
+        |_| {
+            WAKES.fetch_add(1, Ordering::Relaxed);
+        },
+        |_| {},
+    );
+
+    fn waker(id: usize) -> Waker {
+        unsafe { Waker::from_raw(RawWaker::new(id as *const (), &VTABLE)) }
+    }
+
+    #[test]
+    fn conversions() {
+        // Default tick rate is 1 MHz: 16384 / 12 us per tick.
+        assert_eq!((NUM, DEN), (4096, 3));
+        assert_eq!(to_embassy(3), 4096);
+        assert_eq!(from_embassy(4096), 3);
+        assert_eq!(from_embassy(4097), 4);
+    }
+
+    #[test]
+    fn alarms() {
+        let mut a = Alarms {
+            now: 10,
+            waiting: [const { None }; ALARMS],
+        };
+        assert!(!a.schedule(10, &waker(1)));
+        assert!(a.schedule(12, &waker(1)));
+        assert!(a.schedule(11, &waker(1)));
+        assert!(a.schedule(13, &waker(2)));
+
+        a.tick();
+        assert_eq!(WAKES.load(Ordering::Relaxed), 1);
+        a.tick();
+        a.tick();
+        assert_eq!(WAKES.load(Ordering::Relaxed), 2);
+        assert!(a.waiting.iter().all(Option::is_none));
+    }
+}
@@ -0,0 +1,267 @@
+//! What Sentinel doesn't do in hardware, emulated.
+//!
+//! With the `emulate-m` or `emulate-misaligned` feature, the `trap-hooks`
+//! trap entry, which saves every register, first offers the trap to what's
+//! here: if it's one they cover, the instruction is done in software, its
+//! result written to the saved frame, and the trap returns past it.
+//! Instructions emulated never reach the trap hooks or the exception
+//! handler; anything else does, as before.
+//!
+//! # The M extension
+//!
+//! Sentinel is RV32I, so `mul`, `div` and the rest of the M extension trap
+//! as illegal instructions. With `emulate-m`, [`emulate`] does them, and
+//! code built for rv32im runs unmodified:
+//!
+//! ```text
+//! RUSTFLAGS="-C target-feature=+m" cargo build --release --features emulate-m,minimal
+//! ```
+//!
+//! With the `minimal` feature, that is: riscv-rt's start-up code, built
+//! for rv32im, multiplies before there's a trap handler to emulate it.
+//!
+//! It's for testing such code on Sentinel, not for speed: every multiply
+//! or divide is a trap, a full frame saved and restored, and then the
+//! arithmetic, a couple of thousand cycles in all. Code built for rv32i
+//! never traps: it calls `__mulsi3` and friends, which `fast-softmath`
+//! makes the same [`softmath`](crate::softmath) routines.
+//!
+//! The arithmetic is `softmath`'s assembly, so the handler has no M
+//! instructions of its own to trap on, whatever the rest was built for.
+//!
+//! # Misaligned accesses
+//!
+//! A halfword or word load or store to an address that isn't a multiple
+//! of its size traps, too. With `emulate-misaligned`,
+//! [`emulate_misaligned`] does it a byte at a time instead, for code (C
+//! libraries, mostly, and packed structs through raw pointers) that
+//! assumes the hardware copes. That includes
+//! [`fault::try_read_volatile`](crate::fault::try_read_volatile) and
+//! friends: a misaligned address just works, and they only report access
+//! faults, which the trap entry returns from past the access, as the
+//! `fault-handler` asks.
+//!
+//! Emulated accesses are bytes on the bus, so not for peripheral
+//! registers that care about the width of an access.
+
+use crate::softmath::{idivmod, mul, udivmod};
+use crate::trap::TrapFrame;
+
+/// The saved `x<n>`, or `None` for `x0`.
+fn reg(frame: &mut TrapFrame, n: u32) -> Option<&mut usize> {
+    let n = n as usize;
+    Some(match n {
+        0 => return None,
+        1 => &mut frame.ra,
+        2 => &mut frame.sp,
+        3 => &mut frame.gp,
+        4 => &mut frame.tp,
+        5..=7 => &mut frame.t[n - 5],
+        8..=9 => &mut frame.s[n - 8],
+        10..=17 => &mut frame.a[n - 10],
+        18..=27 => &mut frame.s[n - 16],
+        _ => &mut frame.t[n - 25],
+    })
+}
+
+/// The high word of the unsigned 64-bit product, a halfword at a time.
+fn mulhu(a: u32, b: u32) -> u32 {
+    let (al, ah, bl, bh) = (a & 0xffff, a >> 16, b & 0xffff, b >> 16);
+    let (ll, hl, lh) = (mul(al, bl), mul(ah, bl), mul(al, bh));
+    let mid = (ll >> 16) + (hl & 0xffff) + (lh & 0xffff);
+    mul(ah, bh) + (hl >> 16) + (lh >> 16) + (mid >> 16)
+}
+
+/// What the M instruction `insn` leaves in `rd`, given `rs1` and `rs2`, or
+/// `None` if it isn't one.
+fn execute(insn: u32, a: u32, b: u32) -> Option<u32> {
+    if insn & 0x7f != 0x33 || insn >> 25 != 1 {
+        return None;
+    }
+    // A negative operand's high word is that of its unsigned reading, less
+    // the other operand.
+    let neg = |x: u32, y: u32| if (x as i32) < 0 { y } else { 0 };
+    Some(match (insn >> 12) & 7 {
+        0 => mul(a, b),
+        1 => mulhu(a, b).wrapping_sub(neg(a, b)).wrapping_sub(neg(b, a)),
+        2 => mulhu(a, b).wrapping_sub(neg(a, b)),
+        3 => mulhu(a, b),
+        4 if b == 0 => u32::MAX,
+        4 => idivmod(a as i32, b as i32).0 as u32,
+        5 if b == 0 => u32::MAX,
+        5 => udivmod(a, b).0,
+        // Dividing by zero leaves the dividend as the remainder, as the
+        // spec wants.
+        6 => idivmod(a as i32, b as i32).1 as u32,
+        _ => udivmod(a, b).1,
+    })
+}
+
+/// If `insn`, the instruction at `frame.mepc`, is an M one, do it: write
+/// its result to the frame and step `mepc` past it. Returns whether it
+/// was. For trap entries of your own; the `emulate-m` feature calls it
+/// from `trap-hooks`'.
+pub fn emulate(frame: &mut TrapFrame, insn: u32) -> bool {
+    let a = reg(frame, (insn >> 15) & 31).map_or(0, |r| *r as u32);
+    let b = reg(frame, (insn >> 20) & 31).map_or(0, |r| *r as u32);
+    let Some(val) = execute(insn, a, b) else {
+        return false;
+    };
+    if let Some(rd) = reg(frame, (insn >> 7) & 31) {
+        *rd = val as usize;
+    }
+    frame.mepc += 4;
+    true
+}
+
+/// If `insn`, the instruction at `frame.mepc`, is a halfword or word load
+/// or store, do it a byte at a time: load into the frame's `rd`, or store
+/// from its `rs2`, and step `mepc` past it. Returns whether it was. For
+/// trap entries of your own; the `emulate-misaligned` feature calls it
+/// from `trap-hooks`'.
+///
+/// # Safety
+///
+/// The access, at whatever alignment, must be one the code that trapped
+/// was entitled to make.
+pub unsafe fn emulate_misaligned(frame: &mut TrapFrame, insn: u32) -> bool {
+    let funct3 = (insn >> 12) & 7;
+    let (store, len) = match (insn & 0x7f, funct3) {
+        (0x03, 1 | 5) | (0x23, 1) => (insn & 0x20 != 0, 2),
+        (0x03 | 0x23, 2) => (insn & 0x20 != 0, 4),
+        _ => return false,
+    };
+    let imm = if store {
+        (insn as i32 >> 25 << 5) | ((insn >> 7) & 31) as i32
+    } else {
+        insn as i32 >> 20
+    };
+    let base = reg(frame, (insn >> 15) & 31).map_or(0, |r| *r);
+    let addr = base.wrapping_add(imm as isize as usize) as *mut u8;
+
+    if store {
+        let val = reg(frame, (insn >> 20) & 31).map_or(0, |r| *r as u32);
+        for i in 0..len {
+            addr.add(i).write_volatile((val >> (8 * i)) as u8);
+        }
+    } else {
+        let mut val = 0;
+        for i in (0..len).rev() {
+            val = val << 8 | addr.add(i).read_volatile() as u32;
+        }
+        if funct3 == 1 {
+            val = val as i16 as u32;
+        }
+        if let Some(rd) = reg(frame, (insn >> 7) & 31) {
+            *rd = val as usize;
+        }
+    }
+    frame.mepc += 4;
+    true
+}
+
+/// From the trap entry: emulate the instruction that trapped, if it's one
+/// of those the features ask for.
+#[cfg(all(any(feature = "emulate-m", feature = "emulate-misaligned"), target_os = "none"))]
+pub(crate) fn on_trap(frame: &mut TrapFrame) -> bool {
+    let cause = riscv::register::mcause::read().bits();
+    let wanted = (cause == 2 && cfg!(feature = "emulate-m"))
+        || (matches!(cause, 4 | 6) && cfg!(feature = "emulate-misaligned"));
+    if !wanted {
+        return false;
+    }
+    // SAFETY: mepc is the instruction that trapped, in the text section.
+    let insn = unsafe { (frame.mepc as *const u32).read_volatile() };
+    if cause == 2 {
+        emulate(frame, insn)
+    } else {
+        // SAFETY: The code was going to make the access anyway.
+        unsafe { emulate_misaligned(frame, insn) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An R-type M instruction: `funct3 rd, rs1, rs2`.
+    fn insn(funct3: u32, rd: u32, rs1: u32, rs2: u32) -> u32 {
+        1 << 25 | rs2 << 20 | rs1 << 15 | funct3 << 12 | rd << 7 | 0x33
+    }
+
+    /// What the spec says, the easy way.
+    fn spec(funct3: u32, a: u32, b: u32) -> u32 {
+        let (sa, sb) = (a as i32 as i64, b as i32 as i64);
+        match funct3 {
+            0 => a.wrapping_mul(b),
+            1 => ((sa * sb) >> 32) as u32,
+            2 => ((sa * b as i64) >> 32) as u32,
+            3 => ((a as u64 * b as u64) >> 32) as u32,
+            4 if b == 0 => u32::MAX,
+            4 => (a as i32).wrapping_div(b as i32) as u32,
+            5 => a.checked_div(b).unwrap_or(u32::MAX),
+            6 if b == 0 => a,
+            6 => (a as i32).wrapping_rem(b as i32) as u32,
+            _ => a.checked_rem(b).unwrap_or(a),
+        }
+    }
+
+    #[test]
+    fn arithmetic() {
+        let vals = [0, 1, 2, 7, 0xffff, 0x1_0000, 0x1234_5678, 0x7fff_ffff, 0x8000_0000];
+        let vals = vals.into_iter().chain(vals.map(|v: u32| v.wrapping_neg()));
+        for funct3 in 0..8 {
+            for a in vals.clone() {
+                for b in vals.clone() {
+                    let got = execute(insn(funct3, 1, 2, 3), a, b);
+                    assert_eq!(got, Some(spec(funct3, a, b)), "{funct3} {a:#x} {b:#x}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn frame() {
+        let mut f = TrapFrame { mepc: 0x100, ..TrapFrame::default() };
+        f.a[0] = 6;
+        f.s[2] = 7;
+        // mul t6, a0, s2
+        assert!(emulate(&mut f, insn(0, 31, 10, 18)));
+        assert_eq!((f.t[6], f.mepc), (42, 0x104));
+        // divu x0, a0, s2: done, but nothing written.
+        assert!(emulate(&mut f, insn(5, 0, 10, 18)));
+        assert_eq!(f.mepc, 0x108);
+        // rem s11, a0, x0
+        assert!(emulate(&mut f, insn(6, 27, 10, 0)));
+        assert_eq!(f.s[11], 6);
+        // add a0, a0, s2 isn't ours.
+        assert!(!emulate(&mut f, insn(0, 10, 10, 18) & !(1 << 25)));
+        assert_eq!(f.mepc, 0x10c);
+    }
+
+    #[test]
+    fn misaligned() {
+        let mut mem = [0u8; 16];
+        mem[..9].copy_from_slice(&[0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99]);
+        let mut f = TrapFrame::default();
+        f.a[0] = mem.as_mut_ptr() as usize + 4;
+        unsafe {
+            // lw a1, -3(a0)
+            assert!(emulate_misaligned(&mut f, 0xffd5_2583));
+            assert_eq!(f.a[1], 0x5544_3322);
+            // lh a2, 3(a0), and lhu a3, 3(a0)
+            assert!(emulate_misaligned(&mut f, 0x0035_1603));
+            assert!(emulate_misaligned(&mut f, 0x0035_5683));
+            assert_eq!((f.a[2], f.a[3]), (0xffff_9988, 0x9988));
+            // sw a1, 5(a0), and sh a3, -1(a0)
+            assert!(emulate_misaligned(&mut f, 0x00b5_22a3));
+            assert!(emulate_misaligned(&mut f, 0xfed5_1fa3));
+            // lb a1, 1(a0) and add a0, a0, a1 aren't ours.
+            assert!(!emulate_misaligned(&mut f, 0x0015_0583));
+            assert!(!emulate_misaligned(&mut f, 0x00b5_0533));
+        }
+        let want = [0x11, 0x22, 0x33, 0x88, 0x99, 0x66, 0x77, 0x88, 0x99, 0x22, 0x33, 0x44, 0x55];
+        assert_eq!(mem[..13], want);
+        assert_eq!(f.mepc, 20);
+    }
+}
@@ -0,0 +1,190 @@
+//! Error context without `alloc`.
+//!
+//! A [`Ctx`] is an error plus a short chain of `&'static str`s saying what
+//! each layer above it was doing when it failed. Each layer adds its own
+//! with [`ResultExt::context`]:
+//!
+//! ```ignore
+//! fn open_log() -> Result<File, Ctx<SdError>> {
+//!     let fs = fat::mount(&mut sd).context("fat: mount")?;
+//!     fs.open("LOG.TXT").context("logger: open")
+//! }
+//!
+//! if let Err(e) = open_log() {
+//!     e.report(|b| ser.write_bytes(b));
+//! }
+//! ```
+//!
+//! which prints `sd: cmd timeout <- fat: mount <- logger: open`. The error
+//! itself supplies the first part, by implementing [`Describe`].
+
+/// Maximum number of context strings a [`Ctx`] holds. Further (outer)
+/// context is dropped, and the report ends in `<- ...`.
+pub const MAX_DEPTH: usize = 4;
+
+/// An error that can say what went wrong in a few words, e.g.
+/// `"sd: cmd timeout"`.
+pub trait Describe {
+    fn describe(&self) -> &'static str;
+}
+
+impl Describe for &'static str {
+    fn describe(&self) -> &'static str {
+        self
+    }
+}
+
+/// An error, and what was being done when it happened, innermost first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Ctx<E> {
+    pub error: E,
+    frames: [&'static str; MAX_DEPTH],
+    len: u8,
+    truncated: bool,
+}
+
+impl<E> Ctx<E> {
+    pub const fn new(error: E) -> Self {
+        Self {
+            error,
+            frames: [""; MAX_DEPTH],
+            len: 0,
+            truncated: false,
+        }
+    }
+
+    /// Note that the error happened while doing `what`.
+    pub fn context(mut self, what: &'static str) -> Self {
+        match self.frames.get_mut(self.len as usize) {
+            Some(slot) => {
+                *slot = what;
+                self.len += 1;
+            }
+            None => self.truncated = true,
+        }
+        self
+    }
+
+    /// The context strings, innermost first.
+    pub fn frames(&self) -> &[&'static str] {
+        &self.frames[..self.len as usize]
+    }
+
+    /// Whether context was dropped for lack of room.
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Convert the error, keeping the context. For when a layer has its own
+    /// error type.
+    pub fn map<F>(self, f: impl FnOnce(E) -> F) -> Ctx<F> {
+        Ctx {
+            error: f(self.error),
+            frames: self.frames,
+            len: self.len,
+            truncated: self.truncated,
+        }
+    }
+}
+
+impl<E: Describe> Ctx<E> {
+    /// Write `error <- frame <- frame ...` out in pieces, e.g. with
+    /// [`Serial::write_bytes`](crate::hal::serial::Serial::write_bytes).
+    /// No trailing newline.
+    pub fn report(&self, mut write: impl FnMut(&[u8])) {
+        write(self.error.describe().as_bytes());
+        for frame in self.frames() {
+            write(b" <- ");
+            write(frame.as_bytes());
+        }
+        if self.truncated {
+            write(b" <- ...");
+        }
+    }
+}
+
+impl<E: Describe> core::fmt::Display for Ctx<E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let mut res = Ok(());
+        self.report(|b| {
+            // report() only ever hands out whole &strs.
+            if let Ok(s) = core::str::from_utf8(b) {
+                res = res.and_then(|_| f.write_str(s));
+            }
+        });
+        res
+    }
+}
+
+/// Adding context to `Result`s.
+pub trait ResultExt<T, E> {
+    /// If this is an error, note that it happened while doing `what`.
+    fn context(self, what: &'static str) -> Result<T, Ctx<E>>;
+}
+
+impl<T, E: Describe> ResultExt<T, E> for Result<T, E> {
+    fn context(self, what: &'static str) -> Result<T, Ctx<E>> {
+        self.map_err(|e| Ctx::new(e).context(what))
+    }
+}
+
+impl<T, E> ResultExt<T, E> for Result<T, Ctx<E>> {
+    fn context(self, what: &'static str) -> Result<T, Ctx<E>> {
+        self.map_err(|e| e.context(what))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::fmt::Write;
+
+    use heapless::{String, Vec};
+
+    use super::*;
+
+    fn to_string<E: Describe>(e: &Ctx<E>) -> String<64> {
+        let mut s = String::new();
+        write!(s, "{}", e).unwrap();
+        s
+    }
+
+    #[derive(Debug)]
+    struct Timeout;
+
+    impl Describe for Timeout {
+        fn describe(&self) -> &'static str {
+            "sd: cmd timeout"
+        }
+    }
+
+    fn sd() -> Result<(), Timeout> {
+        Err(Timeout)
+    }
+
+    fn logger() -> Result<(), Ctx<Timeout>> {
+        sd().context("fat: mount").context("logger: open")
+    }
+
+    #[test]
+    fn chain() {
+        let e = logger().unwrap_err();
+        assert_eq!(e.frames(), ["fat: mount", "logger: open"]);
+
+        let mut out = Vec::<u8, 64>::new();
+        e.report(|b| out.extend_from_slice(b).unwrap());
+        assert_eq!(out, b"sd: cmd timeout <- fat: mount <- logger: open");
+        assert_eq!(to_string(&e), "sd: cmd timeout <- fat: mount <- logger: open");
+    }
+
+    #[test]
+    fn truncated() {
+        let mut e = Ctx::new("boom");
+        for _ in 0..MAX_DEPTH + 2 {
+            e = e.context("f");
+        }
+        assert!(e.truncated());
+        assert_eq!(e.frames().len(), MAX_DEPTH);
+        assert!(to_string(&e).ends_with("f <- ..."));
+        assert_eq!(to_string(&Ctx::new("x").map(|_| "y")), "y");
+    }
+}
@@ -0,0 +1,282 @@
+//! Crash-safe event log.
+//!
+//! A ring of the last [`LEN`] notable [`Event`]s, each stamped with the
+//! tick count, kept in `.noinit` so it survives resets (including the ones
+//! a panic or watchdog causes) for post-mortem inspection. [`Soc::init`]
+//! logs every boot, with the reset reason, and sentinel-rt's panic handler
+//! logs panics; the rest is up to the application:
+//!
+//! ```ignore
+//! events::record(Event::WatchdogMiss(task_id));
+//! ```
+//!
+//! Each slot carries a sequence number and its own CRC-8, so a write torn
+//! by a reset loses at most that one event. [`command`] dumps the log from
+//! the [`shell`](crate::shell):
+//!
+//! ```text
+//! > events
+//! 0003 000002dc boot 00000002
+//! 0004 00000340 panic 00000000
+//! 0005 00000000 boot 00000002
+//! ```
+//!
+//! [`Soc::init`]: crate::soc::Soc::init
+
+use core::mem::MaybeUninit;
+use core::ptr::addr_of_mut;
+
+use crate::checksum::crc8;
+use crate::hex;
+use crate::reset::Reason;
+use crate::shell::{Args, Output};
+use crate::timebase;
+
+/// How many events are kept.
+pub const LEN: usize = 16;
+
+const MAGIC: u32 = 0x5456_4e45; // "ENVT"
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// The firmware started, after a [`soft_reset`](crate::reset::soft_reset)
+    /// for the given reason, or from power-on (`None`).
+    Boot(Option<Reason>),
+    Panic,
+    /// A watchdog wasn't fed in time. The argument is application-defined,
+    /// e.g. which task missed.
+    WatchdogMiss(u32),
+    /// An interrupt fired unreasonably often. The argument is how many
+    /// times, over whatever window the application measures.
+    IrqStorm(u32),
+    /// Application-defined: a code (0-127) and an argument.
+    Other(u8, u32),
+}
+
+impl Event {
+    fn encode(self) -> (u8, u32) {
+        match self {
+            Self::Boot(r) => (1, r.map_or(0, Reason::encode)),
+            Self::Panic => (2, 0),
+            Self::WatchdogMiss(n) => (3, n),
+            Self::IrqStorm(n) => (4, n),
+            Self::Other(code, arg) => (0x80 | (code & 0x7f), arg),
+        }
+    }
+
+    fn decode(kind: u8, arg: u32) -> Option<Self> {
+        match kind {
+            1 => Some(Self::Boot(Reason::decode(arg))),
+            2 => Some(Self::Panic),
+            3 => Some(Self::WatchdogMiss(arg)),
+            4 => Some(Self::IrqStorm(arg)),
+            0x80..=0xff => Some(Self::Other(kind & 0x7f, arg)),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Boot(_) => "boot",
+            Self::Panic => "panic",
+            Self::WatchdogMiss(_) => "watchdog",
+            Self::IrqStorm(_) => "irq-storm",
+            Self::Other(..) => "other",
+        }
+    }
+
+    fn arg(&self) -> u32 {
+        self.encode().1
+    }
+}
+
+/// A logged event.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Record {
+    /// Increments with every event, wrapping.
+    pub seq: u16,
+    pub ticks: u32,
+    pub event: Event,
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct Slot {
+    seq: u16,
+    kind: u8,
+    /// CRC-8 of the rest of the slot.
+    check: u8,
+    arg: u32,
+    ticks: u32,
+}
+
+impl Slot {
+    fn crc(&self) -> u8 {
+        let mut bytes = [0; 11];
+        bytes[..2].copy_from_slice(&self.seq.to_le_bytes());
+        bytes[2] = self.kind;
+        bytes[3..7].copy_from_slice(&self.arg.to_le_bytes());
+        bytes[7..].copy_from_slice(&self.ticks.to_le_bytes());
+        crc8(&bytes)
+    }
+
+    fn new(seq: u16, ticks: u32, event: Event) -> Self {
+        let (kind, arg) = event.encode();
+        let mut slot = Self {
+            seq,
+            kind,
+            check: 0,
+            arg,
+            ticks,
+        };
+        slot.check = slot.crc();
+        slot
+    }
+
+    fn record(&self) -> Option<Record> {
+        if self.check != self.crc() {
+            return None;
+        }
+
+        Some(Record {
+            seq: self.seq,
+            ticks: self.ticks,
+            event: Event::decode(self.kind, self.arg)?,
+        })
+    }
+}
+
+#[repr(C)]
+struct Log {
+    magic: u32,
+    /// Sequence number of the next event; it goes in slot `next % LEN`.
+    next: u16,
+    slots: [Slot; LEN],
+}
+
+// Only accessed through critical sections.
+#[link_section = ".noinit.sentinel.events"]
+static mut LOG: MaybeUninit<Log> = MaybeUninit::uninit();
+
+/// Run `f` on the log, which has been checked, and if need be wiped.
+fn with_log<R>(f: impl FnOnce(&mut Log) -> R) -> R {
+    critical_section::with(|_| {
+        // SAFETY: In a critical section, and no reference escapes it. Any
+        // bit pattern is a valid Log.
+        let log = unsafe { (*addr_of_mut!(LOG)).assume_init_mut() };
+        if log.magic != MAGIC {
+            log.magic = MAGIC;
+            log.next = 0;
+            // Kind 0 isn't an event. Cheaper than zeroing the whole lot.
+            for slot in &mut log.slots {
+                slot.kind = 0;
+            }
+        }
+        f(log)
+    })
+}
+
+/// Log `event`, overwriting the oldest if the log is full.
+pub fn record(event: Event) {
+    let ticks = timebase::ticks32();
+    with_log(|log| {
+        // The slot first: a reset between the two only loses this event.
+        log.slots[log.next as usize % LEN] = Slot::new(log.next, ticks, event);
+        log.next = log.next.wrapping_add(1);
+    });
+}
+
+/// Call `f` on every logged event, oldest first.
+pub fn for_each(mut f: impl FnMut(Record)) {
+    // Copied out, so `f` can record events of its own.
+    let (slots, next) = with_log(|log| (log.slots, log.next));
+    for age in (1..=LEN as u16).rev() {
+        let seq = next.wrapping_sub(age);
+        match slots[seq as usize % LEN].record() {
+            Some(r) if r.seq == seq => f(r),
+            _ => {}
+        }
+    }
+}
+
+/// Forget every event.
+pub fn clear() {
+    // Wiped the next time it's used.
+    with_log(|log| log.magic = 0);
+}
+
+/// Shell command printing the log, one `seq ticks event arg` line per
+/// event, or clearing it with `clear`.
+///
+/// ```ignore
+/// sentinel_rt::commands! {
+///     /// Dump the event log.
+///     events "[clear]" => sentinel_rt::events::command,
+/// }
+/// ```
+pub fn command(args: &mut Args<'_>, out: &mut dyn Output) -> Result<(), &'static str> {
+    match args.next() {
+        Some("clear") => clear(),
+        Some(_) => return Err("expected clear"),
+        None => for_each(|r| {
+            out.write_bytes(&hex::u32_digits(r.seq as u32)[4..]);
+            out.write_bytes(b" ");
+            out.write_bytes(&hex::u32_digits(r.ticks));
+            out.write_bytes(b" ");
+            out.write_str(r.event.name());
+            out.write_bytes(b" ");
+            out.write_bytes(&hex::u32_digits(r.event.arg()));
+            out.write_bytes(b"\r\n");
+        }),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use heapless::Vec;
+
+    use super::*;
+
+    #[test]
+    fn encoding() {
+        for e in [Event::Boot(None), Event::Boot(Some(Reason::Watchdog)),
+                  Event::Panic, Event::WatchdogMiss(3),
+                  Event::IrqStorm(1000), Event::Other(0x7f, 1)] {
+            let (kind, arg) = e.encode();
+            assert_eq!(Event::decode(kind, arg), Some(e));
+        }
+
+        let mut slot = Slot::new(7, 100, Event::Panic);
+        assert_eq!(slot.record().map(|r| r.seq), Some(7));
+        slot.ticks ^= 1;
+        assert_eq!(slot.record(), None);
+        slot.kind = 0;
+        slot.check = slot.crc();
+        assert_eq!(slot.record(), None);
+    }
+
+    // The log is a single static, so everything using it is one test.
+    #[test]
+    fn ring() {
+        clear();
+        for n in 0..LEN as u32 + 3 {
+            record(Event::WatchdogMiss(n));
+        }
+
+        let mut seen: Vec<u32, LEN> = Vec::new();
+        for_each(|r| seen.push(r.event.arg()).unwrap());
+        assert_eq!(seen.len(), LEN);
+        assert_eq!(seen[0], 3);
+        assert_eq!(seen[LEN - 1], LEN as u32 + 2);
+
+        record(Event::Panic);
+        let mut last = None;
+        for_each(|r| last = Some(r));
+        assert_eq!(last.map(|r| (r.seq, r.event)),
+                   Some((LEN as u16 + 3, Event::Panic)));
+
+        clear();
+        for_each(|r| panic!("{r:?} survived clear"));
+    }
+}
@@ -0,0 +1,117 @@
+//! A single-task `async` executor that sleeps between polls.
+//!
+//! [`block_on`] runs one future to completion. Whenever it's pending, the
+//! core waits in `wfi` until an interrupt handler wakes it (through the
+//! future's waker); the [`Port`] futures are woken from
+//! [`Port::on_interrupt`]. That lets a demo be written as straight-line
+//! code rather than a state machine over queues and atomics:
+//!
+//! ```ignore
+//! static CONSOLE: Port<16, 64> = Port::new();
+//!
+//! executor::block_on(async {
+//!     let con = CONSOLE.as_async();
+//!     loop {
+//!         let b = con.read_byte().await;
+//!         con.write_all(&[b]).await;
+//!     }
+//! })
+//! ```
+//!
+//! There is no spawning and no allocation: to run several things at once,
+//! combine them into the one future, e.g. with `embassy_futures::join`.
+//! With the `embassy-time` feature, `embassy_time` timers work too, woken
+//! from the [`timebase`](crate::timebase) tick; see
+//! `examples/embassy_echo.rs`.
+//!
+//! In a `polled` build, nothing can interrupt `wfi`, so the executor
+//! doesn't sleep. It calls [`irq::dispatch`](crate::irq::dispatch)
+//! between polls instead, which ticks the timebase and runs the registered
+//! handlers as the ISR would.
+//!
+//! [`Port`]: crate::hal::serial::Port
+//! [`Port::on_interrupt`]: crate::hal::serial::Port::on_interrupt
+
+use core::future::Future;
+use core::pin::pin;
+use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+use portable_atomic::{AtomicBool, Ordering};
+
+#[cfg(feature = "polled")]
+use crate::irq;
+#[cfg(not(feature = "polled"))]
+use crate::power;
+
+/// Set by the waker; the executor polls again once it is.
+static WOKEN: AtomicBool = AtomicBool::new(false);
+
+static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, wake, wake, drop);
+
+fn clone(_: *const ()) -> RawWaker {
+    RawWaker::new(core::ptr::null(), &VTABLE)
+}
+
+fn wake(_: *const ()) {
+    WOKEN.store(true, Ordering::Release);
+}
+
+fn drop(_: *const ()) {}
+
+/// Run `fut` to completion, sleeping whenever it can't make progress.
+pub fn block_on<F: Future>(fut: F) -> F::Output {
+    let mut fut = pin!(fut);
+    // SAFETY: The vtable's functions ignore the data pointer, and are all
+    // fine to call from anywhere, any number of times.
+    let waker = unsafe { Waker::from_raw(clone(core::ptr::null())) };
+    let mut cx = Context::from_waker(&waker);
+
+    loop {
+        WOKEN.store(false, Ordering::Release);
+        if let Poll::Ready(out) = fut.as_mut().poll(&mut cx) {
+            return out;
+        }
+        wait();
+    }
+}
+
+/// Sleep until woken. With interrupts masked, so a wake between the check
+/// and the `wfi` isn't missed: a pending interrupt still ends the `wfi`,
+/// and is taken when the critical section ends.
+#[cfg(not(feature = "polled"))]
+fn wait() {
+    critical_section::with(|_| {
+        if !WOKEN.load(Ordering::Acquire) {
+            power::idle();
+        }
+    });
+}
+
+/// Do what the ISR would have, and poll again.
+#[cfg(feature = "polled")]
+fn wait() {
+    irq::dispatch();
+}
+
+#[cfg(test)]
+mod tests {
+    use core::future::poll_fn;
+
+    use super::*;
+
+    #[test]
+    fn runs_to_completion() {
+        // Pending once, having woken itself, then ready.
+        let mut polls = 0;
+        let out = block_on(poll_fn(|cx| {
+            polls += 1;
+            if polls < 2 {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            } else {
+                Poll::Ready(polls)
+            }
+        }));
+        assert_eq!(out, 2);
+    }
+}
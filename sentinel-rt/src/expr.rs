@@ -0,0 +1,519 @@
+//! Integer and fixed-point expression evaluator.
+//!
+//! For shell commands and config values that are nicer as a formula than a
+//! constant: C-like operators and precedence, variables, and functions,
+//! including `peek(addr)` to read a register:
+//!
+//! ```ignore
+//! let mut calc: Calc<4> = Calc::new(0);
+//! calc.eval("base = 0x02000000")?;
+//! calc.eval("peek(base + 4) & 0x0f")?;
+//! ```
+//!
+//! From loosest to tightest:
+//!
+//! | operators            |                                    |
+//! |----------------------|------------------------------------|
+//! | `\|`                 | bitwise or                         |
+//! | `^`                  | bitwise xor                        |
+//! | `&`                  | bitwise and                        |
+//! | `==` `!=`            | 1 if true, else 0                  |
+//! | `<` `<=` `>` `>=`    |                                    |
+//! | `<<` `>>`            | shifts; `>>` is arithmetic         |
+//! | `+` `-`              |                                    |
+//! | `*` `/` `%`          |                                    |
+//! | `-` `~` `!`          | negation, bitwise and logical not  |
+//!
+//! Values are `i32`, and overflow is an error. With `frac_digits` above
+//! zero, values are fixed point in the same sense as [`fixed`]: in units of
+//! `10^-frac_digits`, so with 2, `1.5 * 3` is `450`, i.e. 4.50. Hex
+//! literals, and functions' arguments and results, are raw integers
+//! regardless, which suits addresses and registers.
+//!
+//! Functions are `abs(x)`, `min(a, b)`, `max(a, b)`, and `peek(addr)`,
+//! `peek8(addr)` and `peek16(addr)` (`peek` bus faults are errors, with
+//! [`fault`]'s handler in place), plus any from [`Calc::set_functions`].
+
+use core::cell::RefCell;
+
+use critical_section::Mutex;
+
+use crate::error::Describe;
+use crate::fault;
+use crate::fixed;
+use crate::hex;
+use crate::shell::{Args, Output};
+
+/// Longest variable name.
+pub const MAX_NAME: usize = 8;
+
+/// Most arguments to a function.
+pub const MAX_ARGS: usize = 4;
+
+/// How deeply parentheses, function calls and prefix operators can nest,
+/// to bound stack use.
+pub const MAX_DEPTH: u32 = 8;
+
+/// Most digits after the decimal point: 10^9 is the largest power of ten
+/// an `i32` holds.
+pub const MAX_FRAC_DIGITS: u32 = 9;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    Syntax,
+    UnknownVar,
+    UnknownFunction,
+    /// Wrong number of arguments.
+    BadArgs,
+    DivByZero,
+    Overflow,
+    TooDeep,
+    /// No room for another variable, or its name is too long.
+    TooManyVars,
+    /// A `peek` faulted.
+    Fault,
+}
+
+impl Describe for Error {
+    fn describe(&self) -> &'static str {
+        match self {
+            Self::Syntax => "expr: syntax error",
+            Self::UnknownVar => "expr: unknown variable",
+            Self::UnknownFunction => "expr: unknown function",
+            Self::BadArgs => "expr: wrong number of arguments",
+            Self::DivByZero => "expr: divide by zero",
+            Self::Overflow => "expr: overflow",
+            Self::TooDeep => "expr: nested too deeply",
+            Self::TooManyVars => "expr: too many variables",
+            Self::Fault => "expr: bus fault",
+        }
+    }
+}
+
+/// Extra functions: called with the name and arguments, and returns `None`
+/// for names it doesn't know.
+pub type Functions = fn(&str, &[i32]) -> Option<Result<i32, Error>>;
+
+#[derive(Clone, Copy)]
+struct Var {
+    name: [u8; MAX_NAME],
+    len: u8,
+    val: i32,
+}
+
+impl Var {
+    fn name(&self) -> &[u8] {
+        &self.name[..self.len as usize]
+    }
+}
+
+/// An evaluator, with up to `N` variables.
+pub struct Calc<const N: usize> {
+    frac_digits: u32,
+    vars: [Option<Var>; N],
+    functions: Option<Functions>,
+}
+
+impl<const N: usize> Calc<N> {
+    /// `frac_digits` is 0 for plain integers. Panics (at compile time, in a
+    /// `const`) if it's over [`MAX_FRAC_DIGITS`].
+    pub const fn new(frac_digits: u32) -> Self {
+        assert!(frac_digits <= MAX_FRAC_DIGITS, "expr: too many frac_digits");
+        Self {
+            frac_digits,
+            vars: [None; N],
+            functions: None,
+        }
+    }
+
+    pub fn frac_digits(&self) -> u32 {
+        self.frac_digits
+    }
+
+    pub fn set_functions(&mut self, functions: Functions) {
+        self.functions = Some(functions);
+    }
+
+    pub fn get(&self, name: &str) -> Option<i32> {
+        self.vars.iter().flatten().find(|v| v.name() == name.as_bytes()).map(|v| v.val)
+    }
+
+    pub fn set(&mut self, name: &str, val: i32) -> Result<(), Error> {
+        if name.len() > MAX_NAME {
+            return Err(Error::TooManyVars);
+        }
+        if let Some(var) = self.vars.iter_mut().flatten().find(|v| v.name() == name.as_bytes()) {
+            var.val = val;
+            return Ok(());
+        }
+
+        let slot = self.vars.iter_mut().find(|v| v.is_none()).ok_or(Error::TooManyVars)?;
+        let mut var = Var { name: [0; MAX_NAME], len: name.len() as u8, val };
+        var.name[..name.len()].copy_from_slice(name.as_bytes());
+        *slot = Some(var);
+        Ok(())
+    }
+
+    /// Evaluate `src`: an expression, or `name = expression` to also set a
+    /// variable.
+    pub fn eval(&mut self, src: &str) -> Result<i32, Error> {
+        let mut p = Parser { src: src.as_bytes(), pos: 0, depth: 0, calc: self };
+        let start = p.pos;
+        let target = match p.ident() {
+            Some(name) if p.eat(b"=") && !p.peek_is(b"=") => Some(name),
+            _ => {
+                p.pos = start;
+                None
+            }
+        };
+
+        let val = p.expr()?;
+        p.skip_ws();
+        if p.pos != p.src.len() {
+            return Err(Error::Syntax);
+        }
+        if let Some(name) = target {
+            self.set(name, val)?;
+        }
+        Ok(val)
+    }
+
+    fn call(&self, name: &str, args: &[i32]) -> Result<i32, Error> {
+        let one = || match *args {
+            [a] => Ok(a),
+            _ => Err(Error::BadArgs),
+        };
+        let two = || match *args {
+            [a, b] => Ok((a, b)),
+            _ => Err(Error::BadArgs),
+        };
+        let peek = |mask: u32| -> Result<i32, Error> {
+            let addr = one()? as u32 as usize;
+            let shift = (addr & 3) * 8;
+            // SAFETY: Whoever wrote the expression asked for it; faults are
+            // caught if the handler's in place.
+            let word = unsafe { fault::try_read_volatile(addr & !3) }.map_err(|_| Error::Fault)?;
+            Ok(((word >> shift) & mask) as i32)
+        };
+
+        match name {
+            "abs" => one()?.checked_abs().ok_or(Error::Overflow),
+            "min" => two().map(|(a, b)| a.min(b)),
+            "max" => two().map(|(a, b)| a.max(b)),
+            "peek" => peek(u32::MAX),
+            "peek8" => peek(0xff),
+            "peek16" => peek(0xffff),
+            _ => self
+                .functions
+                .and_then(|f| f(name, args))
+                .unwrap_or(Err(Error::UnknownFunction)),
+        }
+    }
+}
+
+struct Parser<'a, 'c, const N: usize> {
+    src: &'a [u8],
+    pos: usize,
+    depth: u32,
+    calc: &'c Calc<N>,
+}
+
+/// Binary operators, loosest first.
+const LEVELS: &[&[&[u8]]] = &[
+    &[b"|"],
+    &[b"^"],
+    &[b"&"],
+    &[b"==", b"!="],
+    &[b"<=", b">=", b"<", b">"],
+    &[b"<<", b">>"],
+    &[b"+", b"-"],
+    &[b"*", b"/", b"%"],
+];
+
+impl<'a, const N: usize> Parser<'a, '_, N> {
+    fn skip_ws(&mut self) {
+        while self.src.get(self.pos).is_some_and(|b| b.is_ascii_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek_is(&mut self, tok: &[u8]) -> bool {
+        self.skip_ws();
+        self.src[self.pos..].starts_with(tok)
+    }
+
+    fn eat(&mut self, tok: &[u8]) -> bool {
+        let found = self.peek_is(tok);
+        if found {
+            self.pos += tok.len();
+        }
+        found
+    }
+
+    /// `|` isn't `||`, and so on.
+    fn eat_op(&mut self, op: &[u8]) -> bool {
+        if !self.peek_is(op) {
+            return false;
+        }
+        let next = self.src.get(self.pos + op.len()).copied();
+        let doubled = op.len() == 1 && next == Some(op[0]) && b"|&<>=".contains(&op[0]);
+        if doubled || (op.len() == 1 && b"<>!=".contains(&op[0]) && next == Some(b'=')) {
+            return false;
+        }
+        self.pos += op.len();
+        true
+    }
+
+    fn ident(&mut self) -> Option<&'a str> {
+        self.skip_ws();
+        let start = self.pos;
+        while self.src.get(self.pos).is_some_and(|b| {
+            b.is_ascii_alphabetic() || *b == b'_' || (self.pos > start && b.is_ascii_digit())
+        }) {
+            self.pos += 1;
+        }
+        let src: &'a [u8] = self.src;
+        // Only ASCII was taken.
+        (self.pos > start).then(|| core::str::from_utf8(&src[start..self.pos]).unwrap())
+    }
+
+    fn expr(&mut self) -> Result<i32, Error> {
+        self.binary(0)
+    }
+
+    fn binary(&mut self, level: usize) -> Result<i32, Error> {
+        let Some(ops) = LEVELS.get(level) else {
+            return self.unary();
+        };
+        let mut lhs = self.binary(level + 1)?;
+        'outer: loop {
+            for op in *ops {
+                if self.eat_op(op) {
+                    let rhs = self.binary(level + 1)?;
+                    lhs = self.apply(op, lhs, rhs)?;
+                    continue 'outer;
+                }
+            }
+            return Ok(lhs);
+        }
+    }
+
+    fn apply(&self, op: &[u8], a: i32, b: i32) -> Result<i32, Error> {
+        let one = 10i32.pow(self.calc.frac_digits);
+        let scaled = |num: i32, den: i32| -> Result<i32, Error> {
+            let val = a as i64 * num as i64 / den as i64;
+            i32::try_from(val).map_err(|_| Error::Overflow)
+        };
+        match op {
+            b"|" => Ok(a | b),
+            b"^" => Ok(a ^ b),
+            b"&" => Ok(a & b),
+            b"==" => Ok((a == b) as i32 * one),
+            b"!=" => Ok((a != b) as i32 * one),
+            b"<" => Ok((a < b) as i32 * one),
+            b"<=" => Ok((a <= b) as i32 * one),
+            b">" => Ok((a > b) as i32 * one),
+            b">=" => Ok((a >= b) as i32 * one),
+            b"<<" => a.checked_shl(b as u32).ok_or(Error::Overflow),
+            b">>" => a.checked_shr(b as u32).ok_or(Error::Overflow),
+            b"+" => a.checked_add(b).ok_or(Error::Overflow),
+            b"-" => a.checked_sub(b).ok_or(Error::Overflow),
+            b"*" => scaled(b, one),
+            b"/" if b == 0 => Err(Error::DivByZero),
+            b"/" => scaled(one, b),
+            _ if b == 0 => Err(Error::DivByZero),
+            _ => a.checked_rem(b).ok_or(Error::Overflow),
+        }
+    }
+
+    fn unary(&mut self) -> Result<i32, Error> {
+        // Each prefix operator is a level of recursion, so counts as one.
+        if self.eat(b"-") {
+            return self.nested(Self::unary)?.checked_neg().ok_or(Error::Overflow);
+        }
+        if self.eat(b"~") {
+            return Ok(!self.nested(Self::unary)?);
+        }
+        if self.eat_op(b"!") {
+            let one = 10i32.pow(self.calc.frac_digits);
+            return Ok((self.nested(Self::unary)? == 0) as i32 * one);
+        }
+        self.primary()
+    }
+
+    fn nested<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T, Error>) -> Result<T, Error> {
+        if self.depth == MAX_DEPTH {
+            return Err(Error::TooDeep);
+        }
+        self.depth += 1;
+        let res = f(self);
+        self.depth -= 1;
+        res
+    }
+
+    fn primary(&mut self) -> Result<i32, Error> {
+        if self.eat(b"(") {
+            let val = self.nested(Self::expr)?;
+            return if self.eat(b")") { Ok(val) } else { Err(Error::Syntax) };
+        }
+        if let Some(name) = self.ident() {
+            if !self.eat(b"(") {
+                return self.calc.get(name).ok_or(Error::UnknownVar);
+            }
+            let (args, len) = self.nested(Self::args)?;
+            return self.calc.call(name, &args[..len]);
+        }
+        self.number()
+    }
+
+    /// A function's arguments, after the `(`.
+    fn args(&mut self) -> Result<([i32; MAX_ARGS], usize), Error> {
+        let mut args = [0; MAX_ARGS];
+        let mut len = 0;
+        if self.eat(b")") {
+            return Ok((args, 0));
+        }
+        loop {
+            *args.get_mut(len).ok_or(Error::BadArgs)? = self.expr()?;
+            len += 1;
+            if self.eat(b")") {
+                return Ok((args, len));
+            }
+            if !self.eat(b",") {
+                return Err(Error::Syntax);
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<i32, Error> {
+        self.skip_ws();
+        let rest = &self.src[self.pos..];
+        let digits = |s: &[u8], hex: bool| {
+            let is_digit = if hex { u8::is_ascii_hexdigit } else { u8::is_ascii_digit };
+            s.iter().take_while(|b| is_digit(b)).count()
+        };
+
+        if rest.starts_with(b"0x") || rest.starts_with(b"0X") {
+            let n = digits(&rest[2..], true);
+            let s = core::str::from_utf8(&rest[2..2 + n]).unwrap();
+            self.pos += 2 + n;
+            return u32::from_str_radix(s, 16).map(|v| v as i32).map_err(|_| Error::Syntax);
+        }
+
+        let int = digits(rest, false);
+        if int == 0 {
+            return Err(Error::Syntax);
+        }
+        let mut val: i64 = 0;
+        for b in &rest[..int] {
+            val = val * 10 + (b - b'0') as i64;
+            if val > i32::MAX as i64 {
+                return Err(Error::Overflow);
+            }
+        }
+        self.pos += int;
+
+        // Scale to fixed point, taking as many fractional digits as there's
+        // room for (and ignoring the rest).
+        let frac: &[u8] = match rest.get(int) {
+            Some(b'.') => {
+                let n = digits(&rest[int + 1..], false);
+                self.pos += 1 + n;
+                &rest[int + 1..][..n]
+            }
+            _ => &[],
+        };
+        let mut frac = frac.iter();
+        for _ in 0..self.calc.frac_digits {
+            val = val * 10 + frac.next().map_or(0, |b| (b - b'0') as i64);
+        }
+        i32::try_from(val).map_err(|_| Error::Overflow)
+    }
+}
+
+/// Variables for [`command`].
+pub const SHELL_VARS: usize = 8;
+
+static SHELL: Mutex<RefCell<Calc<SHELL_VARS>>> = Mutex::new(RefCell::new(Calc::new(0)));
+
+/// Shell handler: evaluate the rest of the line as an (integer) expression,
+/// and print the result in decimal and hex, e.g. `calc x = 6 * 7` prints
+/// `42 0000002a`. Variables persist between commands.
+pub fn command(args: &mut Args<'_>, out: &mut dyn Output) -> Result<(), &'static str> {
+    let val = critical_section::with(|cs| SHELL.borrow_ref_mut(cs).eval(args.rest()))
+        .map_err(|e| e.describe())?;
+    let mut buf = [0; fixed::MAX_LEN];
+    out.write_bytes(fixed::format(val, 0, &mut buf));
+    out.write_bytes(b" ");
+    out.write_bytes(&hex::u32_digits(val as u32));
+    out.write_bytes(b"\r\n");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(src: &str) -> Result<i32, Error> {
+        Calc::<4>::new(0).eval(src)
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(eval("1 + 2 * 3"), Ok(7));
+        assert_eq!(eval("(1 + 2) * 3"), Ok(9));
+        assert_eq!(eval("1 << 2 + 1"), Ok(8));
+        assert_eq!(eval("0x10 | 1 & 3 == 3"), Ok(0x11));
+        assert_eq!(eval("-2 * -3 - ~0"), Ok(7));
+        assert_eq!(eval("1 < 2 == !0"), Ok(1));
+        assert_eq!(eval("7 % 4 >= 3"), Ok(1));
+        assert_eq!(eval("0xffffffff"), Ok(-1));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(eval("1 +"), Err(Error::Syntax));
+        assert_eq!(eval("1 || 2"), Err(Error::Syntax));
+        assert_eq!(eval("(1"), Err(Error::Syntax));
+        assert_eq!(eval("1 / 0"), Err(Error::DivByZero));
+        assert_eq!(eval("0x7fffffff + 1"), Err(Error::Overflow));
+        assert_eq!(eval("nope"), Err(Error::UnknownVar));
+        assert_eq!(eval("nope(1)"), Err(Error::UnknownFunction));
+        assert_eq!(eval("min(1)"), Err(Error::BadArgs));
+        assert_eq!(eval("((((((((((1))))))))))"), Err(Error::TooDeep));
+        assert_eq!(eval("--1"), Ok(1));
+        assert_eq!(eval("----------1"), Err(Error::TooDeep));
+    }
+
+    #[test]
+    fn variables_and_functions() {
+        let mut calc: Calc<2> = Calc::new(0);
+        assert_eq!(calc.eval("x = 6 * 7"), Ok(42));
+        assert_eq!(calc.eval("x == 42"), Ok(1));
+        assert_eq!(calc.eval("y = max(x, abs(-50)) - min(1, 2)"), Ok(49));
+        assert_eq!(calc.eval("z = 1"), Err(Error::TooManyVars));
+
+        calc.set_functions(|name, args| match (name, args) {
+            ("double", [a]) => Some(Ok(a * 2)),
+            _ => None,
+        });
+        assert_eq!(calc.eval("double(y)"), Ok(98));
+    }
+
+    #[test]
+    fn fixed_point() {
+        let mut calc: Calc<1> = Calc::new(2);
+        assert_eq!(calc.eval("1.5 * 3"), Ok(450));
+        assert_eq!(calc.eval("10 / 4"), Ok(250));
+        assert_eq!(calc.eval("1.999 + .5"), Err(Error::Syntax));
+        assert_eq!(calc.eval("1.999 + 0.5"), Ok(249));
+        assert_eq!(calc.eval("2 > 1"), Ok(100));
+        assert_eq!(Calc::<0>::new(MAX_FRAC_DIGITS).eval("!0"), Ok(1_000_000_000));
+    }
+
+    #[test]
+    #[should_panic(expected = "frac_digits")]
+    fn too_many_frac_digits() {
+        Calc::<0>::new(MAX_FRAC_DIGITS + 1);
+    }
+}
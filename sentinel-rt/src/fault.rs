@@ -0,0 +1,228 @@
+//! Memory accesses that survive bus errors.
+//!
+//! [`try_read_volatile`] and [`try_write_volatile`] do one word access with
+//! the exception handler armed: if it traps (an access fault, or a
+//! misaligned address), the handler skips the instruction and the access
+//! returns a [`Fault`], instead of the core spinning in the default
+//! exception handler. That makes it safe(r) to poke at addresses that may
+//! not be there, to find peripherals or how much RAM there is:
+//!
+//! ```ignore
+//! match unsafe { fault::try_read_volatile(0x0200_0018) } {
+//!     Ok(id) => ...,
+//!     Err(_) => /* nothing there */,
+//! }
+//! ```
+//!
+//! The exception handler has to know about this. Either enable the
+//! `fault-handler` feature, to have sentinel-rt provide riscv-rt's
+//! `ExceptionHandler` (which [`report`](crate::trap::report)s any other
+//! exception), or call [`on_exception`] first thing from your own.
+//!
+//! This only helps where the bus signals an error: an interconnect that
+//! never acknowledges an access to nowhere stalls the core regardless.
+
+use core::cell::Cell;
+use core::sync::atomic::{compiler_fence, Ordering};
+
+use critical_section::Mutex;
+use portable_atomic::AtomicBool;
+
+use crate::error::Describe;
+use crate::hex;
+use crate::shell::{Args, Output};
+
+/// Why an access trapped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cause {
+    LoadMisaligned,
+    LoadAccess,
+    StoreMisaligned,
+    StoreAccess,
+}
+
+/// A trapped access.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Fault {
+    pub cause: Cause,
+    /// The faulting address, if the core reports it in `mtval` (zero
+    /// otherwise).
+    pub addr: usize,
+}
+
+impl Describe for Fault {
+    fn describe(&self) -> &'static str {
+        match self.cause {
+            Cause::LoadMisaligned => "fault: misaligned load",
+            Cause::LoadAccess => "fault: load access",
+            Cause::StoreMisaligned => "fault: misaligned store",
+            Cause::StoreAccess => "fault: store access",
+        }
+    }
+}
+
+impl Cause {
+    /// From an `mcause` exception code.
+    fn from_code(code: usize) -> Option<Self> {
+        Some(match code {
+            4 => Self::LoadMisaligned,
+            5 => Self::LoadAccess,
+            6 => Self::StoreMisaligned,
+            7 => Self::StoreAccess,
+            _ => return None,
+        })
+    }
+}
+
+static ARMED: AtomicBool = AtomicBool::new(false);
+static FAULT: Mutex<Cell<Option<Fault>>> = Mutex::new(Cell::new(None));
+
+/// Run `access` with the handler armed. Interrupts stay off throughout, so
+/// a fault can only be the access's own.
+fn armed<T>(access: impl FnOnce() -> T) -> Result<T, Fault> {
+    critical_section::with(|cs| {
+        FAULT.borrow(cs).set(None);
+        ARMED.store(true, Ordering::SeqCst);
+        compiler_fence(Ordering::SeqCst);
+        let val = access();
+        compiler_fence(Ordering::SeqCst);
+        ARMED.store(false, Ordering::SeqCst);
+        FAULT.borrow(cs).take().map_or(Ok(val), Err)
+    })
+}
+
+/// Read the word at `addr`, or the [`Fault`] if the read traps.
+///
+/// # Safety
+///
+/// Reading `addr` must have no side effects anything else depends on
+/// (like popping a FIFO someone else owns).
+pub unsafe fn try_read_volatile(addr: usize) -> Result<u32, Fault> {
+    armed(|| (addr as *const u32).read_volatile())
+}
+
+/// Write `val` to the word at `addr`, or return the [`Fault`] if the write
+/// traps.
+///
+/// # Safety
+///
+/// Writing `addr` must not break anything: it has to be unused memory, or
+/// a register it's fine to write.
+pub unsafe fn try_write_volatile(addr: usize, val: u32) -> Result<(), Fault> {
+    armed(|| (addr as *mut u32).write_volatile(val))
+}
+
+/// Length of the instruction whose low halfword is `half`: 2 if it's
+/// compressed, else 4.
+#[cfg_attr(not(target_os = "none"), allow(dead_code))]
+fn insn_len(half: u16) -> usize {
+    if half & 0b11 == 0b11 {
+        4
+    } else {
+        2
+    }
+}
+
+/// Record exception `code` (from `mcause`) if it's a fault from an armed
+/// access. `Some(true)` if so, and the access has to be skipped;
+/// `Some(false)` if it's one already recorded.
+fn record(code: usize, tval: usize) -> Option<bool> {
+    let cause = Cause::from_code(code)?;
+    critical_section::with(|cs| {
+        let fault = FAULT.borrow(cs);
+        if ARMED.swap(false, Ordering::SeqCst) {
+            fault.set(Some(Fault { cause, addr: tval }));
+            Some(true)
+        } else {
+            // riscv-rt calls ExceptionHandler again after the handler for
+            // the specific exception (which defaults to ExceptionHandler
+            // too), so this may be the same fault, already dealt with.
+            fault.get().is_some().then_some(false)
+        }
+    })
+}
+
+/// Deal with the current exception, if it's a fault from
+/// [`try_read_volatile`] or [`try_write_volatile`]: record it, and set
+/// `mepc` past the access. Returns whether it did, in which case the
+/// exception handler should just return.
+pub fn on_exception() -> bool {
+    #[cfg(target_os = "none")]
+    let (code, tval) = (riscv::register::mcause::read().code(), riscv::register::mtval::read());
+    // Nothing traps on the host.
+    #[cfg(not(target_os = "none"))]
+    let (code, tval) = (usize::MAX, 0);
+
+    match record(code, tval) {
+        Some(true) => {
+            #[cfg(target_os = "none")]
+            {
+                use riscv::register::mepc;
+                let pc = mepc::read();
+                // SAFETY: mepc is the access instruction, in the text
+                // section.
+                mepc::write(pc + insn_len(unsafe { (pc as *const u16).read_volatile() }));
+            }
+            true
+        }
+        Some(false) => true,
+        None => false,
+    }
+}
+
+#[cfg(all(feature = "fault-handler", target_os = "none"))]
+#[export_name = "ExceptionHandler"]
+extern "C" fn exception_handler(frame: &riscv_rt::TrapFrame) {
+    if !on_exception() {
+        crate::trap::report(frame);
+    }
+}
+
+/// Shell handler: `peek <addr>` prints the word at `addr`, or the fault.
+pub fn peek(args: &mut Args<'_>, out: &mut dyn Output) -> Result<(), &'static str> {
+    let addr = args.next_u32()? as usize;
+    // SAFETY: Whoever's at the shell asked for it.
+    let val = unsafe { try_read_volatile(addr) }.map_err(|f| f.describe())?;
+    out.write_bytes(&hex::u32_digits(val));
+    out.write_bytes(b"\r\n");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn causes() {
+        assert_eq!(Cause::from_code(5), Some(Cause::LoadAccess));
+        assert_eq!(Cause::from_code(7), Some(Cause::StoreAccess));
+        assert_eq!(Cause::from_code(2), None);
+        assert_eq!((insn_len(0x4503), insn_len(0x4108)), (4, 2));
+    }
+
+    #[test]
+    fn trapped() {
+        // What the handler sees when an armed access faults: the fault,
+        // then riscv-rt's second call for it, then nothing of ours.
+        let r = armed(|| {
+            assert_eq!(record(2, 0), None);
+            assert_eq!(record(5, 0x10), Some(true));
+            assert_eq!(record(5, 0x10), Some(false));
+        });
+        let fault = Fault { cause: Cause::LoadAccess, addr: 0x10 };
+        assert_eq!(r, Err(fault));
+        assert_eq!(fault.describe(), "fault: load access");
+        assert_eq!(record(5, 0x10), None);
+    }
+
+    #[test]
+    fn no_fault() {
+        let mut word = 0u32;
+        let addr = &mut word as *mut u32 as usize;
+        unsafe {
+            try_write_volatile(addr, 0x1234_5678).unwrap();
+            assert_eq!(try_read_volatile(addr), Ok(0x1234_5678));
+        }
+        assert!(!on_exception());
+    }
+}
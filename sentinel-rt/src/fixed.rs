@@ -0,0 +1,81 @@
+//! Fixed-point arithmetic and decimal formatting, without floats or
+//! `core::fmt`.
+//!
+//! Sensor readings and the like are kept as integers in hundredths (or
+//! whatever power of ten suits), e.g. 23.45 °C as `2345`, and only turned
+//! into text at the edge:
+//!
+//! ```ignore
+//! let mut buf = [0; fixed::MAX_LEN];
+//! ser.write_bytes(fixed::format(2345, 2, &mut buf)); // "23.45"
+//! ```
+
+/// Longest output of [`format`]: sign, 10 digits, and a decimal point.
+pub const MAX_LEN: usize = 12;
+
+/// `val * num / den`, rounded to nearest, computed without overflowing as
+/// long as the result fits in an `i32`. Handy for converting raw sensor
+/// counts to units.
+pub fn scale(val: i32, num: i32, den: i32) -> i32 {
+    let prod = val as i64 * num as i64;
+    let half = (den as i64).abs() / 2;
+    let rounded = if (prod < 0) == (den < 0) {
+        prod + half
+    } else {
+        prod - half
+    };
+    (rounded / den as i64) as i32
+}
+
+/// Write `val / 10^frac_digits` as a decimal into `buf`, and return the
+/// written part, e.g. `format(-5, 2, ..)` is `"-0.05"`.
+pub fn format(val: i32, frac_digits: u32, buf: &mut [u8; MAX_LEN]) -> &[u8] {
+    let neg = val < 0;
+    let mut mag = val.unsigned_abs();
+    let mut i = MAX_LEN;
+
+    // Digits from the right, making sure there's at least one before the
+    // point.
+    let mut n = 0;
+    while mag != 0 || n <= frac_digits {
+        if n == frac_digits && n != 0 {
+            i -= 1;
+            buf[i] = b'.';
+        }
+        i -= 1;
+        buf[i] = b'0' + (mag % 10) as u8;
+        mag /= 10;
+        n += 1;
+    }
+
+    if neg {
+        i -= 1;
+        buf[i] = b'-';
+    }
+
+    &buf[i..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formatting() {
+        let mut buf = [0; MAX_LEN];
+        assert_eq!(format(2345, 2, &mut buf), b"23.45");
+        assert_eq!(format(-5, 2, &mut buf), b"-0.05");
+        assert_eq!(format(0, 0, &mut buf), b"0");
+        assert_eq!(format(42, 0, &mut buf), b"42");
+        assert_eq!(format(i32::MIN, 9, &mut buf), b"-2.147483648");
+    }
+
+    #[test]
+    fn scaling() {
+        assert_eq!(scale(65535, 17500, 65535), 17500);
+        assert_eq!(scale(1, 1, 2), 1);
+        assert_eq!(scale(-1, 1, 2), -1);
+        assert_eq!(scale(1, 1, 3), 0);
+        assert_eq!(scale(i32::MAX, 2, 4), i32::MAX / 2 + 1);
+    }
+}
@@ -0,0 +1,136 @@
+//! [`embedded-graphics`](https://docs.rs/embedded-graphics) support, with
+//! the `graphics` feature.
+//!
+//! Both the [SSD1306](crate::ssd1306) and the terminal [`Grid`] are
+//! `BinaryColor` targets. A grid cell is 2x2 pixels, drawn with the
+//! quadrant block characters.
+//!
+//! Draw with `embedded-graphics` primitives and fonts straight into a
+//! display's buffer, then flush (or render) as usual:
+//!
+//! ```ignore
+//! Text::new("Hello", Point::new(0, 10), MonoTextStyle::new(&FONT_6X10, BinaryColor::On))
+//!     .draw(&mut oled)?;
+//! oled.flush()?;
+//! ```
+
+use core::convert::Infallible;
+
+use embedded_graphics_core::pixelcolor::BinaryColor;
+use embedded_graphics_core::prelude::*;
+
+use crate::hal::i2c::Pins;
+use crate::ssd1306::{self, Ssd1306};
+use crate::term::{Cell, Grid, QUADRANTS};
+
+impl<P: Pins> OriginDimensions for Ssd1306<P> {
+    fn size(&self) -> Size {
+        Size::new(ssd1306::WIDTH as u32, ssd1306::HEIGHT as u32)
+    }
+}
+
+impl<P: Pins> DrawTarget for Ssd1306<P> {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(p, color) in pixels {
+            // Negative coordinates become huge, and are clipped.
+            self.set_pixel(p.x as usize, p.y as usize, color.is_on());
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        let fill = if color.is_on() { 0xff } else { 0x00 };
+        self.buffer_mut().fill(fill);
+        Ok(())
+    }
+}
+
+impl<const W: usize, const H: usize> OriginDimensions for Grid<W, H> {
+    fn size(&self) -> Size {
+        Size::new(2 * W as u32, 2 * H as u32)
+    }
+}
+
+impl<const W: usize, const H: usize> DrawTarget for Grid<W, H> {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(p, color) in pixels {
+            let (x, y) = (p.x as usize / 2, p.y as usize / 2);
+            let Some(cell) = self.cell(x, y) else {
+                continue;
+            };
+
+            // Text in the cell is replaced by blank quadrants.
+            let quads = match cell.ch() {
+                c if c & 0xf0 == QUADRANTS => c & 0x0f,
+                _ => 0,
+            };
+            let bit = 1 << ((p.y as usize % 2) * 2 + p.x as usize % 2);
+            let quads = if color.is_on() { quads | bit } else { quads & !bit };
+            self.set(x, y, Cell::new(QUADRANTS | quads, cell.attr()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_graphics_core::primitives::Rectangle;
+
+    use super::*;
+    use crate::hal::i2c::I2c;
+
+    struct Idle;
+
+    impl Pins for Idle {
+        fn set_scl(&mut self, _: bool) {}
+        fn set_sda(&mut self, _: bool) {}
+
+        fn scl(&self) -> bool {
+            true
+        }
+
+        fn sda(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn ssd1306_target() {
+        let mut oled = Ssd1306::new(I2c::new(Idle));
+        let area = Rectangle::new(Point::new(-2, 6), Size::new(4, 4));
+        oled.fill_solid(&area, BinaryColor::On).unwrap();
+
+        assert!(oled.pixel(0, 6) && oled.pixel(1, 9));
+        assert!(!oled.pixel(2, 6) && !oled.pixel(0, 10));
+        assert_eq!(oled.buffer()[0], 0xc0);
+        assert_eq!(oled.buffer()[ssd1306::WIDTH], 0x03);
+    }
+
+    #[test]
+    fn grid_target() {
+        let mut grid = Grid::<4, 2>::new();
+        grid.write_str("ab");
+        let area = Rectangle::new(Point::new(1, 0), Size::new(2, 1));
+        grid.fill_solid(&area, BinaryColor::On).unwrap();
+
+        // Top-right of cell 0, top-left of cell 1.
+        assert_eq!(grid.cell(0, 0).unwrap().ch(), QUADRANTS | 0x2);
+        assert_eq!(grid.cell(1, 0).unwrap().ch(), QUADRANTS | 0x1);
+        assert_eq!(grid.cell(2, 0).unwrap().ch(), b' ');
+
+        grid.draw_iter([Pixel(Point::new(1, 0), BinaryColor::Off)]).unwrap();
+        assert_eq!(grid.cell(0, 0).unwrap().ch(), QUADRANTS);
+    }
+}
@@ -0,0 +1,242 @@
+//! Guard zones: a poor man's MPU.
+//!
+//! A guard zone is memory nothing should write, filled with [`PATTERN`].
+//! [`check`] (from the timer ISR, or the idle loop) verifies every zone,
+//! and reports the first overwritten word as a [`Corruption`], with the
+//! address, which usually points straight at the culprit. There are two
+//! kinds of zone:
+//!
+//! * The stack guard, between everything else in RAM and the stack, to
+//!   catch the stack growing into the heap and statics. Set its size in
+//!   your device linker script, before `INCLUDE sentinel.x`, then
+//!   [`add_stack_guard`]:
+//!
+//!   ```text
+//!   _stack_guard_size = 32;
+//!   ```
+//!
+//!   With the `fault-handler` feature, an exception taken with the stack
+//!   in the guard is also reported as a
+//!   [stack overflow](crate::trap#stack-overflow).
+//!
+//! * [`Fenced`] statics, with a [`Fence`] on either side to catch overruns
+//!   from neighbouring statics, or of the static's own buffers:
+//!
+//!   ```ignore
+//!   static RX: Fenced<Mutex<RefCell<[u8; 64]>>> = Fenced::new(...);
+//!   RX.add("rx");
+//!   ```
+//!
+//! By default a corruption panics. Install a handler with [`set_handler`]
+//! to e.g. log it and carry on; the zone is refilled afterwards, so each
+//! corruption is reported once.
+
+use core::cell::{Cell, UnsafeCell};
+use core::ops::{Deref, Range};
+
+use critical_section::Mutex;
+
+use crate::mem;
+
+/// What guard zones are filled with. Not all zeros or ones, which is what
+/// runaway writes tend to leave behind.
+pub const PATTERN: u32 = 0xa5c3_5a3c;
+
+/// Most zones that can be checked.
+pub const MAX_ZONES: usize = 8;
+
+/// Words in a [`Fence`].
+pub const FENCE_WORDS: usize = 2;
+
+/// An overwritten word in a guard zone.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Corruption {
+    pub zone: &'static str,
+    pub addr: usize,
+    pub found: u32,
+}
+
+pub type Handler = fn(&Corruption);
+
+#[derive(Clone, Copy)]
+struct Zone {
+    name: &'static str,
+    /// Word-aligned.
+    start: usize,
+    end: usize,
+}
+
+static ZONES: Mutex<Cell<[Option<Zone>; MAX_ZONES]>> =
+    Mutex::new(Cell::new([None; MAX_ZONES]));
+static HANDLER: Mutex<Cell<Option<Handler>>> = Mutex::new(Cell::new(None));
+
+impl Zone {
+    fn fill(&self) {
+        for addr in (self.start..self.end).step_by(4) {
+            // SAFETY: Zones are word-aligned memory given over to us by
+            // whoever added them.
+            unsafe { (addr as *mut u32).write_volatile(PATTERN) };
+        }
+    }
+
+    fn check(&self) -> Option<Corruption> {
+        (self.start..self.end).step_by(4).find_map(|addr| {
+            // SAFETY: See above.
+            let found = unsafe { (addr as *const u32).read_volatile() };
+            (found != PATTERN).then_some(Corruption {
+                zone: self.name,
+                addr,
+                found,
+            })
+        })
+    }
+}
+
+/// Fill `words` with [`PATTERN`] and check it from now on. The range is
+/// shrunk to whole words.
+///
+/// # Safety
+///
+/// `words` must be memory nothing else uses, for as long as the program
+/// runs.
+pub unsafe fn add(name: &'static str, words: Range<usize>) -> Result<(), &'static str> {
+    let zone = Zone {
+        name,
+        start: (words.start + 3) & !3,
+        end: words.end & !3,
+    };
+
+    critical_section::with(|cs| {
+        let cell = ZONES.borrow(cs);
+        let mut zones = cell.get();
+        let slot = zones.iter_mut().find(|z| z.is_none()).ok_or("guard: too many zones")?;
+        zone.fill();
+        *slot = Some(zone);
+        cell.set(zones);
+        Ok(())
+    })
+}
+
+/// Guard the bottom `_stack_guard_size` bytes of the stack. Call early,
+/// before the stack could have grown that far.
+pub fn add_stack_guard() -> Result<(), &'static str> {
+    let words = mem::stack_guard();
+    if words.is_empty() {
+        return Err("guard: no stack guard in the linker script");
+    }
+    // SAFETY: The linker script set this aside at the far end of the
+    // stack; using it is a stack overflow.
+    unsafe { add("stack", words) }
+}
+
+/// Replace the default (panicking) corruption handler.
+pub fn set_handler(handler: Handler) {
+    critical_section::with(|cs| HANDLER.borrow(cs).set(Some(handler)));
+}
+
+/// Check every zone, and report the first corruption found (if any) to the
+/// handler. Returns it too, for callers that want to do more.
+pub fn check() -> Option<Corruption> {
+    let found = critical_section::with(|cs| {
+        ZONES.borrow(cs).get().into_iter().flatten().find_map(|zone| {
+            let c = zone.check()?;
+            zone.fill();
+            Some(c)
+        })
+    })?;
+
+    match critical_section::with(|cs| HANDLER.borrow(cs).get()) {
+        Some(handler) => handler(&found),
+        None => panic!("guard zone {} corrupted at {:#010x}", found.zone, found.addr),
+    }
+    Some(found)
+}
+
+/// A guard zone to put next to other data.
+#[repr(C, align(4))]
+pub struct Fence(UnsafeCell<[u32; FENCE_WORDS]>);
+
+// SAFETY: Only ever written by the guard module, inside critical sections.
+unsafe impl Sync for Fence {}
+
+impl Fence {
+    pub const fn new() -> Self {
+        Self(UnsafeCell::new([PATTERN; FENCE_WORDS]))
+    }
+
+    fn words(&self) -> Range<usize> {
+        let start = self.0.get() as usize;
+        start..start + FENCE_WORDS * 4
+    }
+}
+
+impl Default for Fence {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A value with a [`Fence`] on either side.
+#[repr(C)]
+pub struct Fenced<T> {
+    before: Fence,
+    value: T,
+    after: Fence,
+}
+
+impl<T> Fenced<T> {
+    pub const fn new(value: T) -> Self {
+        Self {
+            before: Fence::new(),
+            value,
+            after: Fence::new(),
+        }
+    }
+
+    /// Check both fences from now on.
+    pub fn add(&'static self, name: &'static str) -> Result<(), &'static str> {
+        // SAFETY: The fences are ours alone, and 'static.
+        unsafe {
+            add(name, self.before.words())?;
+            add(name, self.after.words())
+        }
+    }
+}
+
+impl<T> Deref for Fenced<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::{AtomicU8, Ordering};
+
+    use super::*;
+
+    static BUF: Fenced<[AtomicU8; 8]> = Fenced::new([const { AtomicU8::new(0) }; 8]);
+
+    // Zones and the handler are global, so everything is one test.
+    #[test]
+    fn fences() {
+        static SEEN: Mutex<Cell<Option<Corruption>>> = Mutex::new(Cell::new(None));
+        set_handler(|c| critical_section::with(|cs| SEEN.borrow(cs).set(Some(*c))));
+
+        BUF.add("buf").unwrap();
+        BUF[7].store(1, Ordering::Relaxed);
+        assert_eq!(check(), None);
+
+        // An overrun, into the fence after.
+        let past_end = BUF.after.words().start;
+        unsafe { (past_end as *mut u32).write_volatile(0xff) };
+        let c = check().unwrap();
+        assert_eq!((c.zone, c.addr, c.found), ("buf", past_end, 0xff));
+        assert_eq!(critical_section::with(|cs| SEEN.borrow(cs).get()), Some(c));
+
+        // Reported once; the fence was refilled.
+        assert_eq!(check(), None);
+    }
+}
@@ -0,0 +1,96 @@
+//! Debounced push buttons on GPIO inputs.
+//!
+//! [`Button::poll`] samples the pin; a new level only counts once it has
+//! been seen on `samples` polls in a row. Poll at a steady rate, e.g. from
+//! the timer tick (about every 1.37 ms), where a `samples` of 8 rides out
+//! around 10 ms of contact bounce.
+
+use crate::hal::capture::Edge;
+use crate::hal::gpio::Gpio;
+
+pub struct Button {
+    mask: u8,
+    active_low: bool,
+    samples: u8,
+    pressed: bool,
+    count: u8,
+}
+
+impl Button {
+    /// A button on GPIO pin `pin` (0-7) that reads high when pressed.
+    pub const fn new(pin: u8, samples: u8) -> Self {
+        Self {
+            mask: 1 << pin,
+            active_low: false,
+            samples,
+            pressed: false,
+            count: 0,
+        }
+    }
+
+    /// A button that pulls the pin low when pressed (the usual wiring, with
+    /// a pull-up).
+    pub const fn active_low(pin: u8, samples: u8) -> Self {
+        Self {
+            active_low: true,
+            ..Self::new(pin, samples)
+        }
+    }
+
+    /// Sample the pin. Returns `Rising` when the button is (debounced)
+    /// pressed, and `Falling` when it's released.
+    pub fn poll(&mut self, gpio: &Gpio) -> Option<Edge> {
+        self.sample(gpio.read_inputs())
+    }
+
+    /// Feed in a sample of the input port. [`poll`] does this for you.
+    ///
+    /// [`poll`]: Self::poll
+    pub fn sample(&mut self, inputs: u8) -> Option<Edge> {
+        let pressed = ((inputs & self.mask) != 0) != self.active_low;
+        if pressed == self.pressed {
+            self.count = 0;
+            return None;
+        }
+
+        self.count += 1;
+        if self.count < self.samples {
+            return None;
+        }
+
+        self.count = 0;
+        self.pressed = pressed;
+        Some(if pressed { Edge::Rising } else { Edge::Falling })
+    }
+
+    /// Debounced state.
+    pub fn is_pressed(&self) -> bool {
+        self.pressed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bounces_are_ignored() {
+        let mut b = Button::active_low(3, 3);
+        let up = 0x08;
+
+        // Bouncing on press.
+        for s in [0, up, 0, 0, up] {
+            assert_eq!(b.sample(s), None);
+        }
+        assert_eq!(b.sample(0), None);
+        assert_eq!(b.sample(0), None);
+        assert_eq!(b.sample(0), Some(Edge::Rising));
+        assert!(b.is_pressed());
+        assert_eq!(b.sample(0), None);
+
+        for _ in 0..2 {
+            assert_eq!(b.sample(up), None);
+        }
+        assert_eq!(b.sample(up), Some(Edge::Falling));
+    }
+}
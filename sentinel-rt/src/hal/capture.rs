@@ -0,0 +1,177 @@
+//! Pulse-width and period measurement on GPIO inputs.
+//!
+//! The AttoSoC timer has no capture unit, so edges are found by sampling:
+//! call [`Capture::poll`] often (from the main loop, or the timer ISR), and
+//! each edge is timestamped with [`cycles::now`]. Accuracy is therefore
+//! bounded by how often you poll and by the resolution of `cycles::now`
+//! (one timer period on Sentinel proper); fine for buttons and
+//! frequency-counting slow signals. For short pulses (e.g. a DHT11's data
+//! bits), spin on the pin with [`measure_pulse`] instead, which counts loop
+//! iterations.
+//!
+//! All timestamps and widths are in cycles, and wrap.
+
+use crate::cycles;
+use crate::hal::gpio::Gpio;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edge {
+    Rising,
+    Falling,
+}
+
+/// Edge timestamps for one input pin.
+pub struct Capture {
+    mask: u8,
+    level: Option<bool>,
+    last_rise: Option<u32>,
+    last_fall: Option<u32>,
+    high: Option<u32>,
+    low: Option<u32>,
+    period: Option<u32>,
+}
+
+impl Capture {
+    /// Watch GPIO pin `pin` (0-7).
+    pub const fn new(pin: u8) -> Self {
+        Self {
+            mask: 1 << pin,
+            level: None,
+            last_rise: None,
+            last_fall: None,
+            high: None,
+            low: None,
+            period: None,
+        }
+    }
+
+    /// Sample the pin, and timestamp it if it changed.
+    pub fn poll(&mut self, gpio: &Gpio) -> Option<Edge> {
+        self.sample(gpio.read_inputs(), cycles::now())
+    }
+
+    /// Feed in a sample of the input port taken at `now`. [`poll`] does
+    /// this for you; use this directly if you have a better timestamp.
+    ///
+    /// [`poll`]: Self::poll
+    pub fn sample(&mut self, inputs: u8, now: u32) -> Option<Edge> {
+        let level = (inputs & self.mask) != 0;
+        // The first sample only establishes the level.
+        let prev = self.level.replace(level)?;
+
+        match (prev, level) {
+            (false, true) => {
+                if let Some(fall) = self.last_fall {
+                    self.low = Some(now.wrapping_sub(fall));
+                }
+                if let Some(rise) = self.last_rise {
+                    self.period = Some(now.wrapping_sub(rise));
+                }
+                self.last_rise = Some(now);
+                Some(Edge::Rising)
+            }
+            (true, false) => {
+                if let Some(rise) = self.last_rise {
+                    self.high = Some(now.wrapping_sub(rise));
+                }
+                self.last_fall = Some(now);
+                Some(Edge::Falling)
+            }
+            _ => None,
+        }
+    }
+
+    /// Width of the last complete high pulse.
+    pub fn high_width(&self) -> Option<u32> {
+        self.high
+    }
+
+    /// Width of the last complete low pulse.
+    pub fn low_width(&self) -> Option<u32> {
+        self.low
+    }
+
+    /// Time between the last two rising edges.
+    pub fn period(&self) -> Option<u32> {
+        self.period
+    }
+
+    /// Frequency from [`period`](Self::period), in Hz.
+    pub fn frequency(&self, clk_hz: u32) -> Option<u32> {
+        match self.period? {
+            0 => None,
+            p => Some(clk_hz / p),
+        }
+    }
+
+    /// Forget all measurements (but not the current level).
+    pub fn reset(&mut self) {
+        self.last_rise = None;
+        self.last_fall = None;
+        self.high = None;
+        self.low = None;
+        self.period = None;
+    }
+}
+
+/// Wait for pin `pin` to go to `level`, then return how many polling
+/// iterations it stayed there, like Arduino's `pulseIn`. Gives up (returning
+/// `None`) if either phase takes more than `timeout` iterations.
+///
+/// Iterations are not calibrated; compare pulses relative to each other, or
+/// to a pulse of known width.
+pub fn measure_pulse(gpio: &Gpio, pin: u8, level: bool, timeout: u32)
+                     -> Option<u32> {
+    let mask = 1 << pin;
+    let is_level = || ((gpio.read_inputs() & mask) != 0) == level;
+
+    let mut n = 0;
+    while !is_level() {
+        n += 1;
+        if n > timeout {
+            return None;
+        }
+    }
+
+    let mut width = 0;
+    while is_level() {
+        width += 1;
+        if width > timeout {
+            return None;
+        }
+    }
+
+    Some(width)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn widths_and_period() {
+        let mut cap = Capture::new(2);
+
+        assert_eq!(cap.sample(0x00, 0), None);
+        assert_eq!(cap.sample(0x04, 100), Some(Edge::Rising));
+        assert_eq!(cap.sample(0x04 | 0x01, 120), None);
+        assert_eq!(cap.sample(0x00, 130), Some(Edge::Falling));
+        assert_eq!(cap.high_width(), Some(30));
+        assert_eq!(cap.period(), None);
+
+        assert_eq!(cap.sample(0x04, 200), Some(Edge::Rising));
+        assert_eq!(cap.low_width(), Some(70));
+        assert_eq!(cap.period(), Some(100));
+        assert_eq!(cap.frequency(12_000_000), Some(120_000));
+    }
+
+    #[test]
+    fn wrapping_timestamps() {
+        let mut cap = Capture::new(0);
+
+        cap.sample(0, u32::MAX - 9);
+        cap.sample(1, u32::MAX - 4);
+        cap.sample(0, 5);
+        assert_eq!(cap.high_width(), Some(10));
+    }
+}
@@ -0,0 +1,209 @@
+//! Busy-wait delays, from the timer.
+//!
+//! The timer can't be read, only told when a tick has passed (about every
+//! 1.4 ms), so [`Delay`] waits out whole ticks by counting them, and
+//! anything shorter with a spin loop. The loop's speed depends on the core,
+//! so [`calibrate`](Delay::calibrate) it against the timer first; until
+//! then it assumes one cycle per iteration, which errs (well) long.
+//!
+//! ```ignore
+//! let mut delay = Delay::new(Source::Timer(soc.timer));
+//! delay.calibrate();
+//! delay.delay_us(50);
+//! ```
+//!
+//! With the `embedded-hal` feature, it is a `DelayNs`.
+//!
+//! Before there's a timer to watch, [`delay_cycles`] and [`delay_us`] count
+//! instructions instead: a loop whose cost in cycles is known from
+//! Sentinel's timings, so they need neither interrupts nor calibration.
+//! They're exact on Sentinel itself, and long on anything faster.
+
+use core::hint::black_box;
+
+use crate::hal::timer::Timer;
+use crate::timebase::{self, CLK_HZ, CYCLES_PER_TICK};
+
+/// Where [`Delay`] learns that a tick has passed.
+pub enum Source {
+    /// Acknowledge the timer directly, for when interrupts are off. Ticks
+    /// seen are passed on to [`timebase::tick`].
+    Timer(Timer),
+    /// Watch [`timebase::ticks32`], for when a `MachineExternal` handler
+    /// acknowledges the timer.
+    Timebase,
+}
+
+pub struct Delay {
+    source: Source,
+    last: u32,
+    loops_per_tick: u32,
+}
+
+/// Spin loop iterations per calibration check. Large, so the check itself
+/// hardly counts.
+const CHUNK: u32 = 64;
+
+impl Delay {
+    pub fn new(source: Source) -> Self {
+        Self {
+            source,
+            last: timebase::ticks32(),
+            loops_per_tick: CYCLES_PER_TICK,
+        }
+    }
+
+    pub fn free(self) -> Source {
+        self.source
+    }
+
+    /// Whether a tick has passed since the last call.
+    fn ticked(&mut self) -> bool {
+        match &mut self.source {
+            Source::Timer(timer) => {
+                let ticked = timer.ack();
+                if ticked {
+                    timebase::tick();
+                }
+                ticked
+            }
+            Source::Timebase => {
+                let now = timebase::ticks32();
+                let ticked = now != self.last;
+                self.last = now;
+                ticked
+            }
+        }
+    }
+
+    fn wait_tick(&mut self) {
+        while !self.ticked() {}
+    }
+
+    /// Measure the spin loop against one tick.
+    pub fn calibrate(&mut self) {
+        self.wait_tick();
+        let mut chunks = 0;
+        while !self.ticked() {
+            spin(CHUNK);
+            chunks += 1;
+        }
+        // Checking for the tick took some of the time; make up for it,
+        // generously, so delays stay at least as long as asked for.
+        let loops = chunks * CHUNK;
+        self.loops_per_tick = (loops + loops / 8).max(1);
+    }
+
+    /// Spin loop iterations per tick, as last calibrated.
+    pub fn loops_per_tick(&self) -> u32 {
+        self.loops_per_tick
+    }
+
+    /// Wait at least `ns` nanoseconds.
+    pub fn delay_ns(&mut self, ns: u32) {
+        match plan(ns, self.loops_per_tick) {
+            Wait::Ticks(n) => {
+                // The first tick may come at once, so wait for one more.
+                self.ticked();
+                for _ in 0..=n {
+                    self.wait_tick();
+                }
+            }
+            Wait::Loops(n) => spin(n),
+        }
+    }
+
+    pub fn delay_us(&mut self, us: u32) {
+        for _ in 0..us / 1_000_000 {
+            self.delay_ns(1_000_000_000);
+        }
+        self.delay_ns(us % 1_000_000 * 1000);
+    }
+
+    pub fn delay_ms(&mut self, ms: u32) {
+        for _ in 0..ms / 1000 {
+            self.delay_ns(1_000_000_000);
+        }
+        self.delay_ns(ms % 1000 * 1_000_000);
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Wait {
+    Ticks(u32),
+    Loops(u32),
+}
+
+/// How to wait `ns`: whole ticks if it's at least one (rounding up), the
+/// spin loop otherwise.
+fn plan(ns: u32, loops_per_tick: u32) -> Wait {
+    let cycles = (ns as u64 * CLK_HZ as u64).div_ceil(1_000_000_000);
+    let cycles_per_tick = CYCLES_PER_TICK as u64;
+    if cycles >= cycles_per_tick {
+        Wait::Ticks(cycles.div_ceil(cycles_per_tick) as u32)
+    } else {
+        Wait::Loops((cycles * loops_per_tick as u64).div_ceil(cycles_per_tick) as u32)
+    }
+}
+
+/// Cycles per iteration of [`delay_cycles`]' loop: an `addi` (4) and a
+/// taken branch (8).
+const LOOP_CYCLES: u32 = 12;
+
+/// Wait at least `cycles` clock cycles, by counting them.
+#[inline(never)]
+pub fn delay_cycles(cycles: u32) {
+    // Compared before counting down, so it can't wrap. What the last pass
+    // leaves over, the call and return make up.
+    #[cfg(target_arch = "riscv32")]
+    // SAFETY: Just a loop.
+    unsafe {
+        core::arch::asm!(
+            "bltu {n}, {step}, 2f",
+            "1:",
+            "addi {n}, {n}, -{c}",
+            "bgeu {n}, {step}, 1b",
+            "2:",
+            n = inout(reg) cycles => _,
+            step = in(reg) LOOP_CYCLES,
+            c = const LOOP_CYCLES,
+            options(nomem, nostack),
+        )
+    };
+    #[cfg(not(target_arch = "riscv32"))]
+    spin(cycles / LOOP_CYCLES);
+}
+
+/// Wait at least `us` microseconds at [`CLK_HZ`], by counting cycles.
+pub fn delay_us(mut us: u32) {
+    const CYCLES_PER_US: u32 = CLK_HZ / 1_000_000;
+    // In pieces, so the product can't overflow.
+    while us > 0 {
+        let n = us.min(u32::MAX / CYCLES_PER_US);
+        delay_cycles(n * CYCLES_PER_US);
+        us -= n;
+    }
+}
+
+#[inline(never)]
+fn spin(n: u32) {
+    for i in 0..n {
+        black_box(i);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plans() {
+        // 16384 cycles per tick at 12 MHz: 1365.33 us.
+        assert_eq!(plan(1_365_333, 1000), Wait::Ticks(1));
+        assert_eq!(plan(1_365_334, 1000), Wait::Ticks(2));
+        assert_eq!(plan(1_400_000, 1000), Wait::Ticks(2));
+        assert_eq!(plan(1_000_000, 1000), Wait::Loops(733));
+        assert_eq!(plan(1, 1000), Wait::Loops(1));
+        assert_eq!(plan(0, 1000), Wait::Loops(0));
+    }
+}
@@ -0,0 +1,185 @@
+//! AttoSoC GPIO driver.
+//!
+//! The GPIO peripheral has an 8-bit LED output port, and 8 bidirectional
+//! pins, all of which are inputs at reset.
+//!
+//! [`Gpio`] drives whole ports at a time. To hand single LEDs or pins to
+//! separate drivers (e.g. through the `embedded-hal` traits, with that
+//! feature), [`split`](Gpio::split) it:
+//!
+//! ```ignore
+//! let Parts { leds: [led0, ..], pins: [p0, p1, ..] } = soc.gpio.split();
+//! let mut busy = p0.into_output();
+//! busy.set(true);
+//! ```
+//!
+//! The output and output-enable registers are write-only, so the split
+//! pins share a copy of them.
+
+use core::cell::Cell;
+use core::ptr::{read_volatile, write_volatile};
+
+use critical_section::Mutex;
+
+use crate::io_addrs::GpioBase;
+use crate::power::{self, Peripheral};
+
+const LEDS: u32 = 0;
+const INOUT: u32 = 4;
+const OE: u32 = 8;
+
+pub struct Gpio {
+    base: GpioBase,
+}
+
+impl Gpio {
+    pub fn new(base: GpioBase) -> Self {
+        Self { base }
+    }
+
+    pub fn base(&self) -> GpioBase {
+        self.base
+    }
+
+    fn write(&mut self, offset: u32, val: u8) {
+        power::check(Peripheral::Gpio);
+        // SAFETY: Valid I/O port address.
+        unsafe { write_volatile((u32::from(self.base) + offset) as *mut u8, val) }
+    }
+
+    pub fn set_leds(&mut self, val: u8) {
+        self.write(LEDS, val);
+    }
+
+    /// Current level of the bidirectional pins.
+    pub fn read_inputs(&self) -> u8 {
+        power::check(Peripheral::Gpio);
+        // SAFETY: Valid I/O port address.
+        unsafe { read_volatile((u32::from(self.base) + INOUT) as *const u8) }
+    }
+
+    /// Level driven on pins whose output enable is set.
+    pub fn write_outputs(&mut self, val: u8) {
+        self.write(INOUT, val);
+    }
+
+    /// Set bit `n` to make pin `n` an output.
+    pub fn set_output_enable(&mut self, mask: u8) {
+        self.write(OE, mask);
+    }
+}
+
+/// Copies of the write-only registers, shared by the split pins.
+#[derive(Clone, Copy)]
+struct Shadow {
+    leds: u8,
+    out: u8,
+    oe: u8,
+}
+
+static SHADOW: Mutex<Cell<Shadow>> = Mutex::new(Cell::new(Shadow { leds: 0, out: 0, oe: 0 }));
+
+/// Change the copy with `f`, and write the registers to match.
+fn update(base: GpioBase, f: impl FnOnce(&mut Shadow)) {
+    critical_section::with(|cs| {
+        let cell = SHADOW.borrow(cs);
+        let mut sh = cell.get();
+        f(&mut sh);
+        cell.set(sh);
+
+        let mut gpio = Gpio::new(base);
+        gpio.set_leds(sh.leds);
+        gpio.write_outputs(sh.out);
+        gpio.set_output_enable(sh.oe);
+    });
+}
+
+fn shadow() -> Shadow {
+    critical_section::with(|cs| SHADOW.borrow(cs).get())
+}
+
+/// The GPIO, split into single LEDs and pins by [`Gpio::split`].
+pub struct Parts {
+    pub leds: [Led; 8],
+    pub pins: [Input; 8],
+}
+
+impl Gpio {
+    /// Split into single LEDs and pins. The LEDs are turned off, and the
+    /// pins all made inputs.
+    pub fn split(self) -> Parts {
+        let base = self.base;
+        update(base, |sh| *sh = Shadow { leds: 0, out: 0, oe: 0 });
+        Parts {
+            leds: core::array::from_fn(|n| Led { base, mask: 1 << n }),
+            pins: core::array::from_fn(|n| Input { base, mask: 1 << n }),
+        }
+    }
+}
+
+/// One LED.
+pub struct Led {
+    base: GpioBase,
+    mask: u8,
+}
+
+impl Led {
+    pub fn set(&mut self, on: bool) {
+        let mask = self.mask;
+        update(self.base, |sh| sh.leds = (sh.leds & !mask) | if on { mask } else { 0 });
+    }
+
+    pub fn is_on(&self) -> bool {
+        (shadow().leds & self.mask) != 0
+    }
+}
+
+/// A bidirectional pin, as an input.
+pub struct Input {
+    base: GpioBase,
+    mask: u8,
+}
+
+impl Input {
+    pub fn is_high(&self) -> bool {
+        (Gpio::new(self.base).read_inputs() & self.mask) != 0
+    }
+
+    /// Start driving the pin, at the level it was last set to (low, if
+    /// never).
+    pub fn into_output(self) -> Output {
+        let mask = self.mask;
+        update(self.base, |sh| sh.oe |= mask);
+        Output { base: self.base, mask }
+    }
+}
+
+/// A bidirectional pin, as an output.
+pub struct Output {
+    base: GpioBase,
+    mask: u8,
+}
+
+impl Output {
+    pub fn set(&mut self, high: bool) {
+        let mask = self.mask;
+        update(self.base, |sh| sh.out = (sh.out & !mask) | if high { mask } else { 0 });
+    }
+
+    /// The level the pin is being driven to.
+    pub fn is_set_high(&self) -> bool {
+        (shadow().out & self.mask) != 0
+    }
+
+    /// The level actually on the pin, which may differ if something else
+    /// is fighting it.
+    pub fn is_high(&self) -> bool {
+        (Gpio::new(self.base).read_inputs() & self.mask) != 0
+    }
+
+    pub fn into_input(self) -> Input {
+        let mask = self.mask;
+        update(self.base, |sh| sh.oe &= !mask);
+        Input { base: self.base, mask }
+    }
+}
@@ -0,0 +1,360 @@
+//! Bit-banged I2C master on two GPIO pins.
+//!
+//! The pins are driven open-drain: a low is driven by enabling the pin's
+//! output (with the output level held at 0), and a high by turning the
+//! output off and letting the bus pull-ups do the work. The GPIO output
+//! enable register is write-only, so [`GpioPins`] keeps a copy of it, and
+//! assumes it owns every pin's output enable; other pins are left as inputs.
+//!
+//! On the AttoSoC's PMOD, pin 3 (GPIO 0) is SCL and pin 4 (GPIO 1) is SDA,
+//! per Digilent's I2C PMOD pinout:
+//!
+//! ```ignore
+//! let mut i2c = I2c::new(GpioPins::new(soc.gpio, 0, 1));
+//! i2c.write_read(0x48, &[0x00], &mut buf)?;
+//! ```
+//!
+//! Even with no delay between edges, Sentinel's clock rate keeps SCL well
+//! under 100 kHz.
+
+use crate::error::Describe;
+use crate::hal::gpio::Gpio;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// Nobody acknowledged the address.
+    AddressNack,
+    /// The device stopped acknowledging partway through a write.
+    DataNack,
+    /// SCL stayed low (clock stretching, or a stuck bus) for too long.
+    Timeout,
+}
+
+impl Describe for Error {
+    fn describe(&self) -> &'static str {
+        match self {
+            Self::AddressNack => "i2c: address nack",
+            Self::DataNack => "i2c: data nack",
+            Self::Timeout => "i2c: scl held low",
+        }
+    }
+}
+
+/// The two lines of the bus. `true` is released (pulled up), `false` is
+/// driven low.
+pub trait Pins {
+    fn set_scl(&mut self, high: bool);
+    fn set_sda(&mut self, high: bool);
+    fn scl(&self) -> bool;
+    fn sda(&self) -> bool;
+}
+
+/// [`Pins`] on the AttoSoC GPIO.
+pub struct GpioPins {
+    gpio: Gpio,
+    scl: u8,
+    sda: u8,
+    oe: u8,
+}
+
+impl GpioPins {
+    /// Use GPIO pins `scl` and `sda` (0-7), starting with both released.
+    pub fn new(mut gpio: Gpio, scl: u8, sda: u8) -> Self {
+        gpio.write_outputs(0);
+        gpio.set_output_enable(0);
+        Self {
+            gpio,
+            scl: 1 << scl,
+            sda: 1 << sda,
+            oe: 0,
+        }
+    }
+
+    /// Give the GPIO back.
+    pub fn free(self) -> Gpio {
+        self.gpio
+    }
+
+    fn drive(&mut self, mask: u8, high: bool) {
+        if high {
+            self.oe &= !mask;
+        } else {
+            self.oe |= mask;
+        }
+        self.gpio.set_output_enable(self.oe);
+    }
+}
+
+impl Pins for GpioPins {
+    fn set_scl(&mut self, high: bool) {
+        self.drive(self.scl, high);
+    }
+
+    fn set_sda(&mut self, high: bool) {
+        self.drive(self.sda, high);
+    }
+
+    fn scl(&self) -> bool {
+        (self.gpio.read_inputs() & self.scl) != 0
+    }
+
+    fn sda(&self) -> bool {
+        (self.gpio.read_inputs() & self.sda) != 0
+    }
+}
+
+/// How many polls of SCL to allow for a device to stop stretching the
+/// clock.
+const STRETCH_TIMEOUT: u32 = 10_000;
+
+pub struct I2c<P> {
+    pins: P,
+    delay: u32,
+}
+
+impl<P: Pins> I2c<P> {
+    pub fn new(pins: P) -> Self {
+        Self { pins, delay: 0 }
+    }
+
+    /// Spin `spins` times around each edge, to slow the bus down for
+    /// devices (or long wires) that need it.
+    pub fn set_delay(&mut self, spins: u32) {
+        self.delay = spins;
+    }
+
+    pub fn free(self) -> P {
+        self.pins
+    }
+
+    fn wait(&self) {
+        for _ in 0..self.delay {
+            core::hint::spin_loop();
+        }
+    }
+
+    fn scl_high(&mut self) -> Result<(), Error> {
+        self.pins.set_scl(true);
+        let mut n = 0;
+        while !self.pins.scl() {
+            n += 1;
+            if n > STRETCH_TIMEOUT {
+                return Err(Error::Timeout);
+            }
+        }
+        self.wait();
+        Ok(())
+    }
+
+    fn scl_low(&mut self) {
+        self.pins.set_scl(false);
+        self.wait();
+    }
+
+    fn start(&mut self) -> Result<(), Error> {
+        // Also a repeated start, if SCL was left low.
+        self.pins.set_sda(true);
+        self.scl_high()?;
+        self.pins.set_sda(false);
+        self.wait();
+        self.scl_low();
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<(), Error> {
+        self.pins.set_sda(false);
+        self.scl_high()?;
+        self.pins.set_sda(true);
+        self.wait();
+        Ok(())
+    }
+
+    fn write_bit(&mut self, bit: bool) -> Result<(), Error> {
+        self.pins.set_sda(bit);
+        self.scl_high()?;
+        self.scl_low();
+        Ok(())
+    }
+
+    fn read_bit(&mut self) -> Result<bool, Error> {
+        self.pins.set_sda(true);
+        self.scl_high()?;
+        let bit = self.pins.sda();
+        self.scl_low();
+        Ok(bit)
+    }
+
+    /// Send a byte; returns whether it was acknowledged.
+    fn write_byte(&mut self, byte: u8) -> Result<bool, Error> {
+        for i in (0..8).rev() {
+            self.write_bit((byte >> i) & 1 != 0)?;
+        }
+        Ok(!self.read_bit()?)
+    }
+
+    fn read_byte(&mut self, ack: bool) -> Result<u8, Error> {
+        let mut byte = 0;
+        for _ in 0..8 {
+            byte = (byte << 1) | self.read_bit()? as u8;
+        }
+        self.write_bit(!ack)?;
+        Ok(byte)
+    }
+
+    fn address(&mut self, addr: u8, read: bool) -> Result<(), Error> {
+        self.start()?;
+        if self.write_byte((addr << 1) | read as u8)? {
+            Ok(())
+        } else {
+            Err(Error::AddressNack)
+        }
+    }
+
+    fn write_body(&mut self, addr: u8, parts: &[&[u8]]) -> Result<(), Error> {
+        self.address(addr, false)?;
+        for b in parts.iter().flat_map(|p| p.iter()) {
+            if !self.write_byte(*b)? {
+                return Err(Error::DataNack);
+            }
+        }
+        Ok(())
+    }
+
+    fn read_body(&mut self, addr: u8, buf: &mut [u8]) -> Result<(), Error> {
+        self.address(addr, true)?;
+        let len = buf.len();
+        for (i, b) in buf.iter_mut().enumerate() {
+            // NACK the last byte, to tell the device we're done.
+            *b = self.read_byte(i + 1 < len)?;
+        }
+        Ok(())
+    }
+
+    /// Release the bus, even after an error (unless it's stuck).
+    fn finish(&mut self, res: Result<(), Error>) -> Result<(), Error> {
+        let stop = self.stop();
+        res.and(stop)
+    }
+
+    /// Write `bytes` to the device at 7-bit address `addr`.
+    pub fn write(&mut self, addr: u8, bytes: &[u8]) -> Result<(), Error> {
+        self.write_vectored(addr, &[bytes])
+    }
+
+    /// Write each of `parts` in turn, as one transfer. Saves copying e.g. a
+    /// control byte and a payload into one buffer.
+    pub fn write_vectored(&mut self, addr: u8, parts: &[&[u8]])
+                          -> Result<(), Error> {
+        let res = self.write_body(addr, parts);
+        self.finish(res)
+    }
+
+    /// Fill `buf` from the device at 7-bit address `addr`.
+    pub fn read(&mut self, addr: u8, buf: &mut [u8]) -> Result<(), Error> {
+        let res = self.read_body(addr, buf);
+        self.finish(res)
+    }
+
+    /// Write `bytes`, then read into `buf` after a repeated start. The usual
+    /// way to read a register.
+    pub fn write_read(&mut self, addr: u8, bytes: &[u8], buf: &mut [u8])
+                      -> Result<(), Error> {
+        let res = self
+            .write_body(addr, &[bytes])
+            .and_then(|_| self.read_body(addr, buf));
+        self.finish(res)
+    }
+
+    /// Whether anything acknowledges `addr`.
+    pub fn probe(&mut self, addr: u8) -> Result<bool, Error> {
+        match self.write(addr, &[]) {
+            Ok(()) => Ok(true),
+            Err(Error::AddressNack) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::RefCell;
+
+    use heapless::{Deque, Vec};
+
+    use super::*;
+
+    /// Records SDA at each rising SCL edge, and answers reads from a list
+    /// of bits (ACKs included).
+    #[derive(Default)]
+    struct Sim {
+        scl: bool,
+        sda: bool,
+        clocked: Vec<bool, 64>,
+        replies: RefCell<Deque<bool, 32>>,
+    }
+
+    impl Pins for Sim {
+        fn set_scl(&mut self, high: bool) {
+            if high && !self.scl {
+                self.clocked.push(self.sda).unwrap();
+            }
+            self.scl = high;
+        }
+
+        fn set_sda(&mut self, high: bool) {
+            self.sda = high;
+        }
+
+        fn scl(&self) -> bool {
+            self.scl
+        }
+
+        fn sda(&self) -> bool {
+            // Only sampled once per bit read.
+            self.replies.borrow_mut().pop_front().unwrap_or(true)
+        }
+    }
+
+    impl Sim {
+        fn reply(&mut self, bits: &[bool]) {
+            for b in bits {
+                self.replies.get_mut().push_back(*b).unwrap();
+            }
+        }
+    }
+
+    fn bits(byte: u8) -> [bool; 8] {
+        core::array::from_fn(|i| (byte >> (7 - i)) & 1 != 0)
+    }
+
+    #[test]
+    fn nack() {
+        let mut i2c = I2c::new(Sim::default());
+        assert_eq!(i2c.probe(0x44), Ok(false));
+        assert_eq!(i2c.write(0x44, &[1]), Err(Error::AddressNack));
+        // Bus released afterwards.
+        let sim = i2c.free();
+        assert!(sim.scl && sim.sda);
+    }
+
+    #[test]
+    fn write_clocks_out_msb_first() {
+        let mut sim = Sim::default();
+        sim.reply(&[false]);
+        let mut i2c = I2c::new(sim);
+
+        // Address acked, data not.
+        assert_eq!(i2c.write(0x44, &[0x24]), Err(Error::DataNack));
+
+        let sim = i2c.free();
+        // Start, address + W, ack slot, data, ack slot, stop.
+        let mut expected = Vec::<bool, 64>::new();
+        expected.push(true).unwrap();
+        expected.extend_from_slice(&bits(0x44 << 1)).unwrap();
+        expected.push(true).unwrap();
+        expected.extend_from_slice(&bits(0x24)).unwrap();
+        expected.push(true).unwrap();
+        expected.push(false).unwrap();
+        assert_eq!(sim.clocked, expected);
+    }
+}
@@ -0,0 +1,10 @@
+//! Drivers for the AttoSoC peripherals.
+
+pub mod button;
+pub mod capture;
+pub mod delay;
+pub mod gpio;
+pub mod i2c;
+pub mod serial;
+pub mod spi;
+pub mod timer;
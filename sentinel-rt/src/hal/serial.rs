@@ -0,0 +1,694 @@
+//! AttoSoC UART driver.
+//!
+//! [`Serial`] is polled, for use with interrupts off. [`Port`] is
+//! interrupt-driven with its own buffers; a SoC with several UARTs (say, a
+//! console and a data link) has one `Port` per UART, each routed from
+//! `MachineExternal`:
+//!
+//! ```ignore
+//! static CONSOLE: Port<16, 64> = Port::new();
+//! static DATA: Port<64, 64> = Port::new();
+//!
+//! #[no_mangle]
+//! fn MachineExternal() {
+//!     CONSOLE.on_interrupt();
+//!     DATA.on_interrupt();
+//! }
+//!
+//! CONSOLE.attach(bases.serial);
+//! DATA.attach(unsafe { SerialBase::new(0x8100_0000) });
+//! ```
+//!
+//! Both work with `write!`: [`Serial`] directly, and a `Port` through its
+//! [`writer`](Port::writer), which goes through the TX buffer. Line
+//! endings are sent as written, so end lines with `\r\n`:
+//!
+//! ```ignore
+//! writeln!(CONSOLE.writer(), "{} overruns\r", CONSOLE.overruns())?;
+//! ```
+//!
+//! Both also have `try_read`/`try_write`, which return [`WouldBlock`]
+//! instead of waiting, so the main loop can get on with something else:
+//!
+//! ```ignore
+//! match serial.try_write(b) {
+//!     Ok(()) => next += 1,
+//!     Err(WouldBlock) => step_animation(),
+//! }
+//! ```
+//!
+//! `core::fmt` is a lot of code on a core without a multiplier. With the
+//! `ufmt` feature, both also implement `ufmt`'s `uWrite`, so firmware
+//! short on BRAM can use `uwrite!` instead.
+//!
+//! A `Port` can also be used from `async` code, through
+//! [`as_async`](Port::as_async); see [`executor`](crate::executor).
+//!
+//! An application that drives the UART itself can still buffer what it
+//! receives, from the RX interrupt, with an [`RxBuffer`].
+
+use core::cell::RefCell;
+use core::fmt;
+use core::future::poll_fn;
+use core::ptr::{read_volatile, write_volatile};
+use core::task::{Context, Poll, Waker};
+
+use critical_section::Mutex;
+
+use crate::error::Describe;
+use crate::io_addrs::SerialBase;
+pub use crate::pac::serial::Irq;
+use crate::power::{self, Peripheral};
+
+const RXTX: u32 = 0;
+const IRQ: u32 = 4;
+
+const IRQ_RX: u8 = 0x01;
+const IRQ_TX: u8 = 0x02;
+
+/// Polled access to the UART.
+///
+/// Reading the IRQ register clears _both_ the RX and TX flags, so the driver
+/// remembers an RX flag it saw while waiting on TX. This is only meant for
+/// when interrupts are off (or `MachineExternal` doesn't touch the UART);
+/// otherwise the ISR and the driver race for the flags.
+pub struct Serial {
+    base: SerialBase,
+    rx_pending: bool,
+    /// A byte from [`try_write`](Self::try_write) may still be going out.
+    tx_busy: bool,
+}
+
+/// The operation can't complete yet; try again later.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WouldBlock;
+
+impl Describe for WouldBlock {
+    fn describe(&self) -> &'static str {
+        "serial: would block"
+    }
+}
+
+impl Serial {
+    pub fn new(base: SerialBase) -> Self {
+        Self {
+            base,
+            rx_pending: false,
+            tx_busy: false,
+        }
+    }
+
+    pub fn base(&self) -> SerialBase {
+        self.base
+    }
+
+    fn read_irq(&mut self) -> u8 {
+        power::check(Peripheral::Serial);
+        // SAFETY: Valid I/O port address.
+        let irq =
+            unsafe { read_volatile((u32::from(self.base) + IRQ) as *const u8) };
+        self.rx_pending |= (irq & IRQ_RX) != 0;
+        irq
+    }
+
+    /// Send a single byte, and wait for the UART to finish shifting it out.
+    pub fn write_byte(&mut self, val: u8) {
+        while self.try_write(val).is_err() {}
+        while self.try_flush().is_err() {}
+    }
+
+    /// Start sending a byte, unless the last one is still going out.
+    /// Doesn't wait for this one to finish.
+    pub fn try_write(&mut self, val: u8) -> Result<(), WouldBlock> {
+        if self.tx_busy {
+            self.try_flush()?;
+        } else {
+            // Discard a stale TX flag (e.g. the one WBSerial asserts at
+            // reset) so we don't mistake it for this byte's completion.
+            self.read_irq();
+        }
+
+        // SAFETY: Valid I/O port address.
+        unsafe {
+            write_volatile((u32::from(self.base) + RXTX) as *mut u8, val)
+        };
+        self.tx_busy = true;
+        Ok(())
+    }
+
+    /// Whether the last byte from [`try_write`](Self::try_write) has gone
+    /// out.
+    pub fn try_flush(&mut self) -> Result<(), WouldBlock> {
+        if self.tx_busy {
+            if (self.read_irq() & IRQ_TX) == 0 {
+                return Err(WouldBlock);
+            }
+            self.tx_busy = false;
+        }
+        Ok(())
+    }
+
+    /// A received byte, if one has arrived since the last call.
+    pub fn try_read(&mut self) -> Result<u8, WouldBlock> {
+        self.read_byte().ok_or(WouldBlock)
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.write_byte(*b);
+        }
+    }
+
+    /// Read, and clear, the IRQ flags. Together with [`read_data`] and
+    /// [`start_write`], this is for a `MachineExternal` handler that
+    /// services the UART itself, rather than through [`Port`]; don't mix
+    /// them with the polled methods.
+    ///
+    /// [`read_data`]: Self::read_data
+    /// [`start_write`]: Self::start_write
+    pub fn take_irq(&mut self) -> Irq {
+        power::check(Peripheral::Serial);
+        // SAFETY: Valid I/O port address.
+        Irq::from(unsafe { read_volatile((u32::from(self.base) + IRQ) as *const u8) })
+    }
+
+    /// The last byte received, without checking one has been. See
+    /// [`take_irq`](Self::take_irq).
+    pub fn read_data(&mut self) -> u8 {
+        power::check(Peripheral::Serial);
+        // SAFETY: Valid I/O port address.
+        unsafe { read_volatile((u32::from(self.base) + RXTX) as *const u8) }
+    }
+
+    /// Start sending a byte, without waiting for it to go. The TX flag is
+    /// set once it has. See [`take_irq`](Self::take_irq).
+    pub fn start_write(&mut self, val: u8) {
+        power::check(Peripheral::Serial);
+        // SAFETY: Valid I/O port address.
+        unsafe { write_volatile((u32::from(self.base) + RXTX) as *mut u8, val) };
+    }
+
+    /// Return a received byte, if one has arrived since the last call.
+    pub fn read_byte(&mut self) -> Option<u8> {
+        self.read_irq();
+
+        if self.rx_pending {
+            self.rx_pending = false;
+            // SAFETY: Valid I/O port address. Reading acks the received byte.
+            Some(unsafe {
+                read_volatile((u32::from(self.base) + RXTX) as *const u8)
+            })
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Write for Serial {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_bytes(s.as_bytes());
+        Ok(())
+    }
+}
+
+#[cfg(feature = "ufmt")]
+impl ufmt_write::uWrite for Serial {
+    type Error = core::convert::Infallible;
+
+    fn write_str(&mut self, s: &str) -> Result<(), Self::Error> {
+        self.write_bytes(s.as_bytes());
+        Ok(())
+    }
+}
+
+/// Fixed-size byte FIFO. `N` must be a power of two, so indices can be
+/// masked rather than divided.
+struct Ring<const N: usize> {
+    buf: [u8; N],
+    head: usize,
+    len: usize,
+}
+
+impl<const N: usize> Ring<N> {
+    const POWER_OF_TWO: () = assert!(N.is_power_of_two(),
+                                     "serial buffers must be a power of two");
+
+    const fn new() -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::POWER_OF_TWO;
+        Self {
+            buf: [0; N],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, b: u8) -> bool {
+        if self.len == N {
+            return false;
+        }
+        self.buf[(self.head + self.len) & (N - 1)] = b;
+        self.len += 1;
+        true
+    }
+
+    fn pop(&mut self) -> Option<u8> {
+        if self.len == 0 {
+            return None;
+        }
+        let b = self.buf[self.head];
+        self.head = (self.head + 1) & (N - 1);
+        self.len -= 1;
+        Some(b)
+    }
+}
+
+struct RxState<const N: usize> {
+    ring: Ring<N>,
+    overruns: u32,
+}
+
+/// A receive buffer of `N` bytes (a power of two), for an RX interrupt
+/// handler to [`push`](Self::push) into and the main loop to read from,
+/// when the rest of the UART is driven some other way (so a [`Port`]
+/// doesn't fit). Meant to live in a `static`:
+///
+/// ```ignore
+/// static RX: RxBuffer<32> = RxBuffer::new();
+///
+/// irq::set(Handler::Rx(|b| RX.push(b)));
+/// loop {
+///     while let Some(b) = RX.read() {
+///         // ...
+///     }
+/// }
+/// ```
+pub struct RxBuffer<const N: usize> {
+    state: Mutex<RefCell<RxState<N>>>,
+}
+
+impl<const N: usize> Default for RxBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> RxBuffer<N> {
+    pub const fn new() -> Self {
+        Self {
+            state: Mutex::new(RefCell::new(RxState { ring: Ring::new(), overruns: 0 })),
+        }
+    }
+
+    /// Add a received byte, or count an overrun if the buffer is full.
+    pub fn push(&self, b: u8) {
+        critical_section::with(|cs| {
+            let mut st = self.state.borrow_ref_mut(cs);
+            if !st.ring.push(b) {
+                st.overruns = st.overruns.saturating_add(1);
+            }
+        });
+    }
+
+    /// The oldest byte, if there is one.
+    pub fn read(&self) -> Option<u8> {
+        critical_section::with(|cs| self.state.borrow_ref_mut(cs).ring.pop())
+    }
+
+    /// The oldest byte, waiting for one if need be. Needs interrupts on, or
+    /// it never returns.
+    pub fn read_blocking(&self) -> u8 {
+        loop {
+            if let Some(b) = self.read() {
+                return b;
+            }
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Bytes received and not yet read.
+    pub fn len(&self) -> usize {
+        critical_section::with(|cs| self.state.borrow_ref(cs).ring.len)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Throw away everything received so far.
+    pub fn clear(&self) {
+        critical_section::with(|cs| self.state.borrow_ref_mut(cs).ring = Ring::new());
+    }
+
+    /// Bytes dropped because the buffer was full.
+    pub fn overruns(&self) -> u32 {
+        critical_section::with(|cs| self.state.borrow_ref(cs).overruns)
+    }
+}
+
+struct PortState<const RX: usize, const TX: usize> {
+    base: Option<SerialBase>,
+    rx: Ring<RX>,
+    tx: Ring<TX>,
+    /// More to send, ahead of `tx`.
+    tx_source: Option<TxSource>,
+    /// A byte is being shifted out; its TX IRQ will send the next.
+    tx_busy: bool,
+    overruns: u32,
+    /// Tasks waiting for a byte, or for room to write.
+    rx_waker: Option<Waker>,
+    tx_waker: Option<Waker>,
+}
+
+/// Where a [`Port`] gets bytes to send ahead of its own TX buffer's.
+/// Called in the port's critical section.
+pub type TxSource = fn() -> Option<u8>;
+
+/// Interrupt-driven UART with `RX`- and `TX`-byte buffers (powers of two).
+/// Meant to live in a `static`; see the [module docs](self).
+pub struct Port<const RX: usize, const TX: usize> {
+    state: Mutex<RefCell<PortState<RX, TX>>>,
+}
+
+impl<const RX: usize, const TX: usize> Default for Port<RX, TX> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const RX: usize, const TX: usize> Port<RX, TX> {
+    pub const fn new() -> Self {
+        Self {
+            state: Mutex::new(RefCell::new(PortState {
+                base: None,
+                rx: Ring::new(),
+                tx: Ring::new(),
+                tx_source: None,
+                tx_busy: false,
+                overruns: 0,
+                rx_waker: None,
+                tx_waker: None,
+            })),
+        }
+    }
+
+    /// Start driving the UART at `base`. Until then, the port ignores
+    /// interrupts and buffers writes.
+    pub fn attach(&self, base: SerialBase) {
+        critical_section::with(|cs| {
+            let mut st = self.state.borrow_ref_mut(cs);
+            st.base = Some(base);
+            Self::kick(&mut st);
+        });
+    }
+
+    /// Send bytes from `source` whenever it has any, ahead of what's in the
+    /// TX buffer, such as [`defmt_uart`](crate::defmt_uart)'s deferred log
+    /// messages. A message made available all at once goes out in one
+    /// piece, between bytes of whatever else is written.
+    pub fn set_tx_source(&self, source: TxSource) {
+        critical_section::with(|cs| self.state.borrow_ref_mut(cs).tx_source = Some(source));
+    }
+
+    /// Start sending, if the UART is idle: for when the
+    /// [TX source](Self::set_tx_source) has something new.
+    pub fn start_tx(&self) {
+        critical_section::with(|cs| Self::kick(&mut self.state.borrow_ref_mut(cs)));
+    }
+
+    // SAFETY (of the register accesses below): `base` is a valid UART, and
+    // we're in a critical section, so nothing else is touching it.
+    fn kick(st: &mut PortState<RX, TX>) {
+        let Some(base) = st.base else {
+            return;
+        };
+        if st.tx_busy {
+            return;
+        }
+        if let Some(b) = st.tx_source.and_then(|source| source()).or_else(|| st.tx.pop()) {
+            power::check(Peripheral::Serial);
+            unsafe { write_volatile((u32::from(base) + RXTX) as *mut u8, b) };
+            st.tx_busy = true;
+        }
+    }
+
+    /// Service the UART's interrupt, if it's this port's. Call from
+    /// `MachineExternal`, for every port, every time.
+    pub fn on_interrupt(&self) {
+        critical_section::with(|cs| {
+            let mut st = self.state.borrow_ref_mut(cs);
+            let Some(base) = st.base else {
+                return;
+            };
+
+            power::check(Peripheral::Serial);
+            // Clears both flags.
+            let irq = unsafe {
+                read_volatile((u32::from(base) + IRQ) as *const u8)
+            };
+
+            if (irq & IRQ_RX) != 0 {
+                let b = unsafe {
+                    read_volatile((u32::from(base) + RXTX) as *const u8)
+                };
+                if !st.rx.push(b) {
+                    st.overruns = st.overruns.saturating_add(1);
+                }
+                if let Some(w) = st.rx_waker.take() {
+                    w.wake();
+                }
+            }
+
+            if (irq & IRQ_TX) != 0 {
+                st.tx_busy = false;
+                Self::kick(&mut st);
+                if let Some(w) = st.tx_waker.take() {
+                    w.wake();
+                }
+            }
+        });
+    }
+
+    /// Queue as much of `bytes` as fits, and return how much that was.
+    pub fn write(&self, bytes: &[u8]) -> usize {
+        critical_section::with(|cs| {
+            let mut st = self.state.borrow_ref_mut(cs);
+            let n = bytes.iter().take_while(|b| st.tx.push(**b)).count();
+            Self::kick(&mut st);
+            n
+        })
+    }
+
+    /// Queue all of `bytes`, waiting for room as needed. Needs interrupts
+    /// on, or it never finishes; in `polled` builds, it services the UART
+    /// itself instead.
+    pub fn write_all(&self, mut bytes: &[u8]) {
+        while !bytes.is_empty() {
+            bytes = &bytes[self.write(bytes)..];
+            self.wait();
+        }
+    }
+
+    /// Called while spinning on the port. Interrupts make the progress,
+    /// unless there are none.
+    pub(crate) fn wait(&self) {
+        #[cfg(feature = "polled")]
+        self.on_interrupt();
+    }
+
+    pub fn read(&self) -> Option<u8> {
+        critical_section::with(|cs| self.state.borrow_ref_mut(cs).rx.pop())
+    }
+
+    /// A received byte, if there is one.
+    pub fn try_read(&self) -> Result<u8, WouldBlock> {
+        self.read().ok_or(WouldBlock)
+    }
+
+    /// Queue one byte, if there's room.
+    pub fn try_write(&self, b: u8) -> Result<(), WouldBlock> {
+        match self.write(&[b]) {
+            1 => Ok(()),
+            _ => Err(WouldBlock),
+        }
+    }
+
+    /// Bytes received and not yet read.
+    pub fn rx_len(&self) -> usize {
+        critical_section::with(|cs| self.state.borrow_ref(cs).rx.len)
+    }
+
+    /// Room left in the TX buffer.
+    pub fn tx_free(&self) -> usize {
+        critical_section::with(|cs| TX - self.state.borrow_ref(cs).tx.len)
+    }
+
+    /// Whether everything written has been sent.
+    pub fn tx_idle(&self) -> bool {
+        critical_section::with(|cs| {
+            let st = self.state.borrow_ref(cs);
+            st.tx.len == 0 && !st.tx_busy
+        })
+    }
+
+    /// A [`fmt::Write`] that queues on this port.
+    pub fn writer(&self) -> SerialWriter<'_, RX, TX> {
+        SerialWriter(self)
+    }
+
+    /// `async` access to this port, for the
+    /// [`executor`](crate::executor).
+    pub fn as_async(&self) -> AsyncPort<'_, RX, TX> {
+        AsyncPort(self)
+    }
+
+    fn poll_read(&self, cx: &mut Context<'_>) -> Poll<u8> {
+        self.wait();
+        critical_section::with(|cs| {
+            let mut st = self.state.borrow_ref_mut(cs);
+            match st.rx.pop() {
+                Some(b) => Poll::Ready(b),
+                None => {
+                    st.rx_waker = Some(cx.waker().clone());
+                    Poll::Pending
+                }
+            }
+        })
+    }
+
+    fn poll_write(&self, cx: &mut Context<'_>, bytes: &[u8]) -> Poll<usize> {
+        self.wait();
+        critical_section::with(|cs| {
+            let mut st = self.state.borrow_ref_mut(cs);
+            let n = bytes.iter().take_while(|b| st.tx.push(**b)).count();
+            if n == 0 {
+                st.tx_waker = Some(cx.waker().clone());
+                return Poll::Pending;
+            }
+            Self::kick(&mut st);
+            Poll::Ready(n)
+        })
+    }
+
+    /// Bytes dropped because the RX buffer was full.
+    pub fn overruns(&self) -> u32 {
+        critical_section::with(|cs| self.state.borrow_ref(cs).overruns)
+    }
+}
+
+/// A [`Port`], from [`Port::as_async`], whose reads and writes wait by
+/// returning to the executor rather than spinning. The wakers are called
+/// from [`Port::on_interrupt`].
+pub struct AsyncPort<'a, const RX: usize, const TX: usize>(&'a Port<RX, TX>);
+
+impl<const RX: usize, const TX: usize> AsyncPort<'_, RX, TX> {
+    /// The next byte received.
+    pub async fn read_byte(&self) -> u8 {
+        poll_fn(|cx| self.0.poll_read(cx)).await
+    }
+
+    /// Queue all of `bytes`, waiting for room as needed.
+    pub async fn write_all(&self, mut bytes: &[u8]) {
+        while !bytes.is_empty() {
+            let n = poll_fn(|cx| self.0.poll_write(cx, bytes)).await;
+            bytes = &bytes[n..];
+        }
+    }
+}
+
+/// Formatted output to a [`Port`], from [`Port::writer`]. Waits for room
+/// in the TX buffer as needed, so like [`Port::write_all`] it needs
+/// interrupts on.
+pub struct SerialWriter<'a, const RX: usize, const TX: usize>(&'a Port<RX, TX>);
+
+impl<const RX: usize, const TX: usize> fmt::Write for SerialWriter<'_, RX, TX> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.write_all(s.as_bytes());
+        Ok(())
+    }
+}
+
+#[cfg(feature = "ufmt")]
+impl<const RX: usize, const TX: usize> ufmt_write::uWrite for SerialWriter<'_, RX, TX> {
+    type Error = core::convert::Infallible;
+
+    fn write_str(&mut self, s: &str) -> Result<(), Self::Error> {
+        self.0.write_all(s.as_bytes());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ring_wraps() {
+        let mut r = Ring::<4>::new();
+        for b in 0..4 {
+            assert!(r.push(b));
+        }
+        assert!(!r.push(4));
+        assert_eq!(r.pop(), Some(0));
+        assert!(r.push(4));
+        assert_eq!([r.pop(), r.pop(), r.pop(), r.pop(), r.pop()],
+                   [Some(1), Some(2), Some(3), Some(4), None]);
+    }
+
+    #[test]
+    fn writer_formats() {
+        use core::fmt::Write;
+
+        let port = Port::<4, 8>::new();
+        write!(port.writer(), "{:02x}:{}", 10, -3).unwrap();
+        let mut out = [0; 5];
+        critical_section::with(|cs| {
+            let mut st = port.state.borrow_ref_mut(cs);
+            out.iter_mut().for_each(|b| *b = st.tx.pop().unwrap());
+        });
+        assert_eq!(&out, b"0a:-3");
+    }
+
+    #[cfg(feature = "ufmt")]
+    #[test]
+    fn writer_uwrite() {
+        use ufmt_write::uWrite;
+
+        let port = Port::<4, 8>::new();
+        port.writer().write_str("ok").unwrap();
+        critical_section::with(|cs| {
+            let mut st = port.state.borrow_ref_mut(cs);
+            assert_eq!([st.tx.pop(), st.tx.pop(), st.tx.pop()], [Some(b'o'), Some(b'k'), None]);
+        });
+    }
+
+    #[test]
+    fn rx_buffer_overruns() {
+        let rx = RxBuffer::<2>::new();
+        for b in b"abc" {
+            rx.push(*b);
+        }
+        assert_eq!((rx.len(), rx.overruns()), (2, 1));
+        assert_eq!(rx.read_blocking(), b'a');
+        assert_eq!([rx.read(), rx.read()], [Some(b'b'), None]);
+    }
+
+    #[test]
+    fn async_port() {
+        let port = Port::<4, 4>::new();
+        let con = port.as_async();
+        crate::executor::block_on(con.write_all(b"hi"));
+        critical_section::with(|cs| port.state.borrow_ref_mut(cs).rx.push(b'!'));
+        assert_eq!(crate::executor::block_on(con.read_byte()), b'!');
+    }
+
+    #[test]
+    fn unattached_port_buffers() {
+        let port = Port::<4, 4>::new();
+        assert_eq!(port.write(b"hello"), 4);
+        port.on_interrupt();
+        assert_eq!(port.read(), None);
+        assert_eq!(port.try_read(), Err(WouldBlock));
+        assert_eq!(port.try_write(b'!'), Err(WouldBlock));
+    }
+}
@@ -0,0 +1,85 @@
+//! Bit-banged SPI master on four GPIO pins.
+//!
+//! Mode 0 (SCK idles low, data sampled on the rising edge), MSB first. As
+//! with [`i2c::GpioPins`](crate::hal::i2c::GpioPins), the GPIO output
+//! registers are write-only, so [`Spi`] assumes it owns every pin: SCK,
+//! MOSI and CS are outputs, and everything else an input.
+//!
+//! ```ignore
+//! let mut spi = Spi::new(soc.gpio, Pins { sck: 3, mosi: 1, miso: 2, cs: 0 });
+//! let mut buf = [0x9f, 0, 0, 0];
+//! spi.transfer(&mut buf); // JEDEC ID
+//! ```
+
+use crate::hal::gpio::Gpio;
+
+/// GPIO pin numbers (0-7).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Pins {
+    pub sck: u8,
+    pub mosi: u8,
+    pub miso: u8,
+    pub cs: u8,
+}
+
+pub struct Spi {
+    gpio: Gpio,
+    sck: u8,
+    mosi: u8,
+    miso: u8,
+    cs: u8,
+    out: u8,
+}
+
+impl Spi {
+    /// Take over the GPIO, with CS deasserted (high) and SCK low.
+    pub fn new(mut gpio: Gpio, pins: Pins) -> Self {
+        let (sck, mosi, cs) = (1 << pins.sck, 1 << pins.mosi, 1 << pins.cs);
+        gpio.write_outputs(cs);
+        gpio.set_output_enable(sck | mosi | cs);
+        Self {
+            gpio,
+            sck,
+            mosi,
+            miso: 1 << pins.miso,
+            cs,
+            out: cs,
+        }
+    }
+
+    /// Turn the outputs off, and give the GPIO back.
+    pub fn free(mut self) -> Gpio {
+        self.gpio.set_output_enable(0);
+        self.gpio
+    }
+
+    fn set(&mut self, mask: u8, high: bool) {
+        if high {
+            self.out |= mask;
+        } else {
+            self.out &= !mask;
+        }
+        self.gpio.write_outputs(self.out);
+    }
+
+    fn exchange(&mut self, byte: u8) -> u8 {
+        let mut read = 0;
+        for i in (0..8).rev() {
+            self.set(self.mosi, (byte >> i) & 1 != 0);
+            self.set(self.sck, true);
+            read = (read << 1) | ((self.gpio.read_inputs() & self.miso) != 0) as u8;
+            self.set(self.sck, false);
+        }
+        read
+    }
+
+    /// One transaction: assert CS, send `buf`, replacing each byte with the
+    /// one read back at the same time, and deassert CS.
+    pub fn transfer(&mut self, buf: &mut [u8]) {
+        self.set(self.cs, false);
+        for b in buf {
+            *b = self.exchange(*b);
+        }
+        self.set(self.cs, true);
+    }
+}
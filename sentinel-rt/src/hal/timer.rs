@@ -0,0 +1,40 @@
+//! AttoSoC timer driver.
+//!
+//! The timer is a free-running prescaler that raises its IRQ every
+//! [`CYCLES_PER_TICK`](crate::timebase::CYCLES_PER_TICK) clocks. Reading the
+//! IRQ register returns whether the IRQ is pending, and acknowledges it.
+
+use core::ptr::read_volatile;
+
+use crate::io_addrs::TimerBase;
+use crate::power::{self, Peripheral};
+
+const IRQ: u32 = 0;
+
+pub struct Timer {
+    base: TimerBase,
+}
+
+impl Timer {
+    pub fn new(base: TimerBase) -> Self {
+        Self { base }
+    }
+
+    pub fn base(&self) -> TimerBase {
+        self.base
+    }
+
+    /// Acknowledge the timer IRQ. Returns whether it was pending.
+    pub fn ack(&mut self) -> bool {
+        power::check(Peripheral::Timer);
+        // SAFETY: Valid I/O port address.
+        let irq =
+            unsafe { read_volatile((u32::from(self.base) + IRQ) as *const u8) };
+        (irq & 0x01) != 0
+    }
+
+    /// Spin until the next tick. For use with interrupts off.
+    pub fn wait_tick(&mut self) {
+        while !self.ack() {}
+    }
+}
@@ -0,0 +1,402 @@
+//! A small instrumented heap.
+//!
+//! [`Heap`] is a first-fit, address-ordered free list allocator that
+//! coalesces on free, over the `_heap_size` bytes riscv-rt's linker script
+//! sets aside (zero unless you set it, or the board has
+//! [SPRAM](crate::io_map)). It keeps [`Stats`], so heap-using firmware can
+//! be tuned to fit in a few KiB of RAM:
+//!
+//! ```ignore
+//! extern crate alloc;
+//!
+//! #[global_allocator]
+//! static HEAP: Heap = Heap::new();
+//!
+//! static COMMANDS: &[Command] = sentinel_rt::commands! {
+//!     /// Heap usage.
+//!     heap => |_, out| {
+//!         HEAP.stats().print(out);
+//!         Ok(())
+//!     },
+//! };
+//! ```
+//!
+//! `largest_free` against `free` is the fragmentation: the largest
+//! allocation that can succeed, out of everything that's free.
+//!
+//! With the `alloc` feature, sentinel-rt registers [`GLOBAL`], a heap like
+//! that, as the global allocator, so firmware only needs
+//! `extern crate alloc;` to use `Vec` and `String`. An allocation it can't
+//! satisfy is reported over the UART, with the stats:
+//!
+//! ```text
+//! heap: out of memory for 00000100 bytes
+//! used 00000380
+//! ...
+//! ```
+//!
+//! after which Rust's allocation error handling panics, and the panic
+//! [policy](crate::panic::Policy) applies. A failed `try_reserve` is
+//! reported too, though it carries on. Remember to give the heap some
+//! memory: `_heap_size` is zero unless the device script or board sets it.
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::cell::RefCell;
+use core::mem::{align_of, size_of};
+use core::ops::Range;
+use core::ptr::{self, null_mut};
+
+use critical_section::Mutex;
+
+use crate::hex;
+use crate::mem;
+use crate::shell::Output;
+
+/// A free block, stored in the block itself.
+struct Node {
+    size: usize,
+    next: *mut Node,
+}
+
+/// Smallest block, and the granularity of every size.
+const MIN: usize = size_of::<Node>();
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Bytes allocated now, and at most. Sizes are rounded up to whole
+    /// blocks.
+    pub used: usize,
+    pub peak: usize,
+    pub allocs: u32,
+    pub frees: u32,
+    /// Allocations that couldn't be satisfied.
+    pub failures: u32,
+    pub free: usize,
+    pub largest_free: usize,
+}
+
+impl Stats {
+    /// Print one `name value` line (hex) per field.
+    pub fn print(&self, out: &mut dyn Output) {
+        let fields = [
+            ("used ", self.used as u32),
+            ("peak ", self.peak as u32),
+            ("allocs ", self.allocs),
+            ("frees ", self.frees),
+            ("failures ", self.failures),
+            ("free ", self.free as u32),
+            ("largest-free ", self.largest_free as u32),
+        ];
+        for (name, val) in fields {
+            out.write_str(name);
+            out.write_bytes(&hex::u32_digits(val));
+            out.write_bytes(b"\r\n");
+        }
+    }
+}
+
+struct State {
+    head: *mut Node,
+    initialized: bool,
+    stats: Stats,
+}
+
+// SAFETY: The free list is only touched through the heap's Mutex.
+unsafe impl Send for State {}
+
+pub struct Heap {
+    state: Mutex<RefCell<State>>,
+}
+
+impl Default for Heap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+const fn align_up(addr: usize, align: usize) -> usize {
+    (addr + align - 1) & !(align - 1)
+}
+
+impl State {
+    /// Put `size` bytes at `addr` on the free list, merging with its
+    /// neighbours. They must not overlap anything already on it.
+    unsafe fn add_free(&mut self, addr: usize, size: usize) {
+        let mut prev: *mut Node = null_mut();
+        let mut cur = self.head;
+        while !cur.is_null() && (cur as usize) < addr {
+            prev = cur;
+            cur = (*cur).next;
+        }
+
+        let node = addr as *mut Node;
+        node.write(Node { size, next: cur });
+        if !cur.is_null() && addr + size == cur as usize {
+            (*node).size += (*cur).size;
+            (*node).next = (*cur).next;
+        }
+
+        if prev.is_null() {
+            self.head = node;
+        } else if prev as usize + (*prev).size == addr {
+            (*prev).size += (*node).size;
+            (*prev).next = (*node).next;
+        } else {
+            (*prev).next = node;
+        }
+    }
+
+    unsafe fn init(&mut self, region: Range<usize>) {
+        self.initialized = true;
+        let start = align_up(region.start, align_of::<Node>());
+        let end = region.end & !(align_of::<Node>() - 1);
+        if end >= start + MIN {
+            self.add_free(start, (end - start) / MIN * MIN);
+        }
+    }
+
+    unsafe fn alloc(&mut self, size: usize, align: usize) -> *mut u8 {
+        let mut prev: *mut Node = null_mut();
+        let mut cur = self.head;
+
+        while !cur.is_null() {
+            let addr = cur as usize;
+            let Node { size: avail, next } = cur.read();
+
+            // Anything left over in front of or behind the allocation has
+            // to be big enough to be a block itself.
+            let mut start = align_up(addr, align);
+            if start != addr && start - addr < MIN {
+                start = align_up(addr + MIN, align);
+            }
+            let end = start + size;
+            let fits = end <= addr + avail
+                && (end == addr + avail || addr + avail - end >= MIN);
+
+            if fits {
+                if prev.is_null() {
+                    self.head = next;
+                } else {
+                    (*prev).next = next;
+                }
+                if start > addr {
+                    self.add_free(addr, start - addr);
+                }
+                if end < addr + avail {
+                    self.add_free(end, addr + avail - end);
+                }
+                return start as *mut u8;
+            }
+
+            prev = cur;
+            cur = next;
+        }
+
+        null_mut()
+    }
+
+    fn free_space(&self) -> (usize, usize) {
+        let (mut total, mut largest) = (0, 0);
+        let mut cur = self.head;
+        while !cur.is_null() {
+            // SAFETY: Everything on the list is a valid Node.
+            let node = unsafe { cur.read() };
+            total += node.size;
+            largest = largest.max(node.size);
+            cur = node.next;
+        }
+        (total, largest)
+    }
+}
+
+/// Round a request up to what's actually handed out.
+fn block(layout: Layout) -> (usize, usize) {
+    (align_up(layout.size().max(MIN), MIN), layout.align().max(align_of::<Node>()))
+}
+
+impl Heap {
+    /// A heap over `_sheap`..`_eheap`, set up on first use.
+    pub const fn new() -> Self {
+        Self {
+            state: Mutex::new(RefCell::new(State {
+                head: null_mut(),
+                initialized: false,
+                stats: Stats {
+                    used: 0,
+                    peak: 0,
+                    allocs: 0,
+                    frees: 0,
+                    failures: 0,
+                    free: 0,
+                    largest_free: 0,
+                },
+            })),
+        }
+    }
+
+    /// Use `region` instead of the linker script's heap. Must be called
+    /// before anything is allocated.
+    ///
+    /// # Safety
+    ///
+    /// `region` must be memory nothing else uses, for as long as the heap
+    /// is in use.
+    pub unsafe fn init(&self, region: Range<usize>) {
+        critical_section::with(|cs| {
+            let mut st = self.state.borrow_ref_mut(cs);
+            assert!(!st.initialized, "heap: init after first use");
+            st.init(region);
+        });
+    }
+
+    pub fn stats(&self) -> Stats {
+        critical_section::with(|cs| {
+            let st = self.state.borrow_ref(cs);
+            let (free, largest_free) = st.free_space();
+            Stats {
+                free,
+                largest_free,
+                ..st.stats
+            }
+        })
+    }
+}
+
+unsafe impl GlobalAlloc for Heap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let (size, align) = block(layout);
+        critical_section::with(|cs| {
+            let mut st = self.state.borrow_ref_mut(cs);
+            if !st.initialized {
+                st.init(mem::heap());
+            }
+
+            let p = st.alloc(size, align);
+            if p.is_null() {
+                st.stats.failures += 1;
+            } else {
+                st.stats.allocs += 1;
+                st.stats.used += size;
+                st.stats.peak = st.stats.peak.max(st.stats.used);
+            }
+            p
+        })
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let (size, _) = block(layout);
+        critical_section::with(|cs| {
+            let mut st = self.state.borrow_ref_mut(cs);
+            st.add_free(ptr as usize, size);
+            st.stats.frees += 1;
+            st.stats.used -= size;
+        });
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+        if block(new_layout).0 == block(layout).0 {
+            return ptr;
+        }
+
+        let new = self.alloc(new_layout);
+        if !new.is_null() {
+            ptr::copy_nonoverlapping(ptr, new, layout.size().min(new_size));
+            self.dealloc(ptr, layout);
+        }
+        new
+    }
+}
+
+/// The `alloc` feature's global allocator: a [`Heap`] that reports
+/// failures.
+#[cfg(feature = "alloc")]
+pub struct Global(Heap);
+
+#[cfg(feature = "alloc")]
+#[cfg_attr(target_os = "none", global_allocator)]
+pub static GLOBAL: Global = Global(Heap::new());
+
+#[cfg(feature = "alloc")]
+impl Global {
+    pub fn stats(&self) -> Stats {
+        self.0.stats()
+    }
+
+    fn check(&self, p: *mut u8, size: usize) -> *mut u8 {
+        if p.is_null() {
+            report_oom(size, &self.stats());
+        }
+        p
+    }
+}
+
+#[cfg(feature = "alloc")]
+unsafe impl GlobalAlloc for Global {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.check(self.0.alloc(layout), layout.size())
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.0.dealloc(ptr, layout);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        self.check(self.0.realloc(ptr, layout, new_size), new_size)
+    }
+}
+
+#[cfg(feature = "alloc")]
+fn report_oom(size: usize, stats: &Stats) {
+    if let Some(bases) = crate::io_addrs::detected() {
+        let mut w = crate::hal::serial::Serial::new(bases.serial);
+        w.write_str("\r\nheap: out of memory for ");
+        w.write_bytes(&hex::u32_digits(size as u32));
+        w.write_str(" bytes\r\n");
+        stats.print(&mut w);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[repr(align(16))]
+    struct Arena([u8; 256]);
+
+    #[test]
+    fn alloc_free_coalesce() {
+        let mut arena = Arena([0; 256]);
+        let heap = Heap::new();
+        let base = arena.0.as_mut_ptr() as usize;
+        unsafe { heap.init(base..base + 256) };
+        assert_eq!(heap.stats().largest_free, 256);
+
+        let small = Layout::from_size_align(1, 1).unwrap();
+        let big = Layout::from_size_align(64, 16).unwrap();
+        let (a, b, c) = unsafe { (heap.alloc(small), heap.alloc(big), heap.alloc(small)) };
+        assert!(!a.is_null() && !b.is_null() && !c.is_null());
+        assert_eq!(b as usize % 16, 0);
+
+        let s = heap.stats();
+        assert_eq!((s.allocs, s.used), (3, 64 + 2 * MIN));
+        assert_eq!(s.free + s.used, 256);
+
+        // Freeing the middle leaves a hole that's no use for a bigger
+        // block...
+        unsafe { heap.dealloc(b, big) };
+        assert!(heap.stats().largest_free < 256 - 2 * MIN);
+        let too_big = Layout::from_size_align(256 - MIN, 1).unwrap();
+        assert!(unsafe { heap.alloc(too_big) }.is_null());
+        assert_eq!(heap.stats().failures, 1);
+
+        // ...until its neighbours go too.
+        unsafe {
+            heap.dealloc(a, small);
+            heap.dealloc(c, small);
+        }
+        let s = heap.stats();
+        assert_eq!((s.used, s.peak, s.frees), (0, 64 + 2 * MIN, 3));
+        assert_eq!(s.largest_free, 256);
+    }
+}
@@ -0,0 +1,29 @@
+//! Hex formatting without pulling in `core::fmt`.
+
+const DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+/// The 8 lowercase hex digits of `val`, most significant first.
+pub fn u32_digits(val: u32) -> [u8; 8] {
+    let mut out = [0; 8];
+    for (i, d) in out.iter_mut().enumerate() {
+        *d = DIGITS[((val >> ((7 - i) * 4)) & 0xf) as usize];
+    }
+    out
+}
+
+/// The 2 lowercase hex digits of `val`.
+pub fn u8_digits(val: u8) -> [u8; 2] {
+    [DIGITS[(val >> 4) as usize], DIGITS[(val & 0xf) as usize]]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn digits() {
+        assert_eq!(&u32_digits(0xdeadbeef), b"deadbeef");
+        assert_eq!(&u32_digits(0x1), b"00000001");
+        assert_eq!(&u8_digits(0xa5), b"a5");
+    }
+}
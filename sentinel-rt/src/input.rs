@@ -0,0 +1,195 @@
+//! Console input, as a queue of key events.
+//!
+//! Bytes from the UART go in, through [`poll`] in the main loop or [`feed`]
+//! from an RX interrupt, and come out of [`next`] as decoded [`Key`]s:
+//!
+//! ```ignore
+//! input::on_interrupt(input::restart);
+//! loop {
+//!     input::poll(&mut soc.serial);
+//!     while let Some(key) = input::next() {
+//!         // ...
+//!     }
+//! }
+//! ```
+//!
+//! Ctrl-C isn't queued. It raises the interrupt signal instead, so an
+//! application that is busy, or has a full queue, can still be broken out
+//! of: either check [`interrupted`] wherever it's convenient to stop, or
+//! register a handler with [`on_interrupt`] to act on it straight away.
+//!
+//! A lone ESC can't be told from the start of an arrow-key sequence until
+//! the line goes quiet, so [`poll`] delivers it as [`Key::Escape`] after
+//! [`ESCAPE_TICKS`] without another byte. That needs the
+//! [`timebase`](crate::timebase) to be ticking.
+
+use core::cell::{Cell, RefCell};
+
+use critical_section::Mutex;
+use portable_atomic::{AtomicBool, Ordering};
+
+use crate::hal::serial::Serial;
+use crate::reset::{self, Reason};
+pub use crate::term::Key;
+use crate::term::Keys;
+use crate::timebase::{self, CLK_HZ, CYCLES_PER_TICK};
+
+/// How many keys are queued before further ones are dropped.
+pub const QUEUE: usize = 16;
+
+/// Idle time after which a pending ESC is taken to be a key of its own,
+/// in ticks (about 50 ms).
+pub const ESCAPE_TICKS: u32 = CLK_HZ / CYCLES_PER_TICK / 20;
+
+struct State {
+    keys: Keys,
+    queue: [Key; QUEUE],
+    /// Index of the oldest queued key.
+    head: usize,
+    len: usize,
+    /// Tick of the last byte fed.
+    last: u32,
+    dropped: u32,
+}
+
+static STATE: Mutex<RefCell<State>> = Mutex::new(RefCell::new(State {
+    keys: Keys::new(),
+    queue: [Key::Escape; QUEUE],
+    head: 0,
+    len: 0,
+    last: 0,
+    dropped: 0,
+}));
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+type Handler = Option<fn()>;
+
+static HANDLER: Mutex<Cell<Handler>> = Mutex::new(Cell::new(None));
+
+fn push(st: &mut State, key: Key) -> bool {
+    if key == Key::Interrupt {
+        return true;
+    }
+    if st.len == QUEUE {
+        st.dropped = st.dropped.wrapping_add(1);
+    } else {
+        let tail = (st.head + st.len) % QUEUE;
+        st.queue[tail] = key;
+        st.len += 1;
+    }
+    false
+}
+
+fn signal() {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+    // Called outside the critical section: the handler may well not return.
+    if let Some(f) = critical_section::with(|cs| HANDLER.borrow(cs).get()) {
+        f();
+    }
+}
+
+/// Decode one received byte. Safe to call from an interrupt handler.
+pub fn feed(b: u8) {
+    let interrupt = critical_section::with(|cs| {
+        let mut st = STATE.borrow_ref_mut(cs);
+        st.last = timebase::ticks32();
+        st.keys.feed(b).is_some_and(|key| push(&mut st, key))
+    });
+    if interrupt {
+        signal();
+    }
+}
+
+/// Feed whatever the UART has received, and deliver a lone ESC once the
+/// line has been idle for [`ESCAPE_TICKS`].
+pub fn poll(ser: &mut Serial) {
+    while let Some(b) = ser.read_byte() {
+        feed(b);
+    }
+
+    critical_section::with(|cs| {
+        let mut st = STATE.borrow_ref_mut(cs);
+        if st.keys.pending() && timebase::ticks32().wrapping_sub(st.last) >= ESCAPE_TICKS {
+            if let Some(key) = st.keys.flush() {
+                push(&mut st, key);
+            }
+        }
+    });
+}
+
+/// The oldest queued key, if any.
+pub fn next() -> Option<Key> {
+    critical_section::with(|cs| {
+        let mut st = STATE.borrow_ref_mut(cs);
+        if st.len == 0 {
+            return None;
+        }
+        let key = st.queue[st.head];
+        st.head = (st.head + 1) % QUEUE;
+        st.len -= 1;
+        Some(key)
+    })
+}
+
+/// Drop all queued keys, e.g. ones typed ahead before a prompt.
+pub fn clear() {
+    critical_section::with(|cs| STATE.borrow_ref_mut(cs).len = 0);
+}
+
+/// Keys dropped because the queue was full.
+pub fn dropped() -> u32 {
+    critical_section::with(|cs| STATE.borrow_ref(cs).dropped)
+}
+
+/// Whether Ctrl-C has been pressed since the last call.
+pub fn interrupted() -> bool {
+    INTERRUPTED.swap(false, Ordering::SeqCst)
+}
+
+/// Call `f` whenever Ctrl-C is pressed, from wherever the byte was fed
+/// ([`feed`] or [`poll`]). [`interrupted`] still reports it afterwards.
+pub fn on_interrupt(f: fn()) {
+    critical_section::with(|cs| HANDLER.borrow(cs).set(Some(f)));
+}
+
+/// Stop calling the [`on_interrupt`] handler.
+pub fn clear_interrupt_handler() {
+    critical_section::with(|cs| HANDLER.borrow(cs).set(None));
+}
+
+/// An [`on_interrupt`] handler that restarts the firmware, with
+/// [`Reason::Requested`]. This is what the examples use.
+pub fn restart() {
+    reset::soft_reset(Some(Reason::Requested))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The state is global, so everything is in one test.
+    #[test]
+    fn queues_keys_and_signals_interrupt() {
+        for b in *b"a\x1b[Ab" {
+            feed(b);
+        }
+        assert_eq!(next(), Some(Key::Char(b'a')));
+        assert_eq!(next(), Some(Key::Up));
+        assert_eq!(next(), Some(Key::Char(b'b')));
+        assert_eq!(next(), None);
+
+        assert!(!interrupted());
+        feed(b'x');
+        feed(0x03);
+        assert!(interrupted());
+        assert!(!interrupted());
+        assert_eq!(next(), Some(Key::Char(b'x')));
+
+        for _ in 0..QUEUE + 2 {
+            feed(b'z');
+        }
+        assert_eq!(dropped(), 2);
+        clear();
+        assert_eq!(next(), None);
+    }
+}
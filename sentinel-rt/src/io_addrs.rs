@@ -0,0 +1,242 @@
+//! Peripheral base addresses for the AttoSoC.
+//!
+//! It is difficult to get CSR and Wishbone periphs to share the same
+//! addresses, so I don't bother. Instead, use base u32s to access hardware,
+//! so that the same firmware can be used regardless of board.
+//!
+//! Detecting the bus has to happen before anything services the serial
+//! port's reset-time IRQ, which is why [`detect`] is `unsafe`. With the
+//! `pre-init-detect` feature, sentinel-rt does it before `main` instead
+//! (as riscv-rt's `__pre_init`), and [`bases`] is always safe to call. An
+//! application with a `#[pre_init]` of its own can call [`early_detect`]
+//! from it to the same effect.
+//!
+//! Firmware for a single board needn't detect anything: the `csr-periphs`
+//! and `wb-periphs` features fix the bus at compile time ([`FIXED`]), so
+//! the probe compiles away and the base addresses are constants. The probe
+//! itself is the (default) `detect` feature.
+
+use core::cell::Cell;
+use core::mem::MaybeUninit;
+use core::ptr::{addr_of, addr_of_mut};
+
+use critical_section::Mutex;
+use riscv::register::mip;
+
+use crate::io_map;
+
+#[derive(Clone, Copy)]
+pub struct GpioBase(u32);
+
+impl From<GpioBase> for u32 {
+    fn from(value: GpioBase) -> Self {
+        value.0
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct TimerBase(u32);
+
+impl From<TimerBase> for u32 {
+    fn from(value: TimerBase) -> Self {
+        value.0
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct SerialBase(u32);
+
+impl SerialBase {
+    /// A UART at `addr`, for SoCs with more than the one [`detect`] finds.
+    ///
+    /// # Safety
+    ///
+    /// `addr` must be the base address of an AttoSoC-compatible UART.
+    pub const unsafe fn new(addr: u32) -> Self {
+        Self(addr)
+    }
+}
+
+impl From<SerialBase> for u32 {
+    fn from(value: SerialBase) -> Self {
+        value.0
+    }
+}
+
+/// Which peripheral interconnect the SoC was built with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bus {
+    Wishbone,
+    Csr,
+}
+
+#[derive(Clone, Copy)]
+pub struct Bases {
+    pub bus: Bus,
+    pub gpio: GpioBase,
+    pub timer: TimerBase,
+    pub serial: SerialBase,
+}
+
+impl Bases {
+    /// From the board's [`io_map`](crate::io_map).
+    pub const fn for_bus(bus: Bus) -> Self {
+        match bus {
+            Bus::Wishbone => Self {
+                bus,
+                gpio: GpioBase(io_map::WB_GPIO),
+                timer: TimerBase(io_map::WB_TIMER),
+                serial: SerialBase(io_map::WB_SERIAL),
+            },
+            Bus::Csr => Self {
+                bus,
+                gpio: GpioBase(io_map::CSR_GPIO),
+                timer: TimerBase(io_map::CSR_TIMER),
+                serial: SerialBase(io_map::CSR_SERIAL),
+            },
+        }
+    }
+}
+
+#[cfg(all(feature = "csr-periphs", feature = "wb-periphs"))]
+compile_error!("enable at most one of `csr-periphs` and `wb-periphs`");
+
+#[cfg(not(any(feature = "detect", feature = "csr-periphs", feature = "wb-periphs")))]
+compile_error!("enable `detect`, or fix the bus with `csr-periphs` or `wb-periphs`");
+
+/// The peripherals, if the bus was fixed at compile time. Takes precedence
+/// over any detection.
+pub const FIXED: Option<Bases> = if cfg!(feature = "csr-periphs") {
+    Some(Bases::for_bus(Bus::Csr))
+} else if cfg!(feature = "wb-periphs") {
+    Some(Bases::for_bus(Bus::Wishbone))
+} else {
+    None
+};
+
+static DETECTED: Mutex<Cell<Option<Bases>>> = Mutex::new(Cell::new(None));
+
+const EARLY_MAGIC: u32 = 0x4c52_4145; // "EARL"
+
+/// What [`early_detect`] found. It runs before `.data` and `.bss` are set
+/// up, so the result has to survive their initialization.
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct Early {
+    magic: u32,
+    bus: u32,
+}
+
+#[link_section = ".noinit.io_addrs"]
+static mut EARLY: MaybeUninit<Early> = MaybeUninit::uninit();
+
+/// Bus found by [`early_detect`], if it ran this boot. Consumed.
+fn take_early() -> Option<Bus> {
+    // SAFETY: .noinit is plain RAM; any bit pattern is a valid Early.
+    critical_section::with(|_| unsafe {
+        let early = addr_of!(EARLY).read_volatile().assume_init();
+        if early.magic != EARLY_MAGIC {
+            return None;
+        }
+        addr_of_mut!(EARLY).write_volatile(MaybeUninit::new(Early { magic: 0, bus: 0 }));
+        Some(if early.bus == 1 { Bus::Wishbone } else { Bus::Csr })
+    })
+}
+
+/// Detect the bus before `main`, for [`detect`], [`detected`] and [`bases`]
+/// to pick up. Only touches `.noinit`, so it can run from `#[pre_init]`.
+///
+/// A bus handed over by a [hot reload](crate::reload) isn't known yet at
+/// this point; it still takes precedence once [`reload::resume`] has run.
+///
+/// # Safety
+///
+/// As for [`detect`].
+///
+/// [`reload::resume`]: crate::reload::resume
+pub unsafe fn early_detect() {
+    if FIXED.is_some() {
+        return;
+    }
+    let bus = match crate::reset::take_bus() {
+        Some(bus) => bus,
+        None if mip::read().mext() => Bus::Wishbone,
+        None => Bus::Csr,
+    };
+    let early = Early { magic: EARLY_MAGIC, bus: (bus == Bus::Wishbone) as u32 };
+    addr_of_mut!(EARLY).write_volatile(MaybeUninit::new(early));
+}
+
+#[cfg(all(feature = "pre-init-detect", target_os = "none"))]
+#[export_name = "__pre_init"]
+unsafe fn pre_init() {
+    early_detect();
+}
+
+/// Detect which peripheral bus the SoC was built with, and remember the
+/// result for [`detected`].
+///
+/// # Safety
+///
+/// Must be called when interrupts are disabled, before anything has had a
+/// chance to service the serial port's reset-time IRQ.
+pub unsafe fn detect() -> Bases {
+    if let Some(bases) = FIXED {
+        return bases;
+    }
+
+    // If IRQ is pending after reset, we are using WBSerial, and thus a
+    // wishbone peripheral bus. A soft reset doesn't reproduce the IRQ state
+    // at power-on, so it remembers the answer for us. Neither does a hot
+    // reload, which hands it over instead. If detection already happened
+    // before main, that's the answer (unless a reload says otherwise).
+    let handed_over = crate::reload::take_bus()
+        .or_else(take_early)
+        .or_else(crate::reset::take_bus);
+    let bases = if let Some(bus) = handed_over {
+        Bases::for_bus(bus)
+    } else if mip::read().mext() {
+        Bases::for_bus(Bus::Wishbone)
+    } else {
+        Bases::for_bus(Bus::Csr)
+    };
+
+    critical_section::with(|cs| DETECTED.borrow(cs).set(Some(bases)));
+    bases
+}
+
+/// The result of the last [`detect`] (or [`early_detect`]), if any. Handy
+/// for ISRs, which can't be handed the drivers' base addresses directly.
+pub fn detected() -> Option<Bases> {
+    if FIXED.is_some() {
+        return FIXED;
+    }
+    critical_section::with(|cs| {
+        let detected = DETECTED.borrow(cs);
+        if detected.get().is_none() {
+            detected.set(take_early().map(Bases::for_bus));
+        }
+        detected.get()
+    })
+}
+
+/// The base addresses of each peripheral.
+///
+/// # Panics
+///
+/// If the bus hasn't been detected: build with `pre-init-detect` (or fix
+/// the bus), or call [`Soc::init`](crate::soc::Soc::init) first.
+pub fn bases() -> Bases {
+    detected().expect("io_addrs: bus not detected")
+}
+
+/// Detect the peripheral bus, and return the base addresses of each
+/// peripheral.
+///
+/// # Safety
+///
+/// Same as [`detect`].
+pub unsafe fn get_bases() -> (GpioBase, TimerBase, SerialBase) {
+    let bases = detect();
+    (bases.gpio, bases.timer, bases.serial)
+}
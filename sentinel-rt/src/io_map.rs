@@ -0,0 +1,39 @@
+//! The memory map of the board being built for, from its description in
+//! `boards/`.
+//!
+//! `build.rs` reads the description (the AttoSoC's, unless a `board-*`
+//! feature or the `SENTINEL_BOARD` environment variable picks another) and
+//! generates these constants, along with a `board.x` for the linker that
+//! lays the sections out in its memory:
+//!
+//! ```text
+//! INCLUDE board.x
+//! INCLUDE rt.x
+//! INCLUDE sentinel.x
+//! ```
+//!
+//! so moving to a board with more RAM, or peripherals elsewhere, takes a new
+//! description rather than edits to linker scripts and [`io_addrs`].
+//!
+//! Descriptions that come with sentinel-rt:
+//!
+//! | Board | Feature | RAM |
+//! |-------|---------|-----|
+//! | `attosoc` | (default) | 4 KiB block RAM |
+//! | `hx8k` | `board-hx8k` | 8 KiB block RAM |
+//! | `icebreaker` | `board-icebreaker` | 64 KiB SPRAM |
+//! | `icebreaker-bram` | `board-icebreaker-bram` | 8 KiB block RAM, 128 KiB SPRAM heap |
+//!
+//! The description also gives the system clock and the UART's baud rate,
+//! [`CLK_HZ`] and [`BAUD`], which the gateware fixes; every board above
+//! runs at 12 MHz and 9600 baud.
+//!
+//! A description may have an `[spram]` region besides `[ram]`. `board.x`
+//! then makes all of it the heap ([`SPRAM_ORIGIN`] and [`SPRAM_LENGTH`]
+//! say where; both are zero without one), while everything else stays in
+//! `RAM`. Scripts that only want the `MEMORY` block can `INCLUDE memory.x`
+//! and place the sections themselves.
+//!
+//! [`io_addrs`]: crate::io_addrs
+
+include!(concat!(env!("OUT_DIR"), "/io_map.rs"));
@@ -0,0 +1,468 @@
+//! `MachineExternal`, shared out by source.
+//!
+//! The AttoSoC has one external interrupt line for every peripheral, so its
+//! handler has to ask each one whether it's the cause and acknowledge it.
+//! [`dispatch`] does that, and calls whatever [`Handler`]s are registered
+//! for what it finds:
+//!
+//! ```ignore
+//! irq::set(Handler::Timer(watchdog::check));
+//! irq::set(Handler::Rx(input::feed));
+//!
+//! #[no_mangle]
+//! fn MachineExternal() {
+//!     irq::dispatch();
+//! }
+//! ```
+//!
+//! or build with the `dispatch` feature, which defines `MachineExternal`
+//! like that. The timer is always acknowledged and the
+//! [`timebase`](crate::timebase) ticked, and the UART's IRQ always cleared,
+//! handler or not: the Wishbone UART's is pending from reset.
+//!
+//! An interrupt-driven [`Port`] reads the UART's IRQ itself, so it takes
+//! the whole UART with [`Handler::Serial`] instead of `Rx`/`Tx`:
+//!
+//! ```ignore
+//! irq::set(Handler::Serial(|| CONSOLE.on_interrupt()));
+//! ```
+//!
+//! The GPIO has no interrupt of its own. [`Handler::Gpio`] is called with
+//! the input levels whenever they've changed since the last dispatch, so
+//! edges are seen at least as often as the timer ticks.
+//!
+//! Nor is there a software interrupt. [`pend`] stands in for one: it marks
+//! [`Handler::Soft`] to be called at the end of the next dispatch, which is
+//! the next timer tick at the latest.
+//!
+//! Handlers can also be fixed at link time, by name, with
+//! [`#[interrupt]`](crate::interrupt):
+//!
+//! ```ignore
+//! #[interrupt]
+//! fn UART_RX(b: u8) {
+//!     input::feed(b);
+//! }
+//! ```
+//!
+//! `TIMER`, `UART_RX(u8)`, `UART_TX` and `GPIO(u8)` are called like their
+//! [`Handler`] counterparts, after any registered one; a misspelt name or
+//! wrong signature doesn't compile. They need `sentinel.x` in the link.
+//!
+//! Handlers run in the ISR, with interrupts off; keep them short. Or, if
+//! one can't be, turn on [nesting](set_nesting), so that the timer can
+//! still interrupt the others.
+//!
+//! [`Port`]: crate::hal::serial::Port
+
+use core::cell::Cell;
+
+use critical_section::Mutex;
+use portable_atomic::{AtomicBool, Ordering};
+
+use crate::hal::gpio::Gpio;
+use crate::hal::serial::Serial;
+use crate::hal::timer::Timer;
+use crate::power::{self, Peripheral};
+use crate::{io_addrs, timebase};
+
+/// Something [`dispatch`] can tell apart.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Source {
+    Timer,
+    Rx,
+    Tx,
+    Serial,
+    Gpio,
+    Soft,
+}
+
+/// What to call for a [`Source`].
+#[derive(Clone, Copy, Debug)]
+pub enum Handler {
+    /// A tick has passed (after [`timebase::tick`]).
+    Timer(fn()),
+    /// A byte has been received: the byte.
+    Rx(fn(u8)),
+    /// A byte has been sent.
+    Tx(fn()),
+    /// Service the UART, IRQ register and all. Takes the place of `Rx` and
+    /// `Tx`.
+    Serial(fn()),
+    /// The GPIO inputs have changed: the new levels.
+    Gpio(fn(u8)),
+    /// [`pend`] has been called.
+    Soft(fn()),
+}
+
+impl Handler {
+    pub fn source(&self) -> Source {
+        match self {
+            Self::Timer(_) => Source::Timer,
+            Self::Rx(_) => Source::Rx,
+            Self::Tx(_) => Source::Tx,
+            Self::Serial(_) => Source::Serial,
+            Self::Gpio(_) => Source::Gpio,
+            Self::Soft(_) => Source::Soft,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Table {
+    timer: Option<fn()>,
+    rx: Option<fn(u8)>,
+    tx: Option<fn()>,
+    serial: Option<fn()>,
+    gpio: Option<fn(u8)>,
+    soft: Option<fn()>,
+    /// Input levels at the last dispatch.
+    pins: u8,
+}
+
+impl Table {
+    const fn new() -> Self {
+        Self { timer: None, rx: None, tx: None, serial: None, gpio: None, soft: None, pins: 0 }
+    }
+
+    fn set(&mut self, handler: Handler) {
+        match handler {
+            Handler::Timer(f) => self.timer = Some(f),
+            Handler::Rx(f) => self.rx = Some(f),
+            Handler::Tx(f) => self.tx = Some(f),
+            Handler::Serial(f) => self.serial = Some(f),
+            Handler::Gpio(f) => self.gpio = Some(f),
+            Handler::Soft(f) => self.soft = Some(f),
+        }
+    }
+
+    fn clear(&mut self, source: Source) {
+        match source {
+            Source::Timer => self.timer = None,
+            Source::Rx => self.rx = None,
+            Source::Tx => self.tx = None,
+            Source::Serial => self.serial = None,
+            Source::Gpio => self.gpio = None,
+            Source::Soft => self.soft = None,
+        }
+    }
+
+    /// Record the input levels; whether they differ from last time.
+    fn pins_changed(&mut self, pins: u8) -> bool {
+        let changed = pins != self.pins;
+        self.pins = pins;
+        changed
+    }
+}
+
+static TABLE: Mutex<Cell<Table>> = Mutex::new(Cell::new(Table::new()));
+static PENDING: AtomicBool = AtomicBool::new(false);
+
+/// Register `handler` for its source, replacing any there was.
+pub fn set(handler: Handler) {
+    critical_section::with(|cs| {
+        let table = TABLE.borrow(cs);
+        let mut t = table.get();
+        t.set(handler);
+        table.set(t);
+    });
+}
+
+/// Stop calling anything for `source`. Its IRQ is still acknowledged.
+pub fn clear(source: Source) {
+    critical_section::with(|cs| {
+        let table = TABLE.borrow(cs);
+        let mut t = table.get();
+        t.clear(source);
+        table.set(t);
+    });
+}
+
+/// Have the next dispatch call [`Handler::Soft`]. Pending it again before
+/// then still calls it once.
+pub fn pend() {
+    PENDING.store(true, Ordering::Release);
+}
+
+/// Take back a [`pend`] that hasn't been dispatched yet.
+pub fn unpend() {
+    PENDING.store(false, Ordering::Release);
+}
+
+pub fn is_pending() -> bool {
+    PENDING.load(Ordering::Acquire)
+}
+
+/// Find out which peripherals want attention, acknowledge them, and call
+/// their handlers. Call from `MachineExternal` (or the main loop, in a
+/// `polled` build). Does nothing before the bus is detected.
+pub fn dispatch() {
+    // Soc::init detects the bus before it enables interrupts.
+    let Some(bases) = io_addrs::detected() else {
+        return;
+    };
+    // Copied out, so handlers can (de)register others.
+    let t = critical_section::with(|cs| TABLE.borrow(cs).get());
+
+    if Timer::new(bases.timer).ack() {
+        timebase::tick();
+        if let Some(f) = t.timer {
+            f();
+        }
+        linked::timer();
+    }
+
+    let mut received = Received::default();
+    if let Some(f) = t.serial {
+        f();
+    } else {
+        // Interrupts are off, so this doesn't race main's drivers.
+        let mut serial = Serial::new(bases.serial);
+        let irq = serial.take_irq();
+        if irq.rx() {
+            received.rx = Some(serial.read_data());
+        }
+        received.tx = irq.tx();
+    }
+    critical_section::with(|cs| RECEIVED.borrow(cs).set(RECEIVED.borrow(cs).get().merge(received)));
+
+    if nesting::preempting() {
+        // The dispatch this interrupted will call the handlers for it.
+        return;
+    }
+    if NESTING.load(Ordering::Relaxed) {
+        nesting::run(|| low_priority(&t, bases.gpio));
+    } else {
+        low_priority(&t, bases.gpio);
+    }
+}
+
+/// What the UART's IRQ said, waiting for its handlers.
+#[derive(Clone, Copy, Default)]
+struct Received {
+    rx: Option<u8>,
+    tx: bool,
+}
+
+impl Received {
+    /// `later` on top of this. A byte not handled yet is lost, as it would
+    /// be in the UART.
+    fn merge(self, later: Self) -> Self {
+        Self { rx: later.rx.or(self.rx), tx: self.tx || later.tx }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.rx.is_none() && !self.tx
+    }
+}
+
+static RECEIVED: Mutex<Cell<Received>> = Mutex::new(Cell::new(Received { rx: None, tx: false }));
+static NESTING: AtomicBool = AtomicBool::new(false);
+
+/// With `true`, [`dispatch`] calls the handlers for everything but the
+/// timer (and a [`Handler::Serial`]) with interrupts back on, so a slow
+/// one doesn't hold up the timer tick. Not in `polled` builds, where
+/// there's nothing to nest.
+///
+/// Only the timer preempts them: what the UART says meanwhile is
+/// acknowledged, and its handler called once the one running returns.
+/// Those handlers then need critical sections for anything they share with
+/// a `Timer` one.
+pub fn set_nesting(enable: bool) {
+    NESTING.store(enable && !cfg!(feature = "polled"), Ordering::Relaxed);
+}
+
+/// The handlers that nesting lets the timer preempt. Calls them for
+/// whatever the UART has said, until it stops saying anything.
+fn low_priority(t: &Table, gpio: io_addrs::GpioBase) {
+    loop {
+        let r = critical_section::with(|cs| RECEIVED.borrow(cs).take());
+        if r.is_empty() {
+            break;
+        }
+        if let Some(b) = r.rx {
+            if let Some(f) = t.rx {
+                f(b);
+            }
+            linked::uart_rx(b);
+        }
+        if r.tx {
+            if let Some(f) = t.tx {
+                f();
+            }
+            linked::uart_tx();
+        }
+    }
+
+    // There may be a linked GPIO handler, so look unless that would panic.
+    if (t.gpio.is_some() || linked::ANY) && power::is_enabled(Peripheral::Gpio) {
+        let pins = Gpio::new(gpio).read_inputs();
+        let changed = critical_section::with(|cs| {
+            let table = TABLE.borrow(cs);
+            let mut t = table.get();
+            let changed = t.pins_changed(pins);
+            table.set(t);
+            changed
+        });
+        if changed {
+            if let Some(f) = t.gpio {
+                f(pins);
+            }
+            linked::gpio(pins);
+        }
+    }
+
+    // Last, so whatever the handlers above pended runs now.
+    if PENDING.swap(false, Ordering::AcqRel) {
+        if let Some(f) = t.soft {
+            f();
+        }
+    }
+}
+
+/// Turning interrupts back on inside the ISR.
+#[cfg(target_os = "none")]
+mod nesting {
+    use portable_atomic::{AtomicBool, Ordering};
+    use riscv::register::{mepc, mstatus};
+
+    use super::RECEIVED;
+
+    static RUNNING: AtomicBool = AtomicBool::new(false);
+
+    /// Whether this dispatch interrupted another's low-priority handlers.
+    pub fn preempting() -> bool {
+        RUNNING.load(Ordering::Acquire)
+    }
+
+    /// Call `f` with interrupts on, until nothing's arrived for it to
+    /// handle. A nested trap overwrites `mepc` and `mstatus.MPIE`, so
+    /// they're put back before returning to the trap handler.
+    pub fn run(mut f: impl FnMut()) {
+        let (pc, mpie) = (mepc::read(), mstatus::read().mpie());
+        RUNNING.store(true, Ordering::Release);
+        loop {
+            // SAFETY: The IRQs this dispatch has seen are all acknowledged,
+            // so the line is quiet unless something new has happened.
+            unsafe { mstatus::set_mie() };
+            f();
+            // SAFETY: Just masking interrupts.
+            unsafe { mstatus::clear_mie() };
+            // Something may have come in after f's last look.
+            if critical_section::with(|cs| RECEIVED.borrow(cs).get().is_empty()) {
+                break;
+            }
+        }
+        RUNNING.store(false, Ordering::Release);
+        mepc::write(pc);
+        // A nested mret leaves MPIE set, so it only ever needs clearing.
+        // (riscv has no clear_mpie.)
+        if !mpie {
+            // SAFETY: Restoring what the trap entry set.
+            unsafe { core::arch::asm!("csrc mstatus, {0}", in(reg) 1 << 7) };
+        }
+    }
+}
+
+#[cfg(not(target_os = "none"))]
+mod nesting {
+    pub fn preempting() -> bool {
+        false
+    }
+
+    pub fn run(mut f: impl FnMut()) {
+        f();
+    }
+}
+
+/// The `#[interrupt]` handlers. The linker script defaults them to
+/// `__sentinel_irq_default`, so they're only there on the target.
+#[cfg(target_os = "none")]
+mod linked {
+    pub const ANY: bool = true;
+
+    extern "C" {
+        fn __sentinel_irq_TIMER();
+        fn __sentinel_irq_UART_RX(b: u8);
+        fn __sentinel_irq_UART_TX();
+        fn __sentinel_irq_GPIO(pins: u8);
+    }
+
+    // SAFETY (all): Either an #[interrupt] fn, which has the signature the
+    // macro checked, or the default, which ignores its arguments.
+    pub fn timer() {
+        unsafe { __sentinel_irq_TIMER() }
+    }
+
+    pub fn uart_rx(b: u8) {
+        unsafe { __sentinel_irq_UART_RX(b) }
+    }
+
+    pub fn uart_tx() {
+        unsafe { __sentinel_irq_UART_TX() }
+    }
+
+    pub fn gpio(pins: u8) {
+        unsafe { __sentinel_irq_GPIO(pins) }
+    }
+
+    #[export_name = "__sentinel_irq_default"]
+    extern "C" fn default() {}
+}
+
+#[cfg(not(target_os = "none"))]
+mod linked {
+    pub const ANY: bool = false;
+
+    pub fn timer() {}
+    pub fn uart_rx(_: u8) {}
+    pub fn uart_tx() {}
+    pub fn gpio(_: u8) {}
+}
+
+#[cfg(all(feature = "dispatch", target_os = "none"))]
+#[export_name = "MachineExternal"]
+fn machine_external() {
+    dispatch();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nop() {}
+    fn byte(_: u8) {}
+
+    #[test]
+    fn table() {
+        let mut t = Table::new();
+        t.set(Handler::Timer(nop));
+        t.set(Handler::Rx(byte));
+        assert!(t.timer.is_some() && t.rx.is_some() && t.tx.is_none());
+        t.clear(Handler::Rx(byte).source());
+        assert!(t.timer.is_some() && t.rx.is_none());
+
+        assert!(!t.pins_changed(0));
+        assert!(t.pins_changed(0x04));
+        assert!(!t.pins_changed(0x04));
+    }
+
+    #[test]
+    fn received() {
+        let r = Received { rx: Some(b'a'), tx: false };
+        assert!(!r.is_empty() && Received::default().is_empty());
+        let r = r.merge(Received { rx: None, tx: true });
+        assert!(r.rx == Some(b'a') && r.tx);
+        let r = r.merge(Received { rx: Some(b'b'), tx: false });
+        assert!(r.rx == Some(b'b') && r.tx);
+    }
+
+    #[test]
+    fn pending() {
+        pend();
+        pend();
+        assert!(is_pending());
+        unpend();
+        assert!(!is_pending());
+    }
+}
@@ -0,0 +1,470 @@
+//! Preemptive round-robin tasks, with the `kernel` feature.
+//!
+//! Each task is a function running on a [`Stack`] of its own, as if it had
+//! the core to itself. `main` is one too, on the stack it started with:
+//!
+//! ```ignore
+//! static BLINK_STACK: Stack<256> = Stack::new();
+//!
+//! kernel::start();
+//! kernel::spawn(blink, &BLINK_STACK)?;
+//! loop {
+//!     // ...
+//!     kernel::sleep(Duration::from_millis(10));
+//! }
+//! ```
+//!
+//! The switching happens in the `trap-hooks` trap entry, which saves every
+//! register in a [`TrapFrame`] on the way in, and restores from it on the
+//! way out: the kernel copies the frame into the running task's slot, and
+//! the next task's into the frame. A task is switched out when it
+//! [`yield_now`]s, [`sleep`]s or returns (each an `ecall` into the trap
+//! entry), or when a timer tick has passed since it was switched in, so no
+//! task holds the core for more than a tick (about 1.37 ms) while another
+//! is ready. The next is the first ready one after it, in slot order. When
+//! none is, an idle task waits in `wfi`.
+//!
+//! Preemption needs the timer interrupt to reach [`timebase::tick`]
+//! (through [`irq::dispatch`], say). Interrupt handlers run on whichever
+//! task's stack was in use, frame (128 bytes) and all, so every stack needs
+//! room for them on top of the task's own use.
+//!
+//! Don't yield or sleep inside a critical section: the next task would
+//! run with interrupts masked too, and never be preempted. Statics shared
+//! between tasks need critical sections (or atomics), as with ISRs.
+//!
+//! [`irq::dispatch`]: crate::irq::dispatch
+
+use core::cell::{RefCell, UnsafeCell};
+use core::time::Duration;
+
+use critical_section::Mutex;
+
+use crate::atomic::{AtomicBool, Ordering};
+use crate::error::Describe;
+use crate::periodic::duration_to_ticks;
+use crate::power;
+use crate::timebase;
+use crate::trap::TrapFrame;
+
+/// Tasks that can be spawned, besides `main` and the idle task.
+pub const MAX_TASKS: usize = 4;
+
+const MAIN: usize = 0;
+const IDLE: usize = 1;
+const SLOTS: usize = MAX_TASKS + 2;
+
+/// The idle task only waits, but the interrupts it waits for run on its
+/// stack.
+const IDLE_STACK_WORDS: usize = 256;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// Every task slot is taken.
+    Full,
+    /// The stack is some other task's.
+    StackInUse,
+}
+
+impl Describe for Error {
+    fn describe(&self) -> &'static str {
+        match self {
+            Self::Full => "kernel: too many tasks",
+            Self::StackInUse => "kernel: stack in use",
+        }
+    }
+}
+
+/// A task's stack, `N` words. Goes back to being free when its task
+/// returns.
+#[repr(C, align(16))]
+pub struct Stack<const N: usize> {
+    mem: UnsafeCell<[usize; N]>,
+    taken: AtomicBool,
+}
+
+// SAFETY: The memory is only ever touched by the one task it's given to.
+unsafe impl<const N: usize> Sync for Stack<N> {}
+
+impl<const N: usize> Default for Stack<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Stack<N> {
+    pub const fn new() -> Self {
+        Self { mem: UnsafeCell::new([0; N]), taken: AtomicBool::new(false) }
+    }
+
+    /// Claim the stack: its top, 16-byte aligned, as the ABI wants.
+    fn take(&'static self) -> Result<usize, Error> {
+        if self.taken.swap(true, Ordering::AcqRel) {
+            return Err(Error::StackInUse);
+        }
+        Ok((self.mem.get() as usize + N * core::mem::size_of::<usize>()) & !15)
+    }
+}
+
+/// A task, as numbered by [`current`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TaskId(pub usize);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    Ready,
+    /// Until the tick count reaches this.
+    Sleeping(u32),
+    /// Returned, and just waiting to be switched out for good.
+    Exited,
+}
+
+#[derive(Clone, Copy)]
+struct Task {
+    /// Its registers, while it's switched out.
+    frame: TrapFrame,
+    state: State,
+    /// To free when it exits; `main`'s isn't ours.
+    stack: Option<&'static AtomicBool>,
+}
+
+const ZERO: TrapFrame =
+    TrapFrame { ra: 0, t: [0; 7], a: [0; 8], s: [0; 12], sp: 0, gp: 0, tp: 0, mepc: 0 };
+
+/// The task table, and who's running.
+struct Scheduler {
+    tasks: [Option<Task>; SLOTS],
+    current: usize,
+    /// The tick the current task was switched in on.
+    since: u32,
+    /// Traps in progress, so a nested one doesn't switch stacks out from
+    /// under the one it interrupted.
+    depth: u32,
+    started: bool,
+}
+
+impl Scheduler {
+    const fn new() -> Self {
+        let mut tasks = [None; SLOTS];
+        tasks[MAIN] = Some(Task { frame: ZERO, state: State::Ready, stack: None });
+        Self { tasks, current: MAIN, since: 0, depth: 0, started: false }
+    }
+
+    /// A new task, ready to start at `entry` with `arg` in `a0`, in slot
+    /// `slot` if given, else the first free one.
+    fn add(
+        &mut self,
+        slot: Option<usize>,
+        entry: usize,
+        arg: usize,
+        sp: usize,
+        gp: usize,
+        stack: &'static AtomicBool,
+    ) -> Result<TaskId, Error> {
+        let slot = match slot {
+            Some(s) => s,
+            None => (IDLE + 1..SLOTS).find(|&s| self.tasks[s].is_none()).ok_or(Error::Full)?,
+        };
+        let mut frame = TrapFrame { sp, gp, mepc: entry, ..ZERO };
+        frame.a[0] = arg;
+        self.tasks[slot] = Some(Task { frame, state: State::Ready, stack: Some(stack) });
+        Ok(TaskId(slot))
+    }
+
+    fn set_state(&mut self, state: State) {
+        if let Some(t) = &mut self.tasks[self.current] {
+            t.state = state;
+        }
+    }
+
+    /// Save `frame` as the current task's, and load the next's into it.
+    fn switch(&mut self, frame: &mut TrapFrame, now: u32) {
+        if let Some(t) = &mut self.tasks[self.current] {
+            t.frame = *frame;
+            if t.state == State::Exited {
+                if let Some(taken) = t.stack {
+                    taken.store(false, Ordering::Release);
+                }
+                self.tasks[self.current] = None;
+            }
+        }
+        for t in self.tasks.iter_mut().flatten() {
+            if let State::Sleeping(until) = t.state {
+                if now.wrapping_sub(until) as i32 >= 0 {
+                    t.state = State::Ready;
+                }
+            }
+        }
+        // The ones after this, round to itself last.
+        let next = (self.current + 1..=self.current + SLOTS)
+            .map(|s| s % SLOTS)
+            .filter(|&s| s != IDLE)
+            .find(|&s| matches!(self.tasks[s], Some(Task { state: State::Ready, .. })))
+            .unwrap_or(IDLE);
+        if let Some(t) = &self.tasks[next] {
+            *frame = t.frame;
+        }
+        self.current = next;
+        self.since = now;
+    }
+
+    /// A trap has come in: an `ecall` to switch on (returning true), or
+    /// something for the handlers.
+    #[cfg_attr(not(target_os = "none"), allow(dead_code))]
+    fn trap_entry(&mut self, frame: &mut TrapFrame, ecall: bool, now: u32) -> bool {
+        if !self.started {
+            return false;
+        }
+        if !ecall {
+            self.depth += 1;
+            return false;
+        }
+        frame.mepc += 4;
+        if self.depth == 0 {
+            self.switch(frame, now);
+        }
+        true
+    }
+
+    /// A trap is done: switch if the current task has had its tick.
+    #[cfg_attr(not(target_os = "none"), allow(dead_code))]
+    fn trap_exit(&mut self, frame: &mut TrapFrame, now: u32) {
+        if !self.started {
+            return;
+        }
+        self.depth = self.depth.saturating_sub(1);
+        if self.depth == 0 && now != self.since {
+            self.switch(frame, now);
+        }
+    }
+}
+
+static SCHEDULER: Mutex<RefCell<Scheduler>> = Mutex::new(RefCell::new(Scheduler::new()));
+static IDLE_STACK: Stack<IDLE_STACK_WORDS> = Stack::new();
+
+/// Start switching: from here on, `main` is a task like the rest, and
+/// shares the core with those [`spawn`]ed, before or after.
+pub fn start() {
+    let sp = IDLE_STACK.take().unwrap_or(0);
+    critical_section::with(|cs| {
+        let mut s = SCHEDULER.borrow_ref_mut(cs);
+        if s.started {
+            return;
+        }
+        let _ = s.add(Some(IDLE), idle as *const () as usize, 0, sp, gp(), &IDLE_STACK.taken);
+        s.since = timebase::ticks32();
+        s.started = true;
+    });
+}
+
+/// Run `f` as a task on `stack`. When `f` returns, the task ends, and the
+/// stack is free for another.
+pub fn spawn<const N: usize>(f: fn(), stack: &'static Stack<N>) -> Result<TaskId, Error> {
+    let sp = stack.take()?;
+    critical_section::with(|cs| {
+        let mut s = SCHEDULER.borrow_ref_mut(cs);
+        let id = s.add(None, run as *const () as usize, f as usize, sp, gp(), &stack.taken);
+        if id.is_err() {
+            stack.taken.store(false, Ordering::Release);
+        }
+        id
+    })
+}
+
+/// The task calling this.
+pub fn current() -> TaskId {
+    TaskId(critical_section::with(|cs| SCHEDULER.borrow_ref(cs).current))
+}
+
+/// Let the next ready task run, if there is one. Before [`start`], does
+/// nothing.
+pub fn yield_now() {
+    if critical_section::with(|cs| SCHEDULER.borrow_ref(cs).started) {
+        ecall();
+    }
+}
+
+/// Let other tasks run for at least `d` (to the nearest tick). Before
+/// [`start`], waits for the ticks in `wfi`.
+pub fn sleep(d: Duration) {
+    let until = timebase::ticks32().wrapping_add(duration_to_ticks(d));
+    let started = critical_section::with(|cs| {
+        let mut s = SCHEDULER.borrow_ref_mut(cs);
+        if s.started {
+            s.set_state(State::Sleeping(until));
+        }
+        s.started
+    });
+    if started {
+        ecall();
+    } else {
+        while (timebase::ticks32().wrapping_sub(until) as i32) < 0 {
+            power::idle();
+        }
+    }
+}
+
+/// End the calling task. What returning from a task's function does.
+pub fn exit() -> ! {
+    critical_section::with(|cs| SCHEDULER.borrow_ref_mut(cs).set_state(State::Exited));
+    loop {
+        ecall();
+    }
+}
+
+/// Where every spawned task starts, with its function in `a0`.
+extern "C" fn run(f: usize) -> ! {
+    // SAFETY: spawn put a `fn()` there.
+    let f: fn() = unsafe { core::mem::transmute(f) };
+    f();
+    exit()
+}
+
+fn idle() {
+    loop {
+        power::idle();
+    }
+}
+
+#[cfg(target_arch = "riscv32")]
+fn ecall() {
+    // SAFETY: The trap entry switches tasks, and returns past it with every
+    // register as it was.
+    unsafe { core::arch::asm!("ecall") };
+}
+
+#[cfg(not(target_arch = "riscv32"))]
+fn ecall() {}
+
+#[cfg(target_arch = "riscv32")]
+fn gp() -> usize {
+    let gp;
+    // SAFETY: Just reads the register.
+    unsafe { core::arch::asm!("mv {0}, gp", out(reg) gp) };
+    gp
+}
+
+#[cfg(not(target_arch = "riscv32"))]
+fn gp() -> usize {
+    0
+}
+
+/// From the trap entry: switch tasks for an `ecall`, returning whether it
+/// was one, and count the trap in otherwise.
+#[cfg(target_os = "none")]
+pub(crate) fn on_trap_entry(frame: &mut TrapFrame) -> bool {
+    let ecall = riscv::register::mcause::read().bits() == 11;
+    crate::critical::in_interrupt(|cs| {
+        SCHEDULER.borrow_ref_mut(cs).trap_entry(frame, ecall, timebase::ticks32())
+    })
+}
+
+/// From the trap exit: switch tasks if the current one's had its tick.
+#[cfg(target_os = "none")]
+pub(crate) fn on_trap_exit(frame: &mut TrapFrame) {
+    crate::critical::in_interrupt(|cs| {
+        SCHEDULER.borrow_ref_mut(cs).trap_exit(frame, timebase::ticks32())
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static TAKEN: [AtomicBool; 3] = [const { AtomicBool::new(true) }; 3];
+
+    /// A scheduler with tasks in slots 2 and 3, started at 0x200 and 0x300.
+    fn two_tasks() -> Scheduler {
+        let mut s = Scheduler::new();
+        s.add(Some(IDLE), 0x100, 0, 0x1100, 0, &TAKEN[0]).unwrap();
+        assert_eq!(s.add(None, 0x200, 2, 0x2200, 0, &TAKEN[1]), Ok(TaskId(2)));
+        assert_eq!(s.add(None, 0x300, 3, 0x3300, 0, &TAKEN[2]), Ok(TaskId(3)));
+        s
+    }
+
+    #[test]
+    fn round_robin() {
+        let mut s = two_tasks();
+        let mut f = TrapFrame { mepc: 0x1234, ..ZERO };
+        s.switch(&mut f, 1);
+        assert_eq!((s.current, f.mepc, f.sp, f.a[0]), (2, 0x200, 0x2200, 2));
+        f.mepc = 0x204;
+        s.switch(&mut f, 2);
+        assert_eq!((s.current, f.mepc), (3, 0x300));
+        s.switch(&mut f, 3);
+        // Back to main, where it left off.
+        assert_eq!((s.current, f.mepc), (MAIN, 0x1234));
+        s.switch(&mut f, 4);
+        assert_eq!((s.current, f.mepc), (2, 0x204));
+    }
+
+    #[test]
+    fn sleep_idle_exit() {
+        let mut s = two_tasks();
+        let mut f = ZERO;
+        // main sleeps until 10, then 2 exits, then 3 sleeps until 5.
+        s.set_state(State::Sleeping(10));
+        s.switch(&mut f, 0);
+        assert_eq!(s.current, 2);
+        s.set_state(State::Exited);
+        s.switch(&mut f, 1);
+        assert_eq!(s.current, 3);
+        assert!(s.tasks[2].is_none());
+        assert!(!TAKEN[1].load(Ordering::Acquire));
+        s.set_state(State::Sleeping(5));
+        s.switch(&mut f, 2);
+        assert_eq!((s.current, f.mepc), (IDLE, 0x100));
+        s.switch(&mut f, 4);
+        assert_eq!(s.current, IDLE);
+        s.switch(&mut f, 5);
+        assert_eq!(s.current, 3);
+        // Alone, it keeps going.
+        s.switch(&mut f, 6);
+        assert_eq!(s.current, 3);
+        s.switch(&mut f, 10);
+        assert_eq!(s.current, MAIN);
+    }
+
+    #[test]
+    fn traps() {
+        let mut s = two_tasks();
+        let mut f = TrapFrame { mepc: 0x1000, ..ZERO };
+        // Not started: hands off.
+        assert!(!s.trap_entry(&mut f, true, 0));
+        s.started = true;
+
+        // An interrupt, and another nested in it, a tick later: only the
+        // outer one switches.
+        assert!(!s.trap_entry(&mut f, false, 1));
+        assert!(!s.trap_entry(&mut f, false, 1));
+        s.trap_exit(&mut f, 1);
+        assert_eq!(s.current, MAIN);
+        s.trap_exit(&mut f, 1);
+        assert_eq!((s.current, f.mepc), (2, 0x200));
+
+        // Within the tick, no switch; an ecall switches regardless.
+        s.trap_entry(&mut f, false, 1);
+        s.trap_exit(&mut f, 1);
+        assert_eq!(s.current, 2);
+        assert!(s.trap_entry(&mut f, true, 1));
+        assert_eq!(s.current, 3);
+        assert_eq!(s.tasks[2].unwrap().frame.mepc, 0x204);
+    }
+
+    #[test]
+    fn full() {
+        let mut s = two_tasks();
+        s.add(None, 0, 0, 0, 0, &TAKEN[0]).unwrap();
+        s.add(None, 0, 0, 0, 0, &TAKEN[0]).unwrap();
+        assert_eq!(s.add(None, 0, 0, 0, 0, &TAKEN[0]), Err(Error::Full));
+    }
+
+    #[test]
+    fn stacks() {
+        static STACK: Stack<10> = Stack::new();
+        let top = STACK.take().unwrap();
+        assert_eq!(top % 16, 0);
+        assert!(top <= STACK.mem.get() as usize + 10 * core::mem::size_of::<usize>());
+        assert_eq!(STACK.take(), Err(Error::StackInUse));
+    }
+}
@@ -1,5 +1,91 @@
 #![no_std]
 
+pub mod atomic;
+pub mod backtrace;
+pub mod banner;
+pub mod board;
+pub mod bridge;
+pub mod caps;
+pub mod channel;
+pub mod checksum;
+pub mod cobs;
+pub mod console;
+pub mod critical;
+pub mod cycles;
+#[cfg(feature = "defmt")]
+pub mod defmt_uart;
+#[cfg(feature = "embedded-hal")]
+pub mod ehal;
+#[cfg(feature = "embedded-hal-nb")]
+pub mod ehal_nb;
+#[cfg(feature = "embedded-io")]
+pub mod eio;
+#[cfg(feature = "embassy-time")]
+pub mod embassy;
+pub mod emulate;
+pub mod error;
+pub mod events;
+pub mod executor;
+pub mod expr;
+pub mod fault;
+pub mod fixed;
+#[cfg(feature = "graphics")]
+pub mod graphics;
+pub mod guard;
+pub mod hal;
+pub mod heap;
+pub mod hex;
+pub mod input;
+pub mod io_addrs;
+pub mod io_map;
+pub mod irq;
+#[cfg(feature = "kernel")]
+pub mod kernel;
+#[cfg(feature = "log")]
+pub mod logger;
+pub mod mem;
+pub mod midi;
+#[cfg(feature = "minimal")]
+pub mod minimal;
+#[cfg(feature = "fugit")]
+pub mod monotonic;
+pub mod num;
+pub mod pac;
+pub mod panic;
+pub mod periodic;
+#[cfg(feature = "polled")]
+pub mod polled;
+pub mod pool;
+pub mod power;
+pub mod prelude;
+pub mod reload;
+pub mod reset;
+#[cfg(feature = "rtic")]
+pub mod rtic;
+pub mod rtc;
+pub mod seg7;
+pub mod shell;
+pub mod signature;
+pub mod sim;
+pub mod snapshot;
+pub mod soc;
+pub mod softmath;
+pub mod spsc;
+pub mod ssd1306;
+pub mod stack;
+pub mod stimulus;
+pub mod tasks;
+pub mod term;
+pub mod testing;
+pub mod timebase;
+pub mod timeout;
+pub mod trap;
+pub mod vm;
+pub mod watchdog;
+
+pub use riscv_rt::{entry, pre_init};
+pub use sentinel_rt_macros::{interrupt, sentinel_test};
+
 pub fn add(left: usize, right: usize) -> usize {
     left + right
 }
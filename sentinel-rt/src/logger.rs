@@ -0,0 +1,139 @@
+//! A `log` backend, with the `log` feature.
+//!
+//! Library crates log through the `log` facade, and print nothing until
+//! the application installs a logger. [`SerialLogger`] prints each message
+//! as a line, stamped with the milliseconds since boot:
+//!
+//! ```ignore
+//! static LOGGER: SerialLogger = SerialLogger::new(logger::polled);
+//!
+//! LOGGER.init(LevelFilter::Info).unwrap();
+//! log::info!("{} samples", n);
+//! ```
+//!
+//! ```text
+//!     1234 INFO  app: 16 samples
+//! ```
+//!
+//! [`polled`] writes to the console UART with the polled driver, which
+//! works anywhere, ISRs included, but waits for each byte to go out, and
+//! mustn't be mixed with a [`Port`](crate::hal::serial::Port) driving the
+//! same UART. With a `Port`, log through it instead:
+//!
+//! ```ignore
+//! static LOGGER: SerialLogger = SerialLogger::new(|b| CONSOLE.write_all(b));
+//! ```
+//!
+//! The level given to [`init`](SerialLogger::init) filters at run time.
+//! The `log-max-level-*` features filter at compile time, so messages above
+//! the level (and their formatting code) aren't built at all, in every
+//! crate.
+//!
+//! Messages logged from an ISR can land in the middle of a line the main
+//! loop was printing.
+
+use core::fmt::{self, Write};
+
+use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+use crate::hal::serial::Serial;
+use crate::io_addrs;
+use crate::timebase;
+
+/// Where log lines go, a piece at a time.
+pub type Output = fn(&[u8]);
+
+/// Prints `log` messages through an [`Output`].
+pub struct SerialLogger {
+    output: Output,
+}
+
+impl SerialLogger {
+    pub const fn new(output: Output) -> Self {
+        Self { output }
+    }
+
+    /// Install this as `log`'s logger, passing messages up to `level`.
+    /// Fails if a logger is already installed.
+    pub fn init(&'static self, level: LevelFilter) -> Result<(), SetLoggerError> {
+        critical_section::with(|_| {
+            // SAFETY: RV32I has no atomic compare-and-swap for set_logger;
+            // in the critical section, nothing else can be setting the
+            // logger or level at the same time.
+            unsafe {
+                log::set_logger_racy(self)?;
+                log::set_max_level_racy(level);
+            }
+            Ok(())
+        })
+    }
+}
+
+struct Writer(Output);
+
+impl Write for Writer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        (self.0)(s.as_bytes());
+        Ok(())
+    }
+}
+
+impl Log for SerialLogger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let _ = write!(
+            Writer(self.output),
+            "{:8} {:<5} {}: {}\r\n",
+            timebase::uptime_ms(),
+            record.level(),
+            record.target(),
+            record.args()
+        );
+    }
+
+    fn flush(&self) {}
+}
+
+/// Write `bytes` to the console UART, polled. Does nothing if the SoC
+/// hasn't been brought up.
+pub fn polled(bytes: &[u8]) {
+    if let Some(bases) = io_addrs::detected() {
+        Serial::new(bases.serial).write_bytes(bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::RefCell;
+    use critical_section::Mutex;
+    use heapless::Vec;
+
+    static LINES: Mutex<RefCell<Vec<u8, 256>>> = Mutex::new(RefCell::new(Vec::new()));
+    static LOGGER: SerialLogger = SerialLogger::new(|b| {
+        critical_section::with(|cs| LINES.borrow_ref_mut(cs).extend_from_slice(b).unwrap())
+    });
+
+    #[test]
+    fn lines() {
+        LOGGER.init(LevelFilter::Info).unwrap();
+        assert!(LOGGER.init(LevelFilter::Info).is_err());
+        log::info!("{} samples", 16);
+        log::debug!("left out");
+        log::warn!(target: "adc", "clipped");
+
+        let lines = critical_section::with(|cs| LINES.borrow_ref(cs).clone());
+        let lines = core::str::from_utf8(&lines).unwrap();
+        let mut lines = lines.split_terminator("\r\n");
+        // After the timestamp, which depends on what else has ticked.
+        assert_eq!(&lines.next().unwrap()[8..], " INFO  sentinel_rt::logger::tests: 16 samples");
+        assert_eq!(&lines.next().unwrap()[8..], " WARN  adc: clipped");
+        assert_eq!(lines.next(), None);
+    }
+}
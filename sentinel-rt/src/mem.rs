@@ -0,0 +1,90 @@
+//! Memory regions, as laid out by `link.x` and `sentinel.x`.
+//!
+//! There's no linker script on the host, so every region is empty there;
+//! this keeps host builds of firmware (clippy, tests) linking.
+
+use core::ops::Range;
+
+#[cfg(target_os = "none")]
+macro_rules! linker_range {
+    ($start:ident, $end:ident) => {{
+        extern "C" {
+            static $start: u8;
+            static $end: u8;
+        }
+
+        // Only the addresses of the linker symbols are taken, never the
+        // values.
+        (core::ptr::addr_of!($start) as usize)..(core::ptr::addr_of!($end) as usize)
+    }};
+}
+
+#[cfg(not(target_os = "none"))]
+macro_rules! linker_range {
+    ($start:ident, $end:ident) => {
+        0..0
+    };
+}
+
+/// Code and read-only data. With everything in RAM, `.data`'s load address
+/// immediately follows `.rodata`.
+pub fn text() -> Range<usize> {
+    linker_range!(_stext, _sidata)
+}
+
+/// Initialized data.
+pub fn data() -> Range<usize> {
+    linker_range!(_sdata, _edata)
+}
+
+/// Zero-initialized data.
+pub fn bss() -> Range<usize> {
+    linker_range!(_sbss, _ebss)
+}
+
+/// The heap, `_heap_size` bytes after `.bss` and `.noinit`. See
+/// [`crate::heap`].
+pub fn heap() -> Range<usize> {
+    linker_range!(_sheap, _eheap)
+}
+
+/// The stack, lowest address first.
+pub fn stack() -> Range<usize> {
+    linker_range!(_estack, _sstack)
+}
+
+/// The bottom `_stack_guard_size` bytes of the stack. See
+/// [`crate::guard`].
+pub fn stack_guard() -> Range<usize> {
+    linker_range!(_sstack_guard, _estack_guard)
+}
+
+/// The riscv-arch-test signature area. See [`crate::signature`].
+pub fn signature() -> Range<usize> {
+    linker_range!(begin_signature, end_signature)
+}
+
+/// Statics that start-up code neither zeroes nor initializes. Place data
+/// here with `#[link_section = ".noinit"]`; it survives soft resets.
+pub fn noinit() -> Range<usize> {
+    linker_range!(_snoinit, _enoinit)
+}
+
+/// The snapshot area at the end of `.noinit`. See [`crate::snapshot`].
+pub fn snapshot() -> Range<usize> {
+    linker_range!(_ssnapshot, _esnapshot)
+}
+
+/// Where [`crate::reload`] loads application images. Empty unless the
+/// device linker script sets `_sreload` and `_ereload`.
+pub fn reload() -> Range<usize> {
+    linker_range!(_sreload, _ereload)
+}
+
+pub(crate) fn snapshot_table() -> Range<usize> {
+    linker_range!(__ssnapshot_table, __esnapshot_table)
+}
+
+pub(crate) fn test_table() -> Range<usize> {
+    linker_range!(__stest_table, __etest_table)
+}
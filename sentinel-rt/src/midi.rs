@@ -0,0 +1,130 @@
+//! MIDI byte-stream parsing.
+//!
+//! Feed each byte received from the UART (at 31250 baud) to a [`Parser`],
+//! and it hands back a [`Message`] whenever one is complete. Running status
+//! is supported, real-time bytes (clock, start/stop, active sensing) may
+//! appear anywhere without disturbing a message in progress, and system
+//! exclusive and other system messages are skipped.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Message {
+    /// A note-on with velocity 0 is reported as a `NoteOff` with velocity 0,
+    /// as the spec intends.
+    NoteOff { channel: u8, note: u8, velocity: u8 },
+    NoteOn { channel: u8, note: u8, velocity: u8 },
+    ControlChange { channel: u8, control: u8, value: u8 },
+    ProgramChange { channel: u8, program: u8 },
+    /// 14 bits, centered on `0x2000`.
+    PitchBend { channel: u8, value: u16 },
+}
+
+/// Control change number for "all notes off".
+pub const ALL_NOTES_OFF: u8 = 123;
+
+pub struct Parser {
+    /// Current (running) channel status, or 0 if none.
+    status: u8,
+    data: [u8; 2],
+    len: u8,
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Parser {
+    pub const fn new() -> Self {
+        Self {
+            status: 0,
+            data: [0; 2],
+            len: 0,
+        }
+    }
+
+    pub fn feed(&mut self, b: u8) -> Option<Message> {
+        match b {
+            // Real-time: single bytes that can interrupt anything.
+            0xf8..=0xff => return None,
+            // System common and exclusive: cancel running status. Their
+            // data bytes are then ignored, for lack of a status.
+            0xf0..=0xf7 => {
+                self.status = 0;
+                return None;
+            }
+            0x80..=0xef => {
+                self.status = b;
+                self.len = 0;
+                return None;
+            }
+            _ => {}
+        }
+
+        if self.status == 0 {
+            return None;
+        }
+
+        self.data[self.len as usize] = b;
+        self.len += 1;
+
+        let kind = self.status & 0xf0;
+        let needed = if kind == 0xc0 || kind == 0xd0 { 1 } else { 2 };
+        if self.len < needed {
+            return None;
+        }
+        // Keep the status for the next message.
+        self.len = 0;
+
+        let channel = self.status & 0x0f;
+        let [d0, d1] = self.data;
+        match kind {
+            0x80 => Some(Message::NoteOff { channel, note: d0, velocity: d1 }),
+            0x90 if d1 == 0 => {
+                Some(Message::NoteOff { channel, note: d0, velocity: 0 })
+            }
+            0x90 => Some(Message::NoteOn { channel, note: d0, velocity: d1 }),
+            0xb0 => Some(Message::ControlChange { channel, control: d0, value: d1 }),
+            0xc0 => Some(Message::ProgramChange { channel, program: d0 }),
+            0xe0 => Some(Message::PitchBend {
+                channel,
+                value: (d1 as u16) << 7 | d0 as u16,
+            }),
+            // Polyphonic and channel pressure.
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use heapless::Vec;
+
+    use super::*;
+
+    fn parse(bytes: &[u8]) -> Vec<Message, 8> {
+        let mut p = Parser::new();
+        bytes.iter().filter_map(|b| p.feed(*b)).collect()
+    }
+
+    #[test]
+    fn running_status() {
+        let msgs = parse(&[0x91, 60, 100, 64, 0, 0xb1, 7, 127]);
+        assert_eq!(msgs, [
+            Message::NoteOn { channel: 1, note: 60, velocity: 100 },
+            Message::NoteOff { channel: 1, note: 64, velocity: 0 },
+            Message::ControlChange { channel: 1, control: 7, value: 127 },
+        ]);
+    }
+
+    #[test]
+    fn realtime_and_sysex() {
+        let msgs = parse(&[0x90, 60, 0xf8, 100, 0xf0, 1, 2, 3, 0xf7, 62, 0xc2,
+                           5, 0xe0, 0, 0x40]);
+        assert_eq!(msgs, [
+            Message::NoteOn { channel: 0, note: 60, velocity: 100 },
+            Message::ProgramChange { channel: 2, program: 5 },
+            Message::PitchBend { channel: 0, value: 0x2000 },
+        ]);
+    }
+}
@@ -0,0 +1,124 @@
+//! A smaller start-up and trap entry than riscv-rt's, with the `minimal`
+//! feature.
+//!
+//! riscv-rt's start-up is written for any number of harts and any layout
+//! of memory, and its trap entry looks each cause up in a table of
+//! handlers. The AttoSoC has one hart, everything in block RAM, and one
+//! interrupt line, so this does only what's left:
+//!
+//! * `_sentinel_start`: set `gp` and `sp`, mask interrupts, call
+//!   `__pre_init` (so `#[pre_init]` and `pre-init-detect` still work),
+//!   zero `.bss`, point `mtvec` at `_start_trap`, and call `main`
+//!   (`#[entry]`, as usual) with the `a0`-`a2` it started with.
+//! * `_sentinel_trap`: save the registers riscv-rt's `TrapFrame` holds,
+//!   and call `MachineExternal` for an interrupt, or
+//!   `ExceptionHandler(frame)` for an exception. That's 20 instructions
+//!   from an interrupt to `MachineExternal`, each costing several cycles
+//!   of Sentinel's microcode; riscv-rt's table lookup takes 42.
+//!
+//! `.data` isn't copied: `minimal.x` links it in place, so its initial
+//! values are loaded with the rest of the image and there's no second
+//! copy of them taking up RAM. As with riscv-rt on the AttoSoC, a
+//! [`soft_reset`](crate::reset::soft_reset) doesn't restore them.
+//!
+//! All told, `examples/attosoc.rs` comes out about 600 bytes smaller.
+//!
+//! The feature has `build.rs` point `rt.x` at `minimal.x` instead of
+//! riscv-rt's `link.x`, so a device script that does `INCLUDE rt.x` (as
+//! `examples/device.x` does) needs no changes. riscv-rt's per-cause
+//! handlers (`MachineTimer`, `DefaultHandler`, `IllegalInstruction`, ...)
+//! and hooks (`_setup_interrupts`, `_mp_hook`) are never called;
+//! `MachineExternal` and `ExceptionHandler` are. With `trap-hooks`, its
+//! entry replaces `_sentinel_trap` and dispatches the same way.
+
+#[cfg(target_os = "none")]
+mod start {
+    use riscv::register::mcause;
+    use riscv_rt::TrapFrame;
+
+    core::arch::global_asm!(
+        ".section .text.sentinel.start, \"ax\"",
+        ".global _sentinel_start",
+        ".global _sentinel_reset",
+        "_sentinel_start:",
+        "_sentinel_reset:",
+        ".option push",
+        ".option norelax",
+        "la gp, __global_pointer$",
+        ".option pop",
+        "la sp, _stack_start",
+        // No frame to walk back to.
+        "li s0, 0",
+        "csrw mie, zero",
+        // main's arguments (a reload's handoff, say), kept where neither
+        // __pre_init nor the loop below clobbers them. Not s0-s2, as
+        // riscv-rt does: s0 is the frame pointer.
+        "mv s1, a0",
+        "mv s2, a1",
+        "mv s3, a2",
+        "jal ra, __pre_init",
+        "la t0, _sbss",
+        "la t1, _ebss",
+        "1:",
+        "bgeu t0, t1, 2f",
+        "sw zero, 0(t0)",
+        "addi t0, t0, 4",
+        "j 1b",
+        "2:",
+        "la t0, _start_trap",
+        "csrw mtvec, t0",
+        "mv a0, s1",
+        "mv a1, s2",
+        "mv a2, s3",
+        "jal ra, main",
+    );
+
+    // Frame offsets as riscv-rt's TrapFrame: ra, t0-t6, a0-a7.
+    core::arch::global_asm!(
+        ".section .text.sentinel.trap, \"ax\"",
+        ".balign 4",
+        ".global _sentinel_trap",
+        "_sentinel_trap:",
+        "addi sp, sp, -64",
+        "sw ra, 0(sp)",
+        "sw t0, 4(sp)", "sw t1, 8(sp)", "sw t2, 12(sp)", "sw t3, 16(sp)",
+        "sw t4, 20(sp)", "sw t5, 24(sp)", "sw t6, 28(sp)",
+        "sw a0, 32(sp)", "sw a1, 36(sp)", "sw a2, 40(sp)", "sw a3, 44(sp)",
+        "sw a4, 48(sp)", "sw a5, 52(sp)", "sw a6, 56(sp)", "sw a7, 60(sp)",
+        "csrr t0, mcause",
+        "bltz t0, 1f",
+        "mv a0, sp",
+        "jal ra, ExceptionHandler",
+        "j 2f",
+        "1:",
+        "jal ra, MachineExternal",
+        "2:",
+        "lw ra, 0(sp)",
+        "lw t0, 4(sp)", "lw t1, 8(sp)", "lw t2, 12(sp)", "lw t3, 16(sp)",
+        "lw t4, 20(sp)", "lw t5, 24(sp)", "lw t6, 28(sp)",
+        "lw a0, 32(sp)", "lw a1, 36(sp)", "lw a2, 40(sp)", "lw a3, 44(sp)",
+        "lw a4, 48(sp)", "lw a5, 52(sp)", "lw a6, 56(sp)", "lw a7, 60(sp)",
+        "addi sp, sp, 64",
+        "mret",
+    );
+
+    /// The dispatch above, for the `trap-hooks` entry, which calls this in
+    /// place of riscv-rt's `_start_trap_rust`.
+    #[export_name = "_sentinel_trap_rust"]
+    extern "C" fn trap_rust(frame: &TrapFrame) {
+        extern "C" {
+            fn ExceptionHandler(frame: &TrapFrame);
+            fn MachineExternal();
+        }
+
+        // SAFETY: Both are the application's (or minimal.x's defaults),
+        // and expect to be called from a trap.
+        unsafe {
+            if mcause::read().is_interrupt() {
+                MachineExternal();
+            } else {
+                ExceptionHandler(frame);
+            }
+        }
+    }
+}
@@ -0,0 +1,74 @@
+//! fugit time on the 64-bit [`timebase`], with the `fugit` feature.
+//!
+//! [`Instant`] and [`Duration`] are fugit's, counting timebase ticks: the
+//! tick rate, [`CLK_HZ`] / [`CYCLES_PER_TICK`], is in their type, so
+//! fugit converts to and from seconds and milliseconds at compile time:
+//!
+//! ```ignore
+//! use sentinel_rt::monotonic::{ExtU64, Monotonic};
+//!
+//! let deadline = Monotonic::now() + 200.millis();
+//! while Monotonic::now() < deadline {
+//!     // ...
+//! }
+//! ```
+//!
+//! Instants are 64 bits and never wrap, so they compare directly, and can
+//! be handed between drivers and schedulers as a shared notion of time.
+//! Their resolution is a tick, about 1.37 ms. [`rtic`](crate::rtic)'s
+//! instants are the low 32 bits of the same count.
+//!
+//! The count only moves when something calls [`timebase::tick`].
+
+pub use fugit::{ExtU64, ExtU64Ceil};
+
+use crate::timebase::{self, CLK_HZ, CYCLES_PER_TICK};
+
+/// A point in time, in timebase ticks since boot.
+pub type Instant = fugit::Instant<u64, CYCLES_PER_TICK, CLK_HZ>;
+/// A span of timebase ticks.
+pub type Duration = fugit::Duration<u64, CYCLES_PER_TICK, CLK_HZ>;
+
+/// The timebase as a clock.
+pub struct Monotonic;
+
+impl Monotonic {
+    /// Now, to the tick.
+    pub fn now() -> Instant {
+        Instant::from_ticks(timebase::ticks())
+    }
+
+    /// How long it's been since `then`.
+    pub fn elapsed(then: Instant) -> Duration {
+        Self::now() - then
+    }
+
+    /// Whether `deadline` has come.
+    pub fn reached(deadline: Instant) -> bool {
+        Self::now() >= deadline
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn units() {
+        // 732.42 ticks a second.
+        let d: Duration = 1.secs();
+        assert_eq!(d.ticks(), 732);
+        assert_eq!(Duration::from_ticks(3).to_micros(), 4096);
+        let t = Instant::from_ticks(u32::MAX as u64) + 200.millis();
+        assert_eq!(t.ticks(), u32::MAX as u64 + 146);
+    }
+
+    #[test]
+    fn clock() {
+        let start = Monotonic::now();
+        let second: Duration = 1.secs();
+        assert!(Monotonic::reached(start));
+        assert!(!Monotonic::reached(start + second));
+        assert!(Monotonic::elapsed(start) < second);
+    }
+}
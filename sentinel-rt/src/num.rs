@@ -0,0 +1,170 @@
+//! Number input: parsing, and reading one from a terminal.
+//!
+//! [`parse`] takes decimal, `0x` hex or `0b` binary, optionally negative,
+//! into whichever integer type the caller wants, and says what was wrong
+//! if it can't:
+//!
+//! ```ignore
+//! let addr: u32 = num::parse("0x80000000")?; // Ok(0x8000_0000)
+//! let step: i8 = num::parse("-0b101")?;      // Ok(-5)
+//! let led: u8 = num::parse("300")?;          // Err(OutOfRange)
+//! ```
+//!
+//! [`Reader`] reads one from the UART a byte at a time, with echo and
+//! backspace, for prompts that want a number rather than a command line.
+
+use crate::error::Describe;
+use crate::shell::Output;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// Nothing (or only a sign or prefix) was given.
+    Empty,
+    /// A character that isn't a digit of the number's base.
+    BadDigit,
+    /// Doesn't fit in the type asked for.
+    OutOfRange,
+}
+
+impl Describe for Error {
+    fn describe(&self) -> &'static str {
+        match self {
+            Self::Empty => "num: no digits",
+            Self::BadDigit => "num: bad digit",
+            Self::OutOfRange => "num: out of range",
+        }
+    }
+}
+
+/// Parse `s` (decimal, `0x` hex or `0b` binary, with an optional `-`) as a
+/// `T`.
+pub fn parse<T: TryFrom<u64> + TryFrom<i64>>(s: &str) -> Result<T, Error> {
+    let (neg, s) = match s.strip_prefix('-') {
+        Some(s) => (true, s),
+        None => (false, s),
+    };
+    let (radix, digits) = match s.get(..2) {
+        Some("0x" | "0X") => (16, &s[2..]),
+        Some("0b" | "0B") => (2, &s[2..]),
+        _ => (10, s),
+    };
+    if digits.is_empty() {
+        return Err(Error::Empty);
+    }
+
+    let mut mag: u64 = 0;
+    for c in digits.chars() {
+        let d = c.to_digit(radix).ok_or(Error::BadDigit)?;
+        mag = mag.checked_mul(radix as u64)
+            .and_then(|m| m.checked_add(d as u64))
+            .ok_or(Error::OutOfRange)?;
+    }
+
+    if neg {
+        let val = 0i64.checked_sub_unsigned(mag).ok_or(Error::OutOfRange)?;
+        T::try_from(val).map_err(|_| Error::OutOfRange)
+    } else {
+        T::try_from(mag).map_err(|_| Error::OutOfRange)
+    }
+}
+
+/// Reads a number typed at a terminal. `N` is the most characters
+/// accepted; `0b` and a sign count.
+pub struct Reader<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> Default for Reader<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Reader<N> {
+    pub const fn new() -> Self {
+        Self { buf: [0; N], len: 0 }
+    }
+
+    /// Handle one received byte, echoing it to `out`. CR or LF ends the
+    /// number, and returns it (or what's wrong with it); the reader is
+    /// then ready for the next one. Backspace works; any other character
+    /// that can't be part of a number, or doesn't fit, is refused with a
+    /// bell.
+    pub fn feed<T>(&mut self, b: u8, out: &mut dyn Output) -> Option<Result<T, Error>>
+    where
+        T: TryFrom<u64> + TryFrom<i64>,
+    {
+        match b {
+            b'\r' | b'\n' => {
+                out.write_bytes(b"\r\n");
+                let len = core::mem::take(&mut self.len);
+                // Only ASCII is ever stored.
+                let s = core::str::from_utf8(&self.buf[..len]).unwrap_or("");
+                return Some(parse(s));
+            }
+            0x08 | 0x7f if self.len > 0 => {
+                self.len -= 1;
+                out.write_bytes(b"\x08 \x08");
+            }
+            b'-' | b'0'..=b'9' | b'a'..=b'f' | b'A'..=b'F' | b'x' | b'X' if self.len < N => {
+                self.buf[self.len] = b;
+                self.len += 1;
+                out.write_bytes(&[b]);
+            }
+            _ => out.write_bytes(b"\x07"),
+        }
+        None
+    }
+
+    /// Forget a partly typed number.
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use heapless::Vec;
+
+    use super::*;
+
+    impl Output for Vec<u8, 64> {
+        fn write_bytes(&mut self, bytes: &[u8]) {
+            let _ = self.extend_from_slice(bytes);
+        }
+    }
+
+    #[test]
+    fn bases_and_ranges() {
+        assert_eq!(parse::<u32>("1234"), Ok(1234));
+        assert_eq!(parse::<u32>("0xDEADbeef"), Ok(0xdead_beef));
+        assert_eq!(parse::<u8>("0b1010"), Ok(10));
+        assert_eq!(parse::<i8>("-128"), Ok(-128));
+        assert_eq!(parse::<i64>("-0x8000000000000000"), Ok(i64::MIN));
+        assert_eq!(parse::<u64>("18446744073709551615"), Ok(u64::MAX));
+
+        assert_eq!(parse::<i8>("128"), Err(Error::OutOfRange));
+        assert_eq!(parse::<u8>("-1"), Err(Error::OutOfRange));
+        assert_eq!(parse::<u64>("18446744073709551616"), Err(Error::OutOfRange));
+        assert_eq!(parse::<u32>("0b102"), Err(Error::BadDigit));
+        assert_eq!(parse::<u32>("12 "), Err(Error::BadDigit));
+        assert_eq!(parse::<u32>("-0x"), Err(Error::Empty));
+        assert_eq!(parse::<u32>(""), Err(Error::Empty));
+    }
+
+    #[test]
+    fn reader() {
+        let mut r = Reader::<3>::new();
+        let mut out = Vec::<u8, 64>::new();
+        let mut result = None;
+        for b in b"12q3\x7f45\r" {
+            result = r.feed::<u16>(*b, &mut out).or(result);
+        }
+        // The 5 doesn't fit.
+        assert_eq!(result, Some(Ok(124)));
+        assert_eq!(out, b"12\x073\x08 \x084\x07\r\n".as_slice());
+
+        assert_eq!(r.feed::<u16>(b'\r', &mut out), Some(Err(Error::Empty)));
+    }
+}
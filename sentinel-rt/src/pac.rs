@@ -0,0 +1,271 @@
+//! Register-level access to the AttoSoC peripherals.
+//!
+//! A handwritten peripheral access layer, in the style of `svd2rust`
+//! output: each peripheral is a `RegisterBlock` of [`Reg`]s, read and
+//! written through typed values with field accessors, rather than through
+//! raw offsets and bit masks:
+//!
+//! ```ignore
+//! let p = pac::Peripherals::take().unwrap();
+//! let irq = p.serial.irq.read();
+//! if irq.rx() {
+//!     let b = p.serial.rxtx.read().bits();
+//!     p.serial.rxtx.write(|w| w.set_bits(b));
+//! }
+//! p.gpio.oe.write(|w| w.set_bit(0, true));
+//! ```
+//!
+//! Registers are a byte each, 4 bytes apart, on both buses. Base addresses
+//! come from [`io_addrs`], so [`Peripherals::take`] only works after
+//! detection (e.g. [`io_addrs::detect`]). It can't be had as well as
+//! the [`Soc`](crate::soc::Soc) drivers.
+//!
+//! The [`hal`](crate::hal) drivers are the usual way in; this is for code
+//! that needs the registers themselves, and doesn't go through
+//! [`power`](crate::power)'s checks.
+
+use core::cell::UnsafeCell;
+use core::marker::PhantomData;
+use core::ops::Deref;
+use core::ptr::{read_volatile, write_volatile};
+
+use crate::{io_addrs, soc};
+
+/// An 8-bit register, read as `R` and written as `W`. Registers without a
+/// `W` that converts to `u8` are read-only.
+#[repr(C)]
+pub struct Reg<R, W> {
+    value: UnsafeCell<u8>,
+    _pad: [u8; 3],
+    _types: PhantomData<(R, W)>,
+}
+
+impl<R: From<u8>, W> Reg<R, W> {
+    pub fn read(&self) -> R {
+        // SAFETY: A Reg only exists inside a RegisterBlock at a valid
+        // peripheral address.
+        R::from(unsafe { read_volatile(self.value.get()) })
+    }
+}
+
+impl<R, W: Default + Into<u8>> Reg<R, W> {
+    /// Write the value `f` builds, starting from all zeros.
+    pub fn write(&self, f: impl FnOnce(&mut W) -> &mut W) {
+        let mut w = W::default();
+        f(&mut w);
+        // SAFETY: See read.
+        unsafe { write_volatile(self.value.get(), w.into()) }
+    }
+}
+
+impl<R: From<u8>, W: Default + From<u8> + Into<u8>> Reg<R, W> {
+    /// Read, change with `f`, and write back.
+    pub fn modify(&self, f: impl for<'w> FnOnce(&R, &'w mut W) -> &'w mut W) {
+        // SAFETY: See read.
+        let val = unsafe { read_volatile(self.value.get()) };
+        let mut w = W::from(val);
+        f(&R::from(val), &mut w);
+        unsafe { write_volatile(self.value.get(), w.into()) }
+    }
+}
+
+/// Eight independent bits: LEDs, pins, or a data byte.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Bits(u8);
+
+impl Bits {
+    pub fn bits(&self) -> u8 {
+        self.0
+    }
+
+    pub fn bit(&self, n: u8) -> bool {
+        (self.0 >> n) & 1 != 0
+    }
+
+    pub fn set_bits(&mut self, val: u8) -> &mut Self {
+        self.0 = val;
+        self
+    }
+
+    pub fn set_bit(&mut self, n: u8, on: bool) -> &mut Self {
+        self.0 = (self.0 & !(1 << n)) | ((on as u8) << n);
+        self
+    }
+}
+
+impl From<u8> for Bits {
+    fn from(val: u8) -> Self {
+        Self(val)
+    }
+}
+
+impl From<Bits> for u8 {
+    fn from(val: Bits) -> Self {
+        val.0
+    }
+}
+
+/// Marks a register read-only.
+pub enum ReadOnly {}
+
+pub mod gpio {
+    use super::{Bits, Reg};
+
+    #[repr(C)]
+    pub struct RegisterBlock {
+        pub leds: Reg<Bits, Bits>,
+        /// Pin levels when read; output levels when written.
+        pub inout: Reg<Bits, Bits>,
+        /// Output enables: a set bit makes that pin an output.
+        pub oe: Reg<Bits, Bits>,
+    }
+}
+
+pub mod timer {
+    use super::{ReadOnly, Reg};
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct Irq(u8);
+
+    impl Irq {
+        /// A tick has passed. Reading the register clears it.
+        pub fn tick(&self) -> bool {
+            self.0 & 0x01 != 0
+        }
+    }
+
+    impl From<u8> for Irq {
+        fn from(val: u8) -> Self {
+            Self(val)
+        }
+    }
+
+    #[repr(C)]
+    pub struct RegisterBlock {
+        pub irq: Reg<Irq, ReadOnly>,
+    }
+}
+
+pub mod serial {
+    use super::{Bits, ReadOnly, Reg};
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct Irq(u8);
+
+    impl Irq {
+        /// A byte has been received.
+        pub fn rx(&self) -> bool {
+            self.0 & 0x01 != 0
+        }
+
+        /// A byte has been sent.
+        pub fn tx(&self) -> bool {
+            self.0 & 0x02 != 0
+        }
+    }
+
+    impl From<u8> for Irq {
+        fn from(val: u8) -> Self {
+            Self(val)
+        }
+    }
+
+    #[repr(C)]
+    pub struct RegisterBlock {
+        /// The received byte when read; sends a byte when written.
+        pub rxtx: Reg<Bits, Bits>,
+        /// Reading clears both flags.
+        pub irq: Reg<Irq, ReadOnly>,
+    }
+}
+
+/// A peripheral's registers, at the address it was found at.
+pub struct Periph<B> {
+    addr: u32,
+    _block: PhantomData<B>,
+}
+
+impl<B> Periph<B> {
+    /// # Safety
+    ///
+    /// `addr` must be the base address of a `B`, and nothing else may be
+    /// using it in a way that conflicts.
+    pub const unsafe fn new(addr: u32) -> Self {
+        Self { addr, _block: PhantomData }
+    }
+
+    pub fn addr(&self) -> u32 {
+        self.addr
+    }
+}
+
+impl<B> Deref for Periph<B> {
+    type Target = B;
+
+    fn deref(&self) -> &B {
+        // SAFETY: Guaranteed by new.
+        unsafe { &*(self.addr as usize as *const B) }
+    }
+}
+
+/// All of the SoC's peripherals.
+pub struct Peripherals {
+    pub gpio: Periph<gpio::RegisterBlock>,
+    pub timer: Periph<timer::RegisterBlock>,
+    pub serial: Periph<serial::RegisterBlock>,
+}
+
+impl Peripherals {
+    /// The peripherals, the first time this is called after the bus has
+    /// been detected; `None` otherwise, including after
+    /// [`Soc::take`](crate::soc::Soc::take) (and vice versa).
+    pub fn take() -> Option<Self> {
+        let bases = io_addrs::detected()?;
+        if !soc::claim() {
+            return None;
+        }
+        // SAFETY: Detected addresses, handed out once.
+        Some(unsafe { Self::steal(bases) })
+    }
+
+    /// The peripherals at `bases`, however many times.
+    ///
+    /// # Safety
+    ///
+    /// As for [`Periph::new`]; in particular, the HAL drivers and anything
+    /// else taking the peripherals may be using them too.
+    pub unsafe fn steal(bases: io_addrs::Bases) -> Self {
+        Self {
+            gpio: Periph::new(bases.gpio.into()),
+            timer: Periph::new(bases.timer.into()),
+            serial: Periph::new(bases.serial.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::mem::size_of;
+
+    use super::*;
+
+    #[test]
+    fn layout() {
+        assert_eq!(size_of::<gpio::RegisterBlock>(), 12);
+        assert_eq!(size_of::<timer::RegisterBlock>(), 4);
+        assert_eq!(size_of::<serial::RegisterBlock>(), 8);
+    }
+
+    #[test]
+    fn fields() {
+        // RAM standing in for the GPIO registers.
+        let mut mem = [0u32; 3];
+        let gpio = unsafe { &*(mem.as_mut_ptr() as *const gpio::RegisterBlock) };
+
+        gpio.oe.write(|w| w.set_bit(0, true).set_bit(3, true));
+        gpio.oe.modify(|_, w| w.set_bit(0, false));
+        assert_eq!(gpio.oe.read().bits(), 0x08);
+        assert!(gpio.oe.read().bit(3));
+        assert_eq!(mem[2].to_le_bytes()[0], 0x08);
+    }
+}
@@ -0,0 +1,149 @@
+//! Panic strategies.
+//!
+//! Enable the `panic-handler` feature to have sentinel-rt provide the
+//! `#[panic_handler]` (instead of e.g. `panic-halt`). What it does on panic
+//! is a [`Policy`], which defaults to:
+//!
+//! * `panic-reset` feature: [`Policy::Reset`]
+//! * `panic-print-reset` feature: [`Policy::PrintReset`]
+//! * `panic-uart` feature: [`Policy::PrintHalt`]
+//! * `panic-bootloader` feature: [`Policy::Bootloader`]
+//! * otherwise: [`Policy::Halt`]
+//!
+//! and can be changed at runtime with [`set_policy`], so e.g. a deployed
+//! board can reset and recover while a development board stops for
+//! inspection.
+//!
+//! The printing policies take the serial port over, whatever driver had
+//! it, and write the message and where it came from:
+//!
+//! ```text
+//! panicked at src/main.rs:42:5:
+//! index out of bounds: the len is 4 but the index is 4
+//! ```
+//!
+//! With the `sim` feature, a panic ends the simulation instead; see
+//! [`sim`](crate::sim).
+//!
+//! With the `backtrace` feature (and frame pointers), they follow that with
+//! the return addresses on the stack; see [`backtrace`](crate::backtrace).
+
+use portable_atomic::{AtomicU8, Ordering};
+
+use crate::events::{self, Event};
+use crate::reset::{enter_bootloader, soft_reset, Reason};
+
+/// What to do when panicking.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Policy {
+    /// Disable interrupts and spin forever.
+    Halt,
+    /// [`soft_reset`], with [`Reason::Panic`].
+    Reset,
+    /// Print the panic message to the serial port, then reset.
+    PrintReset,
+    /// [`enter_bootloader`], with [`Reason::Panic`].
+    Bootloader,
+    /// Print the panic message to the serial port, then halt. Unlike
+    /// `Halt`, a panic can be told from a hang.
+    PrintHalt,
+}
+
+impl Policy {
+    const fn from_u8(val: u8) -> Self {
+        match val {
+            1 => Self::Reset,
+            2 => Self::PrintReset,
+            3 => Self::Bootloader,
+            4 => Self::PrintHalt,
+            _ => Self::Halt,
+        }
+    }
+}
+
+const DEFAULT: Policy = if cfg!(feature = "panic-bootloader") {
+    Policy::Bootloader
+} else if cfg!(feature = "panic-uart") {
+    Policy::PrintHalt
+} else if cfg!(feature = "panic-print-reset") {
+    Policy::PrintReset
+} else if cfg!(feature = "panic-reset") {
+    Policy::Reset
+} else {
+    Policy::Halt
+};
+
+static POLICY: AtomicU8 = AtomicU8::new(DEFAULT as u8);
+
+pub fn set_policy(policy: Policy) {
+    POLICY.store(policy as u8, Ordering::Relaxed);
+}
+
+pub fn policy() -> Policy {
+    Policy::from_u8(POLICY.load(Ordering::Relaxed))
+}
+
+/// Carry out the current [`Policy`]. This is what sentinel-rt's panic
+/// handler calls; it's public for the sake of custom handlers.
+pub fn handle(info: &core::panic::PanicInfo) -> ! {
+    riscv::interrupt::disable();
+    events::record(Event::Panic);
+    crate::testing::on_panic();
+
+    if cfg!(feature = "sim") {
+        crate::sim::panic(info);
+    }
+
+    match policy() {
+        Policy::Halt => halt(),
+        Policy::Reset => soft_reset(Some(Reason::Panic)),
+        Policy::PrintReset => {
+            print(info);
+            soft_reset(Some(Reason::Panic))
+        }
+        Policy::Bootloader => enter_bootloader(Some(Reason::Panic)),
+        Policy::PrintHalt => {
+            print(info);
+            halt()
+        }
+    }
+}
+
+fn halt() -> ! {
+    loop {
+        core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+fn print(info: &core::panic::PanicInfo) {
+    use core::fmt::Write;
+
+    use crate::hal::serial::Serial;
+
+    // Nothing to print to if the SoC was never brought up. Interrupts are
+    // off, so nothing else can start using the UART while this does; but
+    // whatever had it may have left a byte going out, so give that a
+    // character time (10 bits) to finish first.
+    if let Some(bases) = crate::io_addrs::detected() {
+        let board = crate::board::CURRENT;
+        crate::hal::delay::delay_cycles(board.clk_hz / board.baud * 10);
+        let mut w = Serial::new(bases.serial);
+        let _ = write!(w, "\r\n{}\r\n", info);
+
+        #[cfg(feature = "backtrace")]
+        {
+            let _ = w.write_str("backtrace:");
+            for ra in crate::backtrace::Backtrace::capture().frames() {
+                let _ = write!(w, " {:08x}", ra);
+            }
+            let _ = w.write_str("\r\n");
+        }
+    }
+}
+
+#[cfg(all(feature = "panic-handler", target_os = "none"))]
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    handle(info)
+}
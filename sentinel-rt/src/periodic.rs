@@ -0,0 +1,232 @@
+//! Periodic tasks driven by the timer tick.
+//!
+//! ```ignore
+//! periodic::every(Duration::from_millis(500), || blink());
+//!
+//! #[no_mangle]
+//! fn MachineExternal() {
+//!     if timer_irq() {
+//!         timebase::tick();
+//!         periodic::run_due();
+//!     }
+//! }
+//! ```
+//!
+//! Callbacks are plain `fn()`s (closures that don't capture anything), and
+//! run wherever [`run_due`] is called: straight from the timer ISR, or
+//! deferred to the main loop if they're too slow for that. Either way they
+//! should be short; a task that falls a whole period or more behind skips
+//! the missed runs and has its [`overruns`] count bumped instead of running
+//! several times back to back.
+
+use core::cell::RefCell;
+use core::time::Duration;
+
+use critical_section::Mutex;
+
+use crate::timebase::{self, CLK_HZ, CYCLES_PER_TICK};
+
+/// Maximum number of registered tasks.
+pub const MAX_TASKS: usize = 8;
+
+/// A registered task. Pass it to [`cancel`] or [`overruns`]. Once the task
+/// is cancelled, the handle refers to nothing, even after its slot goes to
+/// another task.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Handle {
+    slot: usize,
+    gen: u16,
+}
+
+/// Every task slot is taken.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Full;
+
+#[derive(Clone, Copy)]
+struct Task {
+    period: u32,
+    next: u32,
+    overruns: u32,
+    f: fn(),
+    /// Tells this task from earlier ones in the same slot.
+    gen: u16,
+}
+
+/// The bookkeeping behind [`every`]/[`run_due`], in ticks. Public so it can
+/// be driven from some other time source.
+pub struct Schedule<const N: usize> {
+    tasks: [Option<Task>; N],
+    next_gen: u16,
+}
+
+impl<const N: usize> Default for Schedule<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Schedule<N> {
+    pub const fn new() -> Self {
+        Self { tasks: [None; N], next_gen: 0 }
+    }
+
+    /// Run `f` every `period` ticks (at least 1), starting `period` ticks
+    /// after `now`.
+    pub fn add(&mut self, now: u32, period: u32, f: fn())
+               -> Result<Handle, Full> {
+        let period = period.max(1);
+        let slot = self.tasks.iter().position(Option::is_none).ok_or(Full)?;
+        let next = now.wrapping_add(period);
+        let gen = self.next_gen;
+        self.next_gen = gen.wrapping_add(1);
+        self.tasks[slot] = Some(Task { period, next, overruns: 0, f, gen });
+        Ok(Handle { slot, gen })
+    }
+
+    /// `handle`'s task, if it's still registered.
+    fn task(&self, handle: Handle) -> Option<&Task> {
+        self.tasks.get(handle.slot)?.as_ref().filter(|t| t.gen == handle.gen)
+    }
+
+    pub fn remove(&mut self, handle: Handle) {
+        if self.task(handle).is_some() {
+            self.tasks[handle.slot] = None;
+        }
+    }
+
+    pub fn overruns(&self, handle: Handle) -> u32 {
+        self.task(handle).map_or(0, |t| t.overruns)
+    }
+
+    /// Return the next task due at `now`, and advance it to its next
+    /// deadline. Call until `None`.
+    pub fn take_due(&mut self, now: u32) -> Option<fn()> {
+        self.tasks.iter_mut().flatten().find_map(|t| {
+            // Deadlines wrap, so compare by signed difference.
+            let late = now.wrapping_sub(t.next) as i32;
+            if late < 0 {
+                return None;
+            }
+
+            if late as u32 >= t.period {
+                t.overruns = t.overruns.saturating_add(1);
+                t.next = now.wrapping_add(t.period);
+            } else {
+                t.next = t.next.wrapping_add(t.period);
+            }
+
+            Some(t.f)
+        })
+    }
+}
+
+static SCHEDULE: Mutex<RefCell<Schedule<MAX_TASKS>>> =
+    Mutex::new(RefCell::new(Schedule::new()));
+
+/// `period` in timer ticks, rounded to the nearest one (and at least 1).
+pub fn duration_to_ticks(period: Duration) -> u32 {
+    let cycles = period.as_micros() as u64 * CLK_HZ as u64 / 1_000_000;
+    let ticks = (cycles + CYCLES_PER_TICK as u64 / 2) / CYCLES_PER_TICK as u64;
+    ticks.clamp(1, u32::MAX as u64) as u32
+}
+
+/// Run `f` every `period`. The timer ticks about every 1.37 ms, which is the
+/// resolution of `period`.
+pub fn every(period: Duration, f: fn()) -> Result<Handle, Full> {
+    let ticks = duration_to_ticks(period);
+    critical_section::with(|cs| {
+        SCHEDULE.borrow_ref_mut(cs).add(timebase::ticks32(), ticks, f)
+    })
+}
+
+pub fn cancel(handle: Handle) {
+    critical_section::with(|cs| SCHEDULE.borrow_ref_mut(cs).remove(handle));
+}
+
+/// How many times `handle` fell a period or more behind.
+pub fn overruns(handle: Handle) -> u32 {
+    critical_section::with(|cs| SCHEDULE.borrow_ref(cs).overruns(handle))
+}
+
+/// Run every task that is due. Call from the timer ISR after
+/// [`timebase::tick`], or from the main loop.
+pub fn run_due() {
+    let now = timebase::ticks32();
+    // Don't hold the schedule while running tasks, so they can (un)register
+    // others.
+    while let Some(f) =
+        critical_section::with(|cs| SCHEDULE.borrow_ref_mut(cs).take_due(now))
+    {
+        f();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nop() {}
+
+    fn due<const N: usize>(s: &mut Schedule<N>, now: u32) -> usize {
+        core::iter::from_fn(|| s.take_due(now)).count()
+    }
+
+    #[test]
+    fn runs_on_period() {
+        let mut s = Schedule::<2>::new();
+        let h = s.add(0, 3, nop).unwrap();
+
+        assert_eq!(due(&mut s, 2), 0);
+        assert_eq!(due(&mut s, 3), 1);
+        assert_eq!(due(&mut s, 3), 0);
+        assert_eq!(due(&mut s, 5), 0);
+        // Slightly late is fine; the deadline doesn't drift.
+        assert_eq!(due(&mut s, 7), 1);
+        assert_eq!(due(&mut s, 9), 1);
+        assert_eq!(s.overruns(h), 0);
+    }
+
+    #[test]
+    fn overrun_skips() {
+        let mut s = Schedule::<2>::new();
+        let h = s.add(u32::MAX - 1, 4, nop).unwrap();
+
+        // Deadline was 2 (wrapped); 10 is two periods late.
+        assert_eq!(due(&mut s, 10), 1);
+        assert_eq!(s.overruns(h), 1);
+        assert_eq!(due(&mut s, 13), 0);
+        assert_eq!(due(&mut s, 14), 1);
+    }
+
+    #[test]
+    fn full_and_remove() {
+        let mut s = Schedule::<1>::new();
+        let h = s.add(0, 1, nop).unwrap();
+        assert_eq!(s.add(0, 1, nop), Err(Full));
+        s.remove(h);
+        assert_eq!(due(&mut s, 1), 0);
+        assert!(s.add(0, 1, nop).is_ok());
+    }
+
+    #[test]
+    fn stale_handle() {
+        let mut s = Schedule::<1>::new();
+        let old = s.add(0, 1, nop).unwrap();
+        s.remove(old);
+        let new = s.add(0, 4, nop).unwrap();
+
+        // The new task has the old one's slot, but not its handle.
+        s.remove(old);
+        assert_eq!(due(&mut s, 4), 1);
+        assert_eq!(due(&mut s, 20), 1);
+        assert_eq!(s.overruns(old), 0);
+        assert_eq!(s.overruns(new), 1);
+    }
+
+    #[test]
+    fn ticks_from_duration() {
+        assert_eq!(duration_to_ticks(Duration::ZERO), 1);
+        assert_eq!(duration_to_ticks(Duration::from_secs(64)), 46875);
+        assert_eq!(duration_to_ticks(Duration::from_millis(100)), 73);
+    }
+}
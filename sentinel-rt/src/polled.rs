@@ -0,0 +1,57 @@
+//! Interrupt-free builds.
+//!
+//! For bring-up of a new core or board, where interrupt delivery is itself
+//! the thing being debugged, build sentinel-rt with
+//!
+//! ```text
+//! sentinel-rt = { path = "...", default-features = false, features = ["polled"] }
+//! ```
+//!
+//! and nothing in it enables, or waits on, an interrupt:
+//!
+//! * Critical sections are only compiler fences; this module provides the
+//!   `critical-section` implementation. With nothing to preempt the main
+//!   loop, there is nothing to mask.
+//! * [`SocBuilder::interrupts`] is refused: [`SocBuilder::init`] panics if
+//!   it was asked for.
+//! * Interrupt-driven drivers, like [`Port`], are serviced by calling
+//!   their `on_interrupt` from the main loop instead of `MachineExternal`;
+//!   [`Port::write_all`] does so itself while it waits for room.
+//! * The [`timebase`](crate::timebase) is ticked from the main loop too,
+//!   whenever [`Timer::ack`] sees the timer's flag.
+//!
+//! The application needn't (and shouldn't) define `MachineExternal`.
+//!
+//! [`SocBuilder::interrupts`]: crate::soc::SocBuilder::interrupts
+//! [`SocBuilder::init`]: crate::soc::SocBuilder::init
+//! [`Port`]: crate::hal::serial::Port
+//! [`Port::write_all`]: crate::hal::serial::Port::write_all
+//! [`Timer::ack`]: crate::hal::timer::Timer::ack
+
+#[cfg(any(feature = "interrupts", feature = "critical-section-single-hart"))]
+compile_error!("the `polled` feature needs default features (`interrupts`) off");
+
+// Host builds get std's implementation, for the tests.
+#[cfg(target_os = "none")]
+mod imp {
+    use core::sync::atomic::{compiler_fence, Ordering};
+
+    use critical_section::RawRestoreState;
+
+    struct Polled;
+    critical_section::set_impl!(Polled);
+
+    // SAFETY: Nothing runs but the main loop, so there is nothing to
+    // exclude; the fences keep the compiler from moving accesses out of the
+    // critical section.
+    unsafe impl critical_section::Impl for Polled {
+        unsafe fn acquire() -> RawRestoreState {
+            compiler_fence(Ordering::SeqCst);
+            Default::default()
+        }
+
+        unsafe fn release(_: RawRestoreState) {
+            compiler_fence(Ordering::SeqCst);
+        }
+    }
+}
@@ -0,0 +1,235 @@
+//! Fixed-capacity object pools and arenas.
+//!
+//! For objects with dynamic-ish lifetimes (packet buffers, events in
+//! flight) without a heap: memory use is fixed at build time, and
+//! allocation is O(1) (O(N) for an [`Arena`] reset) and can't fragment.
+//!
+//! A [`Pool`] holds up to `N` values of one type. [`Pool::alloc`] moves a
+//! value in and returns a [`Handle`], which puts the slot back when
+//! dropped:
+//!
+//! ```ignore
+//! static BUFFERS: Pool<[u8; 64], 4> = Pool::new();
+//!
+//! let mut buf = BUFFERS.alloc([0; 64]).map_err(|_| "out of buffers")?;
+//! buf[0] = 0x55;
+//! ```
+//!
+//! An [`Arena`] hands out values of any type from a block of bytes, all
+//! freed at once by [`Arena::reset`], e.g. once per frame or per request.
+
+use core::cell::{Cell, UnsafeCell};
+use core::marker::PhantomData;
+use core::mem::{align_of, size_of, MaybeUninit};
+use core::ops::{Deref, DerefMut};
+
+use critical_section::Mutex;
+
+/// `N` values of type `T`. `N` can be at most 32.
+pub struct Pool<T, const N: usize> {
+    slots: [UnsafeCell<MaybeUninit<T>>; N],
+    /// Bit `i` is set when slot `i` is in use.
+    used: Mutex<Cell<u32>>,
+}
+
+// SAFETY: Through a shared Pool, values are only moved in (by alloc) and
+// dropped (by a Handle, maybe sent elsewhere): T only crosses contexts by
+// value, so needs to be Send. Each slot is only ever accessed through the
+// one Handle that owns it, and handing out slots is serialized by the
+// Mutex. Sharing a Handle shares its T; see its PhantomData.
+unsafe impl<T: Send, const N: usize> Sync for Pool<T, N> {}
+
+impl<T, const N: usize> Default for Pool<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> Pool<T, N> {
+    const FITS: () = assert!(N <= 32, "pools hold at most 32 values");
+
+    pub const fn new() -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::FITS;
+        Self {
+            slots: [const { UnsafeCell::new(MaybeUninit::uninit()) }; N],
+            used: Mutex::new(Cell::new(0)),
+        }
+    }
+
+    /// Move `value` into a free slot. Gives it back if there isn't one.
+    pub fn alloc(&self, value: T) -> Result<Handle<'_, T, N>, T> {
+        let slot = critical_section::with(|cs| {
+            let used = self.used.borrow(cs);
+            let slot = (!used.get()).trailing_zeros() as usize;
+            if slot >= N {
+                return None;
+            }
+            used.set(used.get() | 1 << slot);
+            Some(slot)
+        });
+
+        match slot {
+            Some(slot) => {
+                // SAFETY: The slot was free, and now belongs to us alone.
+                unsafe { (*self.slots[slot].get()).write(value) };
+                Ok(Handle { pool: self, slot, _value: PhantomData })
+            }
+            None => Err(value),
+        }
+    }
+
+    /// Slots in use.
+    pub fn len(&self) -> usize {
+        critical_section::with(|cs| self.used.borrow(cs).get().count_ones() as usize)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+}
+
+/// A value in a [`Pool`]. Dropping it drops the value, and frees the slot.
+pub struct Handle<'a, T, const N: usize> {
+    pool: &'a Pool<T, N>,
+    slot: usize,
+    /// A Handle owns its T, so is only Send or Sync if T is, as for a Box.
+    _value: PhantomData<T>,
+}
+
+impl<T, const N: usize> Deref for Handle<'_, T, N> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: The slot is initialized, and ours for as long as we live.
+        unsafe { (*self.pool.slots[self.slot].get()).assume_init_ref() }
+    }
+}
+
+impl<T, const N: usize> DerefMut for Handle<'_, T, N> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: See above.
+        unsafe { (*self.pool.slots[self.slot].get()).assume_init_mut() }
+    }
+}
+
+impl<T, const N: usize> Drop for Handle<'_, T, N> {
+    fn drop(&mut self) {
+        // SAFETY: See above; nothing uses the slot after this.
+        unsafe { (*self.pool.slots[self.slot].get()).assume_init_drop() };
+        critical_section::with(|cs| {
+            let used = self.pool.used.borrow(cs);
+            used.set(used.get() & !(1 << self.slot));
+        });
+    }
+}
+
+/// `N` bytes to allocate values of any type from, freed all at once.
+///
+/// Values are never dropped, so only types without (important) `Drop`
+/// impls belong in an arena.
+pub struct Arena<const N: usize> {
+    buf: UnsafeCell<[MaybeUninit<u8>; N]>,
+    used: Cell<usize>,
+}
+
+impl<const N: usize> Default for Arena<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Arena<N> {
+    pub const fn new() -> Self {
+        Self {
+            buf: UnsafeCell::new([MaybeUninit::uninit(); N]),
+            used: Cell::new(0),
+        }
+    }
+
+    /// Move `value` into the arena. Gives it back if it doesn't fit.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc<T>(&self, value: T) -> Result<&mut T, T> {
+        let base = self.buf.get() as usize;
+        let start = (base + self.used.get() + align_of::<T>() - 1) & !(align_of::<T>() - 1);
+        let end = start + size_of::<T>();
+        if end > base + N {
+            return Err(value);
+        }
+
+        self.used.set(end - base);
+        let p = start as *mut T;
+        // SAFETY: In bounds, aligned, and handed out to no one else until
+        // a reset, which needs every reference given out to be gone.
+        unsafe {
+            p.write(value);
+            Ok(&mut *p)
+        }
+    }
+
+    /// Bytes handed out, including padding.
+    pub fn used(&self) -> usize {
+        self.used.get()
+    }
+
+    /// Free everything.
+    pub fn reset(&mut self) {
+        self.used.set(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pool() {
+        let pool: Pool<u32, 2> = Pool::new();
+        let a = pool.alloc(1).unwrap();
+        let mut b = pool.alloc(2).unwrap();
+        assert_eq!(pool.alloc(3).err(), Some(3));
+
+        *b += 10;
+        assert_eq!((*a, *b, pool.len()), (1, 12, 2));
+        drop(a);
+        let c = pool.alloc(4).unwrap();
+        assert_eq!((c.slot, *c), (0, 4));
+    }
+
+    #[test]
+    fn pool_drops() {
+        use core::sync::atomic::{AtomicU32, Ordering};
+
+        static DROPS: AtomicU32 = AtomicU32::new(0);
+        struct Counted;
+        impl Drop for Counted {
+            fn drop(&mut self) {
+                DROPS.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let pool: Pool<Counted, 1> = Pool::new();
+        drop(pool.alloc(Counted));
+        assert!(pool.is_empty());
+        assert_eq!(DROPS.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn arena() {
+        let mut arena: Arena<16> = Arena::new();
+        let a = arena.alloc(1u8).unwrap();
+        let b = arena.alloc(2u32).unwrap();
+        *a += 1;
+        assert_eq!((*a, *b), (2, 2));
+        assert_eq!(b as *mut u32 as usize % 4, 0);
+        assert_eq!(arena.alloc([0u64; 2]).err(), Some([0; 2]));
+
+        arena.reset();
+        assert_eq!(arena.used(), 0);
+        assert!(arena.alloc([0u64; 1]).is_ok());
+    }
+}
@@ -0,0 +1,145 @@
+//! Peripheral clock gating and sleep.
+//!
+//! The AttoSoC doesn't have clock or power control registers yet. Until it
+//! does, gating is bookkeeping: [`disable`] marks a peripheral as gated, and
+//! its driver panics if it's used anyway, the same mistake that on gated
+//! hardware would hang or read garbage. A SoC that does have a gating
+//! register can [`set_hook`] a function to write it.
+//!
+//! [`idle`] is the WFI idle path; [`sleep`] additionally gates everything
+//! but the peripherals that should be able to wake the core, for the
+//! duration.
+
+use core::cell::Cell;
+
+use critical_section::Mutex;
+use portable_atomic::{AtomicU8, Ordering};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Peripheral {
+    Gpio,
+    Timer,
+    Serial,
+}
+
+impl Peripheral {
+    pub const ALL: [Self; 3] = [Self::Gpio, Self::Timer, Self::Serial];
+
+    const fn bit(self) -> u8 {
+        1 << self as u8
+    }
+
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Gpio => "gpio",
+            Self::Timer => "timer",
+            Self::Serial => "serial",
+        }
+    }
+}
+
+/// Writes the set of gated peripherals (bit `n` is `Peripheral` `n`) to the
+/// hardware.
+pub type Hook = fn(gated: u8);
+
+static GATED: AtomicU8 = AtomicU8::new(0);
+static HOOK: Mutex<Cell<Option<Hook>>> = Mutex::new(Cell::new(None));
+
+pub fn set_hook(hook: Hook) {
+    critical_section::with(|cs| HOOK.borrow(cs).set(Some(hook)));
+}
+
+fn update(f: impl FnOnce(u8) -> u8) {
+    critical_section::with(|cs| {
+        let gated = f(GATED.load(Ordering::Relaxed));
+        GATED.store(gated, Ordering::Relaxed);
+        if let Some(hook) = HOOK.borrow(cs).get() {
+            hook(gated);
+        }
+    });
+}
+
+/// Ungate `p`'s clock.
+pub fn enable(p: Peripheral) {
+    update(|g| g & !p.bit());
+}
+
+/// Gate `p`'s clock. Its driver panics if used until it's [`enable`]d
+/// again.
+pub fn disable(p: Peripheral) {
+    update(|g| g | p.bit());
+}
+
+pub fn is_enabled(p: Peripheral) -> bool {
+    GATED.load(Ordering::Relaxed) & p.bit() == 0
+}
+
+/// Panic if `p` is gated. Drivers call this before touching their
+/// registers.
+#[inline]
+#[track_caller]
+pub fn check(p: Peripheral) {
+    if !is_enabled(p) {
+        gated(p);
+    }
+}
+
+#[cold]
+#[track_caller]
+fn gated(p: Peripheral) -> ! {
+    panic!("{} used while clock-gated", p.name())
+}
+
+/// Wait for an interrupt. Interrupts must be enabled (at least in `mie`)
+/// for anything to wake the core.
+pub fn idle() {
+    riscv::asm::wfi();
+}
+
+/// Gate every peripheral not in `wake`, wait for an interrupt, then put the
+/// gating back as it was. The interrupt is handled after that, so its
+/// handler can use any peripheral.
+pub fn sleep(wake: &[Peripheral]) {
+    let keep = wake.iter().fold(0, |acc, p| acc | p.bit());
+    let all = Peripheral::ALL.iter().fold(0, |acc, p| acc | p.bit());
+
+    // WFI still wakes with interrupts masked, as long as they're in `mie`.
+    riscv::interrupt::free(|| {
+        let before = GATED.load(Ordering::Relaxed);
+        update(|g| g | (all & !keep));
+        idle();
+        update(|_| before);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bookkeeping() {
+        disable(Peripheral::Gpio);
+        assert!(!is_enabled(Peripheral::Gpio));
+        assert!(is_enabled(Peripheral::Serial));
+        enable(Peripheral::Gpio);
+        check(Peripheral::Gpio);
+    }
+
+    #[test]
+    #[should_panic(expected = "timer used while clock-gated")]
+    fn gated_use_panics() {
+        // Ungate it on the way out, or the other tests' timers would panic
+        // too.
+        struct Enable;
+        impl Drop for Enable {
+            fn drop(&mut self) {
+                enable(Peripheral::Timer);
+            }
+        }
+
+        let _enable = Enable;
+        disable(Peripheral::Timer);
+        check(Peripheral::Timer);
+    }
+}
@@ -0,0 +1,12 @@
+//! Everything most firmware needs, in one import.
+//!
+//! ```ignore
+//! use sentinel_rt::prelude::*;
+//! ```
+
+pub use crate::{entry, interrupt};
+pub use crate::hal::{gpio::Gpio, serial::Serial, timer::Timer};
+pub use crate::io_addrs::{GpioBase, SerialBase, TimerBase};
+pub use crate::error::{Ctx, ResultExt};
+pub use crate::soc::Soc;
+pub use crate::{assert_within_cycles, snapshot, sprint, sprintln};
@@ -0,0 +1,248 @@
+//! Hot reload of a RAM-resident application, without a reset.
+//!
+//! The resident firmware calls [`reload`] (say, from a shell command), which
+//! quiesces interrupts, receives an image over the UART into the reload
+//! region, and jumps to it. The UART is never reset or reconfigured, so the
+//! host's connection carries straight over to the new application, which is
+//! much quicker than a reset and a trip through the bootloader.
+//!
+//! The reload region is set in the resident's device linker script, before
+//! `INCLUDE sentinel.x`; it must not overlap anything the resident uses
+//! until the jump (its code, and its stack):
+//!
+//! ```text
+//! _sreload = 0x800;
+//! _ereload = 0x1000;
+//! ```
+//!
+//! and the application is linked to run entirely from it. The host sends a
+//! 16-byte little-endian [`Header`] (`"SRLD"`, image length, entry point
+//! offset, CRC-32 of the image) followed by the image. The resident answers
+//! `>` once the header checks out, and `ok\r\n` just before the jump; the
+//! host should wait for the latter before sending anything else.
+//!
+//! Bus detection relies on the IRQ state at reset, which a reload doesn't
+//! reproduce, so the bus is handed over in `a0`/`a1`. The application takes
+//! the arguments `#[entry]` passes, and gives them to [`resume`] before
+//! bringing up the SoC:
+//!
+//! ```ignore
+//! #[entry]
+//! fn main(a0: usize, a1: usize, _a2: usize) -> ! {
+//!     reload::resume(a0, a1);
+//!     let mut soc = Soc::init();
+//!     ...
+//! }
+//! ```
+
+use core::cell::Cell;
+use core::convert::Infallible;
+
+use critical_section::Mutex;
+
+use crate::checksum::Crc32;
+use crate::error::Describe;
+use crate::hal::serial::Serial;
+use crate::io_addrs::Bus;
+use crate::mem;
+
+const MAGIC: u32 = 0x444c_5253; // "SRLD"
+/// `a0` at the jump, so [`resume`] can tell a reload from a reset.
+const HANDOFF: u32 = 0x5352_4c44;
+
+/// How many polls of the UART to allow between bytes, once a transfer has
+/// started. The first header byte is waited for indefinitely.
+const BYTE_TIMEOUT: u32 = 1_000_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The linker script doesn't define a reload region.
+    NoRegion,
+    BadMagic,
+    /// The image doesn't fit the reload region, or its entry point is
+    /// outside it.
+    TooLarge,
+    /// The host stopped sending partway through.
+    Timeout,
+    BadCrc,
+}
+
+impl Describe for Error {
+    fn describe(&self) -> &'static str {
+        match self {
+            Self::NoRegion => "reload: no reload region",
+            Self::BadMagic => "reload: bad magic",
+            Self::TooLarge => "reload: image too large",
+            Self::Timeout => "reload: timed out",
+            Self::BadCrc => "reload: bad crc",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Header {
+    pub len: u32,
+    /// Offset of the entry point from the start of the image.
+    pub entry: u32,
+    pub crc: u32,
+}
+
+impl Header {
+    pub const LEN: usize = 16;
+
+    /// Parse and check a header, for an image to be loaded into a region of
+    /// `region_len` bytes.
+    pub fn parse(bytes: &[u8; Self::LEN], region_len: usize) -> Result<Self, Error> {
+        let word = |i: usize| {
+            u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]])
+        };
+        if word(0) != MAGIC {
+            return Err(Error::BadMagic);
+        }
+
+        let hdr = Self {
+            len: word(4),
+            entry: word(8),
+            crc: word(12),
+        };
+        if hdr.len as usize > region_len || hdr.entry >= hdr.len {
+            return Err(Error::TooLarge);
+        }
+        Ok(hdr)
+    }
+
+    pub fn to_bytes(&self) -> [u8; Self::LEN] {
+        let mut bytes = [0; Self::LEN];
+        for (i, w) in [MAGIC, self.len, self.entry, self.crc].into_iter().enumerate() {
+            bytes[i * 4..][..4].copy_from_slice(&w.to_le_bytes());
+        }
+        bytes
+    }
+}
+
+fn read_byte(serial: &mut Serial, timeout: bool) -> Result<u8, Error> {
+    let mut polls = 0;
+    loop {
+        if let Some(b) = serial.read_byte() {
+            return Ok(b);
+        }
+        polls += 1;
+        if timeout && polls == BYTE_TIMEOUT {
+            return Err(Error::Timeout);
+        }
+    }
+}
+
+/// Receive an application image and jump to it. Only returns if the
+/// transfer fails, with interrupts left off.
+///
+/// Whatever was in the reload region is overwritten, even if the transfer
+/// then fails.
+pub fn reload(serial: &mut Serial) -> Result<Infallible, Error> {
+    let region = mem::reload();
+    if region.is_empty() {
+        return Err(Error::NoRegion);
+    }
+
+    // SAFETY: Nothing that expects interrupts runs from here on: we either
+    // jump away, or return an error to a caller who asked for this.
+    unsafe {
+        riscv::register::mstatus::clear_mie();
+    }
+
+    let mut hdr = [0; Header::LEN];
+    for (i, b) in hdr.iter_mut().enumerate() {
+        *b = read_byte(serial, i != 0)?;
+    }
+    let hdr = Header::parse(&hdr, region.len())?;
+    serial.write_byte(b'>');
+
+    let mut crc = Crc32::new();
+    for i in 0..hdr.len as usize {
+        let b = read_byte(serial, true)?;
+        crc.update_byte(b);
+        // SAFETY: In the reload region, which the linker script promises is
+        // ours to overwrite.
+        unsafe { ((region.start + i) as *mut u8).write_volatile(b) };
+    }
+    if crc.finish() != hdr.crc {
+        return Err(Error::BadCrc);
+    }
+
+    serial.write_bytes(b"ok\r\n");
+    jump(region.start + hdr.entry as usize)
+}
+
+#[cfg(target_os = "none")]
+fn jump(addr: usize) -> ! {
+    let bus = match crate::io_addrs::detected() {
+        Some(b) if b.bus == Bus::Wishbone => 1,
+        Some(_) => 2,
+        None => 0,
+    };
+
+    // SAFETY: Interrupts are off, and the image sets up its own state from
+    // scratch (riscv-rt's `_start`, and `minimal`'s, pass a0-a2 on to `main`).
+    unsafe {
+        core::arch::asm!("jr {0}", in(reg) addr, in("a0") HANDOFF,
+                         in("a1") bus, in("a2") 0, options(noreturn))
+    }
+}
+
+#[cfg(not(target_os = "none"))]
+fn jump(_addr: usize) -> ! {
+    unreachable!("reload on host")
+}
+
+static BUS: Mutex<Cell<Option<Bus>>> = Mutex::new(Cell::new(None));
+
+/// Pick up the state handed over by [`reload`], given `main`'s first two
+/// arguments. Harmless after a normal reset.
+pub fn resume(a0: usize, a1: usize) {
+    if a0 as u32 != HANDOFF {
+        return;
+    }
+
+    let bus = match a1 {
+        1 => Some(Bus::Wishbone),
+        2 => Some(Bus::Csr),
+        _ => None,
+    };
+    critical_section::with(|cs| BUS.borrow(cs).set(bus));
+}
+
+/// Bus handed over by [`reload`]. Consumed by
+/// [`io_addrs::detect`](crate::io_addrs::detect).
+pub(crate) fn take_bus() -> Option<Bus> {
+    critical_section::with(|cs| BUS.borrow(cs).take())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header() {
+        let hdr = Header { len: 0x100, entry: 0x20, crc: 0xdead_beef };
+        let bytes = hdr.to_bytes();
+        assert_eq!(&bytes[..4], b"SRLD");
+        assert_eq!(Header::parse(&bytes, 0x100), Ok(hdr));
+        assert_eq!(Header::parse(&bytes, 0xff), Err(Error::TooLarge));
+
+        let bad_entry = Header { entry: 0x100, ..hdr }.to_bytes();
+        assert_eq!(Header::parse(&bad_entry, 0x100), Err(Error::TooLarge));
+
+        let mut bad_magic = bytes;
+        bad_magic[0] = 0;
+        assert_eq!(Header::parse(&bad_magic, 0x100), Err(Error::BadMagic));
+    }
+
+    #[test]
+    fn resume_ignores_reset() {
+        resume(0, 1);
+        assert_eq!(take_bus(), None);
+        resume(HANDOFF as usize, 2);
+        assert_eq!(take_bus(), Some(Bus::Csr));
+        assert_eq!(take_bus(), None);
+    }
+}
@@ -0,0 +1,195 @@
+//! Soft reset.
+//!
+//! The AttoSoC has no reset register, so [`soft_reset`] tears down interrupt
+//! state and jumps to the reset vector. A small record in `.noinit` carries
+//! the [`Reason`] (if any) and the detected peripheral bus across the reset;
+//! the latter matters because bus detection relies on the IRQ state at
+//! reset, which isn't reproduced by jumping to it.
+
+use core::mem::MaybeUninit;
+use core::ptr::{addr_of, addr_of_mut};
+
+use riscv::register::{mie, mstatus};
+
+use crate::io_addrs::{self, Bus};
+
+/// Why the firmware reset itself.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Reason {
+    Requested,
+    Panic,
+    Watchdog,
+    /// An exception nothing handled; see [`trap`](crate::trap).
+    Exception,
+    /// An exception that looked like a stack overflow; see
+    /// [`trap`](crate::trap#stack-overflow).
+    StackOverflow,
+    /// Application-defined.
+    Other(u16),
+}
+
+impl Reason {
+    pub(crate) const fn encode(self) -> u32 {
+        match self {
+            Self::Requested => 1,
+            Self::Panic => 2,
+            Self::Watchdog => 3,
+            Self::Exception => 4,
+            Self::StackOverflow => 5,
+            Self::Other(n) => 0x1_0000 | n as u32,
+        }
+    }
+
+    pub(crate) const fn decode(val: u32) -> Option<Self> {
+        match val {
+            1 => Some(Self::Requested),
+            2 => Some(Self::Panic),
+            3 => Some(Self::Watchdog),
+            4 => Some(Self::Exception),
+            5 => Some(Self::StackOverflow),
+            _ if val >> 16 == 1 => Some(Self::Other(val as u16)),
+            _ => None,
+        }
+    }
+}
+
+const MAGIC: u32 = 0x5453_5352; // "RSST"
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct Record {
+    magic: u32,
+    reason: u32,
+    bus: u32,
+}
+
+// Only accessed with interrupts off (soft_reset, start-up) or through
+// critical sections.
+#[link_section = ".noinit.sentinel.reset"]
+static mut RECORD: MaybeUninit<Record> = MaybeUninit::uninit();
+
+fn read_record() -> Option<Record> {
+    // SAFETY: .noinit is plain RAM; any bit pattern is a valid Record.
+    let rec = critical_section::with(|_| unsafe {
+        addr_of!(RECORD).read_volatile().assume_init()
+    });
+    (rec.magic == MAGIC).then_some(rec)
+}
+
+fn write_record(rec: Record) {
+    // SAFETY: See above.
+    critical_section::with(|_| unsafe {
+        addr_of_mut!(RECORD).write_volatile(MaybeUninit::new(rec))
+    });
+}
+
+/// Reason recorded by the last [`soft_reset`], if the firmware came up
+/// through one. Clears it, so a later power-on isn't misattributed.
+pub fn take_reason() -> Option<Reason> {
+    let mut rec = read_record()?;
+    let reason = Reason::decode(rec.reason);
+    rec.reason = 0;
+    write_record(rec);
+    reason
+}
+
+/// Like [`take_reason`], but leaves it for the application.
+pub(crate) fn peek_reason() -> Option<Reason> {
+    Reason::decode(read_record()?.reason)
+}
+
+/// Bus recorded by the last [`soft_reset`]. Consumed by
+/// [`io_addrs::detect`].
+pub(crate) fn take_bus() -> Option<Bus> {
+    let mut rec = read_record()?;
+    let bus = match rec.bus {
+        1 => Some(Bus::Wishbone),
+        2 => Some(Bus::Csr),
+        _ => None,
+    };
+    rec.bus = 0;
+    write_record(rec);
+    bus
+}
+
+fn teardown(reason: Option<Reason>) {
+    // SAFETY: We never return to whatever the interrupts were guarding.
+    unsafe {
+        mstatus::clear_mie();
+        mie::clear_mext();
+        mie::clear_mtimer();
+        mie::clear_msoft();
+    }
+
+    write_record(Record {
+        magic: MAGIC,
+        reason: reason.map_or(0, Reason::encode),
+        bus: match io_addrs::detected() {
+            Some(b) if b.bus == Bus::Wishbone => 1,
+            Some(_) => 2,
+            None => 0,
+        },
+    });
+}
+
+/// Disable interrupts, record `reason`, and jump to the reset vector.
+///
+/// Note that with all memory regions in RAM (as on the AttoSoC), there is no
+/// pristine copy of `.data` to reload; statics with non-zero initializers
+/// keep whatever value they had before the reset.
+pub fn soft_reset(reason: Option<Reason>) -> ! {
+    teardown(reason);
+
+    #[cfg(target_os = "none")]
+    {
+        extern "C" {
+            fn _sentinel_reset() -> !;
+        }
+
+        jump(_sentinel_reset as *const () as usize)
+    }
+
+    #[cfg(not(target_os = "none"))]
+    unreachable!("soft_reset on host")
+}
+
+/// Like [`soft_reset`], but jump to `_bootloader`, which the linker script
+/// may provide. It is the reset vector if it doesn't.
+pub fn enter_bootloader(reason: Option<Reason>) -> ! {
+    teardown(reason);
+
+    #[cfg(target_os = "none")]
+    {
+        extern "C" {
+            fn _bootloader() -> !;
+        }
+
+        jump(_bootloader as *const () as usize)
+    }
+
+    #[cfg(not(target_os = "none"))]
+    unreachable!("enter_bootloader on host")
+}
+
+#[cfg(target_os = "none")]
+fn jump(addr: usize) -> ! {
+    // SAFETY: Interrupts are off, and everything reachable from the reset
+    // vector sets up its own state from scratch.
+    unsafe { core::arch::asm!("jr {0}", in(reg) addr, options(noreturn)) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reason_roundtrip() {
+        for r in [Reason::Requested, Reason::Panic, Reason::Watchdog, Reason::Exception,
+                  Reason::StackOverflow, Reason::Other(0), Reason::Other(0xffff)] {
+            assert_eq!(Reason::decode(r.encode()), Some(r));
+        }
+
+        assert_eq!(Reason::decode(0), None);
+        assert_eq!(Reason::decode(0x2_0000), None);
+    }
+}
@@ -0,0 +1,367 @@
+//! Software real-time clock.
+//!
+//! Calendar time is kept as an offset from the [`timebase`] tick counter, so
+//! it's only as good as the 12 MHz oscillator. [`set`] it from something
+//! authoritative (a shell command, a host, [`sntp`]) and [`now`] will keep
+//! counting from there. An [`Alarm`] can be set against it, and polled for
+//! with [`alarm_due`].
+//!
+//! Times are UTC; there's no time zone or leap second support.
+
+use core::cell::Cell;
+use core::fmt;
+
+use critical_section::Mutex;
+
+use crate::timebase::{self, CLK_HZ, CYCLES_PER_TICK};
+
+const fn gcd(mut a: u32, mut b: u32) -> u32 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+// Shortest whole number of ticks that is also a whole number of seconds
+// (for 12 MHz and 2**14 cycles/tick, 46875 ticks == 64 s). Folding elapsed
+// time into the base in these units keeps it exact, and the tick counter
+// never gets a chance to wrap on us as long as someone reads the clock at
+// least every ~68 days.
+const FOLD_TICKS: u32 = CLK_HZ / gcd(CLK_HZ, CYCLES_PER_TICK);
+const FOLD_SECS: u32 = CYCLES_PER_TICK / gcd(CLK_HZ, CYCLES_PER_TICK);
+
+#[derive(Clone, Copy)]
+struct Base {
+    unix: u64,
+    tick: u32,
+}
+
+static BASE: Mutex<Cell<Base>> = Mutex::new(Cell::new(Base { unix: 0, tick: 0 }));
+static ALARM: Mutex<Cell<Option<Alarm>>> = Mutex::new(Cell::new(None));
+
+pub fn is_leap_year(year: u16) -> bool {
+    (year.is_multiple_of(4) && !year.is_multiple_of(100))
+        || year.is_multiple_of(400)
+}
+
+/// Days in `month` (1-12) of `year`.
+pub fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// A UTC calendar time, 1970 onwards.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DateTime {
+    pub year: u16,
+    /// 1-12
+    pub month: u8,
+    /// 1-31
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl DateTime {
+    // Civil from/to days algorithms are from Howard Hinnant's
+    // "chrono-Compatible Low-Level Date Algorithms", restricted to
+    // non-negative years so everything stays unsigned.
+
+    pub fn from_unix(secs: u64) -> Self {
+        let days = (secs / 86400) as u32;
+        let rem = (secs % 86400) as u32;
+
+        // Shift the epoch to 0000-03-01 so leap days end each 4/100/400
+        // year cycle.
+        let z = days + 719468;
+        let era = z / 146097;
+        let doe = z - era * 146097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + (month <= 2) as u32;
+
+        Self {
+            year: year as u16,
+            month: month as u8,
+            day: day as u8,
+            hour: (rem / 3600) as u8,
+            minute: (rem / 60 % 60) as u8,
+            second: (rem % 60) as u8,
+        }
+    }
+
+    /// Seconds since the Unix epoch, or `None` if this isn't a valid date
+    /// (see [`is_valid`](Self::is_valid)), such as one before 1970.
+    pub fn to_unix(&self) -> Option<u64> {
+        if !self.is_valid() {
+            return None;
+        }
+
+        let month = self.month as u32;
+        let year = self.year as u32 - (month <= 2) as u32;
+        let era = year / 400;
+        let yoe = year - era * 400;
+        let mp = if month > 2 { month - 3 } else { month + 9 };
+        let doy = (153 * mp + 2) / 5 + self.day as u32 - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        let days = (era * 146097 + doe - 719468) as u64;
+
+        Some(days * 86400
+             + self.hour as u64 * 3600
+             + self.minute as u64 * 60
+             + self.second as u64)
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.year >= 1970
+            && (1..=12).contains(&self.month)
+            && self.day >= 1
+            && self.day <= days_in_month(self.year, self.month)
+            && self.hour < 24
+            && self.minute < 60
+            && self.second < 60
+    }
+
+    /// Day of the week, 0 = Sunday, or `None` if this isn't a valid date.
+    pub fn weekday(&self) -> Option<u8> {
+        // 1970-01-01 was a Thursday.
+        Some(((self.to_unix()? / 86400 + 4) % 7) as u8)
+    }
+}
+
+/// ISO 8601, e.g. `2024-02-29T13:37:00Z`.
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", self.year,
+               self.month, self.day, self.hour, self.minute, self.second)
+    }
+}
+
+/// Set the clock to `secs` since the Unix epoch.
+pub fn set_unix(secs: u64) {
+    critical_section::with(|cs| {
+        BASE.borrow(cs).set(Base { unix: secs, tick: timebase::ticks32() });
+    });
+}
+
+/// Set the clock. Returns `false` (and leaves the clock alone) if `dt`
+/// isn't a valid date.
+pub fn set(dt: &DateTime) -> bool {
+    match dt.to_unix() {
+        Some(secs) => {
+            set_unix(secs);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Seconds since the Unix epoch.
+pub fn unix() -> u64 {
+    critical_section::with(|cs| {
+        let cell = BASE.borrow(cs);
+        let mut base = cell.get();
+
+        let elapsed = timebase::ticks32().wrapping_sub(base.tick);
+        let folds = elapsed / FOLD_TICKS;
+        base.unix += folds as u64 * FOLD_SECS as u64;
+        base.tick = base.tick.wrapping_add(folds * FOLD_TICKS);
+        cell.set(base);
+
+        let rem = (elapsed % FOLD_TICKS) as u64;
+        base.unix + rem * CYCLES_PER_TICK as u64 / CLK_HZ as u64
+    })
+}
+
+pub fn now() -> DateTime {
+    DateTime::from_unix(unix())
+}
+
+/// Goes off at a Unix time, and then every `repeat` seconds if that's
+/// non-zero.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Alarm {
+    pub at: u64,
+    pub repeat: u32,
+}
+
+impl Alarm {
+    pub const fn once(at: u64) -> Self {
+        Self { at, repeat: 0 }
+    }
+
+    pub const fn every(at: u64, repeat: u32) -> Self {
+        Self { at, repeat }
+    }
+
+    /// If the alarm in `slot` is due at `now`, rearm (or clear) it and
+    /// return `true`. A repeating alarm that was missed several times
+    /// only goes off once, and is rearmed for the next time after `now`.
+    pub fn poll(slot: &mut Option<Self>, now: u64) -> bool {
+        match *slot {
+            Some(a) if now >= a.at => {
+                *slot = match a.repeat as u64 {
+                    0 => None,
+                    r => Some(Self { at: a.at + ((now - a.at) / r + 1) * r, ..a }),
+                };
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Replace the alarm. There's only one.
+pub fn set_alarm(alarm: Alarm) {
+    critical_section::with(|cs| ALARM.borrow(cs).set(Some(alarm)));
+}
+
+pub fn cancel_alarm() {
+    critical_section::with(|cs| ALARM.borrow(cs).set(None));
+}
+
+pub fn alarm() -> Option<Alarm> {
+    critical_section::with(|cs| ALARM.borrow(cs).get())
+}
+
+/// Whether the alarm went off since the last call. Call at least once a
+/// second (from the main loop, or a [`periodic`](crate::periodic) task) to
+/// not be late.
+pub fn alarm_due() -> bool {
+    let now = unix();
+    critical_section::with(|cs| {
+        let cell = ALARM.borrow(cs);
+        let mut slot = cell.get();
+        let due = Alarm::poll(&mut slot, now);
+        cell.set(slot);
+        due
+    })
+}
+
+/// Transport-agnostic SNTP (RFC 4330) client packets. The AttoSoC has no
+/// network, so getting these to and from a server (e.g. through a host-side
+/// bridge on the serial port) is up to the application.
+#[cfg(feature = "sntp")]
+pub mod sntp {
+    pub const PACKET_LEN: usize = 48;
+
+    /// Seconds between the NTP (1900) and Unix (1970) epochs.
+    const NTP_TO_UNIX: u64 = 2_208_988_800;
+
+    /// A client request: version 4, mode 3 (client), everything else zero.
+    pub fn request() -> [u8; PACKET_LEN] {
+        let mut pkt = [0; PACKET_LEN];
+        pkt[0] = (4 << 3) | 3;
+        pkt
+    }
+
+    /// Unix time from a server's response (its transmit timestamp, rounded
+    /// down to the second), or `None` if the packet is malformed or the
+    /// server is unsynchronized.
+    pub fn parse_response(pkt: &[u8]) -> Option<u64> {
+        if pkt.len() < PACKET_LEN {
+            return None;
+        }
+
+        let leap = pkt[0] >> 6;
+        let mode = pkt[0] & 0x07;
+        let stratum = pkt[1];
+        // Mode 4 == server; LI 3 or stratum 0 == unsynchronized/kiss-o'-death.
+        if mode != 4 || leap == 3 || stratum == 0 {
+            return None;
+        }
+
+        let secs = u32::from_be_bytes([pkt[40], pkt[41], pkt[42], pkt[43]]);
+        (secs as u64).checked_sub(NTP_TO_UNIX)
+    }
+
+    /// Set the clock from a server's response. Returns whether it was
+    /// usable.
+    pub fn sync(pkt: &[u8]) -> bool {
+        match parse_response(pkt) {
+            Some(secs) => {
+                super::set_unix(secs);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fold_is_exact() {
+        assert_eq!((FOLD_TICKS, FOLD_SECS), (46875, 64));
+        assert_eq!(FOLD_TICKS as u64 * CYCLES_PER_TICK as u64,
+                   FOLD_SECS as u64 * CLK_HZ as u64);
+    }
+
+    #[test]
+    fn unix_roundtrip() {
+        let cases = [
+            (0, DateTime { year: 1970, month: 1, day: 1, hour: 0, minute: 0, second: 0 }),
+            (951_782_400, DateTime { year: 2000, month: 2, day: 29, hour: 0, minute: 0, second: 0 }),
+            (1_709_213_820, DateTime { year: 2024, month: 2, day: 29, hour: 13, minute: 37, second: 0 }),
+            (4_107_542_399, DateTime { year: 2100, month: 2, day: 28, hour: 23, minute: 59, second: 59 }),
+            (4_107_542_400, DateTime { year: 2100, month: 3, day: 1, hour: 0, minute: 0, second: 0 }),
+        ];
+
+        for (secs, dt) in cases {
+            assert_eq!(DateTime::from_unix(secs), dt);
+            assert_eq!(dt.to_unix(), Some(secs));
+        }
+    }
+
+    #[test]
+    fn leap_years() {
+        assert!(is_leap_year(2000));
+        assert!(is_leap_year(2024));
+        assert!(!is_leap_year(1900));
+        assert!(!is_leap_year(2100));
+        assert_eq!(days_in_month(2023, 2), 28);
+        assert_eq!(days_in_month(2024, 2), 29);
+
+        let bad = DateTime { year: 2023, month: 2, day: 29, hour: 0, minute: 0, second: 0 };
+        assert!(!bad.is_valid());
+        assert_eq!(bad.to_unix(), None);
+        for year in [0, 1969] {
+            let early = DateTime { year, month: 1, day: 1, hour: 0, minute: 0, second: 0 };
+            assert_eq!(early.to_unix(), None);
+        }
+    }
+
+    #[test]
+    fn alarms() {
+        let mut slot = Some(Alarm::once(100));
+        assert!(!Alarm::poll(&mut slot, 99));
+        assert!(Alarm::poll(&mut slot, 100));
+        assert_eq!(slot, None);
+
+        let mut slot = Some(Alarm::every(100, 60));
+        assert!(Alarm::poll(&mut slot, 101));
+        assert_eq!(slot, Some(Alarm::every(160, 60)));
+        // Missed a few; catches up without going off repeatedly.
+        assert!(Alarm::poll(&mut slot, 400));
+        assert_eq!(slot, Some(Alarm::every(460, 60)));
+        assert!(!Alarm::poll(&mut slot, 400));
+    }
+
+    #[test]
+    fn weekday() {
+        // Thursday, Thursday, Tuesday.
+        assert_eq!(DateTime::from_unix(0).weekday(), Some(4));
+        assert_eq!(DateTime::from_unix(1_709_213_820).weekday(), Some(4));
+        assert_eq!(DateTime::from_unix(951_782_400).weekday(), Some(2));
+    }
+}
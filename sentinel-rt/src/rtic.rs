@@ -0,0 +1,106 @@
+//! RTIC's monotonic, with the `rtic` feature.
+//!
+//! [`Mono`] implements [`rtic_time::Monotonic`] on the [`timebase`] tick,
+//! so code written against RTIC's timer API carries over:
+//!
+//! ```ignore
+//! use rtic_time::Monotonic;
+//! use fugit::ExtU32;
+//!
+//! Mono::start();
+//! executor::block_on(async {
+//!     loop {
+//!         led.set(!led.is_on());
+//!         Mono::delay(500.millis()).await;
+//!     }
+//! })
+//! ```
+//!
+//! The timer can't be set to fire at a given time, so there's no compare
+//! to program: the queue is checked on every tick, and delays have its
+//! resolution, about 1.37 ms. [`Instant`](fugit::Instant)s count ticks in
+//! a `u32`, so a single delay can be up to half that range (34 days).
+//!
+//! `#[rtic::app]` itself still needs a backend for this core in RTIC,
+//! which doesn't exist yet. Until then, hardware tasks map onto
+//! [`irq::Handler`]s, a software task onto [`Handler::Soft`] with
+//! [`irq::pend`] in place of a software interrupt, and `async` tasks onto
+//! the [`executor`](crate::executor). `examples/rtic_blinky.rs` shows the
+//! pattern.
+//!
+//! [`irq::Handler`]: crate::irq::Handler
+//! [`Handler::Soft`]: crate::irq::Handler::Soft
+//! [`irq::pend`]: crate::irq::pend
+
+use rtic_time::monotonic::TimerQueueBasedMonotonic;
+use rtic_time::timer_queue::{TimerQueue, TimerQueueBackend};
+
+use crate::timebase::{self, CLK_HZ, CYCLES_PER_TICK};
+
+/// Timebase ticks, as a [`Mono`] instant.
+pub type Instant = fugit::Instant<u32, CYCLES_PER_TICK, CLK_HZ>;
+pub type Duration = fugit::Duration<u32, CYCLES_PER_TICK, CLK_HZ>;
+
+/// The monotonic. Call [`start`](Self::start) before waiting on it.
+pub struct Mono;
+
+/// The timer queue's view of the [`timebase`]. Only used through
+/// [`Mono`].
+pub struct TickBackend(());
+
+static QUEUE: TimerQueue<TickBackend> = TimerQueue::new();
+
+impl Mono {
+    /// Get the timer queue going. Delays panic before this.
+    pub fn start() {
+        QUEUE.initialize(TickBackend(()));
+    }
+}
+
+impl TimerQueueBasedMonotonic for Mono {
+    type Backend = TickBackend;
+    type Instant = Instant;
+    type Duration = Duration;
+}
+
+impl TimerQueueBackend for TickBackend {
+    type Ticks = u32;
+
+    fn now() -> u32 {
+        timebase::ticks32()
+    }
+
+    // The queue is checked every tick anyway, so there's no compare to
+    // set, and nothing to pend to get it rechecked.
+    fn set_compare(_: u32) {}
+
+    fn clear_compare_flag() {}
+
+    fn pend_interrupt() {}
+
+    fn timer_queue() -> &'static TimerQueue<Self> {
+        &QUEUE
+    }
+}
+
+/// Called by [`timebase::tick`].
+pub(crate) fn on_tick() {
+    // SAFETY: Only ever called from the tick, which is the monotonic's
+    // interrupt.
+    unsafe { QUEUE.on_monotonic_interrupt() }
+}
+
+#[cfg(test)]
+mod tests {
+    use fugit::ExtU32;
+
+    use super::*;
+
+    #[test]
+    fn units() {
+        // 732.42 ticks a second.
+        let d: Duration = 1.secs();
+        assert_eq!(d.ticks(), 732);
+        assert_eq!(Duration::from_ticks(3).to_micros(), 4096);
+    }
+}
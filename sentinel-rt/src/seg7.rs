@@ -0,0 +1,173 @@
+//! 7-segment displays: segment encoding, and a driver for the TM1637
+//! 4-digit modules.
+//!
+//! Segments are bits 0-6 for `a`-`g`, and bit 7 for the decimal point (or
+//! on most 4-digit clock modules, the colon, on the second digit).
+//!
+//! The TM1637 speaks a two-wire protocol that looks like I2C without
+//! addresses, sent LSB first, so it shares the bit-banged lines of
+//! [`hal::i2c`](crate::hal::i2c):
+//!
+//! ```ignore
+//! let mut disp = Tm1637::new(GpioPins::new(soc.gpio, 0, 1));
+//! disp.show(&seg7::clock(12, 34, true))?;
+//! ```
+
+use crate::hal::i2c::{Error, Pins};
+
+pub const DP: u8 = 0x80;
+pub const MINUS: u8 = 0x40;
+
+const DIGITS: [u8; 16] = [
+    0x3f, 0x06, 0x5b, 0x4f, 0x66, 0x6d, 0x7d, 0x07,
+    0x7f, 0x6f, 0x77, 0x7c, 0x39, 0x5e, 0x79, 0x71,
+];
+
+/// Segments for the hex digit `d` (only the low nibble is used).
+pub const fn digit(d: u8) -> u8 {
+    DIGITS[(d & 0x0f) as usize]
+}
+
+/// `val` as 4 decimal digits, right-aligned with leading blanks (but at
+/// least one digit). Values over 9999 show their last 4 digits.
+pub fn number(val: u16) -> [u8; 4] {
+    let mut out = [0; 4];
+    let mut v = val;
+    for (i, seg) in out.iter_mut().enumerate().rev() {
+        if v != 0 || i == 3 {
+            *seg = digit((v % 10) as u8);
+        }
+        v /= 10;
+    }
+    out
+}
+
+/// `hh:mm` (or `mm:ss`) with leading zeros, the colon lit if `colon`.
+pub fn clock(hi: u8, lo: u8, colon: bool) -> [u8; 4] {
+    [
+        digit(hi / 10 % 10),
+        digit(hi % 10) | if colon { DP } else { 0 },
+        digit(lo / 10),
+        digit(lo % 10),
+    ]
+}
+
+const DATA_AUTO_INCREMENT: u8 = 0x40;
+const ADDRESS: u8 = 0xc0;
+const DISPLAY_ON: u8 = 0x88;
+
+pub struct Tm1637<P> {
+    pins: P,
+    delay: u32,
+    /// Display control command: on/off and brightness.
+    control: u8,
+}
+
+impl<P: Pins> Tm1637<P> {
+    /// Drive the display on `pins` (SCL is CLK, SDA is DIO), at full
+    /// brightness.
+    pub fn new(pins: P) -> Self {
+        Self {
+            pins,
+            delay: 0,
+            control: DISPLAY_ON | 7,
+        }
+    }
+
+    /// Spin `spins` times around each edge; see
+    /// [`I2c::set_delay`](crate::hal::i2c::I2c::set_delay).
+    pub fn set_delay(&mut self, spins: u32) {
+        self.delay = spins;
+    }
+
+    pub fn free(self) -> P {
+        self.pins
+    }
+
+    fn wait(&self) {
+        for _ in 0..self.delay {
+            core::hint::spin_loop();
+        }
+    }
+
+    fn clk(&mut self, high: bool) {
+        self.pins.set_scl(high);
+        self.wait();
+    }
+
+    fn start(&mut self) {
+        self.pins.set_sda(true);
+        self.clk(true);
+        self.pins.set_sda(false);
+        self.wait();
+        self.clk(false);
+    }
+
+    fn stop(&mut self) {
+        self.pins.set_sda(false);
+        self.clk(true);
+        self.pins.set_sda(true);
+        self.wait();
+    }
+
+    fn write_byte(&mut self, byte: u8) -> Result<(), Error> {
+        for i in 0..8 {
+            self.pins.set_sda((byte >> i) & 1 != 0);
+            self.clk(true);
+            self.clk(false);
+        }
+
+        self.pins.set_sda(true);
+        self.clk(true);
+        let nack = self.pins.sda();
+        self.clk(false);
+        if nack {
+            Err(Error::DataNack)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Send one command (and any data that goes with it) in a single
+    /// start/stop frame.
+    fn command(&mut self, cmd: u8, data: &[u8]) -> Result<(), Error> {
+        self.start();
+        let res = core::iter::once(&cmd)
+            .chain(data)
+            .try_for_each(|b| self.write_byte(*b));
+        self.stop();
+        res
+    }
+
+    /// Show 4 digits' worth of segments, leftmost first.
+    pub fn show(&mut self, segs: &[u8; 4]) -> Result<(), Error> {
+        self.command(DATA_AUTO_INCREMENT, &[])?;
+        self.command(ADDRESS, segs)?;
+        self.command(self.control, &[])
+    }
+
+    /// 0 (dimmest) to 7.
+    pub fn set_brightness(&mut self, level: u8) -> Result<(), Error> {
+        self.control = (self.control & !0x07) | level.min(7);
+        self.command(self.control, &[])
+    }
+
+    pub fn set_on(&mut self, on: bool) -> Result<(), Error> {
+        self.control = if on { self.control | 0x08 } else { self.control & !0x08 };
+        self.command(self.control, &[])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encoding() {
+        assert_eq!(number(42), [0, 0, digit(4), digit(2)]);
+        assert_eq!(number(0), [0, 0, 0, digit(0)]);
+        assert_eq!(number(12345), [digit(2), digit(3), digit(4), digit(5)]);
+        assert_eq!(clock(9, 5, true), [digit(0), digit(9) | DP, digit(0), digit(5)]);
+        assert_eq!(digit(0xa), 0x77);
+    }
+}
@@ -0,0 +1,319 @@
+//! A line-oriented command shell over the UART.
+//!
+//! Commands are a static table, most easily built with
+//! [`commands!`](crate::commands!), whose doc comments become the help text:
+//!
+//! ```ignore
+//! static COMMANDS: &[Command] = sentinel_rt::commands! {
+//!     /// Set the LEDs.
+//!     leds "<val>" => |args, _| {
+//!         LEDS.store(args.next_u32()? as u8, Ordering::Relaxed);
+//!         Ok(())
+//!     },
+//! };
+//!
+//! let mut shell = Shell::<32>::new(COMMANDS);
+//! shell.prompt(&mut ser);
+//! loop {
+//!     shell.poll(&mut ser);
+//! }
+//! ```
+//!
+//! `help` is built in; `help <cmd>` shows a single command's usage. Lines are
+//! echoed as typed, and backspace works, but there is no history or cursor
+//! movement; the AttoSoC doesn't have the RAM to spare.
+
+use crate::error::Describe;
+use crate::hal::serial::Serial;
+use crate::num;
+
+/// Somewhere for the shell and commands to write to.
+pub trait Output {
+    fn write_bytes(&mut self, bytes: &[u8]);
+
+    fn write_str(&mut self, s: &str) {
+        self.write_bytes(s.as_bytes());
+    }
+}
+
+impl Output for Serial {
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        Serial::write_bytes(self, bytes);
+    }
+}
+
+/// What a command does. Errors are printed as `error: <msg>`.
+pub type Handler = fn(&mut Args<'_>, &mut dyn Output) -> Result<(), &'static str>;
+
+pub struct Command {
+    pub name: &'static str,
+    /// Arguments, e.g. `"<addr> [len]"`.
+    pub usage: &'static str,
+    /// One line about what the command does.
+    pub help: &'static str,
+    pub run: Handler,
+}
+
+/// A command's arguments, split on whitespace.
+pub struct Args<'a>(&'a str);
+
+impl<'a> Args<'a> {
+    pub fn new(line: &'a str) -> Self {
+        Self(line)
+    }
+
+    /// Everything not yet taken, as one (trimmed) string, e.g. for a
+    /// command that takes an expression.
+    pub fn rest(&mut self) -> &'a str {
+        core::mem::take(&mut self.0).trim_matches(|c: char| c.is_ascii_whitespace())
+    }
+
+    pub fn next_str(&mut self) -> Result<&'a str, &'static str> {
+        self.next().ok_or("missing argument")
+    }
+
+    /// The next argument as a number. See [`num::parse`] for the syntax.
+    pub fn next_num<T: TryFrom<u64> + TryFrom<i64>>(&mut self) -> Result<T, &'static str> {
+        num::parse(self.next_str()?).map_err(|e| e.describe())
+    }
+
+    pub fn next_u32(&mut self) -> Result<u32, &'static str> {
+        self.next_num()
+    }
+}
+
+impl<'a> Iterator for Args<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        let s = self.0.trim_start_matches(|c: char| c.is_ascii_whitespace());
+        let end = s.find(|c: char| c.is_ascii_whitespace()).unwrap_or(s.len());
+        self.0 = &s[end..];
+        (end > 0).then_some(&s[..end])
+    }
+}
+
+const PROMPT: &[u8] = b"> ";
+
+/// Line editor and dispatcher. `N` is the longest accepted line.
+pub struct Shell<const N: usize> {
+    commands: &'static [Command],
+    line: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> Shell<N> {
+    pub const fn new(commands: &'static [Command]) -> Self {
+        Self {
+            commands,
+            line: [0; N],
+            len: 0,
+        }
+    }
+
+    pub fn prompt(&self, out: &mut dyn Output) {
+        out.write_bytes(PROMPT);
+    }
+
+    /// Read a byte from the UART, if one has arrived, and handle it.
+    pub fn poll(&mut self, ser: &mut Serial) {
+        if let Some(b) = ser.read_byte() {
+            self.feed(b, ser);
+        }
+    }
+
+    /// Handle one received byte: edit the line, or run it on CR/LF.
+    pub fn feed(&mut self, b: u8, out: &mut dyn Output) {
+        match b {
+            b'\r' | b'\n' => {
+                out.write_bytes(b"\r\n");
+                let len = core::mem::take(&mut self.len);
+                // Only printable ASCII is ever stored.
+                if let Ok(line) = core::str::from_utf8(&self.line[..len]) {
+                    self.run(line, out);
+                }
+                out.write_bytes(PROMPT);
+            }
+            0x08 | 0x7f if self.len > 0 => {
+                self.len -= 1;
+                out.write_bytes(b"\x08 \x08");
+            }
+            0x20..=0x7e if self.len < N => {
+                self.line[self.len] = b;
+                self.len += 1;
+                out.write_bytes(&[b]);
+            }
+            // Bell on a full line or unsupported key.
+            _ => out.write_bytes(b"\x07"),
+        }
+    }
+
+    /// Run a complete line.
+    pub fn run(&self, line: &str, out: &mut dyn Output) {
+        let mut args = Args::new(line);
+        let Some(name) = args.next() else {
+            return;
+        };
+
+        if name == "help" {
+            self.help(args.next(), out);
+            return;
+        }
+
+        match self.commands.iter().find(|c| c.name == name) {
+            Some(cmd) => {
+                if let Err(msg) = (cmd.run)(&mut args, out) {
+                    out.write_str("error: ");
+                    out.write_str(msg);
+                    out.write_bytes(b"\r\n");
+                }
+            }
+            None => {
+                out.write_str("unknown command: ");
+                out.write_str(name);
+                out.write_str(" (try help)\r\n");
+            }
+        }
+    }
+
+    fn help(&self, topic: Option<&str>, out: &mut dyn Output) {
+        let usage = |cmd: &Command, out: &mut dyn Output| {
+            out.write_str(cmd.name);
+            if !cmd.usage.is_empty() {
+                out.write_bytes(b" ");
+                out.write_str(cmd.usage);
+            }
+        };
+
+        match topic {
+            Some(name) => match self.commands.iter().find(|c| c.name == name) {
+                Some(cmd) => {
+                    out.write_str("usage: ");
+                    usage(cmd, out);
+                    out.write_str("\r\n  ");
+                    out.write_str(cmd.help.trim());
+                    out.write_bytes(b"\r\n");
+                }
+                None => {
+                    out.write_str("no such command: ");
+                    out.write_str(name);
+                    out.write_bytes(b"\r\n");
+                }
+            },
+            None => {
+                for cmd in self.commands {
+                    usage(cmd, out);
+                    out.write_str(" - ");
+                    out.write_str(cmd.help.trim());
+                    out.write_bytes(b"\r\n");
+                }
+                out.write_str("help [cmd] - this text\r\n");
+            }
+        }
+    }
+}
+
+/// Build a `&'static [Command]` table. Each entry is a doc comment (the
+/// help text), the command name, an optional usage string, and the handler:
+///
+/// ```ignore
+/// sentinel_rt::commands! {
+///     /// Print the uptime in ticks.
+///     uptime => cmd_uptime,
+///     /// Peek at memory.
+///     peek "<addr>" => cmd_peek,
+/// }
+/// ```
+#[macro_export]
+macro_rules! commands {
+    ($($(#[doc = $help:literal])+ $name:ident $($usage:literal)? => $run:expr),*
+     $(,)?) => {
+        &[$($crate::shell::Command {
+            name: stringify!($name),
+            usage: concat!("" $(, $usage)?),
+            help: concat!($($help),+),
+            run: $run,
+        }),*]
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use heapless::Vec;
+
+    use super::*;
+
+    impl Output for Vec<u8, 256> {
+        fn write_bytes(&mut self, bytes: &[u8]) {
+            self.extend_from_slice(bytes).unwrap();
+        }
+    }
+
+    fn add(args: &mut Args<'_>, out: &mut dyn Output) -> Result<(), &'static str> {
+        let sum = args.next_u32()? + args.next_u32()?;
+        out.write_bytes(&crate::hex::u32_digits(sum));
+        out.write_bytes(b"\r\n");
+        Ok(())
+    }
+
+    static COMMANDS: &[Command] = crate::commands! {
+        /// Add two numbers.
+        add "<a> <b>" => add,
+        /// Do nothing.
+        nop => |_, _| Ok(()),
+    };
+
+    fn type_line(shell: &mut Shell<16>, line: &[u8]) -> Vec<u8, 256> {
+        let mut out = Vec::new();
+        for b in line {
+            shell.feed(*b, &mut out);
+        }
+        out
+    }
+
+    #[test]
+    fn dispatch() {
+        let mut shell = Shell::<16>::new(COMMANDS);
+        assert_eq!(type_line(&mut shell, b"add 1 0x10\r"),
+                   b"add 1 0x10\r\n00000011\r\n> ".as_slice());
+        assert_eq!(type_line(&mut shell, b"add 1\r"),
+                   b"add 1\r\nerror: missing argument\r\n> ".as_slice());
+        assert_eq!(type_line(&mut shell, b"foo\r"),
+                   b"foo\r\nunknown command: foo (try help)\r\n> ".as_slice());
+    }
+
+    #[test]
+    fn args() {
+        let mut args = Args::new("  peek  0x10 (1 + 2) * 3 ");
+        assert_eq!(args.next(), Some("peek"));
+        assert_eq!(args.next_u32(), Ok(0x10));
+        assert_eq!(args.next_num::<u8>(), Err("num: bad digit"));
+        assert_eq!(args.rest(), "+ 2) * 3");
+        assert_eq!(args.next(), None);
+    }
+
+    #[test]
+    fn editing() {
+        let mut shell = Shell::<16>::new(COMMANDS);
+        let out = type_line(&mut shell, b"nopx\x7f\r");
+        assert_eq!(out, b"nopx\x08 \x08\r\n> ".as_slice());
+
+        // Overlong lines are cut off, with a bell.
+        let out = type_line(&mut shell, &[b'a'; 17]);
+        assert_eq!(out.last(), Some(&0x07));
+    }
+
+    #[test]
+    fn help() {
+        let shell = Shell::<16>::new(COMMANDS);
+        let mut out = Vec::<u8, 256>::new();
+        shell.run("help", &mut out);
+        assert_eq!(out, b"add <a> <b> - Add two numbers.\r\n\
+                          nop - Do nothing.\r\n\
+                          help [cmd] - this text\r\n".as_slice());
+
+        out.clear();
+        shell.run("help add", &mut out);
+        assert_eq!(out, b"usage: add <a> <b>\r\n  Add two numbers.\r\n".as_slice());
+    }
+}
@@ -0,0 +1,89 @@
+//! Signature region support for riscv-arch-test/RISCOF style tests.
+//!
+//! `sentinel.x` collects every `.signature` input section into one 16-byte
+//! aligned output section bracketed by `begin_signature`/`end_signature`,
+//! the same symbols the arch-test macros emit. Tests written in Rust place
+//! their results there with:
+//!
+//! ```ignore
+//! #[link_section = ".signature"]
+//! static mut SIG: [u32; 4] = [0xdeadbeef; 4];
+//! ```
+//!
+//! When the test is done, results are collected either by handing the region
+//! bounds to the simulator ([`report_to_host`]), or by dumping the region as
+//! text ([`dump`]): over the UART, or to the testbench's
+//! [`sim`](crate::sim) ports ([`dump_sim`]), so that what `sentinel-runner`
+//! prints is the signature file.
+//!
+//! An arch-test test linked against sentinel-rt (its code called from
+//! `main`) can end with `j sentinel_rvmodel_halt` as its `RVMODEL_HALT`,
+//! which is [`dump_sim`].
+
+use core::ptr::{read_volatile, write_volatile};
+
+use crate::hal::serial::Serial;
+use crate::{hex, mem, sim};
+
+/// Address the RISCOF sentinel plugin watches for the signature bounds.
+/// `begin_signature` is written to offset 0, then `end_signature` to 4.
+pub const HOST_PORT: u32 = 0x0400_0000;
+
+/// Number of 32-bit words in the signature region.
+pub fn len() -> usize {
+    mem::signature().len() / 4
+}
+
+/// Iterate over the current contents of the signature region.
+pub fn words() -> impl Iterator<Item = u32> {
+    let begin = mem::signature().start as *const u32;
+
+    // SAFETY: Every address in [begin_signature, end_signature) is valid
+    // RAM. Volatile because the test may have written these behind our back
+    // through raw pointers.
+    (0..len()).map(move |i| unsafe { read_volatile(begin.add(i)) })
+}
+
+/// Hand the signature region bounds to the simulator, RVMODEL_HALT style,
+/// and spin until the simulation is stopped.
+pub fn report_to_host() -> ! {
+    let sig = mem::signature();
+    let port = HOST_PORT as *mut u32;
+
+    loop {
+        // SAFETY: The host port is a testbench address, not RAM.
+        unsafe {
+            write_volatile(port, sig.start as u32);
+            write_volatile(port.add(1), sig.end as u32);
+        }
+    }
+}
+
+/// Write the signature region out one word per line as 8 lowercase hex
+/// digits; the same format RISCOF expects from the `.signature` file.
+pub fn dump(mut put: impl FnMut(u8)) {
+    for word in words() {
+        for d in hex::u32_digits(word) {
+            put(d);
+        }
+        put(b'\n');
+    }
+}
+
+/// [`dump`] the signature region over the UART.
+pub fn dump_serial(serial: &mut Serial) {
+    dump(|b| serial.write_byte(b))
+}
+
+/// [`dump`] the signature region to the testbench's putchar port, then
+/// end the simulation with [`sim::exit`].
+pub fn dump_sim() -> ! {
+    dump(sim::putchar);
+    sim::exit(0)
+}
+
+/// [`dump_sim`], for assembly tests to jump to.
+#[export_name = "sentinel_rvmodel_halt"]
+pub extern "C" fn rvmodel_halt() -> ! {
+    dump_sim()
+}
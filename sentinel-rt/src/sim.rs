@@ -0,0 +1,79 @@
+//! Talking to the testbench, when running in simulation.
+//!
+//! A test binary run in the Amaranth or Verilator simulation has no one to
+//! read its UART or notice that it's done, and would spin until the
+//! simulation times out. Instead, it writes to testbench addresses next to
+//! the RISCOF [`HOST_PORT`](crate::signature::HOST_PORT):
+//!
+//! * [`EXIT`]: [`exit`] writes the exit code there, 0 for a pass, and the
+//!   testbench stops the simulation with it.
+//! * [`PUTCHAR`]: [`putchar`] writes a byte there, for the testbench to
+//!   print, without waiting on a simulated UART.
+//!
+//! ```ignore
+//! console::set_output(sim::write_bytes);
+//! sprintln!("{} checks", n);
+//! sim::exit(if failed == 0 { 0 } else { 1 });
+//! ```
+//!
+//! Neither is RAM or a peripheral: on hardware, they write to nowhere.
+//! `sentinel-runner`'s simulation, a cargo runner, handles both.
+//!
+//! With the `sim` feature, sentinel-rt's panic handler prints the message
+//! there and exits with [`PANIC_CODE`], rather than doing what its
+//! [`Policy`](crate::panic::Policy) says, so a failed assertion fails the
+//! test.
+
+use core::fmt::{self, Write};
+use core::ptr::write_volatile;
+
+use crate::signature::HOST_PORT;
+
+/// Address [`exit`] writes the exit code to.
+pub const EXIT: u32 = HOST_PORT + 8;
+
+/// Address [`putchar`] writes each byte to.
+pub const PUTCHAR: u32 = HOST_PORT + 12;
+
+/// What a panic exits with, as for a Rust program on a host.
+pub const PANIC_CODE: u32 = 101;
+
+/// End the simulation with `code`; 0 for a pass. Spins, writing it again,
+/// until the testbench stops.
+pub fn exit(code: u32) -> ! {
+    riscv::interrupt::disable();
+    loop {
+        // SAFETY: EXIT is a testbench address, not RAM.
+        unsafe { write_volatile(EXIT as *mut u32, code) };
+    }
+}
+
+/// Have the testbench print `b`.
+pub fn putchar(b: u8) {
+    // SAFETY: PUTCHAR is a testbench address, not RAM.
+    unsafe { write_volatile(PUTCHAR as *mut u32, b as u32) };
+}
+
+/// Have the testbench print `bytes`; an output for the
+/// [console](crate::console).
+pub fn write_bytes(bytes: &[u8]) {
+    for &b in bytes {
+        putchar(b);
+    }
+}
+
+struct Writer;
+
+impl Write for Writer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        write_bytes(s.as_bytes());
+        Ok(())
+    }
+}
+
+/// Print the panic message, and [`exit`] with [`PANIC_CODE`]. What
+/// [`panic::handle`](crate::panic::handle) does with the `sim` feature.
+pub fn panic(info: &core::panic::PanicInfo) -> ! {
+    let _ = writeln!(Writer, "{}", info);
+    exit(PANIC_CODE)
+}
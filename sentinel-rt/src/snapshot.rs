@@ -0,0 +1,243 @@
+//! Snapshot/restore of registered statics, for simulation fast-forward.
+//!
+//! Statics are registered with [`snapshot!`](crate::snapshot!). [`save`]
+//! serializes all of them into the snapshot area (`_snapshot_size` bytes at
+//! the end of `.noinit`, which start-up code leaves alone), and [`restore`]
+//! copies them back. A simulation can run the expensive warm-up once, dump
+//! the snapshot area, and preload it into RAM on later runs; firmware that
+//! calls [`restore`] first thing in `main` then resumes from the snapshot.
+//!
+//! Set the size of the area in your device linker script, before
+//! `INCLUDE sentinel.x`:
+//!
+//! ```text
+//! _snapshot_size = 256;
+//! ```
+//!
+//! A snapshot is a header (magic, payload length, CRC-32 of the payload)
+//! followed by the raw bytes of each registered static, in link order.
+//! Snapshots from a differently-laid-out firmware are rejected by the length
+//! check, or failing that, the CRC.
+
+use core::ops::Range;
+use core::ptr::{read_volatile, write_volatile};
+
+use crate::checksum::Crc32;
+use crate::mem;
+
+/// A registered static. Created by [`snapshot!`](crate::snapshot!).
+#[repr(C)]
+pub struct Entry {
+    ptr: *mut u8,
+    len: usize,
+}
+
+// SAFETY: Entries are only ever read, and only to find the statics they
+// point to.
+unsafe impl Sync for Entry {}
+
+impl Entry {
+    #[doc(hidden)]
+    pub const fn new<T>(ptr: *mut T) -> Self {
+        Self {
+            ptr: ptr as *mut u8,
+            len: core::mem::size_of::<T>(),
+        }
+    }
+}
+
+/// Register statics for snapshotting. [`restore`] writes them, so they
+/// have to be `static mut`s; anything else fails to compile.
+///
+/// ```ignore
+/// static mut GENERATION: u32 = 0;
+/// static mut CELLS: [u8; 64] = [0; 64];
+///
+/// sentinel_rt::snapshot!(GENERATION, CELLS);
+/// ```
+#[macro_export]
+macro_rules! snapshot {
+    ($($name:ident),+ $(,)?) => {
+        $(
+            const _: () = {
+                #[used]
+                #[link_section = ".sentinel.snapshot"]
+                static ENTRY: $crate::snapshot::Entry =
+                    $crate::snapshot::Entry::new(
+                        ::core::ptr::addr_of_mut!($name)
+                    );
+            };
+        )+
+    };
+}
+
+const MAGIC: u32 = 0x5041_4e53; // "SNAP"
+const HEADER_LEN: usize = 12;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    /// Snapshot area is smaller than the registered statics plus a header.
+    TooSmall,
+    /// No snapshot present.
+    NoSnapshot,
+    /// Snapshot was taken from a firmware with a different set of statics.
+    LayoutMismatch,
+    /// Snapshot is corrupt.
+    BadChecksum,
+}
+
+fn entries() -> &'static [Entry] {
+    let table = mem::snapshot_table();
+    let len = table.len() / core::mem::size_of::<Entry>();
+
+    // SAFETY: The linker collects only `Entry`s into the table.
+    unsafe { core::slice::from_raw_parts(table.start as *const Entry, len) }
+}
+
+/// Total size of the registered statics, in bytes.
+pub fn payload_len() -> usize {
+    len(entries())
+}
+
+fn len(entries: &[Entry]) -> usize {
+    entries.iter().map(|e| e.len).sum()
+}
+
+fn payload_bytes(entries: &[Entry]) -> impl Iterator<Item = *mut u8> + '_ {
+    entries
+        .iter()
+        // SAFETY: Each entry describes a whole static.
+        .flat_map(|e| (0..e.len).map(move |i| unsafe { e.ptr.add(i) }))
+}
+
+/// Serialize all registered statics into the snapshot area. Interrupts are
+/// disabled while copying so the snapshot is consistent.
+pub fn save() -> Result<(), Error> {
+    save_to(mem::snapshot(), entries())
+}
+
+fn save_to(area: Range<usize>, entries: &[Entry]) -> Result<(), Error> {
+    let payload_len = len(entries);
+    if area.len() < HEADER_LEN + payload_len {
+        return Err(Error::TooSmall);
+    }
+
+    let base = area.start as *mut u8;
+    critical_section::with(|_| {
+        let mut crc = Crc32::new();
+        for (i, src) in payload_bytes(entries).enumerate() {
+            // SAFETY: `src` is inside a registered static, and the
+            // destination was bounds-checked above.
+            unsafe {
+                let b = read_volatile(src);
+                crc.update_byte(b);
+                write_volatile(base.add(HEADER_LEN + i), b);
+            }
+        }
+
+        let header = [MAGIC, payload_len as u32, crc.finish()];
+        for (i, w) in header.iter().enumerate() {
+            // SAFETY: The snapshot area is word-aligned and big enough.
+            unsafe { write_volatile((base as *mut u32).add(i), *w) };
+        }
+    });
+
+    Ok(())
+}
+
+/// Validate the snapshot area and copy its contents back into the
+/// registered statics.
+///
+/// # Safety
+///
+/// Nothing may hold a reference to, or otherwise be using, any registered
+/// static. In practice, call this first thing in `main`.
+pub unsafe fn restore() -> Result<(), Error> {
+    restore_from(mem::snapshot(), entries())
+}
+
+/// # Safety
+///
+/// As for [`restore`], and `area` must be readable.
+unsafe fn restore_from(area: Range<usize>, entries: &[Entry]) -> Result<(), Error> {
+    if area.len() < HEADER_LEN {
+        return Err(Error::TooSmall);
+    }
+
+    let base = area.start as *mut u8;
+    let header = base as *const u32;
+    if read_volatile(header) != MAGIC {
+        return Err(Error::NoSnapshot);
+    }
+
+    let payload_len = len(entries);
+    if read_volatile(header.add(1)) as usize != payload_len
+        || area.len() < HEADER_LEN + payload_len {
+        return Err(Error::LayoutMismatch);
+    }
+
+    let mut crc = Crc32::new();
+    for i in 0..payload_len {
+        crc.update_byte(read_volatile(base.add(HEADER_LEN + i)));
+    }
+    if read_volatile(header.add(2)) != crc.finish() {
+        return Err(Error::BadChecksum);
+    }
+
+    for (i, dst) in payload_bytes(entries).enumerate() {
+        write_volatile(dst, read_volatile(base.add(HEADER_LEN + i)));
+    }
+
+    Ok(())
+}
+
+/// Invalidate the snapshot area, so the next [`restore`] does nothing.
+pub fn discard() {
+    let area = mem::snapshot();
+    if area.len() >= HEADER_LEN {
+        // SAFETY: The snapshot area is word-aligned RAM.
+        unsafe { write_volatile(area.start as *mut u32, 0) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::ptr::addr_of_mut;
+
+    use super::*;
+
+    static mut REGISTERED: u16 = 0;
+    crate::snapshot!(REGISTERED);
+
+    #[test]
+    fn round_trip() {
+        let mut a: u32 = 0x1234_5678;
+        let mut b = [1_u8, 2, 3];
+        let entries = [Entry::new(addr_of_mut!(a)), Entry::new(addr_of_mut!(b))];
+        let mut buf = [0_u32; 5];
+        let words = buf.as_mut_ptr();
+        let area = words as usize..words as usize + 4 * buf.len();
+
+        assert_eq!(save_to(area.start..area.end - 4, &entries), Err(Error::TooSmall));
+        save_to(area.clone(), &entries).unwrap();
+        (a, b) = (0, [0; 3]);
+        // SAFETY: Nothing else is using `a` or `b`, and `area` is `buf`.
+        unsafe { restore_from(area.clone(), &entries) }.unwrap();
+        assert_eq!((a, b), (0x1234_5678, [1, 2, 3]));
+
+        // A different set of statics, or a corrupt payload, isn't restored.
+        // SAFETY: As above.
+        let res = unsafe { restore_from(area.clone(), &entries[..1]) };
+        assert_eq!(res, Err(Error::LayoutMismatch));
+        // SAFETY: Inside `buf`.
+        unsafe { *words.add(4) ^= 1 };
+        // SAFETY: As above.
+        let res = unsafe { restore_from(area.clone(), &entries) };
+        assert_eq!(res, Err(Error::BadChecksum));
+        // SAFETY: As above.
+        unsafe { *words = 0 };
+        // SAFETY: As above.
+        let res = unsafe { restore_from(area, &entries) };
+        assert_eq!(res, Err(Error::NoSnapshot));
+    }
+}
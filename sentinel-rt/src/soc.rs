@@ -0,0 +1,144 @@
+//! One-call SoC bring-up.
+//!
+//! ```ignore
+//! let mut soc = Soc::builder().interrupts(true).init();
+//! soc.gpio.set_leds(0xff);
+//! ```
+//!
+//! replaces detecting the peripheral bus, constructing each driver, and
+//! enabling `mie.MEIE`/`mstatus.MIE` by hand.
+//!
+//! The drivers are handed out once: a second [`Soc::take`] gets `None`
+//! (and a second [`Soc::init`] panics), as does
+//! [`pac::Peripherals::take`](crate::pac::Peripherals::take) once the
+//! drivers are out, and vice versa. The drivers aren't `Clone`, so passing
+//! one to a task hands the peripheral over. `Gpio::new` and friends remain,
+//! for ISRs that can't be handed a driver; making sure they don't fight
+//! the owner is up to them.
+
+use portable_atomic::{AtomicBool, Ordering};
+use riscv::register::{mie, mstatus};
+
+use crate::events::{self, Event};
+use crate::hal::{gpio::Gpio, serial::Serial, timer::Timer};
+use crate::io_addrs::{self, Bus};
+use crate::{board, reset};
+
+/// Clock configuration of the SoC.
+#[derive(Clone, Copy)]
+pub struct Clocks {
+    pub sysclk_hz: u32,
+    pub cycles_per_tick: u32,
+}
+
+impl Clocks {
+    /// Timer tick rate, rounded to the nearest Hz.
+    pub const fn tick_hz(&self) -> u32 {
+        (self.sysclk_hz + self.cycles_per_tick / 2) / self.cycles_per_tick
+    }
+}
+
+pub struct Soc {
+    pub bus: Bus,
+    pub clocks: Clocks,
+    pub gpio: Gpio,
+    pub serial: Serial,
+    pub timer: Timer,
+}
+
+pub struct SocBuilder {
+    interrupts: bool,
+}
+
+static TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// Claim the peripherals, for [`Soc`] or [`pac`](crate::pac). Only the
+/// first call gets `true`.
+pub(crate) fn claim() -> bool {
+    !TAKEN.swap(true, Ordering::SeqCst)
+}
+
+impl Soc {
+    pub fn builder() -> SocBuilder {
+        SocBuilder { interrupts: false }
+    }
+
+    /// Bring up the SoC with the default configuration (interrupts left
+    /// disabled).
+    pub fn init() -> Self {
+        Self::builder().init()
+    }
+
+    /// Like [`init`](Self::init), but `None` if the peripherals have
+    /// already been taken.
+    pub fn take() -> Option<Self> {
+        Self::builder().take()
+    }
+}
+
+impl SocBuilder {
+    /// Enable the external interrupt once the drivers are set up. Requires a
+    /// `MachineExternal` handler that services every peripheral's IRQ; the
+    /// Wishbone serial port has one pending at reset.
+    ///
+    /// Not available in `polled` builds.
+    pub fn interrupts(mut self, enable: bool) -> Self {
+        self.interrupts = enable;
+        self
+    }
+
+    /// Detect the peripheral bus and construct the drivers.
+    ///
+    /// # Panics
+    ///
+    /// If the peripherals have already been taken, or as for
+    /// [`take`](Self::take).
+    pub fn init(self) -> Soc {
+        self.take().expect("Soc::init: peripherals already taken")
+    }
+
+    /// Like [`init`](Self::init), but `None` if the peripherals have
+    /// already been taken.
+    ///
+    /// # Panics
+    ///
+    /// If called after interrupts have been enabled (bus detection depends
+    /// on nothing having serviced the reset-time IRQ yet), or if asked to
+    /// enable interrupts in a `polled` build.
+    pub fn take(self) -> Option<Soc> {
+        assert!(!mstatus::read().mie(), "Soc::init called with interrupts on");
+        assert!(!(self.interrupts && cfg!(feature = "polled")),
+                "interrupts requested in a polled build");
+        if !claim() {
+            return None;
+        }
+
+        // SAFETY: Interrupts are disabled, and this is the only detection
+        // ever done.
+        let bases = unsafe { io_addrs::detect() };
+        #[cfg(feature = "stack-paint")]
+        crate::stack::paint();
+        events::record(Event::Boot(reset::peek_reason()));
+
+        let soc = Soc {
+            bus: bases.bus,
+            clocks: Clocks {
+                sysclk_hz: board::CURRENT.clk_hz,
+                cycles_per_tick: board::CURRENT.cycles_per_tick,
+            },
+            gpio: Gpio::new(bases.gpio),
+            serial: Serial::new(bases.serial),
+            timer: Timer::new(bases.timer),
+        };
+
+        if self.interrupts {
+            // SAFETY: Drivers are set up, and detection has happened.
+            unsafe {
+                mie::set_mext();
+                mstatus::set_mie();
+            }
+        }
+
+        Some(soc)
+    }
+}
@@ -0,0 +1,278 @@
+//! Lock-free single-producer, single-consumer byte queue.
+//!
+//! In the style of `bbqueue`: each side asks for a grant of contiguous
+//! bytes, works on it in place, and then commits (or releases) however
+//! much it used. Each index is only ever written by one side, so no
+//! operation needs a read-modify-write or a critical section; on RV32I they
+//! are plain loads and stores. That makes it suitable for a UART TX path,
+//! where the main loop produces and the ISR drains:
+//!
+//! ```ignore
+//! static TXQ: Queue<64> = Queue::new();
+//! static TX: Mutex<RefCell<Option<Consumer<'static, 64>>>> = Mutex::new(RefCell::new(None));
+//!
+//! let (mut tx, cons) = TXQ.split().unwrap();
+//! critical_section::with(|cs| TX.replace(cs, Some(cons)));
+//! tx.write(b"hello\r\n");
+//!
+//! // In the ISR, where interrupts are off anyway:
+//! critical_section::with(|cs| {
+//!     if let Some(b) = TX.borrow_ref_mut(cs).as_mut().and_then(Consumer::pop) {
+//!         serial.start_write(b);
+//!     }
+//! });
+//! ```
+//!
+//! [`Producer`] and [`Consumer`] are `Send`, so they can be moved to
+//! wherever they're used without `unsafe`.
+//!
+//! To queue values one at a time with no ends to park anywhere, use an
+//! [`IrqChannel`](crate::channel::IrqChannel) instead.
+
+use core::cell::UnsafeCell;
+use core::ops::{Deref, DerefMut};
+
+use portable_atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// A queue of `N` bytes; `N` must be a power of two.
+pub struct Queue<const N: usize> {
+    buf: UnsafeCell<[u8; N]>,
+    /// Free-running counts of bytes committed and released. Only the
+    /// producer writes `write`, only the consumer `read`.
+    write: AtomicUsize,
+    read: AtomicUsize,
+    split: AtomicBool,
+}
+
+// SAFETY: The producer only touches bytes between `write` and `read + N`,
+// the consumer only those between `read` and `write`, and each publishes
+// its index (Release) only after it's done with the bytes it passes over.
+unsafe impl<const N: usize> Sync for Queue<N> {}
+
+impl<const N: usize> Default for Queue<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Queue<N> {
+    const POWER_OF_TWO: () = assert!(N.is_power_of_two(), "queue length must be a power of two");
+
+    pub const fn new() -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::POWER_OF_TWO;
+        Self {
+            buf: UnsafeCell::new([0; N]),
+            write: AtomicUsize::new(0),
+            read: AtomicUsize::new(0),
+            split: AtomicBool::new(false),
+        }
+    }
+
+    /// The two ends of the queue, the first time this is called; `None`
+    /// after that.
+    pub fn split(&self) -> Option<(Producer<'_, N>, Consumer<'_, N>)> {
+        if self.split.swap(true, Ordering::AcqRel) {
+            return None;
+        }
+        Some((Producer { q: self }, Consumer { q: self }))
+    }
+
+    /// Bytes committed and not yet released.
+    pub fn len(&self) -> usize {
+        let w = self.write.load(Ordering::Acquire);
+        w.wrapping_sub(self.read.load(Ordering::Acquire))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn bytes(&self, start: usize, len: usize) -> *mut u8 {
+        debug_assert!(start % N + len <= N);
+        // SAFETY: In bounds, per the assertion.
+        unsafe { (self.buf.get() as *mut u8).add(start % N) }
+    }
+}
+
+/// The writing end of a [`Queue`].
+pub struct Producer<'a, const N: usize> {
+    q: &'a Queue<N>,
+}
+
+impl<'a, const N: usize> Producer<'a, N> {
+    /// Room for up to `max` bytes, contiguous, so possibly fewer than are
+    /// free if the space wraps around. `None` if the queue is full.
+    pub fn grant(&mut self, max: usize) -> Option<GrantW<'_, 'a, N>> {
+        let w = self.q.write.load(Ordering::Relaxed);
+        let r = self.q.read.load(Ordering::Acquire);
+        let free = N - w.wrapping_sub(r);
+        let len = free.min(N - w % N).min(max);
+        if len == 0 {
+            return None;
+        }
+        Some(GrantW { p: self, start: w, len })
+    }
+
+    /// Queue one byte; whether there was room.
+    pub fn push(&mut self, b: u8) -> bool {
+        match self.grant(1) {
+            Some(mut g) => {
+                g[0] = b;
+                g.commit(1);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Queue as much of `bytes` as fits, and return how much that was.
+    pub fn write(&mut self, bytes: &[u8]) -> usize {
+        let mut n = 0;
+        while let Some(mut g) = self.grant(bytes.len() - n) {
+            let len = g.len();
+            g.copy_from_slice(&bytes[n..n + len]);
+            g.commit(len);
+            n += len;
+        }
+        n
+    }
+
+    /// Room left.
+    pub fn free(&self) -> usize {
+        N - self.q.len()
+    }
+}
+
+/// The reading end of a [`Queue`].
+pub struct Consumer<'a, const N: usize> {
+    q: &'a Queue<N>,
+}
+
+impl<'a, const N: usize> Consumer<'a, N> {
+    /// The oldest queued bytes, contiguous, so possibly not all of them if
+    /// they wrap around. `None` if the queue is empty.
+    pub fn read(&mut self) -> Option<GrantR<'_, 'a, N>> {
+        let r = self.q.read.load(Ordering::Relaxed);
+        let w = self.q.write.load(Ordering::Acquire);
+        let len = w.wrapping_sub(r).min(N - r % N);
+        if len == 0 {
+            return None;
+        }
+        Some(GrantR { c: self, start: r, len })
+    }
+
+    /// Take the oldest byte.
+    pub fn pop(&mut self) -> Option<u8> {
+        let g = self.read()?;
+        let b = g[0];
+        g.release(1);
+        Some(b)
+    }
+
+    /// Bytes queued.
+    pub fn len(&self) -> usize {
+        self.q.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.q.is_empty()
+    }
+}
+
+/// Contiguous free space in a [`Queue`], from [`Producer::grant`].
+pub struct GrantW<'p, 'a, const N: usize> {
+    p: &'p mut Producer<'a, N>,
+    start: usize,
+    len: usize,
+}
+
+impl<const N: usize> GrantW<'_, '_, N> {
+    /// Make the first `n` bytes of the grant visible to the consumer.
+    /// Dropping a grant commits nothing.
+    pub fn commit(self, n: usize) {
+        let n = n.min(self.len);
+        self.p.q.write.store(self.start.wrapping_add(n), Ordering::Release);
+    }
+}
+
+impl<const N: usize> Deref for GrantW<'_, '_, N> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // SAFETY: Only the producer uses these bytes until it commits them,
+        // and the grant borrows the producer.
+        unsafe { core::slice::from_raw_parts(self.p.q.bytes(self.start, self.len), self.len) }
+    }
+}
+
+impl<const N: usize> DerefMut for GrantW<'_, '_, N> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        // SAFETY: See deref.
+        unsafe { core::slice::from_raw_parts_mut(self.p.q.bytes(self.start, self.len), self.len) }
+    }
+}
+
+/// Contiguous queued bytes, from [`Consumer::read`].
+pub struct GrantR<'c, 'a, const N: usize> {
+    c: &'c mut Consumer<'a, N>,
+    start: usize,
+    len: usize,
+}
+
+impl<const N: usize> GrantR<'_, '_, N> {
+    /// Hand the first `n` bytes of the grant back to the producer.
+    /// Dropping a grant releases nothing.
+    pub fn release(self, n: usize) {
+        let n = n.min(self.len);
+        self.c.q.read.store(self.start.wrapping_add(n), Ordering::Release);
+    }
+}
+
+impl<const N: usize> Deref for GrantR<'_, '_, N> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // SAFETY: Only the consumer uses these bytes until it releases
+        // them, and the grant borrows the consumer.
+        unsafe { core::slice::from_raw_parts(self.c.q.bytes(self.start, self.len), self.len) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wraps() {
+        let q = Queue::<4>::new();
+        let (mut p, mut c) = q.split().unwrap();
+        assert!(q.split().is_none());
+
+        assert_eq!(p.write(b"abc"), 3);
+        assert_eq!([c.pop(), c.pop()], [Some(b'a'), Some(b'b')]);
+        // Three free, but only one before the end.
+        assert_eq!(p.grant(4).map(|g| g.len()), Some(1));
+        assert_eq!(p.write(b"defg"), 3);
+        assert_eq!(p.free(), 0);
+        assert!(!p.push(b'h'));
+
+        let g = c.read().unwrap();
+        assert_eq!(&*g, b"cd");
+        g.release(2);
+        assert_eq!(&*c.read().unwrap(), b"ef");
+        assert_eq!(c.len(), 2);
+    }
+
+    #[test]
+    fn partial_commit() {
+        let q = Queue::<8>::new();
+        let (mut p, mut c) = q.split().unwrap();
+        let mut g = p.grant(8).unwrap();
+        g[..2].copy_from_slice(b"hi");
+        g.commit(2);
+        // Dropped without committing.
+        assert_eq!(p.grant(8).map(|g| g.len()), Some(6));
+        assert_eq!(&*c.read().unwrap(), b"hi");
+    }
+}
@@ -0,0 +1,209 @@
+//! Driver for 128x64 SSD1306 OLED displays on I2C.
+//!
+//! Drawing goes to a buffer in RAM, laid out like the display's: 8 pages
+//! of 8-pixel-tall columns, one byte per column, LSB at the top. [`flush`]
+//! sends the pages that changed since the last flush.
+//!
+//! ```ignore
+//! let mut oled = Ssd1306::new(I2c::new(GpioPins::new(soc.gpio, 0, 1)));
+//! oled.init()?;
+//! oled.set_pixel(10, 20, true);
+//! oled.flush()?;
+//! ```
+//!
+//! The buffer is 1 KiB, a quarter of the AttoSoC's RAM; keep the display in
+//! a `static` rather than on the stack.
+//!
+//! [`flush`]: Ssd1306::flush
+
+use crate::hal::i2c::{Error, I2c, Pins};
+
+pub const WIDTH: usize = 128;
+pub const HEIGHT: usize = 64;
+pub const PAGES: usize = HEIGHT / 8;
+
+/// Address with D/C# (SA0) tied low. `0x3d` if tied high.
+pub const ADDR: u8 = 0x3c;
+
+// Control bytes: the rest of the transfer is commands, or display data.
+const CMD: u8 = 0x00;
+const DATA: u8 = 0x40;
+
+const DISPLAY_OFF: u8 = 0xae;
+const DISPLAY_ON: u8 = 0xaf;
+const SET_CONTRAST: u8 = 0x81;
+const NORMAL: u8 = 0xa6;
+const INVERT: u8 = 0xa7;
+
+/// For a 128x64 panel with the internal charge pump, page addressing.
+const INIT: &[u8] = &[
+    DISPLAY_OFF,
+    0xd5, 0x80, // Clock divide/oscillator: reset value.
+    0xa8, 0x3f, // Multiplex ratio: 64.
+    0xd3, 0x00, // Display offset: 0.
+    0x40,       // Start line: 0.
+    0x8d, 0x14, // Charge pump on.
+    0x20, 0x02, // Page addressing mode.
+    0xa1,       // Column 127 is SEG0, so the image isn't mirrored...
+    0xc8,       // ...and scan COM63 to COM0, so it isn't upside down.
+    0xda, 0x12, // COM pins: alternative, no remap.
+    SET_CONTRAST, 0xcf,
+    0xd9, 0xf1, // Precharge period, for the charge pump.
+    0xdb, 0x40, // VCOMH deselect level.
+    0xa4,       // Show RAM contents.
+    NORMAL,
+    DISPLAY_ON,
+];
+
+pub struct Ssd1306<P> {
+    i2c: I2c<P>,
+    addr: u8,
+    buf: [u8; WIDTH * PAGES],
+    /// Bit `n` set if page `n` needs flushing.
+    dirty: u8,
+}
+
+impl<P: Pins> Ssd1306<P> {
+    pub fn new(i2c: I2c<P>) -> Self {
+        Self::with_addr(i2c, ADDR)
+    }
+
+    pub fn with_addr(i2c: I2c<P>, addr: u8) -> Self {
+        Self {
+            i2c,
+            addr,
+            buf: [0; WIDTH * PAGES],
+            dirty: 0xff,
+        }
+    }
+
+    pub fn free(self) -> I2c<P> {
+        self.i2c
+    }
+
+    fn command(&mut self, cmds: &[u8]) -> Result<(), Error> {
+        self.i2c.write_vectored(self.addr, &[&[CMD], cmds])
+    }
+
+    /// Configure the panel and turn it on. The next [`flush`](Self::flush)
+    /// sends the whole buffer.
+    pub fn init(&mut self) -> Result<(), Error> {
+        self.command(INIT)?;
+        self.dirty = 0xff;
+        Ok(())
+    }
+
+    pub fn set_on(&mut self, on: bool) -> Result<(), Error> {
+        self.command(&[if on { DISPLAY_ON } else { DISPLAY_OFF }])
+    }
+
+    pub fn set_contrast(&mut self, contrast: u8) -> Result<(), Error> {
+        self.command(&[SET_CONTRAST, contrast])
+    }
+
+    /// Swap lit and unlit pixels, without touching the buffer.
+    pub fn set_inverted(&mut self, inverted: bool) -> Result<(), Error> {
+        self.command(&[if inverted { INVERT } else { NORMAL }])
+    }
+
+    /// The raw buffer: `buf[page * WIDTH + x]`, bit `y % 8`.
+    pub fn buffer(&self) -> &[u8; WIDTH * PAGES] {
+        &self.buf
+    }
+
+    /// Like [`buffer`](Self::buffer), but marks everything for flushing.
+    pub fn buffer_mut(&mut self) -> &mut [u8; WIDTH * PAGES] {
+        self.dirty = 0xff;
+        &mut self.buf
+    }
+
+    pub fn clear(&mut self) {
+        self.buffer_mut().fill(0);
+    }
+
+    /// Light (or not) the pixel at `(x, y)`. Out-of-range pixels are
+    /// ignored.
+    pub fn set_pixel(&mut self, x: usize, y: usize, on: bool) {
+        if x >= WIDTH || y >= HEIGHT {
+            return;
+        }
+
+        let page = y / 8;
+        let byte = &mut self.buf[page * WIDTH + x];
+        let old = *byte;
+        if on {
+            *byte |= 1 << (y % 8);
+        } else {
+            *byte &= !(1 << (y % 8));
+        }
+
+        if *byte != old {
+            self.dirty |= 1 << page;
+        }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        x < WIDTH && y < HEIGHT && (self.buf[(y / 8) * WIDTH + x] >> (y % 8)) & 1 != 0
+    }
+
+    /// Send the pages that changed since the last flush.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        for page in 0..PAGES {
+            if self.dirty & (1 << page) == 0 {
+                continue;
+            }
+
+            // Page start, column 0 (low then high nibble).
+            self.command(&[0xb0 | page as u8, 0x00, 0x10])?;
+            let data = &self.buf[page * WIDTH..(page + 1) * WIDTH];
+            self.i2c.write_vectored(self.addr, &[&[DATA], data])?;
+            self.dirty &= !(1 << page);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A bus where everything acks.
+    struct Acks;
+
+    impl Pins for Acks {
+        fn set_scl(&mut self, _: bool) {}
+        fn set_sda(&mut self, _: bool) {}
+
+        fn scl(&self) -> bool {
+            true
+        }
+
+        fn sda(&self) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn pixels_and_dirty_pages() {
+        let mut oled = Ssd1306::new(I2c::new(Acks));
+        oled.flush().unwrap();
+        assert_eq!(oled.dirty, 0);
+
+        oled.set_pixel(3, 17, true);
+        assert!(oled.pixel(3, 17));
+        assert_eq!(oled.buffer()[2 * WIDTH + 3], 0x02);
+        assert_eq!(oled.dirty, 1 << 2);
+
+        // Off the edge, or no change: nothing to flush.
+        oled.flush().unwrap();
+        oled.set_pixel(WIDTH, 0, true);
+        oled.set_pixel(3, 17, true);
+        assert!(!oled.pixel(WIDTH, 0));
+        assert_eq!(oled.dirty, 0);
+
+        oled.clear();
+        assert!(!oled.pixel(3, 17));
+        assert_eq!(oled.dirty, 0xff);
+    }
+}
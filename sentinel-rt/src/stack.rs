@@ -0,0 +1,117 @@
+//! Stack painting, for finding out how much stack firmware really needs.
+//!
+//! [`paint`] fills the unused part of the stack with
+//! [`guard::PATTERN`](crate::guard::PATTERN), and [`stack_usage`] later
+//! scans up from the far end for the first word that isn't the pattern any
+//! more: the high-water mark, the deepest the stack has been since. With
+//! the `stack-paint` feature, [`Soc::init`](crate::soc::Soc::init) paints
+//! before anything else runs, so the mark covers everything `main` and the
+//! ISRs do:
+//!
+//! ```ignore
+//! let soc = Soc::init();
+//! ...
+//! stack::stack_usage().print(&mut serial);
+//! ```
+//!
+//! ```text
+//! stack-used 0000012c
+//! stack-size 000003fc
+//! ```
+//!
+//! The mark is only as deep as what's run so far; exercise the worst case
+//! (every interrupt at its deepest call) before believing it. Size the
+//! stack (`_hart_stack_size`, or whatever's left of RAM) with some margin
+//! over it.
+//!
+//! It's the same pattern as the [stack guard](crate::guard::add_stack_guard),
+//! so painting over an intact guard leaves it intact.
+
+use crate::guard::PATTERN;
+use crate::hex;
+use crate::mem;
+use crate::shell::Output;
+
+/// How much of the stack has been used, in bytes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Usage {
+    /// Deepest since [`paint`]; not how deep it is now.
+    pub used: usize,
+    pub size: usize,
+}
+
+impl Usage {
+    /// Bytes never used.
+    pub fn free(&self) -> usize {
+        self.size - self.used
+    }
+
+    /// Print one `name value` line (hex) per field.
+    pub fn print(&self, out: &mut dyn Output) {
+        for (name, val) in [("stack-used ", self.used), ("stack-size ", self.size)] {
+            out.write_str(name);
+            out.write_bytes(&hex::u32_digits(val as u32));
+            out.write_bytes(b"\r\n");
+        }
+    }
+}
+
+/// Fill the stack below the current stack pointer with the pattern,
+/// forgetting the old high-water mark.
+pub fn paint() {
+    let bottom = mem::stack().start;
+    // Interrupts would push frames below `sp`, which this would paint
+    // over.
+    critical_section::with(|_| {
+        let top = stack_pointer() & !3;
+        for addr in (bottom..top).step_by(4) {
+            // SAFETY: Below the stack pointer, with nothing to interrupt,
+            // the stack is unused.
+            unsafe { (addr as *mut u32).write_volatile(PATTERN) };
+        }
+    });
+}
+
+/// The stack's high-water mark since [`paint`].
+pub fn stack_usage() -> Usage {
+    let stack = mem::stack();
+    let untouched = (stack.start..stack.end)
+        .step_by(4)
+        // SAFETY: Inside the stack, per the linker script.
+        .take_while(|&addr| unsafe { (addr as *const u32).read_volatile() } == PATTERN)
+        .count();
+    usage(untouched, stack.len())
+}
+
+fn usage(untouched_words: usize, size: usize) -> Usage {
+    Usage { used: size - untouched_words * 4, size }
+}
+
+#[cfg(target_arch = "riscv32")]
+#[inline(always)]
+fn stack_pointer() -> usize {
+    let sp;
+    // SAFETY: Just reads a register.
+    unsafe { core::arch::asm!("mv {0}, sp", out(reg) sp) };
+    sp
+}
+
+#[cfg(not(target_arch = "riscv32"))]
+fn stack_pointer() -> usize {
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn usage_from_untouched() {
+        // No stack on the host: nothing to paint, nothing used.
+        paint();
+        assert_eq!(stack_usage(), Usage { used: 0, size: 0 });
+
+        let u = usage(200, 1024);
+        assert_eq!((u.used, u.free()), (224, 800));
+    }
+}
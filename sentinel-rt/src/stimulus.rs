@@ -0,0 +1,151 @@
+//! Seeded randomness and virtual time for reproducible runs.
+//!
+//! Examples take their "random" numbers and timing-dependent events from
+//! here. Normally the seed comes from whatever entropy the caller has (e.g.
+//! how long the user took to press a key) and events follow the hardware
+//! timer. With the `deterministic` feature, the seed is fixed and events are
+//! generated from a count of polls instead, so that a simulation produces
+//! bit-identical output no matter how fast or slow the host running it is.
+//!
+//! The fixed seed can be changed at build time with the `SENTINEL_SEED`
+//! environment variable (decimal or `0x`-prefixed hex).
+
+/// Whether the `deterministic` feature is enabled.
+pub const DETERMINISTIC: bool = cfg!(feature = "deterministic");
+
+/// Seed used in deterministic mode.
+pub const SEED: u32 = match option_env!("SENTINEL_SEED") {
+    Some(s) => parse_seed(s),
+    None => 0x2545_f491,
+};
+
+const fn parse_seed(s: &str) -> u32 {
+    let bytes = s.as_bytes();
+    let (radix, mut i) = if bytes.len() > 2 && bytes[0] == b'0'
+        && (bytes[1] == b'x' || bytes[1] == b'X') {
+        (16, 2)
+    } else {
+        (10, 0)
+    };
+
+    let mut val: u32 = 0;
+    while i < bytes.len() {
+        let d = match bytes[i] {
+            c @ b'0'..=b'9' => c - b'0',
+            c @ b'a'..=b'f' if radix == 16 => c - b'a' + 10,
+            c @ b'A'..=b'F' if radix == 16 => c - b'A' + 10,
+            b'_' => {
+                i += 1;
+                continue;
+            }
+            _ => panic!("SENTINEL_SEED is not a number"),
+        };
+        val = val.wrapping_mul(radix).wrapping_add(d as u32);
+        i += 1;
+    }
+
+    val
+}
+
+/// Pick the seed for this run. `entropy` is only called when not in
+/// deterministic mode.
+pub fn seed(entropy: impl FnOnce() -> u32) -> u32 {
+    if DETERMINISTIC {
+        SEED
+    } else {
+        entropy()
+    }
+}
+
+/// xorshift32. Tiny, and doesn't need a multiplier, which RV32I doesn't
+/// have.
+#[derive(Clone)]
+pub struct Rng(u32);
+
+impl Rng {
+    pub const fn new(seed: u32) -> Self {
+        // xorshift gets stuck at zero.
+        Self(if seed == 0 { 0x2545_f491 } else { seed })
+    }
+
+    /// An [`Rng`] seeded by [`seed`].
+    pub fn seeded(entropy: impl FnOnce() -> u32) -> Self {
+        Self::new(seed(entropy))
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.0 = x;
+        x
+    }
+
+    /// A number in `0..bound`. `bound` must be nonzero.
+    pub fn below(&mut self, bound: u32) -> u32 {
+        self.next_u32() % bound
+    }
+
+    pub fn next_bool(&mut self) -> bool {
+        (self.next_u32() & 1) != 0
+    }
+}
+
+/// A periodic event source that follows the hardware in normal mode, and a
+/// poll count in deterministic mode.
+pub struct Ticker {
+    polls: u32,
+    period: u32,
+}
+
+impl Ticker {
+    /// In deterministic mode, the event fires once every `virtual_period`
+    /// calls to [`Ticker::poll`].
+    pub const fn new(virtual_period: u32) -> Self {
+        Self {
+            polls: 0,
+            period: virtual_period,
+        }
+    }
+
+    /// Report whether the event fired. `hw_event` is whether the real
+    /// source (usually a timer flag set by an ISR) fired since the last
+    /// poll; it is ignored in deterministic mode.
+    pub fn poll(&mut self, hw_event: bool) -> bool {
+        if DETERMINISTIC {
+            self.polls += 1;
+            if self.polls >= self.period {
+                self.polls = 0;
+                true
+            } else {
+                false
+            }
+        } else {
+            hw_event
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seed_parsing() {
+        assert_eq!(parse_seed("1234"), 1234);
+        assert_eq!(parse_seed("0xdead_beef"), 0xdead_beef);
+    }
+
+    #[test]
+    fn rng_is_reproducible() {
+        let mut a = Rng::new(42);
+        let mut b = Rng::new(42);
+        for _ in 0..100 {
+            let x = a.below(10);
+            assert!(x < 10);
+            assert_eq!(x, b.below(10));
+        }
+        assert_ne!(Rng::new(0).next_u32(), 0);
+    }
+}
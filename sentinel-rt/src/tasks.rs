@@ -0,0 +1,140 @@
+//! Cooperative run-to-completion tasks, woken by event flags.
+//!
+//! A demo with several jobs (a console, an LED pattern, a simulation
+//! stepping along) is easier to follow as one function per job than as a
+//! single loop polling for each. [`Tasks`] is a fixed table of them. ISRs
+//! (and other tasks) [`signal`](Tasks::signal) events to a task; the main
+//! loop [`run`](Tasks::run)s whichever tasks have events pending, and
+//! sleeps when none has:
+//!
+//! ```ignore
+//! const CONSOLE: usize = 0;
+//! const LEDS: usize = 1;
+//! const RX: Events = 1 << 0;
+//! const TICK: Events = 1 << 0;
+//!
+//! static TASKS: Tasks<2> = Tasks::new([console, leds]);
+//!
+//! fn console(events: Events) { /* ... */ }
+//! fn leds(events: Events) { /* ... */ }
+//!
+//! periodic::every(Duration::from_millis(100), || TASKS.signal(LEDS, TICK))?;
+//! irq::set(Handler::Rx(|b| { RX_BUF.push(b); TASKS.signal(CONSOLE, RX) }));
+//! TASKS.run()
+//! ```
+//!
+//! A task is called with the events signalled since it last ran, all at
+//! once, and returns when it's done with them: there's no preemption
+//! between tasks and no stack per task, so state lives in statics. Earlier
+//! tasks in the table come first; after each run the table is checked from
+//! the top again, so a busy task late in it can't hold up an earlier one
+//! for more than one run.
+//!
+//! In a `polled` build, nothing can end a `wfi`, so the loop calls
+//! [`irq::dispatch`] instead of sleeping.
+//!
+//! [`irq::dispatch`]: crate::irq::dispatch
+
+use crate::atomic::{AtomicU32, Ordering};
+#[cfg(feature = "polled")]
+use crate::irq;
+#[cfg(not(feature = "polled"))]
+use crate::power;
+
+/// Event flags, one per bit. What each means is up to the task.
+pub type Events = u32;
+
+/// A task: called with the events pending for it.
+pub type Task = fn(Events);
+
+/// A table of tasks, highest priority first.
+pub struct Tasks<const N: usize> {
+    tasks: [Task; N],
+    pending: [AtomicU32; N],
+}
+
+impl<const N: usize> Tasks<N> {
+    pub const fn new(tasks: [Task; N]) -> Self {
+        Self { tasks, pending: [const { AtomicU32::new(0) }; N] }
+    }
+
+    /// Flag `events` for task number `task`, to run it soon. Safe from ISRs.
+    pub fn signal(&self, task: usize, events: Events) {
+        self.pending[task].fetch_or(events, Ordering::Release);
+    }
+
+    /// Whether any task has events pending.
+    pub fn any_pending(&self) -> bool {
+        self.pending.iter().any(|p| p.load(Ordering::Acquire) != 0)
+    }
+
+    /// Run the first task with events pending, if there is one. Returns
+    /// whether there was.
+    pub fn run_one(&self) -> bool {
+        for (task, pending) in self.tasks.iter().zip(&self.pending) {
+            let events = pending.swap(0, Ordering::AcqRel);
+            if events != 0 {
+                task(events);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Run tasks as they're signalled, forever.
+    pub fn run(&self) -> ! {
+        loop {
+            while self.run_one() {}
+            self.wait();
+        }
+    }
+
+    /// Sleep until signalled. With interrupts masked, so a signal between
+    /// the check and the `wfi` isn't missed: a pending interrupt still ends
+    /// the `wfi`, and is taken when the critical section ends.
+    #[cfg(not(feature = "polled"))]
+    fn wait(&self) {
+        critical_section::with(|_| {
+            if !self.any_pending() {
+                power::idle();
+            }
+        });
+    }
+
+    /// Do what the ISR would have, and look again.
+    #[cfg(feature = "polled")]
+    fn wait(&self) {
+        irq::dispatch();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static LOG: AtomicU32 = AtomicU32::new(0);
+
+    /// Appends `task` and `events` to [`LOG`], a nibble each.
+    fn log(task: u32, events: Events) {
+        let log = LOG.load(Ordering::Relaxed);
+        LOG.store(log << 8 | task << 4 | events, Ordering::Relaxed);
+    }
+
+    static TASKS: Tasks<2> = Tasks::new([|e| log(1, e), |e| {
+        log(2, e);
+        // Hands on to the first task, which runs before it's called again.
+        TASKS.signal(0, 8);
+    }]);
+
+    #[test]
+    fn priority_and_events() {
+        assert!(!TASKS.run_one());
+        TASKS.signal(1, 1);
+        TASKS.signal(0, 2);
+        TASKS.signal(1, 4);
+        assert!(TASKS.any_pending());
+        while TASKS.run_one() {}
+        assert_eq!(LOG.load(Ordering::Relaxed), 0x12_25_18);
+        assert!(!TASKS.any_pending());
+    }
+}
@@ -0,0 +1,511 @@
+//! Character-cell framebuffer for ANSI terminals.
+//!
+//! A [`Grid`] is a screenful of [`Cell`]s (a character and its colors),
+//! with a cursor and scroll region for text output, and knows nothing about
+//! where it's displayed. A [`Renderer`] turns it into escape sequences,
+//! sending only the cells that changed since the last render:
+//!
+//! ```ignore
+//! static mut GRID: Grid<40, 12> = Grid::new();
+//! let grid = unsafe { &mut *addr_of_mut!(GRID) };
+//!
+//! let mut term = Renderer::new();
+//! term.reset(grid, |b| ser.write_bytes(b));
+//! loop {
+//!     step(grid);
+//!     term.render(grid, |b| ser.write_bytes(b));
+//! }
+//! ```
+//!
+//! Cells are two bytes, so an 80x24 grid would take nearly all of the
+//! AttoSoC's RAM. Size the grid to fit.
+
+use crate::fixed;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Color {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+}
+
+impl Color {
+    const fn from_bits(bits: u8) -> Self {
+        match bits & 0x07 {
+            0 => Self::Black,
+            1 => Self::Red,
+            2 => Self::Green,
+            3 => Self::Yellow,
+            4 => Self::Blue,
+            5 => Self::Magenta,
+            6 => Self::Cyan,
+            _ => Self::White,
+        }
+    }
+}
+
+const BOLD: u8 = 0x40;
+// Not part of the attribute proper; the renderer's bookkeeping.
+const DIRTY: u8 = 0x80;
+
+/// Foreground and background color, and boldness.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Attr(u8);
+
+impl Attr {
+    /// White on black.
+    pub const DEFAULT: Self = Self::new(Color::White, Color::Black);
+
+    pub const fn new(fg: Color, bg: Color) -> Self {
+        Self(fg as u8 | (bg as u8) << 3)
+    }
+
+    pub const fn bold(self) -> Self {
+        Self(self.0 | BOLD)
+    }
+
+    pub const fn fg(self) -> Color {
+        Color::from_bits(self.0)
+    }
+
+    pub const fn bg(self) -> Color {
+        Color::from_bits(self.0 >> 3)
+    }
+
+    pub const fn is_bold(self) -> bool {
+        self.0 & BOLD != 0
+    }
+}
+
+/// First of the 16 quadrant block glyphs: `QUADRANTS | bits`, where bits
+/// 0-3 are the top-left, top-right, bottom-left and bottom-right quarters.
+/// They're rendered as the Unicode block elements.
+pub const QUADRANTS: u8 = 0x80;
+
+// Indexed by quadrant bits.
+const QUADRANT_UTF8: [&str; 16] = [
+    " ", "\u{2598}", "\u{259d}", "\u{2580}", "\u{2596}", "\u{258c}",
+    "\u{259e}", "\u{259b}", "\u{2597}", "\u{259a}", "\u{2590}", "\u{259c}",
+    "\u{2584}", "\u{2599}", "\u{259f}", "\u{2588}",
+];
+
+/// One character position: an ASCII character or quadrant glyph, and its
+/// attributes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cell {
+    ch: u8,
+    attr: u8,
+}
+
+impl Cell {
+    pub const BLANK: Self = Self::new(b' ', Attr::DEFAULT);
+
+    pub const fn new(ch: u8, attr: Attr) -> Self {
+        Self { ch, attr: attr.0 }
+    }
+
+    pub const fn ch(self) -> u8 {
+        self.ch
+    }
+
+    pub const fn attr(self) -> Attr {
+        Attr(self.attr & !DIRTY)
+    }
+
+    fn same(self, other: Self) -> bool {
+        self.ch == other.ch && (self.attr & !DIRTY) == (other.attr & !DIRTY)
+    }
+}
+
+pub struct Grid<const W: usize, const H: usize> {
+    cells: [[Cell; W]; H],
+    x: usize,
+    y: usize,
+    attr: Attr,
+    top: usize,
+    bottom: usize,
+}
+
+impl<const W: usize, const H: usize> Default for Grid<W, H> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const W: usize, const H: usize> Grid<W, H> {
+    /// A blank grid. Every cell starts out dirty, so the first render draws
+    /// the whole thing.
+    pub const fn new() -> Self {
+        let blank = Cell {
+            ch: b' ',
+            attr: Attr::DEFAULT.0 | DIRTY,
+        };
+        Self {
+            cells: [[blank; W]; H],
+            x: 0,
+            y: 0,
+            attr: Attr::DEFAULT,
+            top: 0,
+            bottom: H - 1,
+        }
+    }
+
+    pub const fn width(&self) -> usize {
+        W
+    }
+
+    pub const fn height(&self) -> usize {
+        H
+    }
+
+    /// The cell at `(x, y)`, or `None` if out of range.
+    pub fn cell(&self, x: usize, y: usize) -> Option<Cell> {
+        self.cells.get(y)?.get(x).copied()
+    }
+
+    /// Set the cell at `(x, y)`, ignoring out-of-range positions. Doesn't
+    /// move the cursor.
+    pub fn set(&mut self, x: usize, y: usize, cell: Cell) {
+        if let Some(c) = self.cells.get_mut(y).and_then(|r| r.get_mut(x)) {
+            if !c.same(cell) {
+                *c = Cell {
+                    attr: cell.attr | DIRTY,
+                    ..cell
+                };
+            }
+        }
+    }
+
+    /// Blank the whole grid (in the current attribute), and home the cursor.
+    pub fn clear(&mut self) {
+        let blank = Cell::new(b' ', self.attr);
+        for y in 0..H {
+            for x in 0..W {
+                self.set(x, y, blank);
+            }
+        }
+        self.x = 0;
+        self.y = 0;
+    }
+
+    /// Mark every cell as needing to be drawn again.
+    pub fn invalidate(&mut self) {
+        for c in self.cells.iter_mut().flatten() {
+            c.attr |= DIRTY;
+        }
+    }
+
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.x, self.y)
+    }
+
+    pub fn set_cursor(&mut self, x: usize, y: usize) {
+        self.x = x.min(W - 1);
+        self.y = y.min(H - 1);
+    }
+
+    /// Attribute for text written from now on.
+    pub fn set_attr(&mut self, attr: Attr) {
+        self.attr = attr;
+    }
+
+    /// Rows `top..=bottom` scroll when text runs off the bottom; the rest
+    /// stay put, e.g. for a status line. Ignored if the range is empty or
+    /// out of bounds.
+    pub fn set_scroll_region(&mut self, top: usize, bottom: usize) {
+        if top <= bottom && bottom < H {
+            self.top = top;
+            self.bottom = bottom;
+        }
+    }
+
+    /// Move the scroll region's contents up a line, blanking the bottom.
+    pub fn scroll_up(&mut self) {
+        for y in self.top..self.bottom {
+            for x in 0..W {
+                let below = self.cells[y + 1][x];
+                self.set(x, y, below);
+            }
+        }
+        let blank = Cell::new(b' ', self.attr);
+        for x in 0..W {
+            self.set(x, self.bottom, blank);
+        }
+    }
+
+    fn newline(&mut self) {
+        if self.y == self.bottom {
+            self.scroll_up();
+        } else if self.y < H - 1 {
+            self.y += 1;
+        }
+    }
+
+    /// Write a byte at the cursor, terminal-style: `\r`, `\n` and backspace
+    /// move the cursor, and text wraps at the right edge.
+    pub fn put(&mut self, b: u8) {
+        match b {
+            b'\r' => self.x = 0,
+            b'\n' => self.newline(),
+            0x08 => self.x = self.x.saturating_sub(1),
+            _ => {
+                if self.x == W {
+                    self.x = 0;
+                    self.newline();
+                }
+                self.set(self.x, self.y, Cell::new(b, self.attr));
+                // Allowed to sit one past the edge until the next byte, so
+                // that filling the last column doesn't scroll.
+                self.x += 1;
+            }
+        }
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.put(*b);
+        }
+    }
+
+    pub fn write_str(&mut self, s: &str) {
+        self.write_bytes(s.as_bytes());
+    }
+}
+
+/// Draws [`Grid`]s on an ANSI terminal, remembering what the terminal's
+/// cursor and colors are to avoid redundant escape sequences.
+pub struct Renderer {
+    attr: Option<Attr>,
+    pos: Option<(usize, usize)>,
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Renderer {
+    pub const fn new() -> Self {
+        Self {
+            attr: None,
+            pos: None,
+        }
+    }
+
+    /// Clear the terminal and forget what's on it, so the next render
+    /// draws everything.
+    pub fn reset<const W: usize, const H: usize>(
+        &mut self, grid: &mut Grid<W, H>, mut write: impl FnMut(&[u8])) {
+        write(b"\x1b[0m\x1b[2J");
+        self.attr = None;
+        self.pos = None;
+        grid.invalidate();
+    }
+
+    fn move_to(&mut self, x: usize, y: usize, write: &mut impl FnMut(&[u8])) {
+        if self.pos == Some((x, y)) {
+            return;
+        }
+
+        let mut buf = [0; fixed::MAX_LEN];
+        write(b"\x1b[");
+        write(fixed::format(y as i32 + 1, 0, &mut buf));
+        write(b";");
+        write(fixed::format(x as i32 + 1, 0, &mut buf));
+        write(b"H");
+        self.pos = Some((x, y));
+    }
+
+    fn set_attr(&mut self, attr: Attr, write: &mut impl FnMut(&[u8])) {
+        if self.attr == Some(attr) {
+            return;
+        }
+
+        let fg = b'0' + attr.fg() as u8;
+        let bg = b'0' + attr.bg() as u8;
+        write(if attr.is_bold() { b"\x1b[0;1;3" } else { b"\x1b[0;3" });
+        write(&[fg, b';', b'4', bg, b'm']);
+        self.attr = Some(attr);
+    }
+
+    /// Send the cells that changed since the last render, then put the
+    /// terminal's cursor where the grid's is.
+    pub fn render<const W: usize, const H: usize>(
+        &mut self, grid: &mut Grid<W, H>, mut write: impl FnMut(&[u8])) {
+        for (y, row) in grid.cells.iter_mut().enumerate() {
+            for (x, cell) in row.iter_mut().enumerate() {
+                if cell.attr & DIRTY == 0 {
+                    continue;
+                }
+                cell.attr &= !DIRTY;
+
+                self.move_to(x, y, &mut write);
+                self.set_attr(cell.attr(), &mut write);
+                match cell.ch {
+                    0x20..=0x7e => write(&[cell.ch]),
+                    c if c & 0xf0 == QUADRANTS => {
+                        write(QUADRANT_UTF8[(c & 0x0f) as usize].as_bytes())
+                    }
+                    _ => write(b"?"),
+                }
+
+                // Terminals don't agree on what happens after the last
+                // column, so don't assume anything.
+                self.pos = (x + 1 < W).then_some((x + 1, y));
+            }
+        }
+
+        let (x, y) = grid.cursor();
+        self.move_to(x.min(W - 1), y, &mut write);
+    }
+}
+
+/// A key press, as decoded by [`Keys`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Key {
+    Char(u8),
+    Up,
+    Down,
+    Right,
+    Left,
+    /// A lone ESC. See [`Keys::flush`].
+    Escape,
+    /// Ctrl-C.
+    Interrupt,
+}
+
+/// Turns the bytes a terminal sends into [`Key`]s, decoding the VT100
+/// arrow-key sequences (`ESC [ A` etc., and the `ESC O A` forms).
+#[derive(Default)]
+pub struct Keys {
+    state: u8,
+}
+
+impl Keys {
+    pub const fn new() -> Self {
+        Self { state: 0 }
+    }
+
+    pub fn feed(&mut self, b: u8) -> Option<Key> {
+        match (self.state, b) {
+            // Ctrl-C, even in the middle of a sequence.
+            (_, 0x03) => {
+                self.state = 0;
+                return Some(Key::Interrupt);
+            }
+            (0, 0x1b) => self.state = 1,
+            (1, 0x1b) => return Some(Key::Escape),
+            (1, b'[' | b'O') => self.state = 2,
+            (2, b'A'..=b'D') => {
+                self.state = 0;
+                return Some(match b {
+                    b'A' => Key::Up,
+                    b'B' => Key::Down,
+                    b'C' => Key::Right,
+                    _ => Key::Left,
+                });
+            }
+            // Parameters of some other sequence, e.g. `ESC [ 5 ~`...
+            (2, 0x20..=0x3f) => {}
+            // ...and its final byte. Dropped.
+            (2, _) => self.state = 0,
+            // A lone ESC, followed by something else.
+            (1, _) => {
+                self.state = 0;
+                return Some(Key::Char(b));
+            }
+            (_, _) => return Some(Key::Char(b)),
+        }
+        None
+    }
+
+    /// An ESC on its own looks like the start of a sequence until the next
+    /// byte arrives. Call this once the line has been idle for a while to
+    /// get it as [`Key::Escape`]; it also drops any partial sequence.
+    pub fn flush(&mut self) -> Option<Key> {
+        let state = core::mem::take(&mut self.state);
+        (state == 1).then_some(Key::Escape)
+    }
+
+    /// Whether a sequence has been started but not finished.
+    pub fn pending(&self) -> bool {
+        self.state != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use heapless::Vec;
+
+    use super::*;
+
+    fn row<const W: usize, const H: usize>(g: &Grid<W, H>, y: usize)
+                                           -> Vec<u8, 16> {
+        (0..W).map(|x| g.cell(x, y).unwrap().ch()).collect()
+    }
+
+    #[test]
+    fn text_wraps_and_scrolls_region() {
+        let mut g = Grid::<4, 3>::new();
+        g.set_scroll_region(0, 1);
+        g.set_cursor(0, 2);
+        g.write_str("stat");
+        g.set_cursor(0, 0);
+
+        g.write_str("abcdef\r\ngh\r\nij");
+        assert_eq!(row(&g, 0), b"gh  ".as_slice());
+        assert_eq!(row(&g, 1), b"ij  ".as_slice());
+        // Outside the region, so it stays.
+        assert_eq!(row(&g, 2), b"stat".as_slice());
+        assert_eq!(g.cursor(), (2, 1));
+    }
+
+    #[test]
+    fn arrow_keys() {
+        let mut k = Keys::new();
+        let keys: Vec<Key, 8> = b"a\x1b[A\x1bOD\x1b[5~b".iter()
+            .filter_map(|b| k.feed(*b))
+            .collect();
+        assert_eq!(keys, [Key::Char(b'a'), Key::Up, Key::Left, Key::Char(b'b')]);
+    }
+
+    #[test]
+    fn escape_and_interrupt() {
+        let mut k = Keys::new();
+        assert_eq!(k.feed(0x03), Some(Key::Interrupt));
+        assert_eq!(k.feed(0x1b), None);
+        assert_eq!(k.feed(0x1b), Some(Key::Escape));
+        assert!(k.pending());
+        assert_eq!(k.flush(), Some(Key::Escape));
+        assert_eq!(k.flush(), None);
+        assert_eq!(k.feed(b'x'), Some(Key::Char(b'x')));
+    }
+
+    #[test]
+    fn renders_only_changes() {
+        let mut g = Grid::<3, 2>::new();
+        let mut r = Renderer::new();
+        let mut out = Vec::<u8, 128>::new();
+        r.render(&mut g, |b| out.extend_from_slice(b).unwrap());
+        assert!(out.starts_with(b"\x1b[1;1H\x1b[0;37;40m   \x1b[2;1H   "));
+
+        out.clear();
+        r.render(&mut g, |b| out.extend_from_slice(b).unwrap());
+        assert!(out.is_empty());
+
+        out.clear();
+        g.set(1, 1, Cell::new(b'x', Attr::new(Color::Red, Color::Black).bold()));
+        g.set(2, 1, Cell::new(QUADRANTS | 0x3, Attr::DEFAULT));
+        g.set_cursor(0, 0);
+        r.render(&mut g, |b| out.extend_from_slice(b).unwrap());
+        assert_eq!(out, "\x1b[2;2H\x1b[0;1;31;40mx\x1b[0;37;40m\u{2580}\x1b[1;1H"
+                            .as_bytes());
+    }
+}
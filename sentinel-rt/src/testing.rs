@@ -0,0 +1,133 @@
+//! Tests that run on the target, with
+//! [`#[sentinel_test]`](crate::sentinel_test).
+//!
+//! `cargo test` runs tests on the host, which says nothing about how code
+//! does on an RV32I core with no M extension and 4 KiB of RAM. A test
+//! binary (an example, say) can carry tests for the core itself instead:
+//!
+//! ```ignore
+//! use sentinel_rt::sentinel_test;
+//!
+//! #[sentinel_test]
+//! fn divides() {
+//!     assert_eq!(core::hint::black_box(1000u32) / 7, 142);
+//! }
+//!
+//! #[sentinel_test]
+//! fn decodes() -> Result<(), &'static str> {
+//!     parse(b"12").map(drop).ok_or("no number")
+//! }
+//!
+//! #[entry]
+//! fn main() -> ! {
+//!     console::init(Soc::init().serial);
+//!     testing::run()
+//! }
+//! ```
+//!
+//! The linker collects the tests from every module and crate, and [`run`]
+//! runs them in link order, printing to the [console](crate::console):
+//!
+//! ```text
+//! running 2 tests
+//! test selftest::divides ... ok
+//! test selftest::decodes ... FAILED: no number
+//!
+//! test result: FAILED. 1 passed; 1 failed
+//! ```
+//!
+//! and then ends the simulation with [`sim::exit`]: 0 if they all passed,
+//! and 1 if any failed. On hardware, that just stops.
+//!
+//! A test fails by returning an `Err`, or by panicking. With no unwinding,
+//! a panic can't be caught, so it ends the run: sentinel-rt's panic
+//! handler marks the test `FAILED` before doing what it does with a panic.
+//! With the `sim` feature, that's to print the message and exit with
+//! [`sim::PANIC_CODE`].
+
+use portable_atomic::{AtomicBool, Ordering};
+
+use crate::{console, mem, sim};
+
+/// A test. Created by [`#[sentinel_test]`](crate::sentinel_test).
+#[repr(C)]
+pub struct Test {
+    name: &'static str,
+    run: fn() -> Result<(), &'static str>,
+}
+
+impl Test {
+    #[doc(hidden)]
+    pub const fn new(name: &'static str, run: fn() -> Result<(), &'static str>) -> Self {
+        Self { name, run }
+    }
+
+    /// The test's path, as `module::function`.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+/// What a test can return.
+pub trait Outcome {
+    fn into_result(self) -> Result<(), &'static str>;
+}
+
+impl Outcome for () {
+    fn into_result(self) -> Result<(), &'static str> {
+        Ok(())
+    }
+}
+
+impl Outcome for Result<(), &'static str> {
+    fn into_result(self) -> Result<(), &'static str> {
+        self
+    }
+}
+
+/// A test is running, and its line waits for a result.
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Every test in the firmware.
+pub fn tests() -> &'static [Test] {
+    let table = mem::test_table();
+    let len = table.len() / core::mem::size_of::<Test>();
+
+    // SAFETY: The linker collects only `Test`s into the table.
+    unsafe { core::slice::from_raw_parts(table.start as *const Test, len) }
+}
+
+/// Run every test, print the results, and [`sim::exit`].
+pub fn run() -> ! {
+    let tests = tests();
+    crate::sprintln!("running {} tests", tests.len());
+
+    let mut failed = 0;
+    for test in tests {
+        crate::sprint!("test {} ... ", test.name);
+        RUNNING.store(true, Ordering::Relaxed);
+        let result = (test.run)();
+        RUNNING.store(false, Ordering::Relaxed);
+        match result {
+            Ok(()) => console::write_bytes(b"ok\r\n"),
+            Err(msg) => {
+                crate::sprintln!("FAILED: {}", msg);
+                failed += 1;
+            }
+        }
+    }
+
+    let verdict = if failed == 0 { "ok" } else { "FAILED" };
+    crate::sprintln!();
+    crate::sprintln!("test result: {}. {} passed; {} failed", verdict, tests.len() - failed,
+                     failed);
+    sim::exit(if failed == 0 { 0 } else { 1 })
+}
+
+/// Finish a running test's line on panic. Called by
+/// [`panic::handle`](crate::panic::handle).
+pub(crate) fn on_panic() {
+    if RUNNING.swap(false, Ordering::Relaxed) {
+        console::write_bytes(b"FAILED\r\n");
+    }
+}
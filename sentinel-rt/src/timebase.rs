@@ -0,0 +1,59 @@
+//! Tick counter maintained from the timer interrupt.
+//!
+//! The AttoSoC timer has no readable count; it only raises an IRQ every
+//! [`CYCLES_PER_TICK`] clocks. Call [`tick`] from `MachineExternal` each time
+//! the timer IRQ is acknowledged, and everything else in the runtime that
+//! needs a notion of time reads the count.
+//!
+//! The count is 64 bits, kept as two words since there are no 64-bit
+//! atomics: [`ticks`] and [`uptime_ms`] never wrap. [`ticks32`] is its low
+//! word, a single load, for timeouts and intervals compared with
+//! `wrapping_sub`; that's what the runtime itself uses.
+//!
+//! Only one context may call [`tick`]: the ISR, or the main loop when
+//! nothing is interrupt driven. Reading is safe from anywhere.
+
+use portable_atomic::{AtomicU32, Ordering};
+
+/// System clock of the board being built for. See [`io_map`](crate::io_map).
+pub const CLK_HZ: u32 = crate::io_map::CLK_HZ;
+
+/// The timer's prescaler is 15 bits, and the IRQ is bit 14.
+pub const CYCLES_PER_TICK: u32 = 1 << 14;
+
+static TICKS: AtomicU32 = AtomicU32::new(0);
+static TICKS_HI: AtomicU32 = AtomicU32::new(0);
+
+/// Record one timer tick. Call from the timer ISR.
+pub fn tick() {
+    // The low word first: a reader that sees it wrap before the high word
+    // has caught up sees the high word change, and reads again.
+    if TICKS.fetch_add(1, Ordering::Release) == u32::MAX {
+        TICKS_HI.fetch_add(1, Ordering::Release);
+    }
+    #[cfg(feature = "embassy-time")]
+    crate::embassy::on_tick();
+    #[cfg(feature = "rtic")]
+    crate::rtic::on_tick();
+}
+
+/// Ticks since boot.
+pub fn ticks() -> u64 {
+    loop {
+        let hi = TICKS_HI.load(Ordering::Acquire);
+        let lo = TICKS.load(Ordering::Acquire);
+        if TICKS_HI.load(Ordering::Acquire) == hi {
+            return (hi as u64) << 32 | lo as u64;
+        }
+    }
+}
+
+/// The low 32 bits of [`ticks`]. Wraps after about 68 days.
+pub fn ticks32() -> u32 {
+    TICKS.load(Ordering::Relaxed)
+}
+
+/// Milliseconds since boot, to the tick (about 1.37 ms).
+pub fn uptime_ms() -> u64 {
+    ticks() * (CYCLES_PER_TICK as u64 * 1000) / CLK_HZ as u64
+}
@@ -0,0 +1,251 @@
+//! One-shot timeouts, as many as needed, off the one timer.
+//!
+//! Where [`periodic`](crate::periodic) repeats, these fire once: a debounce
+//! settling, a frame due, a UART reply that never came. Each either calls
+//! a function or sets a flag for the main loop to notice:
+//!
+//! ```ignore
+//! static REPLY_LATE: AtomicBool = AtomicBool::new(false);
+//!
+//! let late = timeout::schedule(Duration::from_millis(50), Action::Set(&REPLY_LATE))?;
+//! // ... and if the reply comes in time:
+//! timeout::cancel(late);
+//!
+//! irq::set(Handler::Timer(timeout::run_due));
+//! ```
+//!
+//! They're kept in a delta queue, soonest first, each counting ticks from
+//! the one before, so checking for due ones only ever looks at the head.
+//! [`run_due`] catches up on however many ticks have passed, so it can be
+//! called from the timer ISR after [`timebase::tick`], or from the main
+//! loop. Timeouts have a tick's resolution, about 1.37 ms.
+
+use core::cell::RefCell;
+use core::time::Duration;
+
+use critical_section::Mutex;
+
+use crate::atomic::{AtomicBool, Ordering};
+pub use crate::periodic::Full;
+use crate::periodic::duration_to_ticks;
+use crate::timebase;
+
+/// Maximum number of timeouts pending at once.
+pub const MAX_TIMEOUTS: usize = 8;
+
+/// What to do when a timeout expires.
+#[derive(Clone, Copy)]
+pub enum Action {
+    /// Call the function, wherever [`run_due`] is called.
+    Call(fn()),
+    /// Set the flag.
+    Set(&'static AtomicBool),
+}
+
+impl Action {
+    fn run(self) {
+        match self {
+            Self::Call(f) => f(),
+            Self::Set(flag) => flag.store(true, Ordering::Release),
+        }
+    }
+}
+
+/// A pending timeout. Pass it to [`cancel`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Handle(u16);
+
+#[derive(Clone, Copy)]
+struct Entry {
+    /// Ticks after the one before.
+    delta: u32,
+    id: u16,
+    action: Action,
+}
+
+/// The delta queue behind [`schedule`]/[`run_due`], in ticks. Public so it
+/// can be driven from some other time source.
+pub struct DeltaQueue<const N: usize> {
+    /// Soonest first; the first `len` are `Some`.
+    entries: [Option<Entry>; N],
+    len: usize,
+    /// When the head's delta counts from.
+    last: u32,
+    next_id: u16,
+}
+
+impl<const N: usize> Default for DeltaQueue<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> DeltaQueue<N> {
+    pub const fn new() -> Self {
+        Self { entries: [None; N], len: 0, last: 0, next_id: 0 }
+    }
+
+    /// Fire `action` `ticks` after `now`. Timeouts due at the same tick
+    /// fire in the order they were added.
+    pub fn add(&mut self, now: u32, ticks: u32, action: Action) -> Result<Handle, Full> {
+        if self.len == N {
+            return Err(Full);
+        }
+        if self.len == 0 {
+            self.last = now;
+        }
+        let mut delta = now.wrapping_sub(self.last).saturating_add(ticks);
+        let mut at = self.len;
+        for (i, e) in self.entries[..self.len].iter_mut().flatten().enumerate() {
+            if delta < e.delta {
+                e.delta -= delta;
+                at = i;
+                break;
+            }
+            delta -= e.delta;
+        }
+        self.entries.copy_within(at..self.len, at + 1);
+        let id = self.next_id;
+        self.next_id = id.wrapping_add(1);
+        self.entries[at] = Some(Entry { delta, id, action });
+        self.len += 1;
+        Ok(Handle(id))
+    }
+
+    /// Take `handle` out of the queue. Returns whether it was still
+    /// pending.
+    pub fn remove(&mut self, handle: Handle) -> bool {
+        let Some(at) = self.entries[..self.len].iter().flatten().position(|e| e.id == handle.0)
+        else {
+            return false;
+        };
+        // Its ticks count towards the next one's.
+        let delta = self.entries[at].map_or(0, |e| e.delta);
+        if let Some(next) = self.entries.get_mut(at + 1).and_then(Option::as_mut) {
+            next.delta += delta;
+        }
+        self.entries.copy_within(at + 1..self.len, at);
+        self.len -= 1;
+        self.entries[self.len] = None;
+        true
+    }
+
+    /// Timeouts still pending.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Return the next timeout due at `now`, taking it out of the queue.
+    /// Call until `None`.
+    pub fn take_due(&mut self, now: u32) -> Option<Action> {
+        let elapsed = now.wrapping_sub(self.last);
+        let Some(head) = self.entries.first_mut().and_then(Option::as_mut) else {
+            self.last = now;
+            return None;
+        };
+        if head.delta > elapsed {
+            head.delta -= elapsed;
+            self.last = now;
+            return None;
+        }
+        self.last = self.last.wrapping_add(head.delta);
+        let action = head.action;
+        self.entries.copy_within(1..self.len, 0);
+        self.len -= 1;
+        self.entries[self.len] = None;
+        Some(action)
+    }
+}
+
+static QUEUE: Mutex<RefCell<DeltaQueue<MAX_TIMEOUTS>>> =
+    Mutex::new(RefCell::new(DeltaQueue::new()));
+
+/// Do `action` once, `after` from now (to the nearest tick, and at least
+/// one).
+pub fn schedule(after: Duration, action: Action) -> Result<Handle, Full> {
+    let ticks = duration_to_ticks(after);
+    critical_section::with(|cs| QUEUE.borrow_ref_mut(cs).add(timebase::ticks32(), ticks, action))
+}
+
+/// Call off a timeout. Returns whether it was still pending; if not, it
+/// has fired (or is firing).
+pub fn cancel(handle: Handle) -> bool {
+    critical_section::with(|cs| QUEUE.borrow_ref_mut(cs).remove(handle))
+}
+
+/// Fire every timeout that is due. Call from the timer ISR after
+/// [`timebase::tick`], or from the main loop.
+pub fn run_due() {
+    let now = timebase::ticks32();
+    // Don't hold the queue while running actions, so they can schedule
+    // more.
+    while let Some(action) = critical_section::with(|cs| QUEUE.borrow_ref_mut(cs).take_due(now)) {
+        action.run();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static FLAGS: [AtomicBool; 4] = [const { AtomicBool::new(false) }; 4];
+
+    /// Which of [`FLAGS`] fire at `now`, in order.
+    fn fired<const N: usize>(q: &mut DeltaQueue<N>, now: u32) -> [usize; 4] {
+        let mut order = [usize::MAX; 4];
+        let mut n = 0;
+        while let Some(Action::Set(flag)) = q.take_due(now) {
+            order[n] = FLAGS.iter().position(|f| core::ptr::eq(f, flag)).unwrap();
+            n += 1;
+        }
+        order
+    }
+
+    const NONE: usize = usize::MAX;
+
+    #[test]
+    fn fires_in_order() {
+        let mut q = DeltaQueue::<4>::new();
+        q.add(100, 10, Action::Set(&FLAGS[0])).unwrap();
+        q.add(100, 3, Action::Set(&FLAGS[1])).unwrap();
+        q.add(102, 8, Action::Set(&FLAGS[2])).unwrap();
+        q.add(100, 3, Action::Set(&FLAGS[3])).unwrap();
+
+        assert_eq!(fired(&mut q, 102), [NONE; 4]);
+        assert_eq!(fired(&mut q, 103), [1, 3, NONE, NONE]);
+        assert_eq!(fired(&mut q, 109), [NONE; 4]);
+        // Caught up on late.
+        assert_eq!(fired(&mut q, 200), [0, 2, NONE, NONE]);
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    fn cancel_and_full() {
+        let mut q = DeltaQueue::<2>::new();
+        let a = q.add(u32::MAX - 1, 4, Action::Set(&FLAGS[0])).unwrap();
+        let b = q.add(u32::MAX, 6, Action::Set(&FLAGS[1])).unwrap();
+        assert_eq!(q.add(0, 1, Action::Set(&FLAGS[2])), Err(Full));
+
+        assert!(q.remove(a));
+        assert!(!q.remove(a));
+        assert_eq!(q.len(), 1);
+        // b keeps its deadline, across the wrap.
+        assert_eq!(fired(&mut q, 4), [NONE; 4]);
+        assert_eq!(fired(&mut q, 5), [1, NONE, NONE, NONE]);
+        assert!(!q.remove(b));
+    }
+
+    #[test]
+    fn global() {
+        schedule(Duration::ZERO, Action::Set(&FLAGS[3])).unwrap();
+        for _ in 0..2 {
+            timebase::tick();
+        }
+        run_due();
+        assert!(FLAGS[3].load(Ordering::Acquire));
+    }
+}
@@ -0,0 +1,529 @@
+//! Register dumps for unexpected exceptions.
+//!
+//! With the `fault-handler` feature, an exception that isn't a
+//! [`fault`](crate::fault) access is [`report`]ed: the cause, the trap CSRs
+//! and the registers go out over the UART,
+//!
+//! ```text
+//! Illegal instruction at 00000134
+//! mcause 00000002 mepc 00000134 mtval 00000000
+//! ra 000000f0 sp 000007b0 gp 00000a28 tp 00000000
+//! t0 00000001 t1 00000000 t2 00000000 t3 00000000
+//! ...
+//! ```
+//!
+//! and then the core does what the [panic policy](crate::panic::Policy)
+//! says: halt, or reset (into the bootloader, maybe) with
+//! [`Reason::Exception`]. Feed the dump to sentinel-tools' `symbolize` to
+//! find the function `mepc` is in.
+//!
+//! Only the registers riscv-rt's trap entry saves are there, plus `sp`,
+//! `gp` and `tp`, which it leaves alone. `s0`-`s11` have been reused by the
+//! time any Rust code runs, so they aren't.
+//!
+//! [`Cause`] decodes `mcause` on its own, for handlers that want to say
+//! what happened in their own words.
+//!
+//! # Stack overflow
+//!
+//! With a [stack guard](crate::guard) in the linker script
+//! (`_stack_guard_size`), an exception taken with `sp` inside the guard, or
+//! a load or store fault on an address inside it, is reported as a stack
+//! overflow, with [`Reason::StackOverflow`]:
+//!
+//! ```text
+//! Stack overflow
+//! Store access fault at 00000212
+//! ...
+//! ```
+//!
+//! On a bus that reports errors, back the guard with nothing and the first
+//! access into it traps. Sentinel's doesn't, so on the AttoSoC the guard is
+//! RAM and the overflow is caught by what it breaks: usually a smashed
+//! return address, trapping while `sp` is still in the guard. Make the
+//! guard bigger than the largest stack frame, or `sp` can skip past it.
+//!
+//! # Hooks
+//!
+//! With the `trap-hooks` feature, sentinel-rt replaces riscv-rt's trap
+//! entry (`_start_trap`) with one that saves every register, and `mepc`,
+//! in a [`TrapFrame`], and calls a hook on the way in and on the way out:
+//!
+//! ```ignore
+//! trap::on_trap_entry(Some(|f| profile::sample(f.mepc)));
+//! trap::on_trap_exit(Some(syscall::finish));
+//! ```
+//!
+//! Both run with interrupts off, around riscv-rt's dispatch to
+//! `MachineExternal` and the exception handlers. Whatever the exit hook
+//! leaves in the frame is what's restored, `sp` and `mepc` included: to
+//! switch tasks, copy the frame out and another task's in. A handler that
+//! moves `mepc` itself, as `fault-handler`'s does to skip a faulting
+//! access, has it copied into the frame before the exit hook, replacing
+//! whatever the entry hook set; otherwise the entry hook's `mepc` stands.
+//! Without the feature, the hooks are never called.
+//!
+//! With `emulate-m` or `emulate-misaligned` too, an M-extension
+//! instruction or misaligned access is [emulated](crate::emulate) before
+//! either hook, and the trap returns without calling them. With `kernel`,
+//! the [kernel](crate::kernel) switches tasks after the exit hook, and
+//! takes `ecall`s for itself.
+
+use core::fmt;
+
+use core::cell::Cell;
+use core::ops::Range;
+
+use critical_section::Mutex;
+use riscv_rt::TrapFrame as RtTrapFrame;
+
+use crate::hex;
+use crate::mem;
+use crate::panic::{self, Policy};
+use crate::reset::{enter_bootloader, soft_reset, Reason};
+use crate::shell::Output;
+
+/// What `mcause` says trapped: the exceptions and interrupts an RV32
+/// machine-mode core can raise.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cause {
+    InstructionMisaligned,
+    InstructionFault,
+    IllegalInstruction,
+    Breakpoint,
+    LoadMisaligned,
+    LoadFault,
+    StoreMisaligned,
+    StoreFault,
+    UserEnvCall,
+    MachineEnvCall,
+    MachineSoft,
+    MachineTimer,
+    MachineExternal,
+    /// A code the privileged spec reserves, or leaves to the platform: the
+    /// raw `mcause`.
+    Unknown(u32),
+}
+
+impl Cause {
+    pub fn is_interrupt(&self) -> bool {
+        matches!(self, Self::MachineSoft | Self::MachineTimer | Self::MachineExternal)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::InstructionMisaligned => "Instruction address misaligned",
+            Self::InstructionFault => "Instruction access fault",
+            Self::IllegalInstruction => "Illegal instruction",
+            Self::Breakpoint => "Breakpoint",
+            Self::LoadMisaligned => "Load address misaligned",
+            Self::LoadFault => "Load access fault",
+            Self::StoreMisaligned => "Store address misaligned",
+            Self::StoreFault => "Store access fault",
+            Self::UserEnvCall => "Environment call from U-mode",
+            Self::MachineEnvCall => "Environment call from M-mode",
+            Self::MachineSoft => "Machine software interrupt",
+            Self::MachineTimer => "Machine timer interrupt",
+            Self::MachineExternal => "Machine external interrupt",
+            Self::Unknown(_) => "Unknown trap",
+        }
+    }
+}
+
+impl From<u32> for Cause {
+    /// From an `mcause` value.
+    fn from(mcause: u32) -> Self {
+        let code = mcause & !(1 << 31);
+        match (mcause >> 31 != 0, code) {
+            (false, 0) => Self::InstructionMisaligned,
+            (false, 1) => Self::InstructionFault,
+            (false, 2) => Self::IllegalInstruction,
+            (false, 3) => Self::Breakpoint,
+            (false, 4) => Self::LoadMisaligned,
+            (false, 5) => Self::LoadFault,
+            (false, 6) => Self::StoreMisaligned,
+            (false, 7) => Self::StoreFault,
+            (false, 8) => Self::UserEnvCall,
+            (false, 11) => Self::MachineEnvCall,
+            (true, 3) => Self::MachineSoft,
+            (true, 7) => Self::MachineTimer,
+            (true, 11) => Self::MachineExternal,
+            _ => Self::Unknown(mcause),
+        }
+    }
+}
+
+impl From<Cause> for u32 {
+    /// The `mcause` value.
+    fn from(cause: Cause) -> u32 {
+        const IRQ: u32 = 1 << 31;
+        match cause {
+            Cause::InstructionMisaligned => 0,
+            Cause::InstructionFault => 1,
+            Cause::IllegalInstruction => 2,
+            Cause::Breakpoint => 3,
+            Cause::LoadMisaligned => 4,
+            Cause::LoadFault => 5,
+            Cause::StoreMisaligned => 6,
+            Cause::StoreFault => 7,
+            Cause::UserEnvCall => 8,
+            Cause::MachineEnvCall => 11,
+            Cause::MachineSoft => IRQ | 3,
+            Cause::MachineTimer => IRQ | 7,
+            Cause::MachineExternal => IRQ | 11,
+            Cause::Unknown(mcause) => mcause,
+        }
+    }
+}
+
+impl fmt::Display for Cause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unknown(mcause) => write!(f, "Unknown trap (mcause {:#010x})", mcause),
+            _ => f.write_str(self.name()),
+        }
+    }
+}
+
+#[cfg(feature = "ufmt")]
+impl ufmt::uDisplay for Cause {
+    fn fmt<W: ufmt::uWrite + ?Sized>(&self, f: &mut ufmt::Formatter<'_, W>) -> Result<(), W::Error> {
+        f.write_str(self.name())?;
+        if let Self::Unknown(mcause) = self {
+            f.write_str(" (mcause 0x")?;
+            f.write_str(core::str::from_utf8(&hex::u32_digits(*mcause)).unwrap_or(""))?;
+            f.write_str(")")?;
+        }
+        Ok(())
+    }
+}
+
+/// Register names, in [`Snapshot::regs`] order.
+pub const NAMES: [&str; 19] = [
+    "ra", "sp", "gp", "tp", "t0", "t1", "t2", "t3", "t4", "t5", "t6", "a0", "a1", "a2", "a3", "a4",
+    "a5", "a6", "a7",
+];
+
+/// The state of the core when it trapped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    pub mcause: usize,
+    pub mepc: usize,
+    pub mtval: usize,
+    /// As named by [`NAMES`].
+    pub regs: [usize; 19],
+}
+
+impl Snapshot {
+    /// From riscv-rt's trap frame and the rest of the trap state. Call
+    /// first thing in the exception handler.
+    pub fn capture(frame: &RtTrapFrame) -> Self {
+        let (mcause, mepc, mtval, gp, tp) = trap_state();
+        // The trap entry made room for the frame, and nothing else, below
+        // the interrupted code's stack.
+        #[cfg(not(feature = "trap-hooks"))]
+        let sp = frame as *const RtTrapFrame as usize + core::mem::size_of::<RtTrapFrame>();
+        #[cfg(feature = "trap-hooks")]
+        // SAFETY: With trap-hooks, riscv-rt's frame is the start of ours,
+        // which saved sp.
+        let sp = unsafe { &*(frame as *const RtTrapFrame as *const TrapFrame) }.sp;
+        let f = frame;
+        Self {
+            mcause,
+            mepc,
+            mtval,
+            regs: [
+                f.ra, sp, gp, tp, f.t0, f.t1, f.t2, f.t3, f.t4, f.t5, f.t6, f.a0, f.a1, f.a2, f.a3,
+                f.a4, f.a5, f.a6, f.a7,
+            ],
+        }
+    }
+
+    pub fn cause(&self) -> Cause {
+        Cause::from(self.mcause as u32)
+    }
+
+    /// Whether the trap looks like the stack running into `guard`: `sp`
+    /// inside it, or a load or store that faulted on it.
+    pub fn is_stack_overflow(&self, guard: Range<usize>) -> bool {
+        let access = matches!(
+            self.cause(),
+            Cause::LoadMisaligned | Cause::LoadFault | Cause::StoreMisaligned | Cause::StoreFault
+        );
+        guard.contains(&self.regs[1]) || (access && guard.contains(&self.mtval))
+    }
+
+    /// Write the dump to `out`, four registers a line.
+    pub fn write(&self, out: &mut dyn Output) {
+        out.write_str(self.cause().name());
+        out.write_str(" at ");
+        out.write_bytes(&hex::u32_digits(self.mepc as u32));
+        for (i, (name, val)) in [("mcause", self.mcause), ("mepc", self.mepc), ("mtval", self.mtval)]
+            .into_iter()
+            .chain(NAMES.into_iter().zip(self.regs))
+            .enumerate()
+        {
+            // The CSRs get a line of their own.
+            let first = i == 0 || (i >= 3 && (i - 3) % 4 == 0);
+            out.write_str(if first { "\r\n" } else { " " });
+            out.write_str(name);
+            out.write_str(" ");
+            out.write_bytes(&hex::u32_digits(val as u32));
+        }
+        out.write_str("\r\n");
+    }
+}
+
+#[cfg(target_arch = "riscv32")]
+#[inline(always)]
+fn trap_state() -> (usize, usize, usize, usize, usize) {
+    use riscv::register::{mcause, mepc, mtval};
+
+    let (gp, tp);
+    // SAFETY: Just reads registers.
+    unsafe { core::arch::asm!("mv {0}, gp", "mv {1}, tp", out(reg) gp, out(reg) tp) };
+    (mcause::read().bits(), mepc::read(), mtval::read(), gp, tp)
+}
+
+#[cfg(not(target_arch = "riscv32"))]
+fn trap_state() -> (usize, usize, usize, usize, usize) {
+    (0, 0, 0, 0, 0)
+}
+
+/// Dump the trap state to the UART, if the SoC is up, then halt or reset
+/// as the panic policy says. What the `fault-handler` feature's
+/// `ExceptionHandler` does with exceptions it can't skip.
+pub fn report(frame: &RtTrapFrame) -> ! {
+    let snapshot = Snapshot::capture(frame);
+    riscv::interrupt::disable();
+    let overflow = snapshot.is_stack_overflow(mem::stack_guard());
+
+    if let Some(bases) = crate::io_addrs::detected() {
+        let mut w = crate::hal::serial::Serial::new(bases.serial);
+        w.write_str("\r\n");
+        if overflow {
+            w.write_str("Stack overflow\r\n");
+        }
+        snapshot.write(&mut w);
+    }
+
+    let reason = if overflow { Reason::StackOverflow } else { Reason::Exception };
+    match panic::policy() {
+        Policy::Halt | Policy::PrintHalt => loop {
+            core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+        },
+        Policy::Reset | Policy::PrintReset => soft_reset(Some(reason)),
+        Policy::Bootloader => enter_bootloader(Some(reason)),
+    }
+}
+
+/// Every register, as saved by the `trap-hooks` trap entry. It starts
+/// with riscv-rt's `TrapFrame`, which is what riscv-rt's handlers get a
+/// reference to.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TrapFrame {
+    pub ra: usize,
+    pub t: [usize; 7],
+    pub a: [usize; 8],
+    pub s: [usize; 12],
+    pub sp: usize,
+    pub gp: usize,
+    pub tp: usize,
+    /// Where the trap will return to.
+    pub mepc: usize,
+}
+
+/// Called with the frame of each trap.
+pub type Hook = fn(&mut TrapFrame);
+
+static HOOKS: Mutex<Cell<[Option<Hook>; 2]>> = Mutex::new(Cell::new([None; 2]));
+
+/// Call `hook` on every trap, before it's handled (or stop, with `None`).
+pub fn on_trap_entry(hook: Option<Hook>) {
+    set_hook(0, hook);
+}
+
+/// Call `hook` on every trap, after it's handled and before the registers
+/// are restored from the frame (or stop, with `None`).
+pub fn on_trap_exit(hook: Option<Hook>) {
+    set_hook(1, hook);
+}
+
+fn set_hook(which: usize, hook: Option<Hook>) {
+    critical_section::with(|cs| {
+        let hooks = HOOKS.borrow(cs);
+        let mut h = hooks.get();
+        h[which] = hook;
+        hooks.set(h);
+    });
+}
+
+#[cfg(all(feature = "trap-hooks", target_os = "none"))]
+mod entry {
+    use super::{TrapFrame, HOOKS};
+
+    // Frame offsets: ra, t0-t6, a0-a7 (riscv-rt's frame) at 0-60, then
+    // s0-s11 at 64-108, sp 112, gp 116, tp 120, mepc 124.
+    core::arch::global_asm!(
+        ".section .text.sentinel.hooks, \"ax\"",
+        ".balign 4",
+        ".global _start_trap",
+        "_start_trap:",
+        "addi sp, sp, -128",
+        "sw ra, 0(sp)",
+        "sw t0, 4(sp)", "sw t1, 8(sp)", "sw t2, 12(sp)", "sw t3, 16(sp)",
+        "sw t4, 20(sp)", "sw t5, 24(sp)", "sw t6, 28(sp)",
+        "sw a0, 32(sp)", "sw a1, 36(sp)", "sw a2, 40(sp)", "sw a3, 44(sp)",
+        "sw a4, 48(sp)", "sw a5, 52(sp)", "sw a6, 56(sp)", "sw a7, 60(sp)",
+        "sw s0, 64(sp)", "sw s1, 68(sp)", "sw s2, 72(sp)", "sw s3, 76(sp)",
+        "sw s4, 80(sp)", "sw s5, 84(sp)", "sw s6, 88(sp)", "sw s7, 92(sp)",
+        "sw s8, 96(sp)", "sw s9, 100(sp)", "sw s10, 104(sp)", "sw s11, 108(sp)",
+        "addi t0, sp, 128",
+        "sw t0, 112(sp)",
+        "sw gp, 116(sp)",
+        "sw tp, 120(sp)",
+        // Kept in s1 too, which the calls preserve, to tell if a handler
+        // moves it; the frame has s1's own value.
+        "csrr s1, mepc",
+        "sw s1, 124(sp)",
+        "mv a0, sp",
+        "jal ra, __sentinel_trap_entry",
+        // Nonzero: an emulated instruction, and nothing more to do.
+        "bnez a0, 1f",
+        "mv a0, sp",
+        "jal ra, _sentinel_trap_rust",
+        // The handlers only see riscv-rt's frame, so any that skip an
+        // instruction write mepc itself. If none did, keep the frame's, which
+        // the entry hook may have moved.
+        "csrr t0, mepc",
+        "beq t0, s1, 2f",
+        "sw t0, 124(sp)",
+        "2:",
+        "mv a0, sp",
+        "jal ra, __sentinel_trap_exit",
+        "1:",
+        "lw t0, 124(sp)",
+        "csrw mepc, t0",
+        "lw ra, 0(sp)",
+        "lw t0, 4(sp)", "lw t1, 8(sp)", "lw t2, 12(sp)", "lw t3, 16(sp)",
+        "lw t4, 20(sp)", "lw t5, 24(sp)", "lw t6, 28(sp)",
+        "lw a0, 32(sp)", "lw a1, 36(sp)", "lw a2, 40(sp)", "lw a3, 44(sp)",
+        "lw a4, 48(sp)", "lw a5, 52(sp)", "lw a6, 56(sp)", "lw a7, 60(sp)",
+        "lw s0, 64(sp)", "lw s1, 68(sp)", "lw s2, 72(sp)", "lw s3, 76(sp)",
+        "lw s4, 80(sp)", "lw s5, 84(sp)", "lw s6, 88(sp)", "lw s7, 92(sp)",
+        "lw s8, 96(sp)", "lw s9, 100(sp)", "lw s10, 104(sp)", "lw s11, 108(sp)",
+        "lw gp, 116(sp)",
+        "lw tp, 120(sp)",
+        // Last, as it may be another stack now.
+        "lw sp, 112(sp)",
+        "mret",
+    );
+
+    fn call(which: usize, frame: &mut TrapFrame) {
+        if let Some(hook) = crate::critical::in_interrupt(|cs| HOOKS.borrow(cs).get()[which]) {
+            hook(frame);
+        }
+    }
+
+    #[export_name = "__sentinel_trap_entry"]
+    extern "C" fn trap_entry(frame: &mut TrapFrame) -> bool {
+        #[cfg(any(feature = "emulate-m", feature = "emulate-misaligned"))]
+        if crate::emulate::on_trap(frame) {
+            return true;
+        }
+        #[cfg(feature = "kernel")]
+        if crate::kernel::on_trap_entry(frame) {
+            return true;
+        }
+        call(0, frame);
+        false
+    }
+
+    #[export_name = "__sentinel_trap_exit"]
+    extern "C" fn trap_exit(frame: &mut TrapFrame) {
+        call(1, frame);
+        #[cfg(feature = "kernel")]
+        crate::kernel::on_trap_exit(frame);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use heapless::Vec;
+
+    use super::*;
+
+    impl Output for Vec<u8, 1024> {
+        fn write_bytes(&mut self, bytes: &[u8]) {
+            self.extend_from_slice(bytes).unwrap();
+        }
+    }
+
+    #[test]
+    fn dump() {
+        let mut regs = [0; 19];
+        regs[1] = 0x7b0;
+        regs[18] = 0xdead_beef;
+        let s = Snapshot { mcause: 2, mepc: 0x134, mtval: 0, regs };
+        let mut out = Vec::<u8, 1024>::new();
+        s.write(&mut out);
+
+        let text = core::str::from_utf8(&out).unwrap();
+        let lines: Vec<&str, 8> = text.split("\r\n").collect();
+        assert_eq!(lines[0], "Illegal instruction at 00000134");
+        assert_eq!(lines[1], "mcause 00000002 mepc 00000134 mtval 00000000");
+        assert_eq!(lines[2], "ra 00000000 sp 000007b0 gp 00000000 tp 00000000");
+        assert_eq!(lines[6], "a5 00000000 a6 00000000 a7 deadbeef");
+        assert_eq!(lines[7], "");
+    }
+
+    #[test]
+    fn stack_overflow() {
+        let guard = 0x400..0x420;
+        let mut regs = [0; 19];
+        regs[1] = 0x7b0;
+        let store = Snapshot { mcause: 7, mepc: 0x212, mtval: 0x41c, regs };
+        assert!(store.is_stack_overflow(guard.clone()));
+        // mtval is the instruction, not an address.
+        let illegal = Snapshot { mcause: 2, ..store };
+        assert!(!illegal.is_stack_overflow(guard.clone()));
+        regs[1] = 0x410;
+        assert!(Snapshot { regs, ..illegal }.is_stack_overflow(guard.clone()));
+        // No guard, no overflow.
+        assert!(!Snapshot { regs, ..illegal }.is_stack_overflow(0..0));
+    }
+
+    #[test]
+    fn causes() {
+        for mcause in [0, 2, 5, 8, 11, 0x8000_0003, 0x8000_0007, 0x8000_000b, 9, 0x8000_0002] {
+            assert_eq!(u32::from(Cause::from(mcause)), mcause);
+        }
+        assert_eq!(Cause::from(5), Cause::LoadFault);
+        assert!(Cause::from(0x8000_000b).is_interrupt());
+        assert_eq!(Cause::from(0x8000_0002), Cause::Unknown(0x8000_0002));
+    }
+
+    #[test]
+    fn frame_layout() {
+        use core::mem::{offset_of, size_of};
+
+        // What the trap entry's offsets assume.
+        assert_eq!(size_of::<TrapFrame>(), 32 * size_of::<usize>());
+        assert_eq!(size_of::<RtTrapFrame>(), offset_of!(TrapFrame, s));
+        assert_eq!(offset_of!(TrapFrame, sp), 28 * size_of::<usize>());
+        assert_eq!(offset_of!(TrapFrame, mepc), 31 * size_of::<usize>());
+    }
+
+    #[test]
+    fn display() {
+        use core::fmt::Write;
+
+        let mut s = heapless::String::<64>::new();
+        write!(s, "{} at {:#x}", Cause::IllegalInstruction, 0x134).unwrap();
+        assert_eq!(s, "Illegal instruction at 0x134");
+        s.clear();
+        write!(s, "{}", Cause::Unknown(9)).unwrap();
+        assert_eq!(s, "Unknown trap (mcause 0x00000009)");
+    }
+}
@@ -0,0 +1,617 @@
+//! A tiny stack-based bytecode VM, for scripts uploaded over the UART.
+//!
+//! Small behaviours (LED patterns, reacting to the inputs) can then be
+//! changed without rebuilding the firmware. A [`Vm`] holds up to `N` bytes
+//! of code, [`STACK`] values on its stack, and [`VARS`] variables; that's
+//! all the memory a script gets. Values are `i32`.
+//!
+//! [`Vm::run`] executes a bounded number of instructions and returns, so it
+//! can be a [`periodic`](crate::periodic) task; a script also gives up the
+//! CPU at `yield`, and for a while at `sleep`. The hardware is reached
+//! through [`Io`].
+//!
+//! Scripts are written in assembly, assembled on the host (see
+//! `sentinel-tools`' `vm-asm`), and uploaded as a 12-byte little-endian
+//! [`Header`] (`"SVM1"`, length, CRC-32 of the code) followed by the code.
+//! Feed each byte from the UART to [`Vm::receive`], and answer `ok\r\n` (or
+//! the error) once it's done with one:
+//!
+//! ```ignore
+//! if let Some(b) = serial.read_byte() {
+//!     match vm.receive(b) {
+//!         Some(Ok(_)) => serial.write_bytes(b"ok\r\n"),
+//!         Some(Err(e)) => e.report(...),
+//!         None => {}
+//!     }
+//! }
+//! ```
+//!
+//! Instructions are an [`Op`] byte, and for some an immediate operand. Jump
+//! targets are byte offsets into the code.
+
+use crate::checksum::Crc32;
+use crate::error::Describe;
+
+/// Stack depth.
+pub const STACK: usize = 16;
+
+/// Number of variables, for `load` and `store`.
+pub const VARS: usize = 8;
+
+const MAGIC: u32 = 0x314d_5653; // "SVM1"
+
+/// What a script can touch.
+pub trait Io {
+    fn set_leds(&mut self, val: u8);
+    fn inputs(&mut self) -> u8;
+    fn ticks(&self) -> u32;
+}
+
+macro_rules! ops {
+    ($($(#[doc = $doc:literal])*
+       $name:ident = $code:literal, $mnemonic:literal, $operand:literal;)*) => {
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        #[repr(u8)]
+        pub enum Op {
+            $($(#[doc = $doc])* $name = $code,)*
+        }
+
+        impl Op {
+            pub const ALL: &'static [Op] = &[$(Op::$name),*];
+
+            pub const fn from_u8(val: u8) -> Option<Self> {
+                match val {
+                    $($code => Some(Self::$name),)*
+                    _ => None,
+                }
+            }
+
+            /// Assembly name.
+            pub const fn mnemonic(self) -> &'static str {
+                match self {
+                    $(Self::$name => $mnemonic,)*
+                }
+            }
+
+            /// Bytes of immediate operand after the opcode.
+            pub const fn operand_len(self) -> usize {
+                match self {
+                    $(Self::$name => $operand,)*
+                }
+            }
+        }
+    };
+}
+
+ops! {
+    /// Stop the script.
+    Halt = 0x00, "halt", 0;
+    /// Push a sign-extended 8-bit immediate.
+    Push8 = 0x01, "push8", 1;
+    /// Push a sign-extended 16-bit immediate.
+    Push16 = 0x02, "push16", 2;
+    Push32 = 0x03, "push32", 4;
+    Dup = 0x08, "dup", 0;
+    Drop = 0x09, "drop", 0;
+    Swap = 0x0a, "swap", 0;
+    /// Push a copy of the value under the top.
+    Over = 0x0b, "over", 0;
+    Add = 0x10, "add", 0;
+    Sub = 0x11, "sub", 0;
+    Mul = 0x12, "mul", 0;
+    Div = 0x13, "div", 0;
+    Rem = 0x14, "rem", 0;
+    And = 0x15, "and", 0;
+    Or = 0x16, "or", 0;
+    Xor = 0x17, "xor", 0;
+    Shl = 0x18, "shl", 0;
+    /// Arithmetic shift right.
+    Shr = 0x19, "shr", 0;
+    /// Bitwise not.
+    Not = 0x1a, "not", 0;
+    /// Comparisons push 1 if true, else 0.
+    Eq = 0x1b, "eq", 0;
+    Lt = 0x1c, "lt", 0;
+    Gt = 0x1d, "gt", 0;
+    /// Jump to a 16-bit code offset.
+    Jmp = 0x20, "jmp", 2;
+    /// Pop, and jump if it was zero.
+    Jz = 0x21, "jz", 2;
+    Jnz = 0x22, "jnz", 2;
+    /// Push variable n (8-bit immediate).
+    Load = 0x28, "load", 1;
+    /// Pop into variable n.
+    Store = 0x29, "store", 1;
+    /// Pop, and show the low byte on the LEDs.
+    Leds = 0x30, "leds", 0;
+    Inputs = 0x31, "inputs", 0;
+    Ticks = 0x32, "ticks", 0;
+    /// Give up the CPU until the next [`Vm::run`].
+    Yield = 0x38, "yield", 0;
+    /// Pop n, and give up the CPU for n ticks.
+    Sleep = 0x39, "sleep", 0;
+}
+
+/// Why a script stopped, or an upload failed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    StackOverflow,
+    StackUnderflow,
+    BadOp,
+    /// The program counter left the code.
+    BadJump,
+    BadVar,
+    DivByZero,
+    BadMagic,
+    /// The script doesn't fit.
+    TooLarge,
+    BadCrc,
+}
+
+impl Describe for Error {
+    fn describe(&self) -> &'static str {
+        match self {
+            Self::StackOverflow => "vm: stack overflow",
+            Self::StackUnderflow => "vm: stack underflow",
+            Self::BadOp => "vm: bad opcode",
+            Self::BadJump => "vm: jump out of code",
+            Self::BadVar => "vm: bad variable",
+            Self::DivByZero => "vm: divide by zero",
+            Self::BadMagic => "vm: bad magic",
+            Self::TooLarge => "vm: script too large",
+            Self::BadCrc => "vm: bad crc",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Header {
+    pub len: u32,
+    pub crc: u32,
+}
+
+impl Header {
+    pub const LEN: usize = 12;
+
+    pub fn for_code(code: &[u8]) -> Self {
+        let mut crc = Crc32::new();
+        crc.update(code);
+        Self { len: code.len() as u32, crc: crc.finish() }
+    }
+
+    /// Parse and check a header, for a VM holding up to `max_len` bytes.
+    pub fn parse(bytes: &[u8; Self::LEN], max_len: usize) -> Result<Self, Error> {
+        let word = |i: usize| {
+            u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]])
+        };
+        if word(0) != MAGIC {
+            return Err(Error::BadMagic);
+        }
+        let hdr = Self { len: word(4), crc: word(8) };
+        if hdr.len as usize > max_len {
+            return Err(Error::TooLarge);
+        }
+        Ok(hdr)
+    }
+
+    pub fn to_bytes(&self) -> [u8; Self::LEN] {
+        let mut bytes = [0; Self::LEN];
+        for (i, w) in [MAGIC, self.len, self.crc].into_iter().enumerate() {
+            bytes[i * 4..][..4].copy_from_slice(&w.to_le_bytes());
+        }
+        bytes
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum State {
+    /// Runs on the next [`Vm::run`].
+    Ready,
+    /// Sleeping until the given tick.
+    Sleeping(u32),
+    /// Stopped at `halt`, or with no script loaded.
+    Halted,
+    Faulted(Error),
+}
+
+#[derive(Clone, Copy)]
+enum Upload {
+    /// Bytes of the header so far.
+    Header(usize),
+    /// Bytes of code so far.
+    Code(usize, Header, Crc32),
+}
+
+pub struct Vm<const N: usize> {
+    code: [u8; N],
+    len: usize,
+    pc: usize,
+    stack: [i32; STACK],
+    sp: usize,
+    vars: [i32; VARS],
+    state: State,
+    upload: Upload,
+    header: [u8; Header::LEN],
+}
+
+impl<const N: usize> Default for Vm<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Vm<N> {
+    /// A VM with no script.
+    pub const fn new() -> Self {
+        Self {
+            code: [0; N],
+            len: 0,
+            pc: 0,
+            stack: [0; STACK],
+            sp: 0,
+            vars: [0; VARS],
+            state: State::Halted,
+            upload: Upload::Header(0),
+            header: [0; Header::LEN],
+        }
+    }
+
+    /// Replace the script with `code`, and start it from the top.
+    pub fn load(&mut self, code: &[u8]) -> Result<(), Error> {
+        let dst = self.code.get_mut(..code.len()).ok_or(Error::TooLarge)?;
+        dst.copy_from_slice(code);
+        self.len = code.len();
+        self.restart();
+        Ok(())
+    }
+
+    /// Start the script again, with an empty stack and variables zeroed.
+    pub fn restart(&mut self) {
+        self.pc = 0;
+        self.sp = 0;
+        self.vars = [0; VARS];
+        self.state = if self.len > 0 { State::Ready } else { State::Halted };
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// Whether an upload is past its header, so the bytes coming in are
+    /// code rather than anything else.
+    pub fn receiving_code(&self) -> bool {
+        matches!(self.upload, Upload::Code(..))
+    }
+
+    /// Take the next byte of an upload. Returns the new script's length
+    /// once it's loaded and running, or why the upload failed. The old
+    /// script stops as soon as a header checks out.
+    ///
+    /// Bytes that can't be the start of a header are ignored, so stray
+    /// input doesn't leave the uploader stuck partway through one.
+    pub fn receive(&mut self, b: u8) -> Option<Result<usize, Error>> {
+        match self.upload {
+            Upload::Header(i) => {
+                if i < 4 && b != MAGIC.to_le_bytes()[i] {
+                    self.upload = Upload::Header((b == MAGIC as u8) as usize);
+                    return None;
+                }
+                self.header[i] = b;
+                if i + 1 < Header::LEN {
+                    self.upload = Upload::Header(i + 1);
+                    return None;
+                }
+
+                self.upload = Upload::Header(0);
+                let hdr = match Header::parse(&self.header, N) {
+                    Ok(hdr) => hdr,
+                    Err(e) => return Some(Err(e)),
+                };
+                self.len = 0;
+                self.state = State::Halted;
+                if hdr.len == 0 {
+                    return self.finish(hdr, Crc32::new());
+                }
+                self.upload = Upload::Code(0, hdr, Crc32::new());
+                None
+            }
+            Upload::Code(i, hdr, mut crc) => {
+                self.code[i] = b;
+                crc.update_byte(b);
+                if i + 1 < hdr.len as usize {
+                    self.upload = Upload::Code(i + 1, hdr, crc);
+                    return None;
+                }
+                self.upload = Upload::Header(0);
+                self.finish(hdr, crc)
+            }
+        }
+    }
+
+    fn finish(&mut self, hdr: Header, crc: Crc32) -> Option<Result<usize, Error>> {
+        if crc.finish() != hdr.crc {
+            return Some(Err(Error::BadCrc));
+        }
+        self.len = hdr.len as usize;
+        self.restart();
+        Some(Ok(self.len))
+    }
+
+    fn push(&mut self, val: i32) -> Result<(), Error> {
+        *self.stack.get_mut(self.sp).ok_or(Error::StackOverflow)? = val;
+        self.sp += 1;
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Result<i32, Error> {
+        self.sp = self.sp.checked_sub(1).ok_or(Error::StackUnderflow)?;
+        Ok(self.stack[self.sp])
+    }
+
+    fn operand<const L: usize>(&mut self) -> Result<[u8; L], Error> {
+        let bytes = self.code[..self.len]
+            .get(self.pc..self.pc + L)
+            .ok_or(Error::BadJump)?;
+        self.pc += L;
+        Ok(bytes.try_into().unwrap())
+    }
+
+    fn jump(&mut self, target: [u8; 2]) -> Result<(), Error> {
+        let target = u16::from_le_bytes(target) as usize;
+        if target >= self.len {
+            return Err(Error::BadJump);
+        }
+        self.pc = target;
+        Ok(())
+    }
+
+    /// Execute one instruction. Returns whether the script should give up
+    /// the CPU.
+    fn step(&mut self, io: &mut dyn Io) -> Result<bool, Error> {
+        let op = *self.code[..self.len].get(self.pc).ok_or(Error::BadJump)?;
+        let op = Op::from_u8(op).ok_or(Error::BadOp)?;
+        self.pc += 1;
+
+        match op {
+            Op::Halt => {
+                self.state = State::Halted;
+                return Ok(true);
+            }
+            Op::Push8 => {
+                let [b] = self.operand()?;
+                self.push(b as i8 as i32)?;
+            }
+            Op::Push16 => {
+                let b = self.operand()?;
+                self.push(i16::from_le_bytes(b) as i32)?;
+            }
+            Op::Push32 => {
+                let b = self.operand()?;
+                self.push(i32::from_le_bytes(b))?;
+            }
+            Op::Dup => {
+                let a = self.pop()?;
+                self.push(a)?;
+                self.push(a)?;
+            }
+            Op::Drop => {
+                self.pop()?;
+            }
+            Op::Swap => {
+                let (b, a) = (self.pop()?, self.pop()?);
+                self.push(b)?;
+                self.push(a)?;
+            }
+            Op::Over => {
+                let (b, a) = (self.pop()?, self.pop()?);
+                self.push(a)?;
+                self.push(b)?;
+                self.push(a)?;
+            }
+            Op::Add | Op::Sub | Op::Mul | Op::Div | Op::Rem | Op::And | Op::Or | Op::Xor
+            | Op::Shl | Op::Shr | Op::Eq | Op::Lt | Op::Gt => {
+                let (b, a) = (self.pop()?, self.pop()?);
+                let val = match op {
+                    Op::Add => a.wrapping_add(b),
+                    Op::Sub => a.wrapping_sub(b),
+                    Op::Mul => a.wrapping_mul(b),
+                    Op::Div => a.checked_div(b).ok_or(Error::DivByZero)?,
+                    Op::Rem => a.checked_rem(b).ok_or(Error::DivByZero)?,
+                    Op::And => a & b,
+                    Op::Or => a | b,
+                    Op::Xor => a ^ b,
+                    Op::Shl => a.wrapping_shl(b as u32),
+                    Op::Shr => a.wrapping_shr(b as u32),
+                    Op::Eq => (a == b) as i32,
+                    Op::Lt => (a < b) as i32,
+                    _ => (a > b) as i32,
+                };
+                self.push(val)?;
+            }
+            Op::Not => {
+                let a = self.pop()?;
+                self.push(!a)?;
+            }
+            Op::Jmp => {
+                let target = self.operand()?;
+                self.jump(target)?;
+            }
+            Op::Jz | Op::Jnz => {
+                let target = self.operand()?;
+                if (self.pop()? == 0) == (op == Op::Jz) {
+                    self.jump(target)?;
+                }
+            }
+            Op::Load => {
+                let [n] = self.operand()?;
+                let val = *self.vars.get(n as usize).ok_or(Error::BadVar)?;
+                self.push(val)?;
+            }
+            Op::Store => {
+                let [n] = self.operand()?;
+                let val = self.pop()?;
+                *self.vars.get_mut(n as usize).ok_or(Error::BadVar)? = val;
+            }
+            Op::Leds => {
+                let val = self.pop()?;
+                io.set_leds(val as u8);
+            }
+            Op::Inputs => self.push(io.inputs() as i32)?,
+            Op::Ticks => self.push(io.ticks() as i32)?,
+            Op::Yield => return Ok(true),
+            Op::Sleep => {
+                let ticks = self.pop()?.max(0) as u32;
+                self.state = State::Sleeping(io.ticks().wrapping_add(ticks));
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Run the script for at most `budget` instructions, or until it
+    /// yields, sleeps or stops. Returns the state it's left in.
+    pub fn run(&mut self, io: &mut dyn Io, budget: u32) -> State {
+        if let State::Sleeping(until) = self.state {
+            // Wrapping compare, as timebase ticks wrap.
+            if (io.ticks().wrapping_sub(until) as i32) < 0 {
+                return self.state;
+            }
+            self.state = State::Ready;
+        }
+        if self.state != State::Ready {
+            return self.state;
+        }
+
+        for _ in 0..budget {
+            match self.step(io) {
+                Ok(false) => {}
+                Ok(true) => break,
+                Err(e) => {
+                    self.state = State::Faulted(e);
+                    break;
+                }
+            }
+        }
+        self.state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Board {
+        leds: u8,
+        inputs: u8,
+        ticks: u32,
+    }
+
+    impl Io for Board {
+        fn set_leds(&mut self, val: u8) {
+            self.leds = val;
+        }
+
+        fn inputs(&mut self) -> u8 {
+            self.inputs
+        }
+
+        fn ticks(&self) -> u32 {
+            self.ticks
+        }
+    }
+
+    const fn op(op: Op) -> u8 {
+        op as u8
+    }
+
+    #[test]
+    fn arithmetic() {
+        let mut vm: Vm<32> = Vm::new();
+        // leds((7 - 2) * 3 + inputs)
+        vm.load(&[op(Op::Push8), 7, op(Op::Push8), 2, op(Op::Sub), op(Op::Push8), 3,
+                  op(Op::Mul), op(Op::Inputs), op(Op::Add), op(Op::Leds), op(Op::Halt)])
+            .unwrap();
+        let mut board = Board { inputs: 1, ..Board::default() };
+        assert_eq!(vm.run(&mut board, 100), State::Halted);
+        assert_eq!(board.leds, 16);
+    }
+
+    #[test]
+    fn loops_yield_and_sleep() {
+        let mut vm: Vm<32> = Vm::new();
+        // 0: load 0; push8 1; add; dup; store 0; leds; push8 5; sleep; jmp 0
+        vm.load(&[op(Op::Load), 0, op(Op::Push8), 1, op(Op::Add), op(Op::Dup),
+                  op(Op::Store), 0, op(Op::Leds), op(Op::Push8), 5, op(Op::Sleep),
+                  op(Op::Jmp), 0, 0])
+            .unwrap();
+        let mut board = Board::default();
+        assert_eq!(vm.run(&mut board, 100), State::Sleeping(5));
+        assert_eq!(board.leds, 1);
+
+        board.ticks = 4;
+        vm.run(&mut board, 100);
+        assert_eq!(board.leds, 1);
+        board.ticks = 5;
+        assert_eq!(vm.run(&mut board, 100), State::Sleeping(10));
+        assert_eq!(board.leds, 2);
+
+        // A budget bounds a busy loop.
+        vm.load(&[op(Op::Jmp), 0, 0]).unwrap();
+        assert_eq!(vm.run(&mut board, 10), State::Ready);
+    }
+
+    #[test]
+    fn faults() {
+        let mut vm: Vm<8> = Vm::new();
+        let mut board = Board::default();
+        let cases: [(&[u8], Error); 5] = [
+            (&[op(Op::Add)], Error::StackUnderflow),
+            (&[op(Op::Push8), 1, op(Op::Push8), 0, op(Op::Div)], Error::DivByZero),
+            (&[op(Op::Jmp), 9, 0], Error::BadJump),
+            (&[op(Op::Push8)], Error::BadJump),
+            (&[0xff], Error::BadOp),
+        ];
+        for (code, e) in cases {
+            vm.load(code).unwrap();
+            assert_eq!(vm.run(&mut board, 10), State::Faulted(e));
+        }
+
+        vm.load(&[op(Op::Dup), op(Op::Jmp), 0, 0]).unwrap();
+        vm.push(0).unwrap();
+        assert_eq!(vm.run(&mut board, 100), State::Faulted(Error::StackOverflow));
+        assert_eq!(vm.load(&[0; 9]), Err(Error::TooLarge));
+    }
+
+    #[test]
+    fn upload() {
+        let mut vm: Vm<8> = Vm::new();
+        let code = [op(Op::Push8), 3, op(Op::Leds), op(Op::Halt)];
+        let hdr = Header::for_code(&code).to_bytes();
+
+        // Noise first, including a false start.
+        for b in [b'x', b'S', b'S'] {
+            assert_eq!(vm.receive(b), None);
+        }
+        for b in &hdr[1..] {
+            assert_eq!(vm.receive(*b), None);
+        }
+        assert!(vm.receiving_code());
+        let results: heapless::Vec<_, 4> = code.iter().map(|b| vm.receive(*b)).collect();
+        assert_eq!(&results[..], &[None, None, None, Some(Ok(4))]);
+
+        let mut board = Board::default();
+        assert_eq!(vm.run(&mut board, 10), State::Halted);
+        assert_eq!(board.leds, 3);
+
+        let mut bad = Header::for_code(&code);
+        bad.crc ^= 1;
+        let results: heapless::Vec<_, 16> =
+            bad.to_bytes().iter().chain(&code).filter_map(|b| vm.receive(*b)).collect();
+        assert_eq!(&results[..], &[Err(Error::BadCrc)]);
+
+        let big = Header { len: 9, crc: 0 }.to_bytes();
+        let results: heapless::Vec<_, 1> = big.iter().filter_map(|b| vm.receive(*b)).collect();
+        assert_eq!(&results[..], &[Err(Error::TooLarge)]);
+    }
+}
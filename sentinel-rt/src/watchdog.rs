@@ -0,0 +1,230 @@
+//! Watchdog: recover from hangs with a reset.
+//!
+//! The main loop arms a [`Watchdog`] with a timeout, then [`feeds`] it at
+//! least that often; if it ever stops doing so, the watchdog resets the
+//! firmware, with [`Reason::Watchdog`] and an
+//! [`Event::WatchdogMiss`](crate::events::Event::WatchdogMiss) in the
+//! event log.
+//!
+//! No AttoSoC bitstream has a hardware watchdog yet, so [`Software`] does
+//! the job from the timer ISR. It only catches hangs with interrupts still
+//! enabled, but that covers the common ones (a busy-wait on a flag that
+//! never comes, a deadlocked main loop):
+//!
+//! ```ignore
+//! let mut wdt = watchdog::Software;
+//! wdt.start(Duration::from_millis(500))?;
+//!
+//! #[no_mangle]
+//! fn MachineExternal() {
+//!     if timer_irq() {
+//!         timebase::tick();
+//!         watchdog::check();
+//!     }
+//! }
+//!
+//! loop {
+//!     wdt.feed();
+//!     // ...
+//! }
+//! ```
+//!
+//! To do something else on a miss (report it somewhere, save state, or
+//! just count it), [`set_handler`] replaces the reset with a callback.
+//! Either way, the miss is logged first.
+//!
+//! A hardware driver will implement the same trait, so applications can
+//! switch over without changes.
+//!
+//! [`feeds`]: Watchdog::feed
+
+use core::cell::{Cell, RefCell};
+use core::time::Duration;
+
+use critical_section::Mutex;
+
+use crate::error::Describe;
+use crate::events::{self, Event};
+use crate::periodic::duration_to_ticks;
+use crate::reset::{self, Reason};
+use crate::timebase;
+
+/// Longest timeout [`Software`] takes, in ticks. Anything longer risks the
+/// tick count wrapping past the deadline unnoticed.
+pub const MAX_TICKS: u32 = 1 << 30;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The timeout is zero, or longer than the watchdog can count.
+    BadTimeout,
+}
+
+impl Describe for Error {
+    fn describe(&self) -> &'static str {
+        match self {
+            Self::BadTimeout => "watchdog: bad timeout",
+        }
+    }
+}
+
+/// What every watchdog driver provides.
+pub trait Watchdog {
+    /// Arm the watchdog; it resets the firmware unless fed at least every
+    /// `timeout`. Starting an armed watchdog changes its timeout, and
+    /// counts as a feed.
+    fn start(&mut self, timeout: Duration) -> Result<(), Error>;
+
+    /// Push the deadline out by another timeout.
+    fn feed(&mut self);
+
+    /// Disarm the watchdog, if the hardware allows it.
+    fn stop(&mut self);
+}
+
+/// Deadline bookkeeping, in ticks. Public so it can be driven from some
+/// other time source.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Deadline {
+    timeout: u32,
+    last_feed: u32,
+    armed: bool,
+}
+
+impl Deadline {
+    pub const fn new() -> Self {
+        Self { timeout: 0, last_feed: 0, armed: false }
+    }
+
+    pub fn start(&mut self, now: u32, timeout: u32) -> Result<(), Error> {
+        if timeout == 0 || timeout > MAX_TICKS {
+            return Err(Error::BadTimeout);
+        }
+        *self = Self { timeout, last_feed: now, armed: true };
+        Ok(())
+    }
+
+    pub fn feed(&mut self, now: u32) {
+        self.last_feed = now;
+    }
+
+    pub fn stop(&mut self) {
+        self.armed = false;
+    }
+
+    pub fn armed(&self) -> bool {
+        self.armed
+    }
+
+    /// Whether the watchdog is armed, and went unfed for longer than its
+    /// timeout.
+    pub fn expired(&self, now: u32) -> bool {
+        self.armed && now.wrapping_sub(self.last_feed) > self.timeout
+    }
+}
+
+static DEADLINE: Mutex<RefCell<Deadline>> = Mutex::new(RefCell::new(Deadline::new()));
+
+pub type Handler = fn();
+
+static HANDLER: Mutex<Cell<Option<Handler>>> = Mutex::new(Cell::new(None));
+
+/// Call `handler` from the ISR on a miss, instead of resetting. The
+/// watchdog is fed before it's called, so if it returns and the main loop
+/// is still stuck, it's called again a timeout later.
+pub fn set_handler(handler: Handler) {
+    critical_section::with(|cs| HANDLER.borrow(cs).set(Some(handler)));
+}
+
+/// The watchdog for bitstreams without one, run by [`check`] from the timer
+/// ISR.
+pub struct Software;
+
+impl Watchdog for Software {
+    fn start(&mut self, timeout: Duration) -> Result<(), Error> {
+        let ticks = duration_to_ticks(timeout);
+        critical_section::with(|cs| {
+            DEADLINE.borrow_ref_mut(cs).start(timebase::ticks32(), ticks)
+        })
+    }
+
+    fn feed(&mut self) {
+        critical_section::with(|cs| DEADLINE.borrow_ref_mut(cs).feed(timebase::ticks32()));
+    }
+
+    fn stop(&mut self) {
+        critical_section::with(|cs| DEADLINE.borrow_ref_mut(cs).stop());
+    }
+}
+
+/// Reset (or call the [handler](set_handler)), with the miss recorded, if
+/// the [`Software`] watchdog has expired. Call from the timer ISR, after
+/// [`timebase::tick`].
+pub fn check() {
+    let handler = critical_section::with(|cs| {
+        let mut deadline = DEADLINE.borrow_ref_mut(cs);
+        let now = timebase::ticks32();
+        deadline.expired(now).then(|| {
+            deadline.feed(now);
+            HANDLER.borrow(cs).get()
+        })
+    });
+    let Some(handler) = handler else {
+        return;
+    };
+    events::record(Event::WatchdogMiss(0));
+    match handler {
+        Some(f) => f(),
+        None => reset::soft_reset(Some(Reason::Watchdog)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expires_unless_fed() {
+        let mut d = Deadline::new();
+        assert!(!d.expired(1000));
+
+        d.start(u32::MAX - 5, 10).unwrap();
+        assert!(!d.expired(4));
+        assert!(d.expired(5));
+
+        d.feed(5);
+        assert!(!d.expired(15));
+        assert!(d.expired(16));
+
+        d.stop();
+        assert!(!d.expired(100));
+    }
+
+    #[test]
+    fn handler_instead_of_reset() {
+        use portable_atomic::{AtomicU32, Ordering};
+        static CALLS: AtomicU32 = AtomicU32::new(0);
+        set_handler(|| {
+            CALLS.fetch_add(1, Ordering::Relaxed);
+        });
+
+        Software.start(Duration::from_millis(1)).unwrap();
+        check();
+        assert_eq!(CALLS.load(Ordering::Relaxed), 0);
+        timebase::tick();
+        timebase::tick();
+        check();
+        assert_eq!(CALLS.load(Ordering::Relaxed), 1);
+        // Fed for it.
+        check();
+        assert_eq!(CALLS.load(Ordering::Relaxed), 1);
+        Software.stop();
+    }
+
+    #[test]
+    fn bad_timeouts() {
+        let mut d = Deadline::new();
+        assert_eq!(d.start(0, 0), Err(Error::BadTimeout));
+        assert_eq!(d.start(0, MAX_TICKS + 1), Err(Error::BadTimeout));
+        assert!(!d.armed());
+    }
+}
@@ -0,0 +1,137 @@
+"""Run Sentinel firmware in the Amaranth simulator, for sentinel-runner.
+
+The core is simulated on its own, as in the RISCOF plugin; RAM, the UART
+and sentinel_rt::sim's testbench ports are modelled here, on its bus. What
+the firmware writes to the UART or to PUTCHAR goes to stdout, and the
+simulation ends when it writes EXIT, exiting with what it wrote.
+
+The UART always has its last byte sent and never receives anything, and
+raises no IRQ. The timer raises its IRQ every 16384 cycles, as the
+gateware's prescaler does, until it's read. GPIO reads back what was last
+written to it.
+"""
+
+import argparse
+import sys
+
+from amaranth.sim import Simulator, Tick
+from elftools.elf.elffile import ELFFile
+
+from sentinel.top import Top
+
+# What timeout(1) exits with.
+TIMED_OUT = 124
+
+# The UART's registers, from sentinel_rt::hal::serial.
+RXTX = 0
+IRQ = 4
+IRQ_TX = 0x02
+
+# The timer's prescaler, from examples/attosoc.py: its top bit is the IRQ,
+# cleared by reading it.
+PRESCALER = 0x7fff
+TICK = 0x4000
+
+
+def load(path):
+    """The ELF's loadable segments, as a sparse byte-addressed memory."""
+    mem = {}
+    with open(path, "rb") as fp:
+        for seg in ELFFile(fp).iter_segments():
+            if seg["p_type"] != "PT_LOAD":
+                continue
+            for i, b in enumerate(seg.data()):
+                mem[seg["p_paddr"] + i] = b
+    return mem
+
+
+def bus(top, mem, args, result):
+    serial = set(args.serial)
+    timer = set(args.timer)
+    out = sys.stdout.buffer
+
+    def put(b):
+        out.write(bytes([b]))
+        if b == ord("\n"):
+            out.flush()
+
+    def process():
+        cycles = 0
+        prescaler = 0
+        while not args.max_cycles or cycles < args.max_cycles:
+            prescaler = (prescaler + 1) & PRESCALER
+            yield top.irq.eq((prescaler & TICK) != 0)
+            acked = yield top.bus.ack
+            yield top.bus.dat_r.eq(0)
+            yield top.bus.ack.eq(0)
+
+            if (yield top.bus.cyc) and (yield top.bus.stb) and not acked:
+                yield top.bus.ack.eq(1)
+                adr = (yield top.bus.adr) << 2
+                sel = yield top.bus.sel
+                lanes = [i for i in range(4) if sel & (1 << i)]
+
+                if (yield top.bus.we):
+                    dat = yield top.bus.dat_w
+                    if adr == args.exit:
+                        result.append(dat)
+                        return
+                    elif adr == args.putchar or adr - RXTX in serial:
+                        put(dat & 0xff)
+                    else:
+                        for i in lanes:
+                            mem[adr + i] = (dat >> 8 * i) & 0xff
+                elif adr - IRQ in serial:
+                    yield top.bus.dat_r.eq(IRQ_TX)
+                elif adr in timer:
+                    yield top.bus.dat_r.eq(int((prescaler & TICK) != 0))
+                    prescaler &= ~TICK
+                elif adr - RXTX not in serial:
+                    dat = 0
+                    for i in lanes:
+                        dat |= mem.get(adr + i, 0) << 8 * i
+                    yield top.bus.dat_r.eq(dat)
+
+            yield Tick()
+            cycles += 1
+
+    return process
+
+
+def main():
+    parser = argparse.ArgumentParser(description=__doc__.splitlines()[0])
+    parser.add_argument("elf")
+    parser.add_argument("--exit", type=lambda s: int(s, 0), required=True)
+    parser.add_argument("--putchar", type=lambda s: int(s, 0), required=True)
+    parser.add_argument("--serial", type=lambda s: int(s, 0),
+                        action="append", default=[])
+    parser.add_argument("--timer", type=lambda s: int(s, 0),
+                        action="append", default=[])
+    parser.add_argument("--max-cycles", type=int, default=0)
+    parser.add_argument("--vcd")
+    args = parser.parse_args()
+
+    top = Top()
+    result = []
+    sim = Simulator(top)
+    sim.add_clock(1 / 12e6)
+    sim.add_process(bus(top, load(args.elf), args, result))
+
+    if args.vcd:
+        with sim.write_vcd(vcd_file=args.vcd):
+            sim.run()
+    else:
+        sim.run()
+    sys.stdout.flush()
+
+    if not result:
+        print(f"sentinel-runner: no exit after {args.max_cycles} cycles",
+              file=sys.stderr)
+        sys.exit(TIMED_OUT)
+    # Keep a failure a failure when it doesn't fit in an exit status.
+    code = result[0]
+    sys.exit(code & 0xff or int(code != 0))
+
+
+if __name__ == "__main__":
+    main()
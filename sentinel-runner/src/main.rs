@@ -0,0 +1,102 @@
+//! Run Sentinel firmware in the Amaranth simulator.
+//!
+//! ```text
+//! sentinel-runner [--max-cycles N] [--vcd FILE] ELF [ARGS...]
+//! ```
+//!
+//! Loads ELF into the simulated core's RAM and runs it. What it writes to
+//! the UART, or to `sentinel_rt::sim::putchar`, goes to stdout, and when it
+//! calls `sentinel_rt::sim::exit`, this exits with the same code. With
+//! `--max-cycles`, a run that hasn't exited by then fails, with 124.
+//! `--vcd` writes a trace of the core. ARGS are ignored: firmware has no
+//! command line.
+//!
+//! That makes it a cargo runner, so `cargo run` runs an example in the
+//! simulator, and a test binary's result (see `sentinel_rt::testing`)
+//! is cargo's:
+//!
+//! ```toml
+//! # .cargo/config.toml
+//! [target.riscv32i-unknown-none-elf]
+//! runner = "sentinel-runner --max-cycles 50000000"
+//! ```
+//!
+//! ```text
+//! cargo run --release --example selftest --features sim,board-icebreaker
+//! ```
+//!
+//! The simulation is `sim.py`, next to this crate, run with the Python in
+//! `SENTINEL_PYTHON` (default `pdm run python`) from the repository, so it
+//! finds the project's environment. The core is simulated cycle by cycle,
+//! which takes a while: think tens of thousands of instructions a second.
+
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitCode};
+
+use sentinel_rt::{io_map, sim};
+
+const USAGE: &str = "usage: sentinel-runner [--max-cycles N] [--vcd FILE] ELF [ARGS...]";
+
+fn run() -> Result<ExitCode, String> {
+    let mut args = env::args().skip(1);
+    let mut max_cycles = None;
+    let mut vcd = None;
+    let elf = loop {
+        let arg = args.next().ok_or(USAGE)?;
+        let mut value = || args.next().ok_or_else(|| format!("{arg} needs a value"));
+        match arg.as_str() {
+            "--max-cycles" => {
+                let n: u64 = value()?.parse().map_err(|_| "bad --max-cycles".to_string())?;
+                max_cycles = Some(n);
+            }
+            "--vcd" => vcd = Some(absolute(&value()?)?),
+            _ if !arg.starts_with("--") => break absolute(&arg)?,
+            _ => return Err(USAGE.into()),
+        }
+    };
+
+    let crate_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let python = env::var("SENTINEL_PYTHON").unwrap_or_else(|_| "pdm run python".into());
+    let mut python = python.split_whitespace();
+    let mut cmd = Command::new(python.next().ok_or("SENTINEL_PYTHON is empty")?);
+    cmd.args(python)
+        .current_dir(crate_dir.parent().unwrap())
+        .arg(crate_dir.join("sim.py"))
+        .arg(&elf)
+        .arg(format!("--exit={:#x}", sim::EXIT))
+        .arg(format!("--putchar={:#x}", sim::PUTCHAR))
+        // The bus is detected at boot, so the UART and timer could be at
+        // either.
+        .arg(format!("--serial={:#x}", io_map::CSR_SERIAL))
+        .arg(format!("--serial={:#x}", io_map::WB_SERIAL))
+        .arg(format!("--timer={:#x}", io_map::CSR_TIMER))
+        .arg(format!("--timer={:#x}", io_map::WB_TIMER));
+    if let Some(n) = max_cycles {
+        cmd.arg(format!("--max-cycles={n}"));
+    }
+    if let Some(vcd) = vcd {
+        cmd.arg("--vcd").arg(vcd);
+    }
+
+    let status = cmd.status().map_err(|e| format!("{:?}: {e}", cmd.get_program()))?;
+    match status.code() {
+        Some(code) => Ok(ExitCode::from(code as u8)),
+        None => Err(format!("simulation {status}")),
+    }
+}
+
+/// `path`, made absolute, since the simulation runs elsewhere.
+fn absolute(path: &str) -> Result<PathBuf, String> {
+    std::path::absolute(path).map_err(|e| format!("{path}: {e}"))
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::from(2)
+        }
+    }
+}
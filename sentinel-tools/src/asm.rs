@@ -0,0 +1,198 @@
+//! Assembler for `sentinel_rt::vm` scripts.
+//!
+//! One instruction per line, named as in `vm::Op::mnemonic`, with `;`
+//! comments and `name:` labels:
+//!
+//! ```text
+//! ; Count up on the LEDs, one step every 100 ticks.
+//! top:
+//!     load 0
+//!     push 1
+//!     add
+//!     dup
+//!     store 0
+//!     leds
+//!     push 100
+//!     sleep
+//!     jmp top
+//! ```
+//!
+//! Numbers are decimal or `0x` hex, and may be negative. `push` picks the
+//! shortest of `push8`, `push16` and `push32` that fits; jumps take a label
+//! or an offset.
+
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+
+use sentinel_rt::vm::Op;
+
+enum Operand<'a> {
+    None,
+    Number(i64),
+    Label(&'a str),
+}
+
+struct Insn<'a> {
+    line: usize,
+    op: Op,
+    operand: Operand<'a>,
+}
+
+fn number(s: &str) -> Option<i64> {
+    let (neg, s) = match s.strip_prefix('-') {
+        Some(s) => (true, s),
+        None => (false, s),
+    };
+    let n = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => i64::from_str_radix(hex, 16).ok()?,
+        None => s.parse().ok()?,
+    };
+    Some(if neg { -n } else { n })
+}
+
+fn lookup(mnemonic: &str) -> Option<Op> {
+    Op::ALL.iter().copied().find(|op| op.mnemonic() == mnemonic)
+}
+
+fn is_jump(op: Op) -> bool {
+    matches!(op, Op::Jmp | Op::Jz | Op::Jnz)
+}
+
+/// Values `op`'s operand can take, if it has one.
+fn range(op: Op) -> Option<RangeInclusive<i64>> {
+    Some(match op {
+        Op::Push8 => i8::MIN as i64..=i8::MAX as i64,
+        Op::Push16 => i16::MIN as i64..=i16::MAX as i64,
+        Op::Push32 => i32::MIN as i64..=u32::MAX as i64,
+        Op::Load | Op::Store => 0..=u8::MAX as i64,
+        _ if is_jump(op) => 0..=u16::MAX as i64,
+        _ => return None,
+    })
+}
+
+fn parse_line(line: usize, text: &str) -> Result<(Option<&str>, Option<Insn<'_>>), String> {
+    let err = |msg: String| format!("line {line}: {msg}");
+    let mut text = text.split(';').next().unwrap().trim();
+
+    let mut label = None;
+    if let Some((name, rest)) = text.split_once(':') {
+        let name = name.trim();
+        if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            return Err(err(format!("bad label: {name}")));
+        }
+        label = Some(name);
+        text = rest.trim();
+    }
+
+    let mut words = text.split_whitespace();
+    let Some(mnemonic) = words.next() else {
+        return Ok((label, None));
+    };
+    let arg = words.next();
+    if words.next().is_some() {
+        return Err(err("too many operands".into()));
+    }
+
+    let op = match mnemonic {
+        "push" => {
+            let n = arg.and_then(number).ok_or_else(|| err("push needs a number".into()))?;
+            match n {
+                -0x80..=0x7f => Op::Push8,
+                -0x8000..=0x7fff => Op::Push16,
+                _ => Op::Push32,
+            }
+        }
+        _ => lookup(mnemonic).ok_or_else(|| err(format!("unknown instruction: {mnemonic}")))?,
+    };
+
+    let operand = match (range(op), arg) {
+        (None, None) => Operand::None,
+        (None, Some(_)) => return Err(err(format!("{mnemonic} takes no operand"))),
+        (Some(_), None) => return Err(err(format!("{mnemonic} needs an operand"))),
+        (Some(range), Some(arg)) => match number(arg) {
+            Some(n) if range.contains(&n) => Operand::Number(n),
+            Some(_) => return Err(err(format!("{arg} doesn't fit in {mnemonic}"))),
+            None if is_jump(op) => Operand::Label(arg),
+            None => return Err(err(format!("bad number: {arg}"))),
+        },
+    };
+
+    Ok((label, Some(Insn { line, op, operand })))
+}
+
+/// Assemble `src`, or say what's wrong with it (and on which line).
+pub fn assemble(src: &str) -> Result<Vec<u8>, String> {
+    // Every instruction's size is known from the line alone, so labels can
+    // all be found in one pass, and resolved in a second.
+    let mut labels = HashMap::new();
+    let mut insns = Vec::new();
+    let mut offset = 0;
+    for (i, text) in src.lines().enumerate() {
+        let (label, insn) = parse_line(i + 1, text)?;
+        if let Some(label) = label {
+            if labels.insert(label, offset).is_some() {
+                return Err(format!("line {}: {label} defined twice", i + 1));
+            }
+        }
+        if let Some(insn) = insn {
+            offset += 1 + insn.op.operand_len();
+            insns.push(insn);
+        }
+    }
+    if offset > u16::MAX as usize + 1 {
+        return Err("script too long".into());
+    }
+
+    let mut code = Vec::with_capacity(offset);
+    for insn in insns {
+        code.push(insn.op as u8);
+        let n = match insn.operand {
+            Operand::None => continue,
+            Operand::Number(n) => n,
+            Operand::Label(name) => labels
+                .get(name)
+                .map(|&offset| offset as i64)
+                .ok_or_else(|| format!("line {}: no label {name}", insn.line))?,
+        };
+        code.extend_from_slice(&n.to_le_bytes()[..insn.op.operand_len()]);
+    }
+    Ok(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assembles() {
+        let src = "
+            ; count
+            top: load 0
+                push 1
+                push -200
+                push 0x12345
+                jz top  ; back
+                jmp end
+            end: halt
+        ";
+        assert_eq!(assemble(src).unwrap(), [
+            Op::Load as u8, 0,
+            Op::Push8 as u8, 1,
+            Op::Push16 as u8, 0x38, 0xff,
+            Op::Push32 as u8, 0x45, 0x23, 0x01, 0x00,
+            Op::Jz as u8, 0, 0,
+            Op::Jmp as u8, 18, 0,
+            Op::Halt as u8,
+        ]);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(assemble("add 1"), Err("line 1: add takes no operand".into()));
+        assert_eq!(assemble("\nfrob"), Err("line 2: unknown instruction: frob".into()));
+        assert_eq!(assemble("jmp nowhere"), Err("line 1: no label nowhere".into()));
+        assert_eq!(assemble("load 256"), Err("line 1: 256 doesn't fit in load".into()));
+        assert_eq!(assemble("a: halt\na: halt"), Err("line 2: a defined twice".into()));
+        assert_eq!(assemble("push x"), Err("line 1: push needs a number".into()));
+    }
+}
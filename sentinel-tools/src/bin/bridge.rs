@@ -0,0 +1,95 @@
+//! Drive a board running the `bridge` example from the command line.
+//!
+//! ```text
+//! bridge PORT [--baud N] COMMAND [ARGS...]
+//! ```
+//!
+//! Numbers are decimal, or hex with `0x`; data bytes are always hex. Each
+//! command prints what it read, if anything:
+//!
+//! ```text
+//! ping                           protocol version
+//! inputs                         GPIO input levels
+//! outputs VAL                    set the GPIO output levels
+//! oe MASK                        set the GPIO output enables
+//! leds VAL                       set the LEDs
+//! ticks                          timer ticks since the bridge started
+//! wait TICKS                     wait on the board
+//! spi BYTES...                   one SPI transaction
+//! i2c-write ADDR BYTES...
+//! i2c-read ADDR LEN
+//! i2c-write-read ADDR LEN BYTES...
+//! ```
+
+use std::env;
+use std::process::ExitCode;
+
+use sentinel_tools::bridge::Client;
+use sentinel_tools::port;
+
+const USAGE: &str = "usage: bridge PORT [--baud N] COMMAND [ARGS...]";
+
+fn number<T: TryFrom<u32>>(s: &str) -> Result<T, String> {
+    let n = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    n.ok()
+        .and_then(|n| T::try_from(n).ok())
+        .ok_or_else(|| format!("bad number: {s}"))
+}
+
+fn bytes(args: &[String]) -> Result<Vec<u8>, String> {
+    args.iter()
+        .map(|s| u8::from_str_radix(s, 16).map_err(|_| format!("bad byte: {s}")))
+        .collect()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect::<Vec<_>>().join(" ")
+}
+
+fn run() -> Result<(), String> {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let mut baud = 9600;
+    if let Some(i) = args.iter().position(|a| a == "--baud") {
+        let val = args.get(i + 1).ok_or("--baud needs a value")?;
+        baud = val.parse().map_err(|_| "bad --baud")?;
+        args.drain(i..i + 2);
+    }
+
+    let [port, cmd, rest @ ..] = args.as_slice() else {
+        return Err(USAGE.into());
+    };
+    let arg = |i: usize| rest.get(i).map(String::as_str).ok_or(USAGE.to_string());
+    let mut board = Client::new(port::open(port, baud)?);
+
+    match cmd.as_str() {
+        "ping" => println!("version {}", board.ping()?),
+        "inputs" => println!("{:02x}", board.gpio_read()?),
+        "outputs" => board.gpio_write(number(arg(0)?)?)?,
+        "oe" => board.set_output_enable(number(arg(0)?)?)?,
+        "leds" => board.set_leds(number(arg(0)?)?)?,
+        "ticks" => println!("{}", board.ticks()?),
+        "wait" => board.wait(number(arg(0)?)?)?,
+        "spi" => println!("{}", hex(&board.spi_transfer(&bytes(rest)?)?)),
+        "i2c-write" => board.i2c_write(number(arg(0)?)?, &bytes(&rest[1..])?)?,
+        "i2c-read" => println!("{}", hex(&board.i2c_read(number(arg(0)?)?, number(arg(1)?)?)?)),
+        "i2c-write-read" => {
+            let (addr, len) = (number(arg(0)?)?, number(arg(1)?)?);
+            println!("{}", hex(&board.i2c_write_read(addr, &bytes(&rest[2..])?, len)?));
+        }
+        _ => return Err(format!("unknown command: {cmd}\n{USAGE}")),
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::from(2)
+        }
+    }
+}
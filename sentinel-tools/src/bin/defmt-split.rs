@@ -0,0 +1,83 @@
+//! Split console text from the defmt messages between it.
+//!
+//! ```text
+//! defmt-split [PORT] [--baud N]
+//! ```
+//!
+//! Reads PORT, put in raw mode at N baud (default 9600), or stdin if
+//! omitted. Text goes to stderr as it comes; the defmt data in each frame
+//! from sentinel-rt's `defmt_uart` goes to stdout, for `defmt-print` to
+//! decode:
+//!
+//! ```text
+//! defmt-split /dev/ttyUSB0 | defmt-print -e FIRMWARE
+//! ```
+
+use std::env;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::process::ExitCode;
+
+use sentinel_tools::port::configure;
+use sentinel_tools::split::{Piece, Splitter};
+
+const USAGE: &str = "usage: defmt-split [PORT] [--baud N]";
+
+fn run() -> Result<(), String> {
+    let mut args = env::args().skip(1);
+    let mut port = None;
+    let mut baud = 9600;
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{arg} needs a value"));
+        match arg.as_str() {
+            "--baud" => {
+                baud = value()?.parse().map_err(|_| "bad --baud".to_string())?
+            }
+            _ if port.is_none() && !arg.starts_with("--") => port = Some(arg),
+            _ => return Err(USAGE.into()),
+        }
+    }
+
+    let (mut rx, name): (Box<dyn Read>, _) = match port {
+        Some(port) => {
+            configure(&port, baud)?;
+            let file = File::open(&port).map_err(|e| format!("{port}: {e}"))?;
+            (Box::new(file), port)
+        }
+        None => (Box::new(io::stdin().lock()), "stdin".into()),
+    };
+
+    let mut stdout = io::stdout().lock();
+    let mut stderr = io::stderr().lock();
+    let mut splitter = Splitter::new();
+    let mut buf = [0; 256];
+    loop {
+        let n = rx.read(&mut buf).map_err(|e| format!("{name}: {e}"))?;
+        let mut result = Ok(());
+        let mut out = |piece: Piece<'_>| {
+            if result.is_ok() {
+                result = match piece {
+                    Piece::Text(t) => stderr.write_all(t),
+                    Piece::Frame(f) => stdout.write_all(f).and_then(|()| stdout.flush()),
+                };
+            }
+        };
+        if n == 0 {
+            splitter.flush(&mut out);
+            return result.map_err(|e| e.to_string());
+        }
+        splitter.feed(&buf[..n], &mut out);
+        result.map_err(|e| e.to_string())?;
+    }
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::from(2)
+        }
+    }
+}
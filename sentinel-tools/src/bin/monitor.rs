@@ -0,0 +1,86 @@
+//! Serial monitor with live symbolization.
+//!
+//! ```text
+//! monitor PORT [--baud N] [--elf FILE]
+//! ```
+//!
+//! Puts PORT in raw mode at N baud (default 9600) with `stty`, then copies
+//! it to stdout and stdin to it. With `--elf`, every `backtrace:` or `mepc`
+//! line from the target is followed by where its addresses are, as with
+//! `symbolize`.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::process::ExitCode;
+use std::{env, thread};
+
+use sentinel_tools::port::configure;
+use sentinel_tools::symbolize::{annotate, Symbolizer};
+
+const USAGE: &str = "usage: monitor PORT [--baud N] [--elf FILE]";
+
+fn run() -> Result<(), String> {
+    let mut args = env::args().skip(1);
+    let port = args.next().ok_or(USAGE)?;
+    let mut baud = 9600;
+    let mut elf = None;
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{arg} needs a value"));
+        match arg.as_str() {
+            "--baud" => {
+                baud = value()?.parse().map_err(|_| "bad --baud".to_string())?
+            }
+            "--elf" => elf = Some(value()?),
+            _ => return Err(USAGE.into()),
+        }
+    }
+
+    let sym = elf.map(Symbolizer::open).transpose()?;
+    configure(&port, baud)?;
+    let mut rx = File::open(&port).map_err(|e| format!("{port}: {e}"))?;
+    let mut tx = OpenOptions::new()
+        .write(true)
+        .open(&port)
+        .map_err(|e| format!("{port}: {e}"))?;
+
+    thread::spawn(move || io::copy(&mut io::stdin().lock(), &mut tx));
+
+    // Bytes are passed through as they come, so prompts show up; the
+    // current line is kept on the side for symbolizing.
+    let mut stdout = io::stdout().lock();
+    let mut line = Vec::new();
+    let mut buf = [0; 256];
+    loop {
+        let n = rx.read(&mut buf).map_err(|e| format!("{port}: {e}"))?;
+        if n == 0 {
+            return Ok(());
+        }
+        stdout.write_all(&buf[..n]).map_err(|e| e.to_string())?;
+
+        for b in &buf[..n] {
+            if *b != b'\n' {
+                line.push(*b);
+                continue;
+            }
+
+            if let Some(sym) = &sym {
+                for l in annotate(&String::from_utf8_lossy(&line), |a| sym.frames(a)) {
+                    write!(stdout, "{l}\r\n").map_err(|e| e.to_string())?;
+                }
+            }
+            line.clear();
+        }
+        stdout.flush().map_err(|e| e.to_string())?;
+    }
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::from(2)
+        }
+    }
+}
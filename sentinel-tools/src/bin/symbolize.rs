@@ -0,0 +1,60 @@
+//! Symbolize addresses from target backtraces and fault dumps.
+//!
+//! ```text
+//! symbolize ELF ADDR...
+//! symbolize ELF < CAPTURE
+//! ```
+//!
+//! With addresses (hex, `0x` optional) on the command line, print where
+//! each is. Otherwise, copy stdin to stdout, following each `backtrace:` or
+//! `mepc` line with where its addresses are.
+
+use std::env;
+use std::io::{self, BufRead, Write};
+use std::process::ExitCode;
+
+use sentinel_tools::symbolize::{annotate, Symbolizer};
+
+const USAGE: &str = "usage: symbolize ELF [ADDR...]";
+
+fn run() -> Result<(), String> {
+    let mut args = env::args().skip(1);
+    let elf = args.next().ok_or(USAGE)?;
+    let sym = Symbolizer::open(&elf)?;
+    let addrs: Vec<String> = args.collect();
+
+    if !addrs.is_empty() {
+        for a in addrs {
+            let addr = u64::from_str_radix(a.trim_start_matches("0x"), 16)
+                .map_err(|_| format!("bad address: {a}"))?;
+            let frames = sym.frames(addr);
+            if frames.is_empty() {
+                println!("{addr:08x} ??");
+            }
+            for f in frames {
+                println!("{addr:08x} {f}");
+            }
+        }
+        return Ok(());
+    }
+
+    let mut stdout = io::stdout().lock();
+    for line in io::stdin().lock().lines() {
+        let line = line.map_err(|e| e.to_string())?;
+        writeln!(stdout, "{line}").map_err(|e| e.to_string())?;
+        for l in annotate(&line, |a| sym.frames(a)) {
+            writeln!(stdout, "{l}").map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::from(2)
+        }
+    }
+}
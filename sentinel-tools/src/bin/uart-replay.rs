@@ -0,0 +1,81 @@
+//! Check a captured UART stream against an expectation script.
+//!
+//! ```text
+//! uart-replay SCRIPT [CAPTURE]
+//! uart-replay SCRIPT --vcd FILE [--signal NAME] [--baud N]
+//! ```
+//!
+//! A raw capture (e.g. from `cat /dev/ttyUSB0 > log`) is read from CAPTURE,
+//! or stdin if omitted. With `--vcd`, the bytes are instead decoded from
+//! the named signal (default `tx`) of a simulation trace. Exits nonzero if
+//! the script doesn't hold.
+
+use std::io::Read;
+use std::process::ExitCode;
+use std::{env, fs};
+
+use sentinel_tools::expect::Script;
+use sentinel_tools::vcd::Trace;
+
+const USAGE: &str = "usage: uart-replay SCRIPT [CAPTURE | --vcd FILE \
+                     [--signal NAME] [--baud N]]";
+
+fn run() -> Result<bool, String> {
+    let mut args = env::args().skip(1);
+    let script = args.next().ok_or(USAGE)?;
+    let mut capture = None;
+    let mut vcd = None;
+    let mut signal = String::from("tx");
+    let mut baud = 9600;
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{arg} needs a value"));
+        match arg.as_str() {
+            "--vcd" => vcd = Some(value()?),
+            "--signal" => signal = value()?,
+            "--baud" => {
+                baud = value()?.parse().map_err(|_| "bad --baud".to_string())?
+            }
+            _ if capture.is_none() && !arg.starts_with("--") => capture = Some(arg),
+            _ => return Err(USAGE.into()),
+        }
+    }
+
+    let src = fs::read_to_string(&script).map_err(|e| format!("{script}: {e}"))?;
+    let script = Script::parse(&src).map_err(|e| format!("{script}: {e}"))?;
+
+    let bytes = match (vcd, capture) {
+        (Some(_), Some(_)) => return Err(USAGE.into()),
+        (Some(vcd), None) => {
+            let src = fs::read_to_string(&vcd).map_err(|e| format!("{vcd}: {e}"))?;
+            Trace::from_vcd(&src, &signal)
+                .map_err(|e| format!("{vcd}: {e}"))?
+                .decode_uart(baud)
+        }
+        (None, Some(path)) => fs::read(&path).map_err(|e| format!("{path}: {e}"))?,
+        (None, None) => {
+            let mut buf = Vec::new();
+            std::io::stdin().read_to_end(&mut buf).map_err(|e| e.to_string())?;
+            buf
+        }
+    };
+
+    match script.check(&bytes) {
+        Ok(()) => Ok(true),
+        Err(fail) => {
+            eprintln!("FAIL: {fail}");
+            Ok(false)
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::from(2)
+        }
+    }
+}
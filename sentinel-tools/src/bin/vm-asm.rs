@@ -0,0 +1,77 @@
+//! Assemble a `sentinel_rt::vm` script, and optionally upload it.
+//!
+//! ```text
+//! vm-asm FILE [-o OUT] [--upload PORT [--baud N]]
+//! ```
+//!
+//! Writes the bytecode to OUT, if given. With `--upload`, sends it to
+//! firmware running the `script` example on PORT, and waits for it to
+//! start.
+
+use std::io::{Read, Write};
+use std::process::ExitCode;
+use std::{env, fs};
+
+use sentinel_rt::vm::Header;
+use sentinel_tools::asm::assemble;
+use sentinel_tools::port;
+
+const USAGE: &str = "usage: vm-asm FILE [-o OUT] [--upload PORT [--baud N]]";
+
+fn upload(port: &str, baud: u32, code: &[u8]) -> Result<(), String> {
+    let mut port_file = port::open(port, baud)?;
+    port_file.write_all(&Header::for_code(code).to_bytes()).map_err(|e| e.to_string())?;
+    port_file.write_all(code).map_err(|e| e.to_string())?;
+
+    // The firmware answers ok, or what went wrong, on a line of its own.
+    let mut line = Vec::new();
+    let mut b = [0];
+    while !line.ends_with(b"\r\n") {
+        if port_file.read(&mut b).map_err(|e| format!("{port}: {e}"))? == 0 {
+            return Err(format!("{port}: closed"));
+        }
+        line.push(b[0]);
+    }
+    match String::from_utf8_lossy(&line).trim() {
+        "ok" => Ok(()),
+        err => Err(err.to_string()),
+    }
+}
+
+fn run() -> Result<(), String> {
+    let mut args = env::args().skip(1);
+    let file = args.next().ok_or(USAGE)?;
+    let (mut out, mut port, mut baud) = (None, None, 9600);
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{arg} needs a value"));
+        match arg.as_str() {
+            "-o" => out = Some(value()?),
+            "--upload" => port = Some(value()?),
+            "--baud" => baud = value()?.parse().map_err(|_| "bad --baud".to_string())?,
+            _ => return Err(USAGE.into()),
+        }
+    }
+
+    let src = fs::read_to_string(&file).map_err(|e| format!("{file}: {e}"))?;
+    let code = assemble(&src).map_err(|e| format!("{file}: {e}"))?;
+    eprintln!("{} bytes", code.len());
+
+    if let Some(out) = out {
+        fs::write(&out, &code).map_err(|e| format!("{out}: {e}"))?;
+    }
+    if let Some(port) = port {
+        upload(&port, baud, &code)?;
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::from(2)
+        }
+    }
+}
@@ -0,0 +1,185 @@
+//! Client for firmware running `sentinel_rt::bridge`.
+//!
+//! [`Client`] sends one request at a time, over anything that reads and
+//! writes bytes (normally the port from [`port::open`](crate::port::open)),
+//! and waits for its response:
+//!
+//! ```ignore
+//! let mut board = Client::new(port::open("/dev/ttyUSB1", 9600)?);
+//! board.set_output_enable(0x0c)?;
+//! board.gpio_write(0x04)?;
+//! let id = board.i2c_write_read(0x44, &[0xf3, 0x2d], 3)?;
+//! ```
+
+use std::io::{Read, Write};
+
+use sentinel_rt::bridge::{encode, Decoder, Op, Status, MAX_FRAME, MAX_PAYLOAD, VERSION};
+use sentinel_rt::error::Describe;
+
+pub struct Client<P> {
+    port: P,
+    decoder: Decoder,
+}
+
+impl<P: Read + Write> Client<P> {
+    pub fn new(port: P) -> Self {
+        Self { port, decoder: Decoder::new() }
+    }
+
+    /// Send `op` with `args`, and return the results.
+    pub fn request(&mut self, op: Op, args: &[u8]) -> Result<Vec<u8>, String> {
+        if args.len() >= MAX_PAYLOAD {
+            return Err(format!("bridge: at most {} bytes of arguments", MAX_PAYLOAD - 1));
+        }
+        let mut payload = vec![op as u8];
+        payload.extend_from_slice(args);
+        let mut frame = [0; MAX_FRAME];
+        self.port.write_all(encode(&payload, &mut frame)).map_err(|e| e.to_string())?;
+        self.port.flush().map_err(|e| e.to_string())?;
+
+        let mut b = [0];
+        loop {
+            if self.port.read(&mut b).map_err(|e| e.to_string())? == 0 {
+                return Err("bridge: port closed".into());
+            }
+            let resp = match self.decoder.feed(b[0]) {
+                None => continue,
+                Some(Ok(resp)) => resp,
+                Some(Err(status)) => return Err(status.describe().into()),
+            };
+
+            let (&status, results) = resp.split_first().ok_or("bridge: empty response")?;
+            return match Status::from_u8(status) {
+                Some(Status::Ok) => Ok(results.to_vec()),
+                Some(status) => Err(status.describe().into()),
+                None => Err(format!("bridge: unknown status {status}")),
+            };
+        }
+    }
+
+    fn results<const N: usize>(&mut self, op: Op, args: &[u8]) -> Result<[u8; N], String> {
+        self.request(op, args)?
+            .try_into()
+            .map_err(|_| "bridge: short response".into())
+    }
+
+    /// The firmware's protocol version, checked against ours.
+    pub fn ping(&mut self) -> Result<u8, String> {
+        let [version] = self.results(Op::Ping, &[])?;
+        if version != VERSION {
+            return Err(format!("bridge: firmware speaks version {version}, not {VERSION}"));
+        }
+        Ok(version)
+    }
+
+    pub fn gpio_read(&mut self) -> Result<u8, String> {
+        let [inputs] = self.results(Op::GpioRead, &[])?;
+        Ok(inputs)
+    }
+
+    /// Level driven on pins whose output enable is set.
+    pub fn gpio_write(&mut self, val: u8) -> Result<(), String> {
+        self.request(Op::GpioWrite, &[val]).map(drop)
+    }
+
+    /// Set bit `n` to make pin `n` an output.
+    pub fn set_output_enable(&mut self, mask: u8) -> Result<(), String> {
+        self.request(Op::GpioOutputEnable, &[mask]).map(drop)
+    }
+
+    pub fn set_leds(&mut self, val: u8) -> Result<(), String> {
+        self.request(Op::Leds, &[val]).map(drop)
+    }
+
+    /// Timer ticks since the bridge started.
+    pub fn ticks(&mut self) -> Result<u32, String> {
+        self.results(Op::Ticks, &[]).map(u32::from_le_bytes)
+    }
+
+    /// Have the firmware wait `ticks` timer ticks before answering.
+    pub fn wait(&mut self, ticks: u16) -> Result<(), String> {
+        self.request(Op::Wait, &ticks.to_le_bytes()).map(drop)
+    }
+
+    /// Send `bytes` in one SPI transaction, and return what was read back.
+    pub fn spi_transfer(&mut self, bytes: &[u8]) -> Result<Vec<u8>, String> {
+        self.request(Op::Spi, bytes)
+    }
+
+    pub fn i2c_write(&mut self, addr: u8, bytes: &[u8]) -> Result<(), String> {
+        let args: Vec<u8> = [addr].iter().chain(bytes).copied().collect();
+        self.request(Op::I2cWrite, &args).map(drop)
+    }
+
+    pub fn i2c_read(&mut self, addr: u8, len: u8) -> Result<Vec<u8>, String> {
+        self.request(Op::I2cRead, &[addr, len])
+    }
+
+    /// Write `bytes`, then read `len` bytes after a repeated start.
+    pub fn i2c_write_read(&mut self, addr: u8, bytes: &[u8], len: u8)
+                          -> Result<Vec<u8>, String> {
+        let args: Vec<u8> = [addr, len].iter().chain(bytes).copied().collect();
+        self.request(Op::I2cWriteRead, &args)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Cursor};
+
+    use sentinel_rt::bridge::SYNC;
+
+    use super::*;
+
+    /// Canned responses in, requests out.
+    struct Fake {
+        rx: Cursor<Vec<u8>>,
+        tx: Vec<u8>,
+    }
+
+    impl Read for Fake {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.rx.read(buf)
+        }
+    }
+
+    impl Write for Fake {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.tx.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn client(responses: &[&[u8]]) -> Client<Fake> {
+        let mut rx = Vec::new();
+        for resp in responses {
+            let mut frame = [0; MAX_FRAME];
+            rx.extend_from_slice(encode(resp, &mut frame));
+        }
+        Client::new(Fake { rx: Cursor::new(rx), tx: Vec::new() })
+    }
+
+    #[test]
+    fn requests() {
+        let mut c = client(&[&[0, VERSION], &[0, 0x78, 0x56, 0x34, 0x12], &[0, 0xaa, 0xbb]]);
+        assert_eq!(c.ping(), Ok(VERSION));
+        assert_eq!(c.ticks(), Ok(0x1234_5678));
+        assert_eq!(c.i2c_write_read(0x44, &[0x24, 0x00], 2), Ok(vec![0xaa, 0xbb]));
+
+        let tx = &c.port.tx;
+        assert_eq!(&tx[..2], &[SYNC, 1]);
+        let last = &tx[tx.len() - 7..tx.len() - 1];
+        assert_eq!(last, &[5, Op::I2cWriteRead as u8, 0x44, 2, 0x24, 0x00]);
+    }
+
+    #[test]
+    fn errors() {
+        let mut c = client(&[&[Status::I2cAddressNack as u8], &[0]]);
+        assert_eq!(c.i2c_read(0x50, 1), Err("i2c: address nack".into()));
+        assert_eq!(c.gpio_read(), Err("bridge: short response".into()));
+        assert_eq!(c.set_leds(1), Err("bridge: port closed".into()));
+    }
+}
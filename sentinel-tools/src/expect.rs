@@ -0,0 +1,216 @@
+//! Expectation scripts for captured UART output.
+//!
+//! A script is a list of steps, one per line, matched in order against the
+//! captured byte stream (decoded lossily as UTF-8). Blank lines and lines
+//! starting with `#` are ignored.
+//!
+//! * `expect REGEX`: skip ahead to the next match of `REGEX`.
+//! * `line REGEX`: the next line must match `REGEX` in its entirety.
+//! * `never REGEX`: `REGEX` must not match anywhere in the capture.
+//! * `eof`: nothing but whitespace may follow.
+//!
+//! For example, for the attosoc demo:
+//!
+//! ```text
+//! # Banner, then the timer starts ticking.
+//! expect ^A
+//! expect T{5}
+//! never \x00
+//! ```
+
+use std::fmt;
+
+use regex::Regex;
+
+#[derive(Debug)]
+pub enum Step {
+    Expect(Regex),
+    Line(Regex),
+    Never(Regex),
+    Eof,
+}
+
+#[derive(Debug)]
+pub struct Script {
+    steps: Vec<(usize, Step)>,
+}
+
+#[derive(Debug)]
+pub struct ParseError {
+    pub line: usize,
+    pub msg: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.msg)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// A step that didn't hold.
+#[derive(Debug)]
+pub struct Failure {
+    /// Line of the script the step came from.
+    pub line: usize,
+    /// Byte offset into the capture where matching stopped.
+    pub offset: usize,
+    pub msg: String,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "script line {} (capture offset {}): {}", self.line,
+               self.offset, self.msg)
+    }
+}
+
+impl Script {
+    pub fn parse(src: &str) -> Result<Self, ParseError> {
+        let mut steps = Vec::new();
+
+        for (i, raw) in src.lines().enumerate() {
+            let line = i + 1;
+            let text = raw.trim_start();
+            if text.is_empty() || text.starts_with('#') {
+                continue;
+            }
+
+            let (cmd, arg) = match text.split_once(char::is_whitespace) {
+                Some((cmd, arg)) => (cmd, Some(arg)),
+                None => (text, None),
+            };
+
+            let regex = |arg: Option<&str>| -> Result<Regex, ParseError> {
+                let arg = arg.ok_or_else(|| ParseError {
+                    line,
+                    msg: format!("`{cmd}` needs a regex"),
+                })?;
+                Regex::new(arg).map_err(|e| ParseError {
+                    line,
+                    msg: e.to_string(),
+                })
+            };
+
+            let step = match cmd {
+                "expect" => Step::Expect(regex(arg)?),
+                // Anchored, so a shorter leftmost match can't hide one of
+                // the whole line.
+                "line" => Step::Line(regex(arg.map(|a| format!("^(?:{a})$")).as_deref())?),
+                "never" => Step::Never(regex(arg)?),
+                "eof" => Step::Eof,
+                _ => {
+                    return Err(ParseError {
+                        line,
+                        msg: format!("unknown step `{cmd}`"),
+                    })
+                }
+            };
+
+            steps.push((line, step));
+        }
+
+        Ok(Self { steps })
+    }
+
+    /// Check `capture` against every step, stopping at the first failure.
+    pub fn check(&self, capture: &[u8]) -> Result<(), Failure> {
+        let text = String::from_utf8_lossy(capture);
+        let mut pos = 0;
+
+        for (line, step) in &self.steps {
+            let fail = |offset, msg: String| Failure {
+                line: *line,
+                offset,
+                msg,
+            };
+
+            match step {
+                Step::Expect(re) => match re.find_at(&text, pos) {
+                    Some(m) => pos = m.end(),
+                    None => {
+                        return Err(fail(pos, format!("`{re}` not found, \
+                                                      remaining: {:?}",
+                                                     excerpt(&text[pos..]))))
+                    }
+                },
+                Step::Line(re) => {
+                    let rest = &text[pos..];
+                    let (l, next) = match rest.find('\n') {
+                        Some(nl) => (&rest[..nl], pos + nl + 1),
+                        None => (rest, text.len()),
+                    };
+                    let l = l.strip_suffix('\r').unwrap_or(l);
+
+                    if !re.is_match(l) {
+                        return Err(fail(pos, format!("line {:?} doesn't \
+                                                      match `{re}`",
+                                                     excerpt(l))));
+                    }
+                    pos = next;
+                }
+                Step::Never(re) => {
+                    if let Some(m) = re.find(&text) {
+                        return Err(fail(m.start(), format!("`{re}` matched \
+                                                            {:?}",
+                                                           m.as_str())));
+                    }
+                }
+                Step::Eof => {
+                    if !text[pos..].trim().is_empty() {
+                        return Err(fail(pos, format!("trailing output {:?}",
+                                                     excerpt(&text[pos..]))));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn excerpt(s: &str) -> &str {
+    match s.char_indices().nth(40) {
+        Some((i, _)) => &s[..i],
+        None => s,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_in_order() {
+        let script = Script::parse("# demo\nexpect ^A\nline T+\nline ok\neof\n")
+            .unwrap();
+        assert!(script.check(b"ATTT\nok\r\n").is_ok());
+
+        let err = script.check(b"ATTT\nbad\n").unwrap_err();
+        assert_eq!(err.line, 4);
+    }
+
+    #[test]
+    fn line_matches_whole() {
+        // `a` is the leftmost-first match, but `ab` matches the line.
+        let script = Script::parse("line a|ab\nline x").unwrap();
+        assert!(script.check(b"ab\nx\n").is_ok());
+        assert_eq!(script.check(b"ab\nxy\n").unwrap_err().line, 2);
+    }
+
+    #[test]
+    fn never_and_expect() {
+        let script = Script::parse("never panic\nexpect done").unwrap();
+        assert!(script.check(b"working... done").is_ok());
+        assert_eq!(script.check(b"panic! done").unwrap_err().line, 1);
+        assert_eq!(script.check(b"working...").unwrap_err().line, 2);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(Script::parse("\n\nbogus x").unwrap_err().line, 3);
+        assert!(Script::parse("expect").is_err());
+        assert!(Script::parse("expect (").is_err());
+    }
+}
@@ -0,0 +1,9 @@
+//! Host-side utilities for working with Sentinel firmware.
+
+pub mod asm;
+pub mod bridge;
+pub mod expect;
+pub mod port;
+pub mod split;
+pub mod symbolize;
+pub mod vcd;
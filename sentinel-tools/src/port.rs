@@ -0,0 +1,27 @@
+//! Serial ports.
+
+use std::fs::{File, OpenOptions};
+use std::process::Command;
+
+/// Put `port` in raw mode at `baud`, with `stty`.
+pub fn configure(port: &str, baud: u32) -> Result<(), String> {
+    let flag = if cfg!(target_os = "macos") { "-f" } else { "-F" };
+    let status = Command::new("stty")
+        .args([flag, port, &baud.to_string(), "raw", "-echo"])
+        .status()
+        .map_err(|e| format!("stty: {e}"))?;
+    if !status.success() {
+        return Err(format!("stty: couldn't configure {port}"));
+    }
+    Ok(())
+}
+
+/// [`configure`] `port`, and open it for reading and writing.
+pub fn open(port: &str, baud: u32) -> Result<File, String> {
+    configure(port, baud)?;
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(port)
+        .map_err(|e| format!("{port}: {e}"))
+}
@@ -0,0 +1,161 @@
+//! Console text and framed data, apart again.
+//!
+//! Firmware can send [`sentinel_rt::cobs`] frames (defmt messages, from
+//! `defmt_uart`) between its console text. A [`Splitter`] is fed the UART
+//! stream as it comes and hands it back in pieces: text as soon as it's
+//! seen, frames once they've ended and checked out.
+//!
+//! A frame starts and ends with a zero, and text has none, so whatever
+//! follows a frame is text until the next zero. A run between zeros that
+//! isn't a frame (the splitter started mid-frame, or a frame lost bytes)
+//! is passed on as text, and the zero after it taken as the start of the
+//! next frame.
+
+use sentinel_rt::cobs;
+
+/// Longest run held back waiting for a zero before it's given up on as a
+/// frame.
+pub const MAX_FRAME: usize = 1024;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Piece<'a> {
+    Text(&'a [u8]),
+    /// A frame's data, without the CRC.
+    Frame(&'a [u8]),
+}
+
+#[derive(Default)]
+pub struct Splitter {
+    /// Past a zero that may start a frame.
+    in_frame: bool,
+    buf: Vec<u8>,
+    decoded: Vec<u8>,
+}
+
+impl Splitter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take the next bytes from the stream, passing on the pieces they
+    /// finish.
+    pub fn feed(&mut self, mut bytes: &[u8], mut out: impl FnMut(Piece<'_>)) {
+        while !bytes.is_empty() {
+            let zero = bytes.iter().position(|&b| b == 0);
+            let (run, rest) = match zero {
+                Some(n) => (&bytes[..n], &bytes[n + 1..]),
+                None => (bytes, &[][..]),
+            };
+            bytes = rest;
+
+            if !self.in_frame {
+                if !run.is_empty() {
+                    out(Piece::Text(run));
+                }
+                self.in_frame = zero.is_some();
+                continue;
+            }
+            self.buf.extend_from_slice(run);
+            if zero.is_some() {
+                self.end(&mut out);
+            } else if self.buf.len() > MAX_FRAME {
+                out(Piece::Text(&self.buf));
+                self.buf.clear();
+                self.in_frame = false;
+            }
+        }
+    }
+
+    /// Pass on whatever's held back as text, as at the end of the stream.
+    pub fn flush(&mut self, mut out: impl FnMut(Piece<'_>)) {
+        if !self.buf.is_empty() {
+            out(Piece::Text(&self.buf));
+            self.buf.clear();
+        }
+        self.in_frame = false;
+    }
+
+    /// A zero after `buf`: the end of a frame, or, if `buf` isn't one, the
+    /// start of the next.
+    fn end(&mut self, out: &mut impl FnMut(Piece<'_>)) {
+        self.decoded.resize(self.buf.len(), 0);
+        match cobs::decode(&self.buf, &mut self.decoded) {
+            Some(data) => {
+                out(Piece::Frame(data));
+                self.in_frame = false;
+            }
+            None if !self.buf.is_empty() => out(Piece::Text(&self.buf)),
+            None => {}
+        }
+        self.buf.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Eq)]
+    enum Owned {
+        Text(Vec<u8>),
+        Frame(Vec<u8>),
+    }
+
+    fn frame(data: &[u8]) -> Vec<u8> {
+        let mut v = Vec::new();
+        let mut enc = cobs::Encoder::new();
+        enc.start(|b| v.extend_from_slice(b));
+        enc.write(data, |b| v.extend_from_slice(b));
+        enc.finish(|b| v.extend_from_slice(b));
+        v
+    }
+
+    /// Feed `stream` in `chunk`-byte pieces.
+    fn split(stream: &[u8], chunk: usize) -> Vec<Owned> {
+        let mut s = Splitter::new();
+        let mut pieces: Vec<Owned> = Vec::new();
+        for c in stream.chunks(chunk) {
+            s.feed(c, |p| match (p, pieces.last_mut()) {
+                // Text comes out as it arrives; join it up to compare.
+                (Piece::Text(t), Some(Owned::Text(last))) => last.extend_from_slice(t),
+                (Piece::Text(t), _) => pieces.push(Owned::Text(t.to_vec())),
+                (Piece::Frame(f), _) => pieces.push(Owned::Frame(f.to_vec())),
+            });
+        }
+        pieces
+    }
+
+    #[test]
+    fn interleaved() {
+        let stream = [&b"hel"[..], &frame(b"\x01\0\x02"), b"lo\r\n", &frame(b""), &frame(b"x")]
+            .concat();
+        let want = [
+            Owned::Text(b"hel".to_vec()),
+            Owned::Frame(b"\x01\0\x02".to_vec()),
+            Owned::Text(b"lo\r\n".to_vec()),
+            Owned::Frame(b"".to_vec()),
+            Owned::Frame(b"x".to_vec()),
+        ];
+        for chunk in [1, 3, 64] {
+            assert_eq!(split(&stream, chunk), want);
+        }
+    }
+
+    #[test]
+    fn resyncs() {
+        // Started partway into a frame: its tail is taken for text, and
+        // the text after it for the start of a frame, until it isn't.
+        let f = frame(b"abc");
+        let stream = [&f[3..], b"ok\r\n", &frame(b"def")].concat();
+        let pieces = split(&stream, 64);
+        assert_eq!(pieces[0], Owned::Text([&f[3..f.len() - 1], b"ok\r\n"].concat()));
+        assert_eq!(pieces[1], Owned::Frame(b"def".to_vec()));
+        assert_eq!(pieces.len(), 2);
+
+        let mut s = Splitter::new();
+        s.feed(b"\0\x04\x01\x02\x03\0bye\r\n", |p| assert!(matches!(p, Piece::Text(_))));
+        let mut held = Vec::new();
+        s.flush(|p| held.push(p == Piece::Text(b"bye\r\n")));
+        assert_eq!(held, [true]);
+    }
+}
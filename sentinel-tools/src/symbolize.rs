@@ -0,0 +1,177 @@
+//! Symbolization of addresses in target output.
+//!
+//! The target prints raw addresses: return addresses after `backtrace:`
+//! (see `sentinel_rt::backtrace`), and program counters after `mepc`. Given
+//! the firmware's ELF, [`Symbolizer`] turns each into function, file and
+//! line (more than one, if it's in inlined code), and [`annotate`] finds
+//! the addresses in a line of output to symbolize.
+
+use std::fmt;
+use std::path::Path;
+
+use addr2line::Loader;
+
+/// Where an address is. Any of the fields can be missing, e.g. without
+/// debug info.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Frame {
+    pub function: Option<String>,
+    pub file: Option<String>,
+    pub line: Option<u32>,
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.function.as_deref().unwrap_or("??"))?;
+        if let Some(file) = &self.file {
+            write!(f, " at {file}")?;
+            if let Some(line) = self.line {
+                write!(f, ":{line}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct Symbolizer {
+    loader: Loader,
+}
+
+impl Symbolizer {
+    pub fn open(elf: impl AsRef<Path>) -> Result<Self, String> {
+        let elf = elf.as_ref();
+        let loader = Loader::new(elf).map_err(|e| format!("{}: {e}", elf.display()))?;
+        Ok(Self { loader })
+    }
+
+    /// Frames at `addr`, innermost (most inlined) first. Falls back to the
+    /// symbol table when there's no debug info, and is empty if that fails
+    /// too.
+    pub fn frames(&self, addr: u64) -> Vec<Frame> {
+        let mut frames = Vec::new();
+        if let Ok(mut iter) = self.loader.find_frames(addr) {
+            while let Ok(Some(frame)) = iter.next() {
+                frames.push(Frame {
+                    function: frame.function
+                        .and_then(|f| f.demangle().ok().map(|n| n.into_owned())),
+                    file: frame.location.as_ref().and_then(|l| l.file.map(String::from)),
+                    line: frame.location.and_then(|l| l.line),
+                });
+            }
+        }
+
+        if frames.iter().all(|f| f.function.is_none()) {
+            if let Some(sym) = self.loader.find_symbol(addr) {
+                let function = Some(addr2line::demangle_auto(sym.into(), None).into_owned());
+                match frames.first_mut() {
+                    Some(f) => f.function = function,
+                    None => frames.push(Frame { function, ..Frame::default() }),
+                }
+            }
+        }
+
+        frames
+    }
+}
+
+/// An address found in target output.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Addr {
+    /// A return address; the call is just before it.
+    Return(u64),
+    Pc(u64),
+}
+
+impl Addr {
+    /// The address to look up. For a return address that's inside the
+    /// call, so a call at the end of an inlined block or function isn't
+    /// attributed to whatever follows it.
+    pub fn probe(self) -> u64 {
+        match self {
+            Self::Return(a) => a.saturating_sub(1),
+            Self::Pc(a) => a,
+        }
+    }
+
+    pub fn raw(self) -> u64 {
+        match self {
+            Self::Return(a) | Self::Pc(a) => a,
+        }
+    }
+}
+
+fn hex(word: &str) -> Option<u64> {
+    let word = word.trim_start_matches("0x");
+    if word.is_empty() || word.len() > 16 {
+        return None;
+    }
+    u64::from_str_radix(word, 16).ok()
+}
+
+/// Addresses worth symbolizing in a line of target output.
+pub fn addresses(line: &str) -> Vec<Addr> {
+    let line = line.trim();
+    if let Some(rest) = line.strip_prefix("backtrace:") {
+        return rest.split_whitespace().filter_map(hex).map(Addr::Return).collect();
+    }
+
+    let mut words = line.split_whitespace();
+    let mut addrs = Vec::new();
+    while let Some(word) = words.next() {
+        if word.trim_end_matches([':', '=']) == "mepc" {
+            addrs.extend(words.next().and_then(hex).map(Addr::Pc));
+        }
+    }
+    addrs
+}
+
+/// Lines to print after `line`, one per frame of each address in it.
+pub fn annotate(line: &str, frames: impl Fn(u64) -> Vec<Frame>) -> Vec<String> {
+    let mut out = Vec::new();
+    for (i, addr) in addresses(line).into_iter().enumerate() {
+        let frames = frames(addr.probe());
+        if frames.is_empty() {
+            out.push(format!("  #{i} {:08x} ??", addr.raw()));
+        }
+        for (j, frame) in frames.iter().enumerate() {
+            if j == 0 {
+                out.push(format!("  #{i} {:08x} {frame}", addr.raw()));
+            } else {
+                out.push(format!("           (inlined into) {frame}"));
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_addresses() {
+        assert_eq!(addresses("backtrace: 00000104 0000020c"),
+                   [Addr::Return(0x104), Addr::Return(0x20c)]);
+        assert_eq!(addresses("fault mepc 00000abc mcause 2"), [Addr::Pc(0xabc)]);
+        assert_eq!(addresses("mepc=0x10"), []);
+        assert_eq!(addresses("mepc: 0x10"), [Addr::Pc(0x10)]);
+        assert_eq!(addresses("ticks 00000104"), []);
+    }
+
+    #[test]
+    fn annotates() {
+        let frames = |addr| match addr {
+            0x103 => vec![
+                Frame { function: Some("inner".into()), file: Some("a.rs".into()), line: Some(3) },
+                Frame { function: Some("outer".into()), ..Frame::default() },
+            ],
+            _ => vec![],
+        };
+
+        assert_eq!(annotate("backtrace: 00000104 00000200", frames), [
+            "  #0 00000104 inner at a.rs:3",
+            "           (inlined into) outer",
+            "  #1 00000200 ??",
+        ]);
+    }
+}
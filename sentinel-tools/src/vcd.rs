@@ -0,0 +1,186 @@
+//! Pull a UART byte stream out of a simulation VCD.
+//!
+//! Only what's needed to follow one single-bit signal is parsed: the
+//! timescale, the `$var` declarations, timestamps and scalar value changes.
+
+use std::fmt;
+
+#[derive(Debug)]
+pub struct Error(pub String);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Value changes of a single-bit signal.
+pub struct Trace {
+    /// Length of one VCD time unit, in seconds.
+    pub unit: f64,
+    /// `(time, level)` pairs, in time order.
+    pub changes: Vec<(u64, bool)>,
+}
+
+fn parse_timescale(ts: &str) -> Result<f64, Error> {
+    let ts: String = ts.split_whitespace().collect();
+    let split = ts.find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| Error(format!("bad timescale {ts:?}")))?;
+    let (num, unit) = ts.split_at(split);
+    let num: f64 = num.parse()
+        .map_err(|_| Error(format!("bad timescale {ts:?}")))?;
+    let scale = match unit {
+        "s" => 1.0,
+        "ms" => 1e-3,
+        "us" => 1e-6,
+        "ns" => 1e-9,
+        "ps" => 1e-12,
+        "fs" => 1e-15,
+        _ => return Err(Error(format!("bad timescale unit {unit:?}"))),
+    };
+
+    Ok(num * scale)
+}
+
+impl Trace {
+    /// Extract the signal called `name` from VCD text. `name` is matched
+    /// against the full dotted path (e.g. `top.serial.tx`), or failing that,
+    /// just the signal's own name.
+    pub fn from_vcd(src: &str, name: &str) -> Result<Self, Error> {
+        let mut unit = 1e-12;
+        let mut scope: Vec<&str> = Vec::new();
+        let mut id = None;
+        let mut tokens = src.split_whitespace();
+
+        // Header.
+        while let Some(tok) = tokens.next() {
+            let mut body = Vec::new();
+            if tok.starts_with('$') && tok != "$end" {
+                for t in tokens.by_ref() {
+                    if t == "$end" {
+                        break;
+                    }
+                    body.push(t);
+                }
+            }
+
+            match tok {
+                "$timescale" => unit = parse_timescale(&body.join(""))?,
+                "$scope" => scope.push(body.get(1).copied().unwrap_or("")),
+                "$upscope" => {
+                    scope.pop();
+                }
+                "$var" => {
+                    // $var <type> <width> <id> <name> [range] $end
+                    if let [_, width, code, var, ..] = body[..] {
+                        let path = scope.iter().chain([&var]).copied()
+                            .collect::<Vec<_>>().join(".");
+                        let exact = path == name;
+                        if width == "1" && (exact || (var == name && id.is_none())) {
+                            id = Some(code.to_string());
+                        }
+                    }
+                }
+                "$enddefinitions" => break,
+                _ => {}
+            }
+        }
+
+        let id = id.ok_or_else(|| Error(format!("no 1-bit signal {name:?}")))?;
+
+        // Value changes.
+        let mut now = 0;
+        let mut changes: Vec<(u64, bool)> = Vec::new();
+        for tok in tokens {
+            if let Some(t) = tok.strip_prefix('#') {
+                now = t.parse().map_err(|_| Error(format!("bad time {tok:?}")))?;
+            } else if let Some(code) = tok.strip_prefix(['0', '1']) {
+                if code == id {
+                    let level = tok.starts_with('1');
+                    if changes.last().map(|c| c.1) != Some(level) {
+                        changes.push((now, level));
+                    }
+                }
+            }
+        }
+
+        Ok(Self { unit, changes })
+    }
+
+    fn level_at(&self, t: u64) -> bool {
+        match self.changes.partition_point(|c| c.0 <= t) {
+            // UART lines idle high.
+            0 => true,
+            i => self.changes[i - 1].1,
+        }
+    }
+
+    /// Decode the trace as 8N1 serial at `baud`. Characters with a bad stop
+    /// bit are dropped.
+    pub fn decode_uart(&self, baud: u32) -> Vec<u8> {
+        let bit = 1.0 / (baud as f64 * self.unit);
+        let mut out = Vec::new();
+        let mut after = 0;
+
+        for &(t, level) in &self.changes {
+            if level || t < after {
+                continue;
+            }
+
+            // Start bit edge; sample in the middle of each bit.
+            let sample = |n: f64| self.level_at(t + (bit * (n + 0.5)) as u64);
+            let mut byte = 0;
+            for i in 0..8 {
+                byte |= (sample(1.0 + i as f64) as u8) << i;
+            }
+
+            if sample(9.0) {
+                out.push(byte);
+            }
+            after = t + (bit * 9.5) as u64;
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 'A' (0x41) then 'T' (0x54) at 1 bit per 10 time units.
+    fn vcd() -> String {
+        let mut body = String::new();
+        let mut t = 100;
+        for byte in [0x41u8, 0x54] {
+            let bits = std::iter::once(false)
+                .chain((0..8).map(|i| (byte >> i) & 1 != 0))
+                .chain(std::iter::once(true));
+            for b in bits {
+                body += &format!("#{t}\n{}!\n", b as u8);
+                t += 10;
+            }
+        }
+
+        format!("$timescale 1 us $end\n$scope module top $end\n\
+                 $var wire 1 ! tx $end\n$var wire 8 \" data $end\n\
+                 $upscope $end\n$enddefinitions $end\n\
+                 #0\n$dumpvars 1! b0 \" $end\n{body}")
+    }
+
+    #[test]
+    fn decode() {
+        let trace = Trace::from_vcd(&vcd(), "top.tx").unwrap();
+        assert_eq!(trace.unit, 1e-6);
+        // 1 bit per 10us is 100 kbaud.
+        assert_eq!(trace.decode_uart(100_000), b"AT");
+    }
+
+    #[test]
+    fn missing_signal() {
+        assert!(Trace::from_vcd(&vcd(), "rx").is_err());
+        assert!(Trace::from_vcd(&vcd(), "data").is_err());
+    }
+}